@@ -0,0 +1,75 @@
+// Surplus accounting for a run, in utils as total_surplus is: total utility before and
+// after trading, and the gains from trade split between the two sides of the market.
+// Each trade's effect on its buyer's utility counts toward consumer surplus and on its
+// seller's toward producer surplus, replaying the trades in order so each is valued at
+// what its parties held at the time. An agent can be on both sides over a run, so the
+// split is by trade rather than by agent; the two add up to the gains from trade.
+// The same accounts can be kept in B, valuing every holding by its money-metric
+// utility at one price (see welfare::Currency).
+
+use crate::welfare::money_metric;
+use crate::{settle, Agent, Balance, Price, Trade};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Accounts {
+  pub utility_before: f64,
+  pub utility_after: f64,
+  pub consumer_surplus: f64,
+  pub producer_surplus: f64,
+}
+
+impl Accounts {
+  pub fn gains_from_trade(&self) -> f64 {
+    self.utility_after - self.utility_before
+  }
+}
+
+pub fn of(initial: &[(Agent, Balance)], trades: &[Trade]) -> Accounts {
+  valued(initial, trades, |agent, b| agent.utility(b.a, b.b))
+}
+
+// The accounts in B at `price`.
+pub fn in_b(initial: &[(Agent, Balance)], trades: &[Trade], price: Price) -> Accounts {
+  valued(initial, trades, |agent, b| money_metric(agent, b, price))
+}
+
+fn valued(initial: &[(Agent, Balance)], trades: &[Trade], value: impl Fn(&Agent, &Balance) -> f64) -> Accounts {
+  let total = |assets: &[(Agent, Balance)]| assets.iter().map(|(agent, b)| value(agent, b)).sum::<f64>();
+  let utility = |assets: &[(Agent, Balance)], id: usize| value(&assets[id].0, &assets[id].1);
+  let mut assets = initial.to_vec();
+  let (mut consumer_surplus, mut producer_surplus) = (0.0, 0.0);
+  for trade in trades {
+    let before = (utility(&assets, trade.buyer), utility(&assets, trade.seller));
+    settle(&mut assets, trade);
+    consumer_surplus += utility(&assets, trade.buyer) - before.0;
+    producer_surplus += utility(&assets, trade.seller) - before.1;
+  }
+  Accounts { utility_before: total(initial), utility_after: total(&assets), consumer_surplus, producer_surplus }
+}
+
+pub fn print_report(accounts: &Accounts, unit: &str) {
+  println!("surplus accounts ({}):", unit);
+  println!("  total utility: {} before trading, {} after", accounts.utility_before, accounts.utility_after);
+  println!("  gains from trade: {} (consumer surplus {}, producer surplus {})", accounts.gains_from_trade(), accounts.consumer_surplus, accounts.producer_surplus);
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::accounting::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_accounts() {
+    let initial = vec![(linear_agent(3.0), Balance { a: 0.0, b: 4.0 }), (linear_agent(1.0), Balance { a: 2.0, b: 0.0 }), (linear_agent(2.0), Balance { a: 0.0, b: 2.0 })];
+    let trade = |buyer, seller, amount_a, amount_b| Trade { tick: 0, buyer, seller, amount_a, amount_b, bid_price: 3.0, ask_price: 1.0, ..Trade::default() };
+    // agent 1 sells 1 A to agent 0 for 2 B, both gaining 1; agent 0 sells it on to
+    // agent 2 for 2 B, giving its gain back as a seller while agent 2 breaks even
+    let accounts = of(&initial, &[trade(0, 1, 1.0, 2.0), trade(2, 0, 1.0, 2.0)]);
+    assert_eq!(accounts, Accounts { utility_before: 8.0, utility_after: 9.0, consumer_surplus: 1.0, producer_surplus: 0.0 });
+    assert_eq!(accounts.gains_from_trade(), accounts.consumer_surplus + accounts.producer_surplus);
+    // at 2 B per A agent 1 would rather hold B, so its util is worth a B; agent 0
+    // would rather spend on A, at 1.5 utils per B, so its util is worth 2/3 B
+    let b = in_b(&initial, &[trade(0, 1, 1.0, 2.0)], 2.0);
+    assert!((b.producer_surplus - 1.0).abs() < 1e-9 && (b.consumer_surplus - 2.0 / 3.0).abs() < 1e-9, "{:?}", b);
+  }
+}
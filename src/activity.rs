@@ -0,0 +1,160 @@
+// Who traded how often, how long each waited for its first trade, and why the agents
+// who never traded didn't.
+
+use std::collections::BTreeMap;
+
+use crate::clock::Tick;
+use crate::inequality::gini;
+use crate::mobility::{quintiles, QUINTILES};
+use crate::runlog::RunLog;
+use crate::stats::quantile;
+use crate::strategy::{Strategies, Strategy};
+use crate::summary::Summary;
+use crate::{Agent, AgentId, Balance, Price, Trade};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, PartialOrd, Ord)]
+pub enum IdleReason {
+  // it held only the good it wanted more of, so had nothing to pay or sell with
+  NoBudget,
+  // it had an order in the book, but someone else always had the better price
+  NeverBestPriced,
+  // it was kept out of the market (e.g. didn't pay the entry cost)
+  Blocked,
+}
+
+pub fn trade_counts(n_agents: usize, trades: &[Trade]) -> Vec<usize> {
+  let mut counts = vec![0; n_agents];
+  for trade in trades {
+    counts[trade.buyer] += 1;
+    counts[trade.seller] += 1;
+  }
+  counts
+}
+
+// The tick of each agent's first trade, None if it never traded.
+pub fn first_trades(n_agents: usize, trades: &[Trade]) -> Vec<Option<Tick>> {
+  let mut first = vec![None; n_agents];
+  for trade in trades {
+    for id in [trade.buyer, trade.seller] {
+      first[id] = first[id].or(Some(trade.tick));
+    }
+  }
+  first
+}
+
+// For each quintile of the indifference price of A at the start (0 the lowest), how
+// many of its agents traded and the median tick of their first trades. The extremes have the most
+// to gain, so a mechanism that serves them first shows up as the middle waiting.
+pub fn latency_by_preference(initial_assets: &[(Agent, Balance)], first: &[Option<Tick>]) -> Vec<(usize, Option<f64>)> {
+  let reservations: Vec<f64> = initial_assets.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).collect();
+  let ranks = quintiles(&reservations);
+  (0..QUINTILES).map(|q| {
+    let ticks: Vec<f64> = (0..first.len()).filter(|&id| ranks[id] == q).filter_map(|id| first[id]).map(|t| t as f64).collect();
+    (ticks.len(), if ticks.is_empty() { None } else { Some(quantile(&ticks, 0.5)) })
+  }).collect()
+}
+
+// number of agents with each trade count
+pub fn histogram(counts: &[usize]) -> BTreeMap<usize, usize> {
+  let mut result = BTreeMap::new();
+  for &count in counts {
+    *result.entry(count).or_insert(0) += 1;
+  }
+  result
+}
+
+// How concentrated trading was; undefined if nobody traded.
+pub fn activity_gini(counts: &[usize]) -> Option<f64> {
+  if counts.iter().all(|&c| c == 0) {
+    return None;
+  }
+  Some(gini(&counts.iter().map(|&c| c as f64).collect::<Vec<_>>()))
+}
+
+// Every agent that held goods but never traded, with the reason. `price` stands for
+// where the market went: an agent valuing A above it would have been a buyer.
+pub fn idle_agents(initial_assets: &[(Agent, Balance)], counts: &[usize], strategies: &Strategies, price: Price) -> Vec<(AgentId, IdleReason)> {
+  let blocked = strategies.agents_using(Strategy::Abstain);
+  initial_assets.iter().enumerate()
+    .filter(|(id, (_, balance))| counts[*id] == 0 && (balance.a > 0.0 || balance.b > 0.0))
+    .map(|(id, (agent, balance))| {
+      let wants_a = agent.indifference_price_at(balance) > price;
+      let reason = if blocked.contains(&id) {
+        IdleReason::Blocked
+      } else if (wants_a && balance.b == 0.0) || (!wants_a && balance.a == 0.0) {
+        IdleReason::NoBudget
+      } else {
+        IdleReason::NeverBestPriced
+      };
+      (id, reason)
+    })
+    .collect()
+}
+
+pub fn print_report(log: &RunLog, strategies: &Strategies) {
+  let counts = trade_counts(log.initial_assets.len(), &log.trades);
+  println!("trades per agent:");
+  for (count, agents) in histogram(&counts) {
+    println!("  {} trades: {} agents", count, agents);
+  }
+  match activity_gini(&counts) {
+    Some(g) => println!("Gini of trades per agent: {}", g),
+    None => println!("Gini of trades per agent: undefined (no trades)"),
+  }
+
+  let first = first_trades(log.initial_assets.len(), &log.trades);
+  let ticks: Vec<f64> = first.iter().flatten().map(|&t| t as f64).collect();
+  if !ticks.is_empty() {
+    println!("time to first trade: {} of {} agents traded; first trade at tick {} (median), {} (90th percentile), {} (last)",
+      ticks.len(), first.len(), quantile(&ticks, 0.5), quantile(&ticks, 0.9), quantile(&ticks, 1.0));
+    for (q, (traded, median)) in latency_by_preference(&log.initial_assets, &first).into_iter().enumerate() {
+      println!("  preference quintile {} (by value of A): {} traded, median first trade {}", q + 1, traded, median.map_or("n/a".to_string(), |m| m.to_string()));
+    }
+  }
+
+  let idle = idle_agents(&log.initial_assets, &counts, strategies, Summary::of(log).valuation_price);
+  if idle.is_empty() {
+    return;
+  }
+  println!("{} agents held goods but never traded:", idle.len());
+  let mut by_reason: BTreeMap<IdleReason, usize> = BTreeMap::new();
+  for (_, reason) in &idle {
+    *by_reason.entry(*reason).or_default() += 1;
+  }
+  for (reason, n) in by_reason {
+    println!("  {:?}: {}", reason, n);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::activity::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_idle_reasons() {
+    let assets = vec![
+      (linear_agent(1.0), Balance { a: 1.0, b: 0.0 }),
+      (linear_agent(3.0), Balance { a: 0.0, b: 1.0 }),
+      (linear_agent(3.0), Balance { a: 1.0, b: 0.0 }), // wants A, has no B
+      (linear_agent(2.5), Balance { a: 0.0, b: 1.0 }), // outbid by agent 1
+      (linear_agent(1.0), Balance { a: 1.0, b: 0.0 }),
+    ];
+    let trades = vec![Trade { tick: 0, buyer: 1, seller: 0, amount_a: 0.5, amount_b: 1.0, bid_price: 3.0, ask_price: 1.0, ..Trade::default() }];
+    let counts = trade_counts(assets.len(), &trades);
+    let first = first_trades(assets.len(), &trades);
+    assert_eq!(first, vec![Some(0), Some(0), None, None, None]);
+    // one agent per quintile: 0 values A least, then 4 (a tie, by id), 3, 1 and 2
+    let latency = latency_by_preference(&assets, &first);
+    assert_eq!((latency[0], latency[3], latency[2]), ((1, Some(0.0)), (1, Some(0.0)), (0, None)));
+    assert_eq!(histogram(&counts).into_iter().collect::<Vec<_>>(), vec![(0, 3), (1, 2)]);
+
+    let mut strategies = Strategies::truthful(assets.len());
+    strategies.set(4, Strategy::Abstain);
+    assert_eq!(idle_agents(&assets, &counts, &strategies, 2.0), vec![
+      (2, IdleReason::NoBudget),
+      (3, IdleReason::NeverBestPriced),
+      (4, IdleReason::Blocked),
+    ]);
+  }
+}
@@ -0,0 +1,112 @@
+// Non-market baselines for the same population, which give the market's numbers some
+// meaning: a random reallocation, in which the endowments are dealt back out to the
+// agents at random; equal division, in which everyone gets an equal share of each good;
+// and the planner's optimum (see planner), the ceiling on the utilitarian welfare any
+// mechanism could reach. None of them uses prices.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::inequality::gini;
+use crate::planner;
+use crate::welfare::{self, Normalization, Welfare};
+use crate::{realized_surplus, Agent, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Allocation {
+  Random,
+  EqualDivision,
+  Planner,
+}
+
+pub const ALL: [Allocation; 3] = [Allocation::Random, Allocation::EqualDivision, Allocation::Planner];
+
+impl Allocation {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Allocation::Random => "random",
+      Allocation::EqualDivision => "equal",
+      Allocation::Planner => "planner",
+    }
+  }
+
+  // Everyone's holdings under the mechanism, from the same totals as `initial`; None
+  // if the planner can't solve the population under `norm`.
+  pub fn allocate(&self, initial: &[(Agent, Balance)], norm: Normalization, rng: &mut StdRng) -> Option<Vec<(Agent, Balance)>> {
+    let agents = initial.iter().map(|(agent, _)| *agent);
+    match self {
+      Allocation::Random => {
+        let mut bundles: Vec<Balance> = initial.iter().map(|(_, balance)| *balance).collect();
+        bundles.shuffle(rng);
+        Some(agents.zip(bundles).collect())
+      }
+      Allocation::EqualDivision => {
+        let n = initial.len() as f64;
+        let share = Balance { a: total(initial, |b| b.a) / n, b: total(initial, |b| b.b) / n };
+        Some(agents.map(|agent| (agent, share)).collect())
+      }
+      Allocation::Planner => Some(agents.zip(planner::solve(initial, norm)?).collect()),
+    }
+  }
+}
+
+fn total(assets: &[(Agent, Balance)], good: impl Fn(&Balance) -> f64) -> f64 {
+  assets.iter().map(|(_, balance)| good(balance)).sum()
+}
+
+pub fn print_report(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], fns: &[Welfare], norm: Normalization, price: Price, rng: &mut StdRng) {
+  let mut columns = vec![Some(last.to_vec())];
+  columns.extend(ALL.iter().map(|m| m.allocate(initial, norm, rng)));
+  println!("against non-market allocations of the same endowment:");
+  println!("  {:<22}{:>12}{}", "", "market", ALL.iter().map(|m| format!("{:>12}", m.name())).collect::<String>());
+  print_row("total surplus", &columns, |assets| Some(realized_surplus(initial, assets).iter().sum()));
+  for f in fns {
+    print_row(&format!("{} welfare", f.name()), &columns, |assets| welfare::welfare(*f, norm, initial, assets));
+  }
+  // rounding can take an even split's gini a hair below 0
+  print_row(&format!("gini (at {:.4})", price), &columns, |assets| Some(gini(&assets.iter().map(|(_, b)| price * b.a + b.b).collect::<Vec<_>>()).max(0.0)));
+  if let Some(planned) = &columns[3] {
+    let utilitarian = |assets: &[(Agent, Balance)]| welfare::welfare(Welfare::Utilitarian, norm, initial, assets);
+    if let (Some(start), Some(market), Some(ceiling)) = (utilitarian(initial), utilitarian(last), utilitarian(planned)) {
+      println!("  the market realizes {:.4} of the gain in utilitarian welfare the planner does", (market - start) / (ceiling - start));
+    }
+  }
+}
+
+fn print_row(label: &str, columns: &[Option<Vec<(Agent, Balance)>>], metric: impl Fn(&[(Agent, Balance)]) -> Option<f64>) {
+  let show = |x: Option<f64>| format!("{:>12}", x.map_or("n/a".to_string(), |x| format!("{:.4}", x)));
+  println!("  {:<22}{}", label, columns.iter().map(|assets| show(assets.as_deref().and_then(&metric))).collect::<String>());
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::allocation::*;
+  use crate::utility::UtilityFn;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_allocations() {
+    let agent = |ca, utility_fn| Agent { utility_fn, ..linear_agent(ca) };
+    let initial = vec![
+      (agent(3.0, UtilityFn::Linear), Balance { a: 0.0, b: 4.0 }),
+      (agent(1.0, UtilityFn::Log), Balance { a: 2.0, b: 0.0 }),
+      (agent(7.0, UtilityFn::Log), Balance { a: 4.0, b: 2.0 }),
+    ];
+    let rng = &mut StdRng::seed_from_u64(0);
+    let mut dealt: Vec<Balance> = Allocation::Random.allocate(&initial, Normalization::Raw, rng).unwrap().iter().map(|(_, b)| *b).collect();
+    dealt.sort_by(|x, y| x.a.total_cmp(&y.a));
+    assert_eq!(dealt, vec![Balance { a: 0.0, b: 4.0 }, Balance { a: 2.0, b: 0.0 }, Balance { a: 4.0, b: 2.0 }]);
+    assert!(Allocation::EqualDivision.allocate(&initial, Normalization::Raw, rng).unwrap().iter().all(|(_, b)| *b == Balance { a: 2.0, b: 2.0 }));
+
+    // the planner's is the best of them under the normalization it solved for, with
+    // Cobb-Douglas agents too
+    let cobb: Vec<(Agent, Balance)> = initial.iter().map(|&(a, b)| (Agent { utility_fn: UtilityFn::CobbDouglas, ..a }, Balance { a: b.a + 1.0, b: b.b + 1.0 })).collect();
+    for assets in [&initial, &cobb] {
+      let welfare = |m: Allocation, rng: &mut StdRng| welfare::welfare(Welfare::Utilitarian, Normalization::Endowment, assets, &m.allocate(assets, Normalization::Endowment, rng).unwrap());
+      let ceiling = welfare(Allocation::Planner, rng).unwrap();
+      assert!([Allocation::Random, Allocation::EqualDivision].iter().all(|&m| welfare(m, rng).unwrap() <= ceiling));
+    }
+  }
+}
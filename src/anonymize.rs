@@ -0,0 +1,96 @@
+// A run log made fit to publish. Agent ids are replaced by a secret random
+// permutation, everywhere they appear, and each agent's four parameters are scaled by
+// independent noise in [1 - jitter, 1 + jitter], so the calibrated values can't be
+// read off; the seed, which would regenerate them, is dropped. Holdings and trades are
+// left exact, so the price path, volumes and every aggregate over balances are the
+// run's own, and the log still replays.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::risk::RejectReason;
+use crate::runlog::RunLog;
+use crate::{Agent, AgentId, Trade};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Anonymizer {
+  pub jitter: f64,
+}
+
+impl Anonymizer {
+  pub fn new(jitter: f64) -> Result<Anonymizer, String> {
+    if !(0.0..1.0).contains(&jitter) {
+      return Err(format!("jitter must be in [0, 1), got {}", jitter));
+    }
+    Ok(Anonymizer { jitter })
+  }
+
+  // `rng` should be seeded from something kept private, or the permutation and noise
+  // can be drawn again.
+  pub fn apply(&self, log: &RunLog, rng: &mut StdRng) -> RunLog {
+    let n = log.initial_assets.len();
+    // new id of each old one
+    let mut ids: Vec<AgentId> = (0..n).collect();
+    ids.shuffle(rng);
+    let mut noise = |x: f64| x * (1.0 + self.jitter * rng.gen_range(-1.0, 1.0));
+    let mut initial_assets = log.initial_assets.clone();
+    for (old, (agent, balance)) in log.initial_assets.iter().enumerate() {
+      let agent = Agent {
+        production_a: noise(agent.production_a),
+        production_b: noise(agent.production_b),
+        consumption_a_coeff: noise(agent.consumption_a_coeff),
+        consumption_b_coeff: noise(agent.consumption_b_coeff),
+        ..*agent
+      };
+      initial_assets[ids[old]] = (agent, *balance);
+    }
+    let trade = |t: &Trade| Trade { buyer: ids[t.buyer], seller: ids[t.seller], ..t.clone() };
+    RunLog {
+      seed: 0,
+      initial_assets,
+      quotes: log.quotes.iter().map(|q| { let mut q = q.clone(); q.agent_id = ids[q.agent_id]; q }).collect(),
+      rejections: log.rejections.iter().map(|r| {
+        let mut r = r.clone();
+        r.trade = trade(&r.trade);
+        if let RejectReason::InsufficientBalance { agent } = &mut r.reason {
+          *agent = ids[*agent];
+        }
+        r
+      }).collect(),
+      trades: log.trades.iter().map(trade).collect(),
+      stop: log.stop,
+      decay: log.decay,
+      short: log.short,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::anonymize::*;
+  use crate::simulation::SimulationBuilder;
+  use crate::summary::Summary;
+
+  #[test]
+  fn test_anonymize() {
+    let log = SimulationBuilder::new().agents(20).build().unwrap().run();
+    let shared = Anonymizer::new(0.1).unwrap().apply(&log, &mut StdRng::seed_from_u64(9));
+    assert_eq!(shared.seed, 0);
+    // the same holdings, under other names
+    let mut before: Vec<(f64, f64)> = log.final_assets().iter().map(|(_, b)| (b.a, b.b)).collect();
+    let mut after: Vec<(f64, f64)> = shared.final_assets().iter().map(|(_, b)| (b.a, b.b)).collect();
+    assert_ne!(before, after);
+    before.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    after.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    assert_eq!(before, after);
+    let (s, t) = (Summary::of(&log), Summary::of(&shared));
+    assert_eq!((s.mean_price, s.gini_final), (t.mean_price, t.gini_final));
+    // and no agent keeps its exact parameters
+    let agents: Vec<Agent> = shared.initial_assets.iter().map(|(a, _)| *a).collect();
+    assert!(log.initial_assets.iter().all(|(a, _)| !agents.contains(a)));
+    assert!(Anonymizer::new(1.0).is_err());
+  }
+}
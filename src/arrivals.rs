@@ -0,0 +1,72 @@
+// When agents get to (re)quote. By default everyone requotes every tick; with Poisson
+// arrivals each agent requotes only when its own process fires, and the rest of the
+// time its last quote stands. That makes the order flow asynchronous, and the
+// intensities set how fast the market moves regardless of how many agents there are.
+// Whether an agent arrives at a tick is a common random number (see crn), so it's the
+// same whatever else differs between two runs of a seed. So is the order agents
+// arriving in the same tick come in, for matching them as they arrive (see continuous).
+
+use crate::clock::Tick;
+use crate::crn::{Crn, Stream};
+use crate::AgentId;
+
+pub enum Arrivals {
+  EveryTick { crn: Crn },
+  // per-agent intensity, in quoting events per tick
+  Poisson { rates: Vec<f64>, crn: Crn },
+}
+
+impl Arrivals {
+  pub fn every_tick(seed: u64) -> Arrivals {
+    Arrivals::EveryTick { crn: Crn::new(seed) }
+  }
+
+  // Intensities drawn uniformly from [lo, hi] (all equal if lo == hi).
+  pub fn poisson(n_agents: usize, (lo, hi): (f64, f64), seed: u64) -> Arrivals {
+    assert!(lo > 0.0 && lo <= hi, "arrival rates must be positive, got {}..{}", lo, hi);
+    let crn = Crn::new(seed);
+    let rates = (0..n_agents).map(|id| lo + (hi - lo) * crn.uniform(Stream::ArrivalRates, &[id as u64])).collect();
+    Arrivals::Poisson { rates, crn }
+  }
+
+  // Whether `agent` has at least one quoting event during this tick.
+  pub fn arrives(&self, agent: usize, tick: Tick) -> bool {
+    match self {
+      Arrivals::EveryTick { .. } => true,
+      Arrivals::Poisson { rates, crn } => crn.chance(1.0 - (-rates[agent]).exp(), Stream::Arrivals, agent, tick, 0),
+    }
+  }
+
+  // The agents arriving during `tick`, in the order they arrive.
+  pub fn queue(&self, n_agents: usize, tick: Tick) -> Vec<AgentId> {
+    let (Arrivals::EveryTick { crn } | Arrivals::Poisson { crn, .. }) = self;
+    let mut queue: Vec<(f64, AgentId)> = (0..n_agents)
+      .filter(|&id| self.arrives(id, tick))
+      .map(|id| (crn.uniform(Stream::ArrivalOrder, &[id as u64, tick]), id))
+      .collect();
+    queue.sort_by(|x, y| x.0.total_cmp(&y.0));
+    queue.into_iter().map(|(_, id)| id).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arrivals::*;
+
+  #[test]
+  fn test_poisson_arrival_frequency() {
+    let arrivals = Arrivals::poisson(2, (0.1, 0.1), 0);
+    let ticks = 100_000;
+    let hits = (0..ticks).filter(|&t| arrivals.arrives(1, t)).count();
+    // P(at least one event in a tick) = 1 - e^-0.1 ~= 0.095
+    assert!((hits as f64 / ticks as f64 - 0.0952).abs() < 0.005);
+    let every_tick = Arrivals::every_tick(0);
+    assert!((0..10).all(|t| every_tick.arrives(0, t)));
+    // everyone, shuffled differently each tick
+    let (first, second) = (every_tick.queue(10, 0), every_tick.queue(10, 1));
+    assert_ne!(first, second);
+    let mut sorted = first.clone();
+    sorted.sort_unstable();
+    assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+  }
+}
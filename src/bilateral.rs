@@ -0,0 +1,140 @@
+// Every pair's potential gains from trading with each other alone, at the initial
+// endowment, against which pairs actually traded. A pair's potential is what its
+// two truthful quotes would gain if matched with nobody else in the way: the whole of
+// the seller's A or as much as the buyer's B pays for, at the pricing rule's price,
+// or for non-linear agents only as much as both their demands take at that price.
+// The potentials overlap (an agent's goods can only go to one partner), so their sum
+// isn't attainable; what matters is where the potential the mechanism passed over
+// lies, which the report breaks down by the buyer's and seller's preference quintiles.
+// Quadratic in the population, so it's for small ones.
+
+use std::fmt::Write;
+
+use crate::mobility::{quintiles, QUINTILES};
+use crate::pricing::PricingRule;
+use crate::runlog::RunLog;
+use crate::{Agent, AgentId, Balance};
+
+pub const MAX_AGENTS: usize = 2000;
+
+// [buyer][seller] utils, 0 unless the buyer values A more than the seller.
+pub fn potential(assets: &[(Agent, Balance)], pricing: PricingRule) -> Vec<Vec<f64>> {
+  assets.iter().map(|(buyer, buyer_balance)| {
+    assets.iter().map(|(seller, seller_balance)| {
+      let (bid, ask) = (buyer.indifference_price_at(buyer_balance), seller.indifference_price_at(seller_balance));
+      if bid <= ask {
+        return 0.0;
+      }
+      let price = pricing.price(bid, ask);
+      if buyer.utility_fn.is_linear() && seller.utility_fn.is_linear() {
+        let a = seller_balance.a.min(buyer_balance.b / price);
+        return (buyer.utility(a, -a * price) + seller.utility(-a, a * price)).max(0.0);
+      }
+      let a = (-seller.demand_for_a(seller_balance, price)).min(buyer.demand_for_a(buyer_balance, price)).max(0.0);
+      let gain = |agent: &Agent, balance: &Balance, a: f64| {
+        agent.utility(balance.a + a, balance.b - a * price) - agent.utility(balance.a, balance.b)
+      };
+      (gain(buyer, buyer_balance, a) + gain(seller, seller_balance, -a)).max(0.0)
+    }).collect()
+  }).collect()
+}
+
+// [buyer][seller], whether they traded that way round.
+pub fn traded(n_agents: usize, log: &RunLog) -> Vec<Vec<bool>> {
+  let mut out = vec![vec![false; n_agents]; n_agents];
+  for trade in &log.trades {
+    out[trade.buyer][trade.seller] = true;
+  }
+  out
+}
+
+// One row per pair with any potential.
+pub fn csv(potential: &[Vec<f64>], traded: &[Vec<bool>]) -> String {
+  let mut out = String::from("buyer,seller,potential,traded\n");
+  for (buyer, row) in potential.iter().enumerate() {
+    for (seller, &p) in row.iter().enumerate().filter(|(_, &p)| p > 0.0) {
+      writeln!(out, "{},{},{},{}", buyer, seller, p, traded[buyer][seller]).unwrap();
+    }
+  }
+  out
+}
+
+// [buyer quintile][seller quintile] (potential, potential of the pairs that traded),
+// quintiles by the indifference price of A, 0 the lowest.
+pub fn by_preference(assets: &[(Agent, Balance)], potential: &[Vec<f64>], traded: &[Vec<bool>]) -> [[(f64, f64); QUINTILES]; QUINTILES] {
+  let reservations: Vec<f64> = assets.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).collect();
+  let ranks = quintiles(&reservations);
+  let mut out = [[(0.0, 0.0); QUINTILES]; QUINTILES];
+  for (buyer, row) in potential.iter().enumerate() {
+    for (seller, &p) in row.iter().enumerate() {
+      let cell = &mut out[ranks[buyer]][ranks[seller]];
+      cell.0 += p;
+      if traded[buyer][seller] {
+        cell.1 += p;
+      }
+    }
+  }
+  out
+}
+
+pub fn print_report(log: &RunLog, pricing: PricingRule) {
+  let n = log.initial_assets.len();
+  let potential = potential(&log.initial_assets, pricing);
+  let traded = traded(n, log);
+  let pairs: Vec<(AgentId, AgentId, f64)> = (0..n).flat_map(|b| (0..n).map(move |s| (b, s)))
+    .map(|(b, s)| (b, s, potential[b][s])).filter(|p| p.2 > 0.0).collect();
+  let captured: Vec<&(AgentId, AgentId, f64)> = pairs.iter().filter(|(b, s, _)| traded[*b][*s]).collect();
+  let total = |ps: &mut dyn Iterator<Item = &(AgentId, AgentId, f64)>| ps.map(|p| p.2).sum::<f64>();
+  println!("pairwise gains from trade at the initial endowment:");
+  println!("  {} of {} ordered pairs could gain from trading; {} of them traded, holding {} of {} potential utils",
+    pairs.len(), n * (n - 1), captured.len(), total(&mut captured.iter().copied()), total(&mut pairs.iter()));
+  let mut missed: Vec<&(AgentId, AgentId, f64)> = pairs.iter().filter(|(b, s, _)| !traded[*b][*s]).collect();
+  missed.sort_by(|x, y| y.2.partial_cmp(&x.2).unwrap());
+  for (buyer, seller, p) in missed.iter().take(5) {
+    println!("  missed: agent {} buying from agent {} ({} utils)", buyer, seller, p);
+  }
+  println!("  share of potential in pairs that traded, by preference quintile (rows: buyer, from lowest; columns: seller):");
+  for row in by_preference(&log.initial_assets, &potential, &traded) {
+    let row: Vec<String> = row.iter().map(|(p, c)| if *p > 0.0 { format!("{:.2}", c / p) } else { "-".to_string() }).collect();
+    println!("    [{}]", row.join(", "));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bilateral::*;
+  use crate::Trade;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_potential() {
+    let assets = vec![
+      (linear_agent(1.0), Balance { a: 10.0, b: 0.0 }),
+      (linear_agent(3.0), Balance { a: 0.0, b: 4.0 }),
+      (linear_agent(5.0), Balance { a: 0.0, b: 30.0 }),
+    ];
+    let potential = potential(&assets, PricingRule::default());
+    // agent 1 pays 2 per A, affording 2 A, each worth 2 more to it than to agent 0
+    assert_eq!(potential[1][0], 4.0);
+    // agent 2 pays 3 per A for all 10 of agent 0's
+    assert_eq!(potential[2][0], 40.0);
+    // agent 1 has no A to sell
+    assert_eq!((potential[0][1], potential[2][1]), (0.0, 0.0));
+
+    let log = RunLog {
+      seed: 0,
+      initial_assets: assets.clone(),
+      quotes: vec![],
+      rejections: vec![],
+      trades: vec![Trade { tick: 0, buyer: 2, seller: 0, amount_a: 10.0, amount_b: 30.0, bid_price: 5.0, ask_price: 1.0, ..Trade::default() }],
+      stop: None,
+      decay: None,
+      short: None,
+    };
+    let traded = traded(3, &log);
+    assert_eq!(csv(&potential, &traded), "buyer,seller,potential,traded\n1,0,4,false\n2,0,40,true\n");
+    // agents 0, 1 and 2 fall in preference quintiles 0, 1 and 3
+    let cells = by_preference(&assets, &potential, &traded);
+    assert_eq!((cells[1][0], cells[3][0]), ((4.0, 0.0), (40.0, 40.0)));
+  }
+}
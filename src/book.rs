@@ -0,0 +1,205 @@
+// The order book: everyone's standing (bid, ask), with the bids and the asks also
+// kept sorted by price, so the matching pass finds the best of each in log time
+// instead of scanning every agent's quote. Requoting an agent touches only its own
+// entries. Ties in price go as a scan of the quotes in agent order would break them:
+// the lowest-numbered of the lowest asks and the highest-numbered of the highest bids;
+// or, with time priority (see continuous), to whichever order was entered first. An
+// order requoted at the same price keeps its place.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::Bound::{Excluded, Included, Unbounded};
+
+use crate::limits::Enforcement;
+use crate::pricing::PricingRule;
+use crate::{all_orders, generate_orders, Agent, AgentId, Balance, Order, Price};
+
+// A price, the order's rank among others at that price, and whose order it is. The
+// rank is the agent, or for time priority when it was entered (counting down for
+// bids, so the earliest is last).
+#[derive(PartialEq, Debug, Copy, Clone)]
+struct Key(Price, u64, AgentId);
+
+impl Eq for Key {}
+
+impl Ord for Key {
+  fn cmp(&self, other: &Key) -> Ordering {
+    self.0.total_cmp(&other.0).then(self.1.cmp(&other.1)).then(self.2.cmp(&other.2))
+  }
+}
+
+impl PartialOrd for Key {
+  fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+  quotes: Vec<(Option<Order>, Option<Order>)>,
+  bids: BTreeSet<Key>,
+  asks: BTreeSet<Key>,
+  // when each side of each agent's order was entered, with time priority
+  entered: Option<Vec<(u64, u64)>>,
+  next_entry: u64,
+}
+
+impl OrderBook {
+  pub fn new(n_agents: usize) -> OrderBook {
+    OrderBook { quotes: vec![(None, None); n_agents], ..OrderBook::default() }
+  }
+
+  pub fn with_time_priority(n_agents: usize) -> OrderBook {
+    OrderBook { entered: Some(vec![(0, 0); n_agents]), ..OrderBook::new(n_agents) }
+  }
+
+  // A time-priority book as `entered` says its orders were entered.
+  pub fn restore(quotes: &[(Option<Order>, Option<Order>)], entered: &[(u64, u64)]) -> OrderBook {
+    let mut book = OrderBook::with_time_priority(quotes.len());
+    let mut order: Vec<(u64, AgentId, bool)> = entered.iter().enumerate().flat_map(|(id, &(b, a))| [(b, id, true), (a, id, false)]).collect();
+    order.sort_unstable();
+    for (_, id, bid) in order {
+      if bid { book.set_bid(id, quotes[id].0) } else { book.set_ask(id, quotes[id].1) }
+    }
+    book
+  }
+
+  pub fn from_quotes(quotes: &[(Option<Order>, Option<Order>)]) -> OrderBook {
+    let mut book = OrderBook::new(quotes.len());
+    for (id, quote) in quotes.iter().enumerate() {
+      book.set(id, *quote);
+    }
+    book
+  }
+
+  // Everyone quoting truthfully, as generate_orders has them.
+  pub fn truthful(assets: &[(Agent, Balance)]) -> OrderBook {
+    OrderBook::from_quotes(&all_orders(assets))
+  }
+
+  // Quotes `id` truthfully again, once its balance has changed.
+  pub fn requote(&mut self, id: AgentId, assets: &[(Agent, Balance)]) {
+    let (agent, balance) = &assets[id];
+    self.set(id, generate_orders(id, agent, balance));
+  }
+
+  // The highest bid and lowest ask whatever their prices, as best_quotes has them.
+  pub fn best(&self) -> (Option<Price>, Option<Price>) {
+    (self.bids.iter().next_back().map(|k| k.0), self.asks.iter().next().map(|k| k.0))
+  }
+
+  // Indexed by agent.
+  pub fn quotes(&self) -> &[(Option<Order>, Option<Order>)] {
+    &self.quotes
+  }
+
+  pub fn set(&mut self, id: AgentId, (bid, ask): (Option<Order>, Option<Order>)) {
+    self.set_bid(id, bid);
+    self.set_ask(id, ask);
+  }
+
+  // When each side of each agent's order was entered, for a time-priority book.
+  pub fn entered(&self) -> Option<&[(u64, u64)]> {
+    self.entered.as_deref()
+  }
+
+  pub fn set_bid(&mut self, id: AgentId, bid: Option<Order>) {
+    if self.entered.is_some() && self.quotes[id].0 == bid {
+      return;
+    }
+    let rank = |book: &OrderBook| book.entered.as_ref().map_or(id as u64, |e| u64::MAX - e[id].0);
+    if let Some(old) = self.quotes[id].0 {
+      self.bids.remove(&Key(old.price_per_a_in_b, rank(self), id));
+    }
+    self.quotes[id].0 = bid;
+    if let Some(new) = bid {
+      self.stamp(id, true);
+      self.bids.insert(Key(new.price_per_a_in_b, rank(self), id));
+    }
+  }
+
+  pub fn set_ask(&mut self, id: AgentId, ask: Option<Order>) {
+    if self.entered.is_some() && self.quotes[id].1 == ask {
+      return;
+    }
+    let rank = |book: &OrderBook| book.entered.as_ref().map_or(id as u64, |e| e[id].1);
+    if let Some(old) = self.quotes[id].1 {
+      self.asks.remove(&Key(old.price_per_a_in_b, rank(self), id));
+    }
+    self.quotes[id].1 = ask;
+    if let Some(new) = ask {
+      self.stamp(id, false);
+      self.asks.insert(Key(new.price_per_a_in_b, rank(self), id));
+    }
+  }
+
+  fn stamp(&mut self, id: AgentId, bid: bool) {
+    if let Some(entered) = self.entered.as_mut() {
+      let side = if bid { &mut entered[id].0 } else { &mut entered[id].1 };
+      *side = self.next_entry;
+      self.next_entry += 1;
+    }
+  }
+
+  // The highest bid the pricing rule admits, if any.
+  pub fn highest_bid(&self, pricing: PricingRule) -> Option<Order> {
+    // limits apply to the bid net of tax
+    let lo = pricing.floor.map_or(Unbounded, |floor| Excluded(Key(pricing.gross(floor), u64::MAX, AgentId::MAX)));
+    let hi = match pricing.cap {
+      Some(cap) if pricing.enforcement == Enforcement::Reject => Included(Key(pricing.gross(cap), u64::MAX, AgentId::MAX)),
+      _ => Unbounded,
+    };
+    self.bids.range((lo, hi)).next_back().map(|k| self.quotes[k.2].0.unwrap())
+  }
+
+  // The lowest ask the pricing rule admits under `below` (strictly), if any.
+  pub fn lowest_ask(&self, pricing: PricingRule, below: Option<Price>) -> Option<Order> {
+    let lo = match pricing.floor {
+      Some(floor) if pricing.enforcement == Enforcement::Reject => Included(Key(floor, 0, 0)),
+      _ => Unbounded,
+    };
+    let hi = [pricing.cap, below].iter().flatten().fold(None, |m: Option<Price>, &p| Some(m.map_or(p, |m| m.min(p))));
+    let hi = hi.map_or(Unbounded, |hi| Excluded(Key(hi, 0, 0)));
+    if matches!((lo, hi), (Included(l), Excluded(h)) if l > h) {
+      return None;
+    }
+    self.asks.range((lo, hi)).next().map(|k| self.quotes[k.2].1.unwrap())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::book::*;
+  use crate::OrderType;
+
+  #[test]
+  fn test_order_book() {
+    let quote = |id, bid: Option<f64>, ask: Option<f64>| (
+      bid.map(|p| Order { agent_id: id, typ: OrderType::Bid, price_per_a_in_b: p }),
+      ask.map(|p| Order { agent_id: id, typ: OrderType::Ask, price_per_a_in_b: p }),
+    );
+    let mut book = OrderBook::from_quotes(&[quote(0, Some(3.0), Some(1.0)), quote(1, Some(3.0), Some(1.0)), quote(2, Some(2.0), Some(2.5))]);
+    let pricing = PricingRule::default();
+    // ties as a scan in agent order breaks them
+    assert_eq!((book.highest_bid(pricing).unwrap().agent_id, book.lowest_ask(pricing, None).unwrap().agent_id), (1, 0));
+    book.set(1, quote(1, None, Some(0.5)));
+    assert_eq!((book.highest_bid(pricing).unwrap().agent_id, book.lowest_ask(pricing, None).unwrap().agent_id), (0, 1));
+    assert_eq!(book.lowest_ask(pricing, Some(0.5)), None);
+    // a rejecting floor of 1 shuts out the ask of 0.5 and takes the one at 1
+    let floor = pricing.with_limits(Some(1.0), None).with_enforcement(Enforcement::Reject);
+    assert_eq!(book.lowest_ask(floor, None).unwrap().agent_id, 0);
+    assert_eq!(book.quotes()[1].0, None);
+
+    // replaying trades, requoting only their parties keeps the book truthful
+    let log = crate::simulation::SimulationBuilder::new().agents(20).build().unwrap().run();
+    let mut assets = log.initial_assets.clone();
+    let mut book = OrderBook::truthful(&assets);
+    for trade in &log.trades {
+      crate::settle(&mut assets, trade);
+      book.requote(trade.buyer, &assets);
+      book.requote(trade.seller, &assets);
+      assert_eq!(book.best(), crate::best_quotes(&assets));
+    }
+    assert_eq!(book.quotes(), OrderBook::truthful(&assets).quotes());
+  }
+}
@@ -0,0 +1,93 @@
+// Confidence intervals for a single run's statistics, by bootstrap: resample the
+// run's trades (or its agents, for statistics over agents) with replacement, recompute
+// the statistic on each resample, and take the middle 95% of those. The width is how
+// much the number owes to which particular trades or agents the run happened to have,
+// so a difference between two mechanisms that's well outside both intervals is more
+// than sampling noise, without running hundreds of seeds to find out.
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::inequality::gini;
+use crate::pricing::total_improvement;
+use crate::runlog::RunLog;
+use crate::stats::quantile;
+use crate::summary::{wealth_in_b, Summary};
+use crate::realized_surplus;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Interval {
+  // on the run itself
+  pub estimate: f64,
+  pub lo: f64,
+  pub hi: f64,
+}
+
+// The percentile interval of `statistic` over `n_resamples` resamples of `items`;
+// None if the statistic is undefined on the run itself.
+pub fn bootstrap<T: Clone>(items: &[T], statistic: impl Fn(&[T]) -> Option<f64>, n_resamples: usize, rng: &mut StdRng) -> Option<Interval> {
+  let estimate = statistic(items)?;
+  let stats: Vec<f64> = (0..n_resamples)
+    .filter_map(|_| {
+      let resample: Vec<T> = (0..items.len()).map(|_| items[rng.gen_range(0, items.len())].clone()).collect();
+      statistic(&resample)
+    })
+    .collect();
+  if stats.is_empty() {
+    return None;
+  }
+  Some(Interval { estimate, lo: quantile(&stats, 0.025), hi: quantile(&stats, 0.975) })
+}
+
+// Intervals for the run's volume-weighted mean price, the buyers' share of the quoted
+// surplus, the total realized surplus and the final wealth Gini (at the run's
+// valuation price, held fixed), each named as in Summary::metrics.
+pub fn intervals(log: &RunLog, n_resamples: usize, rng: &mut StdRng) -> Vec<(&'static str, Option<Interval>)> {
+  let final_assets = log.final_assets();
+  let valuation_price = Summary::of(log).valuation_price;
+  let surplus = realized_surplus(&log.initial_assets, &final_assets);
+  vec![
+    ("mean_price", bootstrap(&log.trades, |trades| {
+      let volume_a: f64 = trades.iter().map(|t| t.amount_a).sum();
+      if volume_a > 0.0 { Some(trades.iter().map(|t| t.amount_b).sum::<f64>() / volume_a) } else { None }
+    }, n_resamples, rng)),
+    ("buyer_surplus_share", bootstrap(&log.trades, |trades| {
+      let (buyers, sellers) = total_improvement(trades);
+      if buyers + sellers > 0.0 { Some(buyers / (buyers + sellers)) } else { None }
+    }, n_resamples, rng)),
+    ("total_surplus", bootstrap(&surplus, |s| Some(s.iter().sum()), n_resamples, rng)),
+    ("gini_final", bootstrap(&wealth_in_b(&final_assets, valuation_price), |w| Some(gini(w)), n_resamples, rng)),
+  ]
+}
+
+pub fn print_report(intervals: &[(&str, Option<Interval>)], n_resamples: usize) {
+  println!("bootstrap 95% intervals ({} resamples of the run's trades or agents):", n_resamples);
+  for (name, interval) in intervals {
+    match interval {
+      Some(i) => println!("  {}: {} [{}, {}]", name, i.estimate, i.lo, i.hi),
+      None => println!("  {}: n/a", name),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::bootstrap::*;
+
+  #[test]
+  fn test_bootstrap() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mean = |xs: &[f64]| Some(crate::stats::mean(xs));
+    // resamples of identical values are all the same
+    assert_eq!(bootstrap(&[2.0; 10], mean, 100, &mut rng), Some(Interval { estimate: 2.0, lo: 2.0, hi: 2.0 }));
+    // a mean of 100 draws uniform on [0, 1) has a standard error near 0.029, so the
+    // interval is about 0.11 wide around the estimate
+    let xs: Vec<f64> = (0..100).map(|_| rng.gen()).collect();
+    let i = bootstrap(&xs, mean, 2000, &mut rng).unwrap();
+    assert!(i.lo < i.estimate && i.estimate < i.hi);
+    assert!((i.hi - i.lo - 0.11).abs() < 0.03, "{:?}", i);
+    assert_eq!(bootstrap(&[] as &[f64], |_| None, 100, &mut rng), None);
+  }
+}
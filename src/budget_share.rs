@@ -0,0 +1,69 @@
+// With linear utility every agent's optimum is a corner: all its wealth in whichever
+// good it values more at the market price. How far agents end up from that corner
+// measures the frictions in the mechanism.
+
+use crate::stats::{mean, quantile};
+use crate::{Agent, Balance, Price};
+
+// an agent with at least this share is counted as having reached its corner
+pub const CORNER: f64 = 0.99;
+
+// Each agent's share of wealth (valued at `price`) held in the good it prefers at
+// that price. Agents with no wealth, or indifferent at `price`, are left out, and so
+// are non-linear ones, whose optimum is inside the budget rather than at a corner.
+pub fn preferred_shares(assets: &[(Agent, Balance)], price: Price) -> Vec<f64> {
+  assets.iter()
+    .filter(|(agent, _)| agent.utility_fn.is_linear())
+    .filter_map(|(agent, balance)| {
+      let wealth = balance.a * price + balance.b;
+      let reservation = agent.indifference_price_of_a_in_b();
+      if wealth == 0.0 || reservation == price {
+        None
+      } else if reservation > price {
+        Some(balance.a * price / wealth)
+      } else {
+        Some(balance.b / wealth)
+      }
+    })
+    .collect()
+}
+
+// Fraction of agents (among those with a preference) holding at least CORNER of
+// their wealth in their preferred good.
+pub fn corner_fraction(shares: &[f64]) -> f64 {
+  shares.iter().filter(|&&s| s >= CORNER).count() as f64 / shares.len() as f64
+}
+
+pub fn print_report(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], price: Price) {
+  println!("share of wealth held in the preferred good (at price {}):", price);
+  for (label, assets) in [("initial", initial), ("final", last)] {
+    let shares = preferred_shares(assets, price);
+    if shares.is_empty() {
+      println!("  {}: no linear agents with a preference", label);
+      continue;
+    }
+    println!("  {}: mean {}, 10% {}, median {}, {}% of agents at a corner", label,
+      mean(&shares), quantile(&shares, 0.1), quantile(&shares, 0.5), 100.0 * corner_fraction(&shares));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::budget_share::*;
+  use crate::utility::UtilityFn;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_preferred_shares() {
+    let assets = vec![
+      (linear_agent(3.0), Balance { a: 1.0, b: 2.0 }), // likes A: 2 of 4 in A
+      (linear_agent(1.0), Balance { a: 0.0, b: 5.0 }), // likes B, all in B
+      (linear_agent(2.0), Balance { a: 1.0, b: 1.0 }), // indifferent at 2
+      (linear_agent(3.0), Balance { a: 0.0, b: 0.0 }), // nothing
+      (Agent { utility_fn: UtilityFn::CobbDouglas, ..linear_agent(3.0) }, Balance { a: 0.0, b: 5.0 }), // no corner to reach
+    ];
+    let shares = preferred_shares(&assets, 2.0);
+    assert_eq!(shares, vec![0.5, 1.0]);
+    assert_eq!(corner_fraction(&shares), 0.5);
+  }
+}
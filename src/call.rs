@@ -0,0 +1,135 @@
+// The one-shot call auction, a theoretical baseline against the sequential market:
+// everyone's orders are collected at once and all crossing orders trade at a single
+// uniform price, the one that maximizes the volume of A traded. Demand at a price is
+// what every bid above it would buy there (all its B, for a linear buyer) and supply
+// what every ask below it would sell; the short side fills completely and the long
+// side is rationed pro rata. Candidate prices are the midpoints between adjacent
+// order prices, so every order that trades strictly gains, as in the k-double
+// auction; ties go to the smaller imbalance, then the lower price. A floor or cap
+// clamps the price as it does there.
+//
+// The fills are paired off in price priority, the highest bids against the lowest
+// asks, to record them as trades. The market then stops, cleared, after one tick.
+
+use crate::pricing::PricingRule;
+use crate::clock::Tick;
+use crate::{Agent, Balance, Order, OrderType, Price, Trade};
+
+// The A a buyer would take at `price`, or a seller give.
+fn quantity(agent: &Agent, balance: &Balance, order: &Order, price: Price) -> f64 {
+  match (order.typ, agent.utility_fn.is_linear()) {
+    (OrderType::Bid, true) => balance.b / price,
+    (OrderType::Bid, false) => agent.demand_for_a(balance, price).max(0.0),
+    (OrderType::Ask, true) => balance.a,
+    (OrderType::Ask, false) => (-agent.demand_for_a(balance, price)).max(0.0),
+  }
+}
+
+// Demand and supply at `price`.
+fn depth(assets: &[(Agent, Balance)], bids: &[Order], asks: &[Order], price: Price) -> (f64, f64) {
+  let total = |orders: &[Order], crosses: &dyn Fn(&Order) -> bool| -> f64 {
+    orders.iter().filter(|o| crosses(o)).map(|o| quantity(&assets[o.agent_id].0, &assets[o.agent_id].1, o, price)).sum()
+  };
+  (total(bids, &|o| o.price_per_a_in_b > price), total(asks, &|o| o.price_per_a_in_b < price))
+}
+
+fn clamp(pricing: PricingRule, price: Price) -> Price {
+  let price = pricing.floor.map_or(price, |floor| price.max(floor));
+  pricing.cap.map_or(price, |cap| price.min(cap))
+}
+
+// The pricing rule's admitted orders, bids highest first and asks lowest first.
+fn admitted(quotes: &[(Option<Order>, Option<Order>)], pricing: PricingRule) -> (Vec<Order>, Vec<Order>) {
+  let mut bids: Vec<Order> = quotes.iter().filter_map(|q| q.0).filter(|o| pricing.admits_bid(o.price_per_a_in_b)).collect();
+  let mut asks: Vec<Order> = quotes.iter().filter_map(|q| q.1).filter(|o| pricing.admits_ask(o.price_per_a_in_b)).collect();
+  bids.sort_by(|x, y| y.price_per_a_in_b.total_cmp(&x.price_per_a_in_b).then(x.agent_id.cmp(&y.agent_id)));
+  asks.sort_by(|x, y| x.price_per_a_in_b.total_cmp(&y.price_per_a_in_b).then(x.agent_id.cmp(&y.agent_id)));
+  (bids, asks)
+}
+
+// The volume-maximizing uniform price, None if nothing would trade.
+pub fn clearing_price(assets: &[(Agent, Balance)], quotes: &[(Option<Order>, Option<Order>)], pricing: PricingRule) -> Option<Price> {
+  let (bids, asks) = admitted(quotes, pricing);
+  let mut prices: Vec<Price> = bids.iter().chain(&asks).map(|o| o.price_per_a_in_b).collect();
+  prices.sort_by(|x, y| x.total_cmp(y));
+  prices.dedup();
+  let mut best: Option<(f64, f64, Price)> = None;
+  for pair in prices.windows(2) {
+    let price = clamp(pricing, (pair[0] + pair[1]) / 2.0);
+    let (demand, supply) = depth(assets, &bids, &asks, price);
+    let (volume, imbalance) = (demand.min(supply), (demand - supply).abs());
+    if volume > 0.0 && best.is_none_or(|(v, i, _)| volume > v || (volume == v && imbalance < i)) {
+      best = Some((volume, imbalance, price));
+    }
+  }
+  best.map(|(_, _, price)| price)
+}
+
+// Every fill at the clearing price, as trades.
+pub fn clear(assets: &[(Agent, Balance)], quotes: &[(Option<Order>, Option<Order>)], pricing: PricingRule, now: Tick) -> Vec<Trade> {
+  let Some(price) = clearing_price(assets, quotes, pricing) else { return vec![] };
+  let (bids, asks) = admitted(quotes, pricing);
+  let bids: Vec<Order> = bids.into_iter().filter(|o| o.price_per_a_in_b > price).collect();
+  let asks: Vec<Order> = asks.into_iter().filter(|o| o.price_per_a_in_b < price).collect();
+  let (demand, supply) = depth(assets, &bids, &asks, price);
+  let volume = demand.min(supply);
+  // what's left to fill: each buyer's in B, so one spending all its B pays exactly
+  // that, and each seller's in A
+  let mut budgets: Vec<f64> = bids.iter().map(|o| {
+    let (agent, balance) = &assets[o.agent_id];
+    let wanted = quantity(agent, balance, o, price);
+    if volume < demand { price * wanted * volume / demand } else if agent.utility_fn.is_linear() { balance.b } else { price * wanted }
+  }).collect();
+  let mut offers: Vec<f64> = asks.iter().map(|o| {
+    let (agent, balance) = &assets[o.agent_id];
+    let offered = quantity(agent, balance, o, price);
+    if volume < supply { offered * volume / supply } else { offered }
+  }).collect();
+  let mut trades = vec![];
+  let (mut i, mut j) = (0, 0);
+  while i < bids.len() && j < asks.len() {
+    let (amount_a, amount_b) = if budgets[i] / price <= offers[j] {
+      (budgets[i] / price, budgets[i])
+    } else {
+      (offers[j], (price * offers[j]).min(budgets[i]))
+    };
+    budgets[i] -= amount_b;
+    offers[j] = (offers[j] - amount_a).max(0.0);
+    if amount_a > 0.0 {
+      trades.push(Trade { tick: now, buyer: bids[i].agent_id, seller: asks[j].agent_id, amount_a, amount_b, bid_price: bids[i].price_per_a_in_b, ask_price: asks[j].price_per_a_in_b, ..Trade::default() });
+    }
+    if budgets[i] <= 0.0 || budgets[i] / price <= 0.0 {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+  trades
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::call::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_call_auction() {
+    let agent = linear_agent(1.0);
+    let bid = |id, p| Some(Order { agent_id: id, typ: OrderType::Bid, price_per_a_in_b: p });
+    let ask = |id, p| Some(Order { agent_id: id, typ: OrderType::Ask, price_per_a_in_b: p });
+    // buyers with 3 and 2 B bidding 3 and 2, sellers with 1 A each asking 1 and 2.5
+    let assets = vec![(agent, Balance { a: 0.0, b: 3.0 }), (agent, Balance { a: 0.0, b: 2.0 }), (agent, Balance { a: 1.0, b: 0.0 }), (agent, Balance { a: 1.0, b: 0.0 })];
+    let quotes = vec![(bid(0, 3.0), None), (bid(1, 2.0), None), (None, ask(2, 1.0)), (None, ask(3, 2.5))];
+    let pricing = PricingRule::default();
+    // at 2.75 both asks are in: demand 3 / 2.75 against supply 2 beats the 1 unit of
+    // supply anywhere below 2.5
+    assert_eq!(clearing_price(&assets, &quotes, pricing), Some(2.75));
+    let trades = clear(&assets, &quotes, pricing, 0);
+    assert!(trades.iter().all(|t| t.buyer == 0 && (t.price_per_a_in_b() - 2.75).abs() < 1e-12));
+    assert!((trades.iter().map(|t| t.amount_b).sum::<f64>() - 3.0).abs() < 1e-12);
+    // the sellers are rationed equally
+    assert!((trades[0].amount_a - trades[1].amount_a).abs() < 1e-12);
+    // a cap below the asks leaves nothing to trade
+    assert_eq!(clear(&assets, &quotes, pricing.with_limits(None, Some(0.5)), 0), vec![]);
+  }
+}
@@ -0,0 +1,216 @@
+// The simmarket command line: which subcommand, and its positional arguments. Flags
+// stay on the argument list for whatever reads them, Config::from_args for the
+// simulation's and each subcommand for its own, with the flag_* helpers here, and like
+// everything here a bad one comes back as an error naming it for main to print, not a
+// panic. A bare seed list, as in `simmarket 3 --agents 200`, still means `run`. The
+// parsing is by hand rather than clap, which isn't among the dependencies the crate
+// can build with.
+
+pub const USAGE: &str = "usage: simmarket <command> [flags]
+
+commands:
+  run <seeds>          simulate each seed and print its reports, e.g. `run 3` or `run 0..10`
+  sweep --vary <path>=<v1>,<v2>,...
+                       a batch over --seeds (0..10 by default) at each value of one config
+                       field, e.g. `--vary inequality=0,0.5,1`, --jobs at a time; -o
+                       writes every run's summary as CSV; --pareto <metric>:max|min,...
+                       prints the values whose means are pareto-efficient, and
+                       --pareto-csv writes them
+                       (a run over --time-limit or --memory-limit is cut off, its
+                       summary flagged truncated, and the sweep goes on)
+  batch <seeds>        only the batch's intervals over the seeds, run in parallel --jobs at a
+                       time (one per core by default); -o writes every run's summary as CSV
+  plot <seed>          simulate a seed and write its charts to -o (plot.html by default)
+  report <log>         chart a saved run log to -o (report.html by default); --depth-at
+                       <ticks> prints the book's --top-k levels at each of them
+  anonymize <log>      a copy of a run log fit to share, to -o (shared.ndjson by default):
+                       agents renamed and their parameters scaled by up to ±--jitter
+                       (0.1 by default), with a --salt to repeat it (random by default)
+  query <query>        a table of the runs in the --archive of out-dirs (. by default), e.g.
+                       `query 'select seed, mean_price from runs where pricing.k > 0.5
+                       order by mean_price desc limit 10'`; --csv prints it as CSV
+  find-seeds           seeds in --seeds (0..100 by default) whose runs meet every --where
+                       predicate, the first --limit of them
+  watch                step the run for --seed one trade at a time, with a --forecast;
+                       --checkpoint <path> saves where it stopped
+  resume <checkpoint>  carry on watching a checkpointed run, its config changed by any
+                       --set (e.g. --set max_ticks=null); --checkpoint as for watch
+  gains-matrix         every pair's potential surplus for --seed against who actually
+                       traded; -o writes it as CSV
+  goods                an economy of --goods a,b,c..., one book per pair, with an optional
+                       --shock <good>:<factor> and --log <path>, under the simulation
+                       flags a many-goods run supports; --money <each> runs it again with
+                       money, every good priced in it, against the barter (goods only:
+                       other commands trade A for B directly)
+  thesis               the supply and demand curves around the run for --seed, to -o
+  transfers            for --seed, the equilibrium after each lump-sum --transfers share
+                       (0,0.25,0.5,0.75,1 by default) against the market's outcome from it
+  stress               for --seed (or an --initial-state), each of the --scenarios reversal,
+                       destruction and exit (all by default) against the unshocked economy
+  recommend            the frontier of --taxes, --transfers and --floors (multiples of the
+                       unregulated mean price) over --seeds (0..10 by default), maximizing
+                       an --objective (utility_final by default) subject to each --target,
+                       e.g. `--target gini_final<=0.4`
+  influence            for --seed (or an --initial-state), the economy with one --perturb
+                       (e.g. `3.consumption_a_coeff*1.5` or `3.b=0`) against it as it is:
+                       how the change reaches prices and everyone's holdings, the --top-k
+                       (5 by default) most affected
+  help                 this message
+
+Ctrl-C stops whatever is running at its next tick and writes out what it has so far
+(a run's summary, log and --out-dir, a batch's or sweep's summaries, a watch's
+--checkpoint), marking it truncated; a second Ctrl-C kills it as usual.
+
+simulation flags (any command that simulates):
+  --agents <n>  --population <shape>  --utility <fn>  --initial-state <path>
+  --preference-correlation <rho>  --inequality <sigma>  --transfer <share>  --perturb <spec>
+  --copula <spec>  --sampling <scheme>  --monopoly  --cartel <ids>  --defection <p>
+  --adaptive  --reporting-delay <ticks>  --horizon <rounds>  --transparency full|top|last-trade|dark
+  --dealers <n>:<spread>:<inventory>  --zero-intelligence <bound>[:<ids>]  --zip <rate>[:<ids>]
+  --privileged <spec>  --entry-cost <b>  --arrival-rate <rate|lo..hi>  --fat-finger <spec>
+  --pricing-k <k>  --floor <price>  --cap <price>  --limit-mode clamp|reject  --lots <spec>
+  --matching batch|continuous|call|sessions:<ticks>  --position-limit <a>  --max-trades <n>
+  --tax ad-valorem:<rate>|per-unit:<b>  --subsidy <b>[:buyers|sellers]  --regions <home share>
+  --tariff <tax>  --dark-pool <share>  --decay <a>:<b>  --credit <limit>:<rate>
+  --short <limit>  --max-ticks <n>  --converge <spec>  --stop-at-gains <spec>
+  --time-limit <seconds>  --memory-limit <MB>  --numeraire <good>  --welfare-in utils|b[:<price>]
+  --set <path>=<value>
+
+output flags (run):
+  --log <path>  --trades <path.csv|path.json>  --out-dir <dir>  --trade-stream <path>
+  --stream-batch <n>  --stop-file <path>  --periods <n>  --top-k <k>  --cohort <spec>
+  --deflate <index>  --welfare <fns>  --normalize <scheme>  --bootstrap <resamples>
+  --curves-csv <prefix>  --curves-grid <lo>:<hi>:<points>  --burn-in auto|<trades>
+  --lorenz-csv <path>  --timings
+
+export flags (sweep -o, batch -o):
+  --dp-epsilon <eps>  --dp-sensitivity <metric>=<s>,...  --dp-salt <n>
+                       release the summaries with Laplace noise; metrics with no
+                       sensitivity are left blank";
+
+#[derive(PartialEq, Debug)]
+pub enum Command {
+  Run { seeds: Vec<u64> },
+  Sweep { path: String, values: Vec<String>, seeds: Vec<u64> },
+  Batch { seeds: Vec<u64> },
+  Plot { seed: u64 },
+  Report { log: String },
+  Resume { checkpoint: String },
+  Anonymize { log: String },
+  Query { query: String },
+  FindSeeds,
+  Watch,
+  GainsMatrix,
+  Goods,
+  Thesis,
+  Transfers,
+  Stress,
+  Recommend,
+  Influence,
+  Help,
+}
+
+impl Command {
+  pub fn parse(args: &[String]) -> Result<Command, String> {
+    let positional = |what: &str| args.get(2).filter(|a| !a.starts_with("--"))
+      .ok_or_else(|| format!("{} needs {}", args[1], what));
+    match args.get(1).map(|a| a.as_str()) {
+      None | Some("help") | Some("--help") | Some("-h") => Ok(Command::Help),
+      Some("run") => Ok(Command::Run { seeds: parse_seeds(positional("seeds, e.g. `run 3` or `run 0..10`")?)? }),
+      Some("sweep") => {
+        let vary = flag_value(args, "--vary").ok_or("sweep needs --vary <path>=<v1>,<v2>,...")?;
+        let (path, values) = vary.split_once('=').ok_or_else(|| format!("--vary: expected <path>=<values>, got {:?}", vary))?;
+        let seeds = parse_seeds(flag_value(args, "--seeds").unwrap_or("0..10"))?;
+        Ok(Command::Sweep { path: path.to_string(), values: values.split(',').map(|v| v.to_string()).collect(), seeds })
+      }
+      Some("batch") => Ok(Command::Batch { seeds: parse_seeds(positional("seeds, e.g. `batch 0..500`")?)? }),
+      Some("plot") => positional("a seed")?.parse().map(|seed| Command::Plot { seed }).map_err(|_| format!("can't read seed {:?}", args[2])),
+      Some("report") => Ok(Command::Report { log: positional("a run log")?.clone() }),
+      Some("resume") => Ok(Command::Resume { checkpoint: positional("a checkpoint")?.clone() }),
+      Some("anonymize") => Ok(Command::Anonymize { log: positional("a run log")?.clone() }),
+      Some("query") => Ok(Command::Query { query: args.get(2).ok_or("query needs a query, e.g. \"select * from runs\"")?.clone() }),
+      Some("find-seeds") => Ok(Command::FindSeeds),
+      Some("watch") => Ok(Command::Watch),
+      Some("gains-matrix") => Ok(Command::GainsMatrix),
+      Some("goods") => Ok(Command::Goods),
+      Some("thesis") => Ok(Command::Thesis),
+      Some("transfers") => Ok(Command::Transfers),
+      Some("stress") => Ok(Command::Stress),
+      Some("recommend") => Ok(Command::Recommend),
+      Some("influence") => Ok(Command::Influence),
+      Some(other) => parse_seeds(other).map(|seeds| Command::Run { seeds })
+        .map_err(|_| format!("unknown command {:?}", other)),
+    }
+  }
+}
+
+// Seeds given as a single number, a comma-separated list, or a half-open range `a..b`.
+pub fn parse_seeds(s: &str) -> Result<Vec<u64>, String> {
+  let seed = |seed: &str| seed.parse::<u64>().map_err(|_| format!("can't read seed {:?}", seed));
+  let seeds = match s.split_once("..") {
+    Some((lo, hi)) => (seed(lo)?..seed(hi)?).collect(),
+    None => s.split(',').map(seed).collect::<Result<Vec<u64>, String>>()?,
+  };
+  if seeds.is_empty() {
+    return Err(format!("no seeds in {:?}", s));
+  }
+  Ok(seeds)
+}
+
+// The value following `name` on the command line, if any.
+pub fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+  args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(|s| s.as_str())
+}
+
+// The value following `name` read by `parse`, with errors naming the flag: a flag
+// given without a value is one, rather than going unnoticed.
+pub fn flag_with<'a, T>(args: &'a [String], name: &str, parse: impl FnOnce(&'a str) -> Result<T, String>) -> Result<Option<T>, String> {
+  match args.iter().position(|a| a == name) {
+    None => Ok(None),
+    Some(i) => match args.get(i + 1) {
+      None => Err(format!("{} needs a value", name)),
+      Some(value) => parse(value).map(Some).map_err(|e| format!("{}: {}", name, e)),
+    },
+  }
+}
+
+// The value following `name` as a number (or anything else `FromStr`).
+pub fn flag_parsed<T: std::str::FromStr>(args: &[String], name: &str) -> Result<Option<T>, String> where T::Err: std::fmt::Display {
+  flag_with(args, name, |value| value.parse().map_err(|e| format!("can't read {:?} ({})", value, e)))
+}
+
+// How many periods reports over stretches of a run cut it into.
+pub fn n_periods(args: &[String]) -> Result<usize, String> {
+  flag_parsed(args, "--periods").map(|n| n.unwrap_or(10))
+}
+
+// The values following every occurrence of `name`.
+pub fn flag_values<'a>(args: &'a [String], name: &str) -> Vec<&'a str> {
+  args.windows(2).filter(|w| w[0] == name).map(|w| w[1].as_str()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cli::*;
+
+  #[test]
+  fn test_commands() {
+    let args = |s: &str| s.split(' ').map(|a| a.to_string()).collect::<Vec<_>>();
+    assert_eq!(Command::parse(&args("simmarket 3 --agents 200")), Ok(Command::Run { seeds: vec![3] }));
+    assert_eq!(Command::parse(&args("simmarket run 0..3")), Ok(Command::Run { seeds: vec![0, 1, 2] }));
+    assert_eq!(Command::parse(&args("simmarket sweep --vary pricing.k=0.2,0.8 --seeds 4,5")),
+      Ok(Command::Sweep { path: "pricing.k".to_string(), values: vec!["0.2".to_string(), "0.8".to_string()], seeds: vec![4, 5] }));
+    assert_eq!(Command::parse(&args("simmarket run --agents 200")), Err("run needs seeds, e.g. `run 3` or `run 0..10`".to_string()));
+    assert_eq!(Command::parse(&args("simmarket rn 3")), Err("unknown command \"rn\"".to_string()));
+    assert_eq!(parse_seeds("5..5"), Err("no seeds in \"5..5\"".to_string()));
+
+    // flags as well: bad values and missing ones name the flag
+    assert_eq!(crate::config::Config::from_args(&args("simmarket 3 --agents lots")).unwrap_err(),
+      "--agents: can't read \"lots\" (invalid digit found in string)");
+    assert_eq!(crate::config::Config::from_args(&args("simmarket 3 --agents")).unwrap_err(), "--agents needs a value");
+    // and values the flag reads but the run can't use
+    assert_eq!(crate::config::Config::from_args(&args("simmarket 3 --pricing-k 1.5")).unwrap_err(), "k must be strictly between 0 and 1, got 1.5");
+    assert!(crate::config::Config::from_args(&args("simmarket 3 --pricing-k 0")).is_err());
+    assert_eq!(crate::config::Config::from_args(&args("simmarket 3 --floor 2 --cap 1")).unwrap_err(), "price floor 2 must be below the cap 1");
+  }
+}
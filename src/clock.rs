@@ -0,0 +1,30 @@
+// Simulated time. A tick is one matching pass over the book: every agent (re)quotes,
+// then at most one pair trades. Everything that happens during a run is stamped
+// with the tick it happened at, so later features can make time pass differently
+// from "one trade per step".
+
+use serde::{Deserialize, Serialize};
+
+pub type Tick = u64;
+
+// [0, end) cut into `n` periods of equal length (the last one may be short), for
+// reporting on stretches of a run.
+pub fn periods(end: Tick, n: usize) -> Vec<(Tick, Tick)> {
+  let length = end.div_ceil(n as Tick).max(1);
+  (0..end).step_by(length as usize).map(|start| (start, (start + length).min(end))).collect()
+}
+
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct Clock {
+  now: Tick,
+}
+
+impl Clock {
+  pub fn now(&self) -> Tick {
+    self.now
+  }
+
+  pub fn advance(&mut self) {
+    self.now += 1;
+  }
+}
@@ -0,0 +1,135 @@
+// Groups of agents picked out by something about them at the start of a run (how
+// wealthy they were, when they first came to the market, or just by id), followed
+// through it: at the end of every period, the cohort's share of all wealth, its mean
+// surplus so far, and how many of its members have traded. Longitudinal questions
+// ("do the initially poor catch up?") come down to how those move against the
+// cohort's starting share.
+
+use crate::clock::{self, Tick};
+use crate::runlog::RunLog;
+use crate::summary::{wealth_in_b, Summary};
+use crate::{realized_surplus, AgentId, Price};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Cohort {
+  // the `quantile`th poorest of `of` equal groups by wealth at the start
+  Wealth { quantile: usize, of: usize },
+  // agents first quoting in [from, to)
+  Entered { from: Tick, to: Tick },
+  Agents(Vec<AgentId>),
+}
+
+impl Cohort {
+  // `wealth:<q>/<n>`, `entered:<from>..<to>` or `agents:<ids>`, e.g. `wealth:1/4`
+  pub fn parse(s: &str) -> Result<Cohort, String> {
+    let bad = || format!("bad cohort {:?} (expected wealth:<q>/<n>, entered:<from>..<to> or agents:<ids>)", s);
+    let (kind, spec) = s.split_once(':').ok_or_else(bad)?;
+    let cohort = match kind {
+      "wealth" => spec.split_once('/').and_then(|(q, n)| Some(Cohort::Wealth { quantile: q.parse().ok()?, of: n.parse().ok()? })),
+      "entered" => spec.split_once("..").and_then(|(from, to)| Some(Cohort::Entered { from: from.parse().ok()?, to: to.parse().ok()? })),
+      "agents" => spec.split(',').map(|id| id.parse().ok()).collect::<Option<_>>().map(Cohort::Agents),
+      _ => None,
+    };
+    match cohort {
+      Some(Cohort::Wealth { quantile, of }) if quantile == 0 || quantile > of => Err(bad()),
+      Some(cohort) => Ok(cohort),
+      None => Err(bad()),
+    }
+  }
+
+  // Wealth is valued at `price`; ties at a quantile boundary go by agent order.
+  pub fn members(&self, log: &RunLog, price: Price) -> Vec<AgentId> {
+    let n = log.initial_assets.len();
+    match self {
+      Cohort::Wealth { quantile, of } => {
+        let wealth = wealth_in_b(&log.initial_assets, price);
+        let mut ranked: Vec<AgentId> = (0..n).collect();
+        ranked.sort_by(|&x, &y| wealth[x].partial_cmp(&wealth[y]).unwrap());
+        let mut members = ranked[n * (quantile - 1) / of..n * quantile / of].to_vec();
+        members.sort_unstable();
+        members
+      }
+      Cohort::Entered { from, to } => (0..n).filter(|&id| {
+        log.quotes.iter().find(|q| q.agent_id == id && q.price.is_some()).is_some_and(|q| q.tick >= *from && q.tick < *to)
+      }).collect(),
+      Cohort::Agents(ids) => ids.iter().copied().filter(|&id| id < n).collect(),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+  pub tick: Tick,
+  pub wealth_share: f64,
+  // utils
+  pub mean_surplus: f64,
+  pub traded: usize,
+}
+
+// The cohort at the start and at the end of each of `n_periods` periods.
+pub fn track(log: &RunLog, members: &[AgentId], n_periods: usize, price: Price) -> Vec<Checkpoint> {
+  let mut ticks = vec![0];
+  ticks.extend(clock::periods(log.end(), n_periods).into_iter().map(|(_, end)| end));
+  ticks.iter().zip(log.assets_at(&ticks)).map(|(&tick, assets)| {
+    let wealth = wealth_in_b(&assets, price);
+    let surplus = realized_surplus(&log.initial_assets, &assets);
+    let traders: Vec<AgentId> = log.trades.iter().filter(|t| t.tick < tick).flat_map(|t| [t.buyer, t.seller]).collect();
+    Checkpoint {
+      tick,
+      wealth_share: members.iter().map(|&id| wealth[id]).sum::<f64>() / wealth.iter().sum::<f64>(),
+      mean_surplus: members.iter().map(|&id| surplus[id]).sum::<f64>() / members.len() as f64,
+      traded: members.iter().filter(|id| traders.contains(id)).count(),
+    }
+  }).collect()
+}
+
+pub fn print_report(log: &RunLog, cohorts: &[(&str, Cohort)], n_periods: usize) {
+  let price = Summary::of(log).valuation_price;
+  for (name, cohort) in cohorts {
+    let members = cohort.members(log, price);
+    println!("cohort {} ({} agents), wealth valued at {}:", name, members.len(), price);
+    if members.is_empty() {
+      continue;
+    }
+    for c in track(log, &members, n_periods, price) {
+      println!("  tick {}: share of wealth {}, mean surplus {}, {} have traded", c.tick, c.wealth_share, c.mean_surplus, c.traded);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cohort::*;
+  use crate::runlog::Quote;
+  use crate::{linear_agent, Balance, OrderType, Trade};
+
+  #[test]
+  fn test_cohorts() {
+    let quote = |tick, agent_id| Quote { tick, agent_id, side: OrderType::Ask, price: Some(1.0) };
+    let log = RunLog {
+      seed: 0,
+      initial_assets: vec![
+        (linear_agent(1.0), Balance { a: 10.0, b: 0.0 }),
+        (linear_agent(4.0), Balance { a: 0.0, b: 2.0 }),
+        (linear_agent(1.0), Balance { a: 0.0, b: 5.0 }),
+        (linear_agent(1.0), Balance { a: 1.0, b: 0.0 }),
+      ],
+      quotes: vec![quote(0, 0), quote(3, 1)],
+      rejections: vec![],
+      trades: vec![Trade { tick: 3, buyer: 1, seller: 0, amount_a: 1.0, amount_b: 2.0, bid_price: 4.0, ask_price: 1.0, ..Trade::default() }],
+      stop: None,
+      decay: None,
+      short: None,
+    };
+    assert_eq!(Cohort::parse("wealth:1/2").unwrap().members(&log, 1.0), vec![1, 3]);
+    assert_eq!(Cohort::parse("entered:1..5").unwrap().members(&log, 1.0), vec![1]);
+    assert_eq!(Cohort::parse("agents:0,9").unwrap().members(&log, 1.0), vec![0]);
+    assert!(Cohort::parse("wealth:5/4").is_err() && Cohort::parse("vibes:1").is_err());
+
+    // the poorer half starts with 3 of 18 B of wealth; agent 1 buys 1 A (worth 1) for 2 B
+    let track = track(&log, &[1, 3], 2, 1.0);
+    assert_eq!(track.len(), 3);
+    assert_eq!(track[0], Checkpoint { tick: 0, wealth_share: 3.0 / 18.0, mean_surplus: 0.0, traded: 0 });
+    assert_eq!(track[2], Checkpoint { tick: 4, wealth_share: 2.0 / 18.0, mean_surplus: 1.0, traded: 1 });
+  }
+}
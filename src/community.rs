@@ -0,0 +1,111 @@
+// Community detection on the realized trade network, for spotting market
+// segmentation: if agents mostly trade within clusters, prices can differ
+// between clusters.
+
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+use crate::network::TradeNetwork;
+use crate::stats::{mean, std_dev};
+use crate::{AgentId, Trade};
+
+const MAX_SWEEPS: usize = 100;
+
+// Asynchronous label propagation, weighted by number of trades. Each agent starts
+// in its own community and repeatedly adopts the label most common among its
+// counterparties (ties broken toward the smallest label), visiting agents in a
+// random order each sweep. Returns a community index per agent, numbered 0..k in
+// order of first appearance.
+pub fn label_propagation(network: &TradeNetwork, rng: &mut StdRng) -> Vec<usize> {
+  let n = network.n_agents();
+  let mut labels: Vec<usize> = (0..n).collect();
+  let mut order: Vec<AgentId> = (0..n).collect();
+  for _ in 0..MAX_SWEEPS {
+    order.shuffle(rng);
+    let mut changed = false;
+    for &agent in &order {
+      let mut votes: BTreeMap<usize, usize> = BTreeMap::new();
+      for (neighbor, weight) in network.weighted_neighbors(agent) {
+        *votes.entry(labels[neighbor]).or_insert(0) += weight;
+      }
+      let best = votes.iter().max_by(|(l1, w1), (l2, w2)| w1.cmp(w2).then(l2.cmp(l1)));
+      if let Some((&label, &weight)) = best {
+        // keep the current label if it's tied for best, so the sweep can settle
+        if label != labels[agent] && votes.get(&labels[agent]) != Some(&weight) {
+          labels[agent] = label;
+          changed = true;
+        }
+      }
+    }
+    if !changed {
+      break;
+    }
+  }
+
+  let mut renumbered = BTreeMap::new();
+  labels.iter().map(|l| {
+    let next = renumbered.len();
+    *renumbered.entry(*l).or_insert(next)
+  }).collect()
+}
+
+pub fn print_report(network: &TradeNetwork, trades: &[Trade], rng: &mut StdRng) {
+  let communities = label_propagation(network, rng);
+  let n_communities = communities.iter().max().map_or(0, |c| c + 1);
+  let mut sizes = vec![0; n_communities];
+  for &c in &communities {
+    sizes[c] += 1;
+  }
+  println!("trade communities: {} ({} with more than one agent)", n_communities, sizes.iter().filter(|&&s| s > 1).count());
+
+  let mut within: BTreeMap<usize, Vec<f64>> = BTreeMap::new();
+  let mut across = vec![];
+  for trade in trades {
+    let (cb, cs) = (communities[trade.buyer], communities[trade.seller]);
+    if cb == cs {
+      within.entry(cb).or_default().push(trade.price_per_a_in_b());
+    } else {
+      across.push(trade.price_per_a_in_b());
+    }
+  }
+
+  let within_prices: Vec<f64> = within.values().flatten().copied().collect();
+  let community_means: Vec<f64> = within.values().map(|ps| mean(ps)).collect();
+  println!("  within-community trades: {}, mean price {}", within_prices.len(), mean(&within_prices));
+  println!("  across-community trades: {}, mean price {}", across.len(), mean(&across));
+  if !community_means.is_empty() {
+    let lo = community_means.iter().cloned().fold(f64::INFINITY, f64::min);
+    let hi = community_means.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    println!("  mean price by community: std dev {}, range {} .. {}", std_dev(&community_means), lo, hi);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::community::*;
+
+  fn trade(buyer: AgentId, seller: AgentId) -> Trade {
+    Trade { tick: 0, buyer, seller, amount_a: 1.0, amount_b: 1.0, bid_price: 1.0, ask_price: 1.0, ..Trade::default() }
+  }
+
+  #[test]
+  fn test_two_cliques() {
+    // two triangles joined by a single trade between 2 and 3
+    let trades = vec![
+      trade(0, 1), trade(1, 2), trade(2, 0),
+      trade(3, 4), trade(4, 5), trade(5, 3),
+      trade(2, 3),
+    ];
+    let network = TradeNetwork::from_trades(6, &trades);
+    let communities = label_propagation(&network, &mut StdRng::seed_from_u64(0));
+    assert_eq!(communities[0], communities[1]);
+    assert_eq!(communities[1], communities[2]);
+    assert_eq!(communities[3], communities[4]);
+    assert_eq!(communities[4], communities[5]);
+    assert_ne!(communities[0], communities[3]);
+  }
+}
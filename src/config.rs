@@ -0,0 +1,426 @@
+// Run parameters shared by every subcommand that simulates.
+
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cli::{flag_parsed, flag_value, flag_values, flag_with};
+use crate::{interrupt, runlog, Agent, AgentId, Balance};
+use crate::arrivals::Arrivals;
+use crate::clock::Tick;
+use crate::copula::GaussianCopula;
+use crate::fat_finger::FatFinger;
+use crate::horizon::Horizon;
+use crate::influence::Perturbation;
+use crate::continuous::Matching;
+use crate::credit::Credit;
+use crate::dark_pool::DarkPool;
+use crate::dealer::Dealers;
+use crate::decay::Decay;
+use crate::limits::Enforcement;
+use crate::lots::Lots;
+use crate::numeraire::Numeraire;
+use crate::population::{self, Population};
+use crate::pricing::PricingRule;
+use crate::privilege::Privilege;
+use crate::regions::Regions;
+use crate::risk::RiskRules;
+use crate::sampling::Sampling;
+use crate::short::Short;
+use crate::simulation::SimulationBuilder;
+use crate::stopping::{Convergence, GainsTarget, StoppingRules};
+use crate::strategy::{Strategies, Strategy};
+use crate::stress::Scenario;
+use crate::subsidy::Subsidy;
+use crate::tax::Tax;
+use crate::transparency::Transparency;
+use crate::utility::UtilityFn;
+use crate::welfare::Currency;
+use crate::zero_intelligence::ZeroIntelligence;
+use crate::zip::ZipSpec;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+  pub n_agents: usize,
+  pub population: Population,
+  // a saved state (e.g. a previous run's final_state.json) to start from instead of
+  // generating the population
+  pub initial_state: Option<String>,
+  // correlation between agents' production mix and preferences, in [-1, 1]
+  pub preference_correlation: f64,
+  // lognormal sigma of the scale of agents' endowments; see population::spread_endowments
+  pub inequality: f64,
+  // share of every endowment pooled and split equally before trading; see
+  // population::redistribute
+  pub transfer: f64,
+  // a shock to the economy before it trades; see stress
+  pub stress: Option<Scenario>,
+  // one agent's parameter changed before it trades; see influence
+  pub perturbation: Option<Perturbation>,
+  // joint distribution to draw agents' parameters from, instead of independent uniforms
+  pub copula: Option<GaussianCopula>,
+  // how a batch's seeds draw their populations; see sampling::Sampling
+  pub sampling: Sampling,
+  // every generated agent's; see utility::UtilityFn
+  pub utility: UtilityFn,
+  // the sellers in a monopolist/oligopoly population quote strategically
+  pub monopoly: bool,
+  // every agent quotes toward the last price on the tape; see strategy::Strategy
+  pub adaptive: bool,
+  // the most ticks a trade's report is delayed on top of the next tick's; see reporting
+  pub reporting_delay: Tick,
+  // rounds agents look ahead, 1 for only the current one; see horizon
+  pub horizon: Tick,
+  // what strategic agents see before quoting
+  pub transparency: Transparency,
+  // agents quoting at random inside their truthful quotes
+  pub zero_intelligence: Option<ZeroIntelligence>,
+  // agents learning a margin on their truthful quotes; see zip
+  pub zip: Option<ZipSpec>,
+  // the last agents recast as market makers; see dealer
+  pub dealers: Option<Dealers>,
+  // agents quoting a common ask as a cartel, each defecting with `defection` probability per pass
+  pub cartel: Vec<AgentId>,
+  pub defection: f64,
+  // agents favored by matching priority and/or exemption from the entry cost
+  pub privilege: Privilege,
+  // B each agent pays to take part in the market; see entry::enter
+  pub entry_cost: f64,
+  // range of per-agent Poisson quoting intensities (events per tick); None means every tick
+  pub arrival_rate: Option<(f64, f64)>,
+  // chance and size of mistyped order prices
+  pub fat_finger: Option<FatFinger>,
+  pub pricing: PricingRule,
+  pub risk: RiskRules,
+  // optional stopping rules on top of exhaustion; see stopping::StoppingRules
+  pub max_trades: Option<usize>,
+  pub max_ticks: Option<Tick>,
+  pub convergence: Option<Convergence>,
+  pub gains_target: Option<GainsTarget>,
+  // per-run budgets, in seconds and megabytes; see stopping
+  pub time_limit: Option<f64>,
+  pub memory_limit: Option<f64>,
+  // what reported prices are quoted in
+  pub numeraire: Numeraire,
+  // what reported welfare and surplus are in; see welfare::Currency
+  pub welfare_currency: Currency,
+}
+
+impl Default for Config {
+  fn default() -> Config {
+    Config {
+      n_agents: 1000,
+      population: Population::Uniform,
+      initial_state: None,
+      preference_correlation: 0.0,
+      inequality: 0.0,
+      transfer: 0.0,
+      stress: None,
+      perturbation: None,
+      copula: None,
+      sampling: Sampling::default(),
+      utility: UtilityFn::default(),
+      monopoly: false,
+      adaptive: false,
+      reporting_delay: 0,
+      horizon: 1,
+      transparency: Transparency::Full,
+      zero_intelligence: None,
+      zip: None,
+      dealers: None,
+      cartel: vec![],
+      defection: 0.0,
+      privilege: Privilege::default(),
+      entry_cost: 0.0,
+      arrival_rate: None,
+      fat_finger: None,
+      pricing: PricingRule::default(),
+      risk: RiskRules::default(),
+      max_trades: None,
+      max_ticks: None,
+      convergence: None,
+      gains_target: None,
+      time_limit: None,
+      memory_limit: None,
+      numeraire: Numeraire::B,
+      welfare_currency: Currency::Utils,
+    }
+  }
+}
+
+impl Config {
+  // Every simulation flag on the command line; an error names the offending flag.
+  pub fn from_args(args: &[String]) -> Result<Config, String> {
+    let mut builder = SimulationBuilder::new();
+    if let Some(n) = flag_parsed(args, "--agents")? {
+      builder = builder.agents(n);
+    }
+    if let Some(p) = flag_with(args, "--population", Population::parse)? {
+      builder = builder.population(p);
+    }
+    if let Some(rho) = flag_parsed(args, "--preference-correlation")? {
+      builder = builder.preference_correlation(rho);
+    }
+    if let Some(sigma) = flag_parsed(args, "--inequality")? {
+      builder = builder.inequality(sigma);
+    }
+    if let Some(share) = flag_parsed(args, "--transfer")? {
+      builder = builder.transfer(share);
+    }
+    if let Some(p) = flag_with(args, "--perturb", Perturbation::parse)? {
+      builder = builder.perturbation(p);
+    }
+    if let Some(c) = flag_with(args, "--copula", GaussianCopula::parse)? {
+      builder = builder.copula(c);
+    }
+    if let Some(s) = flag_with(args, "--sampling", Sampling::parse)? {
+      builder = builder.sampling(s);
+    }
+    if let Some(u) = flag_with(args, "--utility", UtilityFn::parse)? {
+      builder = builder.utility(u);
+    }
+    if let Some(path) = flag_value(args, "--initial-state") {
+      if ["--population", "--copula", "--sampling", "--utility", "--inequality"].iter().any(|f| flag_value(args, f).is_some()) {
+        return Err("--population, --copula, --sampling, --utility and --inequality have no effect with --initial-state".to_string());
+      }
+      builder = builder.initial_state(path);
+    }
+    builder = builder.monopoly(args.iter().any(|a| a == "--monopoly"));
+    builder = builder.adaptive(args.iter().any(|a| a == "--adaptive"));
+    if let Some(ticks) = flag_parsed(args, "--reporting-delay")? {
+      builder = builder.reporting_delay(ticks);
+    }
+    if let Some(rounds) = flag_parsed(args, "--horizon")? {
+      builder = builder.horizon(rounds);
+    }
+    if let Some(t) = flag_with(args, "--transparency", Transparency::parse)? {
+      builder = builder.transparency(t);
+    }
+    if let Some(zi) = flag_with(args, "--zero-intelligence", ZeroIntelligence::parse)? {
+      builder = builder.zero_intelligence(zi);
+    }
+    if let Some(zip) = flag_with(args, "--zip", ZipSpec::parse)? {
+      builder = builder.zip(zip);
+    }
+    if let Some(dealers) = flag_with(args, "--dealers", Dealers::parse)? {
+      builder = builder.dealers(dealers);
+    }
+    if let Some(ids) = flag_with(args, "--cartel", |ids| ids.split(',').map(|id| id.parse().map_err(|_| format!("can't read agent id {:?}", id))).collect())? {
+      builder = builder.cartel(ids);
+    }
+    if let Some(p) = flag_parsed(args, "--defection")? {
+      builder = builder.defection(p);
+    }
+    if let Some(p) = flag_with(args, "--privileged", Privilege::parse)? {
+      builder = builder.privilege(p);
+    }
+    if let Some(c) = flag_parsed(args, "--entry-cost")? {
+      builder = builder.entry_cost(c);
+    }
+    // a single intensity, or a range `lo..hi` to draw each agent's from
+    if let Some(range) = flag_with(args, "--arrival-rate", |r| {
+      let (lo, hi) = r.split_once("..").unwrap_or((r, r));
+      match (lo.parse(), hi.parse()) {
+        (Ok(lo), Ok(hi)) => Ok((lo, hi)),
+        _ => Err(format!("can't read {:?} (expected a rate or a range lo..hi)", r)),
+      }
+    })? {
+      builder = builder.arrival_rate(range);
+    }
+    if let Some(f) = flag_with(args, "--fat-finger", FatFinger::parse)? {
+      builder = builder.fat_finger(f);
+    }
+    // k and the limits are checked with the rest, not asserted as k_double and with_limits do
+    let mut pricing = PricingRule::default();
+    if let Some(k) = flag_parsed(args, "--pricing-k")? {
+      pricing.k = k;
+    }
+    let lots = flag_with(args, "--lots", Lots::parse)?;
+    let enforcement = flag_with(args, "--limit-mode", Enforcement::parse)?.unwrap_or_default();
+    let matching = flag_with(args, "--matching", Matching::parse)?.unwrap_or_default();
+    let tax = flag_with(args, "--tax", Tax::parse)?;
+    let subsidy = flag_with(args, "--subsidy", Subsidy::parse)?;
+    let tariff = flag_with(args, "--tariff", Tax::parse)?;
+    let regions = match flag_parsed(args, "--regions")? {
+      Some(home) => Some(Regions { home, tariff }),
+      None if tariff.is_some() => return Err("a tariff needs --regions".to_string()),
+      None => None,
+    };
+    let pricing = PricingRule { floor: flag_parsed(args, "--floor")?, cap: flag_parsed(args, "--cap")?, ..pricing }.with_lots(lots).with_enforcement(enforcement);
+    let dark_pool = flag_parsed(args, "--dark-pool")?.map(|share| DarkPool { share });
+    let (decay, credit) = (flag_with(args, "--decay", Decay::parse)?, flag_with(args, "--credit", Credit::parse)?);
+    let short = flag_parsed(args, "--short")?.map(|limit| Short { limit });
+    builder = builder.pricing(pricing.with_matching(matching).with_tax(tax).with_subsidy(subsidy).with_regions(regions).with_dark_pool(dark_pool).with_decay(decay).with_credit(credit).with_short(short));
+    builder = builder.risk(RiskRules { position_limit: flag_parsed(args, "--position-limit")? });
+    if let Some(n) = flag_parsed(args, "--max-trades")? {
+      builder = builder.max_trades(n);
+    }
+    if let Some(n) = flag_parsed(args, "--max-ticks")? {
+      builder = builder.max_ticks(n);
+    }
+    if let Some(seconds) = flag_parsed(args, "--time-limit")? {
+      builder = builder.time_limit(seconds);
+    }
+    if let Some(mb) = flag_parsed(args, "--memory-limit")? {
+      builder = builder.memory_limit(mb);
+    }
+    if let Some(n) = flag_with(args, "--numeraire", Numeraire::parse)? {
+      builder = builder.numeraire(n);
+    }
+    if let Some(c) = flag_with(args, "--welfare-in", Currency::parse)? {
+      builder = builder.welfare_currency(c);
+    }
+    if let Some(c) = flag_with(args, "--converge", Convergence::parse)? {
+      builder = builder.convergence(c);
+    }
+    if let Some(g) = flag_with(args, "--stop-at-gains", GainsTarget::parse)? {
+      builder = builder.gains_target(g);
+    }
+    let config = builder.validate()?;
+    let overrides = flag_values(args, "--set");
+    if overrides.is_empty() {
+      return Ok(config);
+    }
+    config.with_overrides(&overrides).map_err(|e| format!("--set: {}", e))
+  }
+
+  // Applies `path=value` assignments to the config's fields, e.g. `pricing.k=0.3`
+  // or `max_ticks=500`, after every other flag. Values are read as JSON, falling
+  // back to a plain string, and the result is validated again.
+  pub fn with_overrides(&self, assignments: &[&str]) -> Result<Config, String> {
+    let mut value = serde_json::to_value(self).unwrap();
+    for assignment in assignments {
+      let (path, raw) = assignment.split_once('=').ok_or_else(|| format!("expected path=value, got {:?}", assignment))?;
+      let mut target = &mut value;
+      for key in path.split('.') {
+        target = target.as_object_mut()
+          .and_then(|fields| fields.get_mut(key))
+          .ok_or_else(|| format!("no config field {:?} in {:?}", key, path))?;
+      }
+      *target = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    }
+    let config = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    SimulationBuilder::from_config(config).validate()
+  }
+
+  // The population's starting allocation for `seed`: generated from `rng`, or loaded.
+  pub fn initial_assets(&self, seed: u64, rng: &mut StdRng) -> Vec<(Agent, Balance)> {
+    let mut assets = match &self.initial_state {
+      Some(path) => runlog::read_state(path).unwrap(),
+      None => {
+        let mut assets = match (&self.copula, self.sampling) {
+          (Some(copula), _) => population::shape(self.population, (0..self.n_agents).map(|_| copula.sample(rng)).collect(), rng),
+          (None, Sampling::Independent) => population::generate(self.population, self.n_agents, rng),
+          (None, sampling) => {
+            let agents = sampling.quantiles(self.n_agents, seed, rng).into_iter().map(Agent::from_quantiles).collect();
+            population::shape(self.population, agents, rng)
+          }
+        };
+        if self.preference_correlation != 0.0 {
+          population::correlate_preferences(&mut assets, self.preference_correlation, rng);
+        }
+        if self.inequality != 0.0 {
+          population::spread_endowments(&mut assets, self.inequality, seed);
+        }
+        for (agent, _) in assets.iter_mut() {
+          agent.utility_fn = self.utility;
+        }
+        assets
+      }
+    };
+    if self.transfer != 0.0 {
+      population::redistribute(&mut assets, self.transfer);
+    }
+    if let Some(scenario) = self.stress {
+      scenario.apply(&mut assets);
+    }
+    if let Some(perturbation) = self.perturbation {
+      perturbation.apply(&mut assets);
+    }
+    if let Some(dealers) = self.dealers {
+      dealers.install(&mut assets);
+    }
+    assets
+  }
+
+  pub fn stopping(&self) -> StoppingRules {
+    StoppingRules {
+      max_trades: self.max_trades,
+      max_ticks: self.max_ticks,
+      convergence: self.convergence,
+      gains: self.gains_target,
+      signal: interrupt::signal(),
+      time_limit: self.time_limit.map(Duration::from_secs_f64),
+      memory_limit: self.memory_limit.map(|mb| (mb * 1e6) as usize),
+    }
+  }
+
+  pub fn arrivals(&self, seed: u64) -> Arrivals {
+    match self.arrival_rate {
+      Some(range) => Arrivals::poisson(self.n_agents, range, seed),
+      None => Arrivals::every_tick(seed),
+    }
+  }
+
+  // `seed` drives the strategies' own randomness (cartel defections, entry errors).
+  pub fn strategies(&self, seed: u64) -> Strategies {
+    let mut strategies = Strategies::truthful(self.n_agents);
+    if self.adaptive {
+      for id in 0..self.n_agents {
+        strategies.set(id, Strategy::Adaptive);
+      }
+    }
+    strategies.set_reporting_delay(self.reporting_delay, seed);
+    if self.monopoly {
+      for id in self.population.sellers().unwrap() {
+        strategies.set(id, Strategy::Monopolist);
+      }
+    }
+    if !self.cartel.is_empty() {
+      strategies.add_coalition(&self.cartel, self.defection, seed);
+    }
+    strategies.grant_priority(self.privilege.priority_agents());
+    if let Some(fat_finger) = self.fat_finger {
+      strategies.set_fat_finger(fat_finger, seed);
+    }
+    if let Some(zi) = &self.zero_intelligence {
+      strategies.set_zero_intelligence(zi.clone(), seed);
+    }
+    if let Some(zip) = &self.zip {
+      strategies.set_zip(zip, seed);
+    }
+    if let Some(dealers) = self.dealers {
+      strategies.set_dealers(dealers);
+    }
+    if self.horizon > 1 {
+      strategies.set_horizon(Horizon { rounds: self.horizon, decay: self.pricing.decay });
+    }
+    strategies.set_transparency(self.transparency);
+    if self.stress == Some(Scenario::Exit) {
+      for id in Scenario::affected(self.n_agents) {
+        strategies.set(id, Strategy::Abstain);
+      }
+    }
+    if let Some(pool) = self.pricing.dark_pool {
+      strategies.hide(&pool.ids(self.n_agents));
+    }
+    strategies
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::*;
+
+  #[test]
+  fn test_overrides() {
+    let config = Config::default().with_overrides(&["pricing.k=0.25", "max_ticks=40", "population=identical", "risk.position_limit=5"]).unwrap();
+    assert_eq!((config.pricing.k, config.max_ticks, config.population), (0.25, Some(40), Population::Identical));
+    assert_eq!(config.risk.position_limit, Some(5.0));
+    assert!(Config::default().with_overrides(&["pricing.kk=0.25"]).is_err());
+    // still validated
+    assert!(Config::default().with_overrides(&["pricing.k=2"]).is_err());
+  }
+}
@@ -0,0 +1,125 @@
+// Continuous matching, to set against the batch market's one clearing pass per tick.
+// Agents arriving in a tick come in one at a time, in a random order (see
+// arrivals::Arrivals::queue), and each incoming order trades straight away against
+// the best resting order on the other side for as long as they cross: price-time
+// priority, the resting orders at the best price going earliest entered first (see
+// book). What doesn't trade rests. The price of each fill is still the pricing rule's
+// k of the way from the ask to the bid, so both sides strictly gain as in the batch
+// market; what the mode changes is who meets whom, and in what order. Under a clamped
+// floor or cap, the earliest arrivals quoting at the limit fill first, not the keenest,
+// and what's left between agents who both quote at it never crosses: the run stops
+// short of the corner a market without limits would reach (see market).
+//
+// The batch run of the same seed is reported alongside, for the comparison; so it is
+// for the call auction.
+
+use serde::{Deserialize, Serialize};
+
+use crate::book::OrderBook;
+use crate::clock::Tick;
+use crate::pricing::PricingRule;
+use crate::risk::{self, Rejection, RiskRules};
+use crate::runlog::RunLog;
+use crate::summary::Summary;
+use crate::timing::{self, Phase};
+use crate::{fill, Agent, AgentId, Balance, Trade};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Matching {
+  #[default]
+  Batch,
+  Continuous,
+  // one uniform-price clearing of everyone's orders; see call
+  Call,
+  // rounds of an opening call, `continuous` ticks of continuous matching and a
+  // closing call; see session
+  Sessions { continuous: Tick },
+}
+
+impl Matching {
+  pub fn parse(s: &str) -> Result<Matching, String> {
+    match s {
+      "batch" => Ok(Matching::Batch),
+      "continuous" => Ok(Matching::Continuous),
+      "call" => Ok(Matching::Call),
+      _ => match s.strip_prefix("sessions:").map(|t| t.parse::<Tick>()) {
+        Some(Ok(continuous)) if continuous > 0 => Ok(Matching::Sessions { continuous }),
+        _ => Err(format!("unknown matching {:?} (expected batch, continuous, call or sessions:<ticks> with ticks > 0)", s)),
+      },
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Matching::Batch => "batch",
+      Matching::Continuous => "continuous",
+      Matching::Call => "call",
+      Matching::Sessions { .. } => "session",
+    }
+  }
+}
+
+// The trade `id`'s standing orders make on arriving: its bid against the lowest ask
+// below it, else its ask against the highest bid above it.
+pub fn incoming_trade(assets: &[(Agent, Balance)], book: &OrderBook, id: AgentId, pricing: PricingRule, now: Tick) -> Option<Trade> {
+  let (bid, ask) = book.quotes()[id];
+  if let Some(bid) = bid.filter(|o| pricing.admits_bid(o.price_per_a_in_b)) {
+    if let Some(ask) = book.lowest_ask(pricing, Some(pricing.net_bid(bid.price_per_a_in_b))) {
+      return Some(timing::time(Phase::Clearing, || fill(assets, bid, ask, pricing, now)));
+    }
+  }
+  let ask = ask.filter(|o| pricing.admits_ask(o.price_per_a_in_b))?;
+  let bid = book.highest_bid(pricing).filter(|o| pricing.net_bid(o.price_per_a_in_b) > ask.price_per_a_in_b)?;
+  Some(timing::time(Phase::Clearing, || fill(assets, bid, ask, pricing, now)))
+}
+
+// The next trade for `id`'s incoming orders that passes the risk rules, reporting each
+// rejected match along the way.
+pub fn match_incoming(assets: &[(Agent, Balance)], book: &OrderBook, id: AgentId, pricing: PricingRule, rules: &RiskRules, now: Tick, on_reject: impl FnMut(Rejection)) -> Option<Trade> {
+  risk::find_allowed(assets, book, rules, now, |book| incoming_trade(assets, book, id, pricing, now), on_reject)
+}
+
+// A run under another `matching` against the batch run of the same seed.
+pub fn print_comparison(matching: Matching, batch: &RunLog, other: &RunLog) {
+  let (b, c) = (Summary::of(batch), Summary::of(other));
+  let price = |p: Option<f64>| p.map_or("n/a".to_string(), |p| p.to_string());
+  println!("{} matching vs batch:", matching.name());
+  println!("  trades:        {} vs {}", c.trades, b.trades);
+  println!("  mean price:    {} vs {}", price(c.mean_price), price(b.mean_price));
+  println!("  total surplus: {} vs {} (change {})", c.total_surplus, b.total_surplus, c.total_surplus - b.total_surplus);
+  println!("  final gini:    {} vs {}", c.gini_final, b.gini_final);
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::continuous::*;
+  use crate::simulation::SimulationBuilder;
+  use crate::{linear_agent, Order, OrderType};
+
+  #[test]
+  fn test_price_time_priority() {
+    let order = |id, typ, p| Some(Order { agent_id: id, typ, price_per_a_in_b: p });
+    let assets: Vec<(Agent, Balance)> = (0..3).map(|_| (linear_agent(1.0), Balance { a: 1.0, b: 1.0 })).collect();
+    let pricing = PricingRule::default();
+    // two asks at the same price, agent 1's entered first
+    let mut book = OrderBook::with_time_priority(3);
+    book.set_ask(1, order(1, OrderType::Ask, 1.0));
+    book.set_ask(0, order(0, OrderType::Ask, 1.0));
+    book.set_bid(2, order(2, OrderType::Bid, 2.0));
+    let trade = incoming_trade(&assets, &book, 2, pricing, 0).unwrap();
+    assert_eq!((trade.buyer, trade.seller, trade.price_per_a_in_b()), (2, 1, 1.5));
+    // requoting at the same price keeps its place; a new price goes to the back
+    book.set_ask(1, order(1, OrderType::Ask, 1.0));
+    assert_eq!(incoming_trade(&assets, &book, 2, pricing, 0).unwrap().seller, 1);
+    book.set_ask(1, order(1, OrderType::Ask, 1.5));
+    book.set_ask(1, order(1, OrderType::Ask, 1.0));
+    assert_eq!(incoming_trade(&assets, &book, 2, pricing, 0).unwrap().seller, 0);
+    let restored = OrderBook::restore(book.quotes(), book.entered().unwrap());
+    assert_eq!(incoming_trade(&assets, &restored, 2, pricing, 0).unwrap().seller, 0);
+
+    let log = SimulationBuilder::new().agents(30).seed(3).pricing(pricing.with_matching(Matching::Continuous)).build().unwrap().run();
+    assert_eq!(log.stop.unwrap().reason, crate::stopping::StopReason::Exhausted);
+    assert!(!log.trades.is_empty());
+  }
+}
@@ -0,0 +1,138 @@
+// Jointly sampled agent parameters: a Gaussian copula over (production_a,
+// production_b, consumption_a_coeff, consumption_b_coeff) with the same uniform
+// marginals as independent sampling, so only the dependence structure changes.
+
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::Agent;
+
+const PARAMS: [&str; 4] = ["pa", "pb", "ca", "cb"];
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Correlation")]
+pub struct GaussianCopula {
+  pub correlation: [[f64; 4]; 4],
+  #[serde(skip)]
+  cholesky: [[f64; 4]; 4],
+}
+
+// What a copula is deserialized from; the factorization is recomputed.
+#[derive(Deserialize)]
+struct Correlation {
+  correlation: [[f64; 4]; 4],
+}
+
+impl TryFrom<Correlation> for GaussianCopula {
+  type Error = String;
+
+  fn try_from(c: Correlation) -> Result<GaussianCopula, String> {
+    GaussianCopula::new(c.correlation)
+  }
+}
+
+impl GaussianCopula {
+  pub fn new(correlation: [[f64; 4]; 4]) -> Result<GaussianCopula, String> {
+    let cholesky = cholesky(&correlation).ok_or("correlation matrix isn't positive definite")?;
+    Ok(GaussianCopula { correlation, cholesky })
+  }
+
+  // Pairwise correlations like `pa-ca=0.5,pb-cb=-0.3` over the parameters pa, pb
+  // (production of A and B) and ca, cb (consumption coefficients); unlisted pairs
+  // are uncorrelated.
+  pub fn parse(s: &str) -> Result<GaussianCopula, String> {
+    let mut correlation = [[0.0; 4]; 4];
+    for (i, row) in correlation.iter_mut().enumerate() {
+      row[i] = 1.0;
+    }
+    for entry in s.split(',') {
+      let bad = || format!("bad copula entry {:?} (expected e.g. pa-ca=0.5)", entry);
+      let (pair, rho) = entry.split_once('=').ok_or_else(bad)?;
+      let (x, y) = pair.split_once('-').ok_or_else(bad)?;
+      let index = |name: &str| PARAMS.iter().position(|p| *p == name.trim()).ok_or_else(bad);
+      let (i, j) = (index(x)?, index(y)?);
+      let rho: f64 = rho.trim().parse().map_err(|_| bad())?;
+      if i == j || !(-1.0..=1.0).contains(&rho) {
+        return Err(bad());
+      }
+      correlation[i][j] = rho;
+      correlation[j][i] = rho;
+    }
+    GaussianCopula::new(correlation)
+  }
+
+  pub fn sample(&self, rng: &mut StdRng) -> Agent {
+    let z: Vec<f64> = (0..4).map(|_| standard_normal(rng)).collect();
+    let mut u = [0.0; 4];
+    for (i, row) in self.cholesky.iter().enumerate() {
+      u[i] = normal_cdf((0..=i).map(|j| row[j] * z[j]).sum());
+    }
+    Agent::from_quantiles(u)
+  }
+}
+
+fn cholesky(m: &[[f64; 4]; 4]) -> Option<[[f64; 4]; 4]> {
+  let mut l = [[0.0; 4]; 4];
+  for i in 0..4 {
+    for j in 0..=i {
+      let s: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+      if i == j {
+        let d = m[i][i] - s;
+        if d <= 0.0 {
+          return None;
+        }
+        l[i][j] = d.sqrt();
+      } else {
+        l[i][j] = (m[i][j] - s) / l[j][j];
+      }
+    }
+  }
+  Some(l)
+}
+
+// Box-Muller.
+fn standard_normal(rng: &mut StdRng) -> f64 {
+  let u1: f64 = 1.0 - rng.gen::<f64>(); // in (0, 1], so the log is finite
+  let u2: f64 = rng.gen();
+  (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+// Abramowitz & Stegun 7.1.26; absolute error below 1.5e-7.
+fn normal_cdf(x: f64) -> f64 {
+  let t = 1.0 / (1.0 + 0.3275911 * x.abs() / 2f64.sqrt());
+  let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+  let erf = 1.0 - poly * (-x * x / 2.0).exp();
+  if x >= 0.0 { (1.0 + erf) / 2.0 } else { (1.0 - erf) / 2.0 }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::copula::*;
+  use crate::stats::pearson;
+
+  #[test]
+  fn test_copula_dependence() {
+    assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+    assert!((normal_cdf(1.96) - 0.975).abs() < 1e-4);
+    assert!(GaussianCopula::parse("pa-pa=0.5").is_err());
+    assert!(GaussianCopula::parse("pa-pb=1,pb-ca=1,pa-ca=-1").is_err());
+
+    let copula = GaussianCopula::parse("pa-ca=0.8").unwrap();
+    let mut rng = StdRng::seed_from_u64(0);
+    let agents: Vec<Agent> = (0..5000).map(|_| copula.sample(&mut rng)).collect();
+    let pa: Vec<f64> = agents.iter().map(|a| a.production_a).collect();
+    let ca: Vec<f64> = agents.iter().map(|a| a.consumption_a_coeff).collect();
+    let cb: Vec<f64> = agents.iter().map(|a| a.consumption_b_coeff).collect();
+    assert!((pearson(&pa, &ca).unwrap() - 0.79).abs() < 0.05);
+    assert!(pearson(&pa, &cb).unwrap().abs() < 0.05);
+    // marginals stay uniform
+    assert!(pa.iter().all(|&x| (0.0..1000.0).contains(&x)));
+    assert!((crate::stats::mean(&ca) - 0.5).abs() < 0.02);
+  }
+}
@@ -0,0 +1,187 @@
+// Credit: agents may buy beyond the B they hold, borrowing the shortfall from a lender
+// outside the economy, up to a line of credit the same for everyone. What's owed grows
+// with interest at the end of every tick, and whatever B a debtor holds once it's
+// paid for a sale goes to the lender first, until the debt is cleared. A debtor whose
+// interest carries it past its line can borrow no more, and a debt still owed when
+// the run stops is in default: it's written off, the lender bearing the loss, and
+// reported as such.
+//
+// The loans and repayments ride on the trades (see Trade::borrowed), so balances never
+// go negative and the trades are still enough to replay a run; the debts themselves
+// are replayed from the trades and the rate (see debts). Only linear agents borrow, by
+// batch matching: others value B by how much of it they hold, and would quote against
+// B they don't have.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::runlog::RunLog;
+use crate::{Agent, Balance, Trade};
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Credit {
+  // the most anyone may borrow; interest can carry a debt past it
+  pub limit: f64,
+  // interest on what's owed, per tick
+  pub rate: f64,
+}
+
+impl Credit {
+  // `<limit>:<rate>`, e.g. `50:0.01` for up to 50 B at 1% a tick
+  pub fn parse(s: &str) -> Result<Credit, String> {
+    let (limit, rate) = s.split_once(':').ok_or_else(|| format!("bad credit {:?} (expected <limit>:<rate>)", s))?;
+    let read = |x: &str| x.parse().map_err(|_| format!("bad credit {:?} (expected <limit>:<rate>)", s));
+    Ok(Credit { limit: read(limit)?, rate: read(rate)? })
+  }
+
+  pub fn is_valid(&self) -> bool {
+    self.limit >= 0.0 && self.limit.is_finite() && self.rate >= 0.0 && self.rate.is_finite()
+  }
+
+  // What an agent holding `balance` and owing `debt` can spend.
+  pub fn spendable(&self, balance: Balance, debt: f64) -> Balance {
+    Balance { b: balance.b + (self.limit - debt).max(0.0), ..balance }
+  }
+
+  // Lends `trade`'s buyer what it's short of paying and takes what the seller then
+  // holds toward its debt, recording both on the trade and in `debts`. The loan is
+  // rounded up, if need be, so a buyer borrowing to pay ends at 0 rather than a hair
+  // below.
+  pub fn finance(&self, trade: &mut Trade, assets: &[(Agent, Balance)], debts: &mut [f64]) {
+    let held = assets[trade.buyer].1.b - trade.tax + trade.buyer_subsidy;
+    let mut borrowed = (trade.amount_b - held).max(0.0);
+    while held + borrowed < trade.amount_b {
+      borrowed = borrowed.next_up();
+    }
+    let paid = assets[trade.seller].1.b + trade.seller_subsidy + trade.amount_b;
+    trade.borrowed = borrowed;
+    trade.repaid = debts[trade.seller].min(paid);
+    debts[trade.buyer] += trade.borrowed;
+    debts[trade.seller] -= trade.repaid;
+  }
+
+  // A tick's interest.
+  pub fn accrue(&self, debts: &mut [f64]) {
+    for debt in debts.iter_mut() {
+      *debt *= 1.0 + self.rate;
+    }
+  }
+}
+
+// B lent over `trades`, less what was repaid.
+pub fn net_lent(trades: &[Trade]) -> f64 {
+  trades.iter().fold(0.0, |l, t| l + t.borrowed - t.repaid)
+}
+
+// Everyone's debts as of `end`, replayed from the `trades` with a tick's interest at
+// the end of every tick, as the run charged it.
+pub fn debts(n_agents: usize, trades: &[Trade], credit: Credit, end: Tick) -> Vec<f64> {
+  let mut debts = vec![0.0; n_agents];
+  let mut trades = trades.iter().peekable();
+  for now in 0..end {
+    while let Some(trade) = trades.next_if(|t| t.tick <= now) {
+      debts[trade.buyer] += trade.borrowed;
+      debts[trade.seller] -= trade.repaid;
+    }
+    credit.accrue(&mut debts);
+  }
+  debts
+}
+
+pub struct Lending {
+  pub borrowers: usize,
+  pub borrowed: f64,
+  pub repaid: f64,
+  // what's owed when the run stops, by agent
+  pub outstanding: Vec<f64>,
+}
+
+impl Lending {
+  pub fn of(log: &RunLog, credit: Credit) -> Lending {
+    let borrowers = (0..log.initial_assets.len()).filter(|&id| log.trades.iter().any(|t| t.buyer == id && t.borrowed > 0.0)).count();
+    Lending {
+      borrowers,
+      borrowed: log.trades.iter().fold(0.0, |b, t| b + t.borrowed),
+      repaid: log.trades.iter().fold(0.0, |r, t| r + t.repaid),
+      outstanding: debts(log.initial_assets.len(), &log.trades, credit, log.end()),
+    }
+  }
+
+  pub fn defaulted(&self) -> f64 {
+    self.outstanding.iter().fold(0.0, |d, x| d + x)
+  }
+
+  // Charged, whether or not it was paid.
+  pub fn interest(&self) -> f64 {
+    self.repaid + self.defaulted() - self.borrowed
+  }
+}
+
+pub fn print_report(baseline: &RunLog, log: &RunLog, credit: Credit) {
+  let change = if baseline.volume_a() > 0.0 { format!(", {:+.4}%", 100.0 * (log.volume_a() / baseline.volume_a() - 1.0)) } else { String::new() };
+  println!("credit of up to {} B at {} a tick vs none:", credit.limit, credit.rate);
+  println!("  trades: {} (without {}), volume {} A (without {}{})", log.trades.len(), baseline.trades.len(), log.volume_a(), baseline.volume_a(), change);
+  let lending = Lending::of(log, credit);
+  println!("  {} of {} agents borrowed {} B in all and repaid {}; {} B of interest charged", lending.borrowers, log.initial_assets.len(), lending.borrowed, lending.repaid, lending.interest());
+  match lending.outstanding.iter().filter(|&&x| x > 0.0).count() {
+    0 => println!("  every debt was repaid"),
+    n => println!("  {} in default when the run stopped, owing {} B, written off", n, lending.defaulted()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arrivals::Arrivals;
+  use crate::config::Config;
+  use crate::credit::*;
+  use crate::market::Market;
+  use crate::pricing::PricingRule;
+  use crate::risk::RiskRules;
+  use crate::stopping::{StopReason, StoppingRules};
+  use crate::strategy::Strategies;
+  use crate::simulate;
+  use crate::simulation::SimulationBuilder;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_credit() {
+    assert_eq!(Credit::parse("50:0.01"), Ok(Credit { limit: 50.0, rate: 0.01 }));
+    assert!(Credit::parse("50").is_err() && !Credit { limit: -1.0, rate: 0.0 }.is_valid());
+
+    // borrowing lets buyers take more, and the debts replay from the log as they ran
+    let config = Config { n_agents: 40, ..Config::default() };
+    let credited = Config { pricing: config.pricing.with_credit(Some(Credit { limit: 20.0, rate: 0.01 })), ..config.clone() };
+    let mut simulation = SimulationBuilder::from_config(credited.clone()).seed(3).build().unwrap();
+    while simulation.advance_round().is_none() {}
+    let debts = simulation.debts().to_vec();
+    let log = simulation.run();
+    let lending = Lending::of(&log, credited.pricing.credit.unwrap());
+    assert_eq!(lending.outstanding, debts);
+    assert!(lending.borrowers > 0 && lending.borrowed > 0.0 && lending.interest() >= 0.0);
+    assert!(log.final_assets().iter().all(|(_, balance)| balance.b >= 0.0));
+    assert!(log.volume_a() > simulate(&config, 3).volume_a());
+  }
+
+  #[test]
+  fn test_credit_fixture() {
+    // a buyer valuing A at 3 B with 1 B of its own, a seller at 1 with 10 A: they trade
+    // at 2, the buyer borrowing all 4 B of its line though it would buy more
+    let agent = |a, b| Agent { consumption_b_coeff: b, ..linear_agent(a) };
+    let assets = vec![(agent(3.0, 1.0), Balance { a: 0.0, b: 1.0 }), (agent(1.0, 1.0), Balance { a: 10.0, b: 0.0 })];
+    let credit = Credit { limit: 4.0, rate: 0.5 };
+    let mut market = Market::new(assets, Arrivals::every_tick(0), PricingRule::default().with_credit(Some(credit)), RiskRules::default(), StoppingRules::default());
+    let stop = market.run(&mut Strategies::truthful_for(&market.assets), |_| {});
+    let log = market.into_outcome().into_log(0, vec![]);
+    assert_eq!(log.trades.len(), 1);
+    let trade = &log.trades[0];
+    assert_eq!((trade.amount_a, trade.amount_b, trade.borrowed, trade.repaid), (2.5, 5.0, 4.0, 0.0));
+    assert_eq!(log.final_assets().iter().map(|(_, b)| *b).collect::<Vec<_>>(), vec![Balance { a: 2.5, b: 0.0 }, Balance { a: 7.5, b: 5.0 }]);
+
+    // a tick's interest takes the debt to 6, past the line, so the buyer can borrow no
+    // more; nothing else crosses, and the debt is written off when the run stops
+    assert_eq!((stop.tick, stop.reason), (1, StopReason::Exhausted));
+    let lending = Lending::of(&log, credit);
+    assert_eq!(lending.outstanding, vec![6.0, 0.0]);
+    assert_eq!((lending.borrowers, lending.borrowed, lending.defaulted(), lending.interest()), (1, 4.0, 6.0, 2.0));
+  }
+}
@@ -0,0 +1,71 @@
+// Common random numbers for paired comparisons. Every random decision made during a
+// run is a pure function of the seed, the kind of decision (its stream) and what it's
+// about (an agent, a tick, a side), rather than the next draw from a shared generator.
+// So when a policy changes how many draws get made (a cartel defects, a withdrawn
+// quote isn't mistyped), the draws for everything else stay the same in both arms,
+// and the comparison measures the policy rather than a reshuffle of the noise. The
+// population is drawn once, before any policy acts, so it's common already.
+
+use crate::clock::Tick;
+use crate::AgentId;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Stream {
+  ArrivalRates,
+  Arrivals,
+  Defection,
+  FatFinger,
+  FatFingerDirection,
+  ArrivalOrder,
+  Endowment,
+  ReportingDelay,
+  ZeroIntelligence,
+  Zip,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct Crn {
+  seed: u64,
+}
+
+// splitmix64's finalizer: a bijection that scrambles every bit into every other
+fn mix(x: u64) -> u64 {
+  let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+  let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+  x ^ (x >> 31)
+}
+
+impl Crn {
+  pub fn new(seed: u64) -> Crn {
+    Crn { seed }
+  }
+
+  // Uniform on [0, 1), fixed by the stream and `keys`.
+  pub fn uniform(&self, stream: Stream, keys: &[u64]) -> f64 {
+    let start = mix(self.seed ^ mix(stream as u64 + 1));
+    let hash = keys.iter().fold(start, |h, &k| mix(h ^ k.wrapping_mul(0x9e3779b97f4a7c15)));
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  pub fn chance(&self, probability: f64, stream: Stream, agent: AgentId, tick: Tick, side: u64) -> bool {
+    self.uniform(stream, &[agent as u64, tick, side]) < probability
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::crn::*;
+
+  #[test]
+  fn test_draws_are_keyed() {
+    let crn = Crn::new(7);
+    let draw = crn.uniform(Stream::Arrivals, &[3, 5]);
+    assert_eq!(draw, Crn::new(7).uniform(Stream::Arrivals, &[3, 5]));
+    assert_ne!(draw, crn.uniform(Stream::Arrivals, &[5, 3]));
+    assert_ne!(draw, crn.uniform(Stream::Defection, &[3, 5]));
+    assert_ne!(draw, Crn::new(8).uniform(Stream::Arrivals, &[3, 5]));
+    let draws: Vec<f64> = (0..10_000).map(|i| crn.uniform(Stream::Arrivals, &[i])).collect();
+    assert!(draws.iter().all(|&u| (0.0..1.0).contains(&u)));
+    assert!((draws.iter().sum::<f64>() / draws.len() as f64 - 0.5).abs() < 0.01);
+  }
+}
@@ -0,0 +1,88 @@
+// Parametric summaries of the supply and demand curves, so curves from different
+// runs can be compared as a handful of numbers: a linear fit q = intercept + slope * p
+// and a constant-elasticity fit q = scale * p^elasticity, each with its R^2.
+
+use serde::Serialize;
+
+use crate::{supply_demand_curves, Agent, Balance};
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize)]
+pub struct Fit {
+  pub intercept: f64,
+  pub slope: f64,
+  pub r2: f64,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize)]
+pub struct CurveFits {
+  pub linear: Fit,
+  // intercept is ln(scale), slope is the elasticity
+  pub log_log: Fit,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize)]
+pub struct MarketFits {
+  pub supply: Option<CurveFits>,
+  pub demand: Option<CurveFits>,
+}
+
+// Ordinary least squares of y on x; None with fewer than two distinct x.
+pub fn ols(xs: &[f64], ys: &[f64]) -> Option<Fit> {
+  let n = xs.len() as f64;
+  let (mx, my) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+  let sxx: f64 = xs.iter().map(|x| (x - mx).powi(2)).sum();
+  let sxy: f64 = xs.iter().zip(ys).map(|(x, y)| (x - mx) * (y - my)).sum();
+  let syy: f64 = ys.iter().map(|y| (y - my).powi(2)).sum();
+  if xs.len() < 2 || sxx == 0.0 {
+    return None;
+  }
+  let slope = sxy / sxx;
+  let r2 = if syy == 0.0 { 1.0 } else { sxy * sxy / (sxx * syy) };
+  Some(Fit { intercept: my - slope * mx, slope, r2 })
+}
+
+// Fits one curve given as (price, quantity) points; the log-log fit only uses
+// points with a positive quantity.
+pub fn fit_curve(points: &[(f64, f64)]) -> Option<CurveFits> {
+  let (ps, qs): (Vec<f64>, Vec<f64>) = points.iter().copied().unzip();
+  let (log_ps, log_qs): (Vec<f64>, Vec<f64>) = points.iter()
+    .filter(|(p, q)| *p > 0.0 && *q > 0.0)
+    .map(|(p, q)| (p.ln(), q.ln()))
+    .unzip();
+  Some(CurveFits { linear: ols(&ps, &qs)?, log_log: ols(&log_ps, &log_qs)? })
+}
+
+pub fn fit_market(assets: &[(Agent, Balance)]) -> MarketFits {
+  let curves = supply_demand_curves(assets);
+  let supply: Vec<(f64, f64)> = curves.iter().map(|(p, s, _)| (*p, *s)).collect();
+  let demand: Vec<(f64, f64)> = curves.iter().map(|(p, _, d)| (*p, *d)).collect();
+  MarketFits { supply: fit_curve(&supply), demand: fit_curve(&demand) }
+}
+
+pub fn print_report(label: &str, fits: &MarketFits) {
+  println!("curve fits ({}):", label);
+  for (name, fit) in [("supply", fits.supply), ("demand", fits.demand)] {
+    match fit {
+      Some(f) => println!("  {}: linear q = {} + {} p (R^2 {}); elasticity {} (R^2 {})",
+        name, f.linear.intercept, f.linear.slope, f.linear.r2, f.log_log.slope, f.log_log.r2),
+      None => println!("  {}: too few points to fit", name),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::curve_fit::*;
+
+  #[test]
+  fn test_fit_curve() {
+    // q = 8 p^-2 exactly
+    let points: Vec<(f64, f64)> = [1.0, 2.0, 4.0, 8.0].iter().map(|&p: &f64| (p, 8.0 * p.powi(-2))).collect();
+    let fits = fit_curve(&points).unwrap();
+    assert!((fits.log_log.slope + 2.0).abs() < 1e-12);
+    assert!((fits.log_log.intercept.exp() - 8.0).abs() < 1e-9);
+    assert!((fits.log_log.r2 - 1.0).abs() < 1e-12);
+    assert!(fits.linear.slope < 0.0 && fits.linear.r2 < 1.0);
+    assert_eq!(fit_curve(&points[..1]), None);
+  }
+}
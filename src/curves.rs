@@ -0,0 +1,86 @@
+// Aggregate supply and demand of A sampled on a price grid, for plotting outside the
+// simulator: unlike supply_demand_curves, which steps at every agent's reservation
+// price, the grid is the same whatever the population, so the start and end states
+// of a run (or two runs) line up row for row. Points are spaced geometrically, since
+// reservation prices spread over orders of magnitude.
+
+use crate::numeraire::Numeraire;
+use crate::{supply_demand_at, Agent, Balance, Price};
+
+const DEFAULT_POINTS: usize = 101;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct PriceGrid {
+  pub lo: Price,
+  pub hi: Price,
+  pub points: usize,
+}
+
+impl PriceGrid {
+  // `lo:hi:points`, in B per A
+  pub fn parse(s: &str) -> Result<PriceGrid, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let grid = match parts[..] {
+      [lo, hi, points] => match (lo.parse(), hi.parse(), points.parse()) {
+        (Ok(lo), Ok(hi), Ok(points)) => PriceGrid { lo, hi, points },
+        _ => return Err(format!("can't read price grid {:?}", s)),
+      },
+      _ => return Err(format!("expected a price grid lo:hi:points, got {:?}", s)),
+    };
+    if !(grid.lo > 0.0 && grid.hi > grid.lo && grid.points >= 2) {
+      return Err(format!("price grid {:?} needs 0 < lo < hi and at least 2 points", s));
+    }
+    Ok(grid)
+  }
+
+  // Spanning the agents' marginal rates at their holdings; None if no agent has a
+  // finite, positive rate or they all share one.
+  pub fn spanning(assets: &[(Agent, Balance)]) -> Option<PriceGrid> {
+    let rates = assets.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).filter(|r| r.is_finite() && *r > 0.0);
+    let (lo, hi) = rates.fold((f64::INFINITY, 0.0_f64), |(lo, hi), r| (lo.min(r), hi.max(r)));
+    if lo < hi { Some(PriceGrid { lo, hi, points: DEFAULT_POINTS }) } else { None }
+  }
+
+  pub fn prices(&self) -> Vec<Price> {
+    let step = (self.hi / self.lo).ln() / (self.points - 1) as f64;
+    (0..self.points).map(|i| self.lo * (step * i as f64).exp()).collect()
+  }
+}
+
+// (price, supply, demand) at every price on the grid.
+pub fn on_grid(assets: &[(Agent, Balance)], grid: PriceGrid) -> Vec<(Price, f64, f64)> {
+  grid.prices().into_iter().map(|price| {
+    let (supply, demand) = supply_demand_at(assets, price);
+    (price, supply, demand)
+  }).collect()
+}
+
+pub fn csv(curves: &[(Price, f64, f64)], numeraire: Numeraire) -> String {
+  let mut out = String::from("price,supply,demand\n");
+  for (price, supply, demand) in curves {
+    out.push_str(&format!("{},{},{}\n", numeraire.price(*price), supply, demand));
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::curves::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_grid_curves() {
+    assert_eq!(PriceGrid::parse("0.5:2:3"), Ok(PriceGrid { lo: 0.5, hi: 2.0, points: 3 }));
+    assert!(PriceGrid::parse("2:1:3").is_err() && PriceGrid::parse("1:2").is_err());
+    let prices = PriceGrid { lo: 0.5, hi: 2.0, points: 3 }.prices();
+    assert!((prices[1] - 1.0).abs() < 1e-12 && (prices[2] - 2.0).abs() < 1e-12);
+
+    let assets = vec![(linear_agent(0.75), Balance { a: 10.0, b: 0.0 }), (linear_agent(1.5), Balance { a: 0.0, b: 6.0 })];
+    let grid = PriceGrid::spanning(&assets).unwrap();
+    assert_eq!((grid.lo, grid.hi), (0.75, 1.5));
+    let curves = on_grid(&assets, PriceGrid { points: 2, ..grid });
+    // at the seller's rate it sells everything and the buyer spends everything
+    assert_eq!(curves, vec![(0.75, 10.0, 8.0), (1.5, 10.0, 4.0)]);
+    assert_eq!(csv(&curves[..1], Numeraire::B), "price,supply,demand\n0.75,10,8\n");
+  }
+}
@@ -0,0 +1,115 @@
+// A dark pool beside the lit market. The lowest-numbered share of agents route their
+// orders to it rather than to the lit book, where they rest hidden: nobody quoting
+// against the book sees them (see strategy). The pool has no prices of its own. A dark
+// bid above the lit market's midpoint meets a dark ask below it at the midpoint, so
+// dark trades take the price the lit book discovers and add nothing to it, and the
+// pool can't trade at all while the lit book is missing a side. Each tick trades in
+// the pool first if it can, and on the lit book otherwise.
+//
+// The report sets a run against the same population all trading lit: how much the
+// pool takes, and how well the lit trades that are left find the price the initial
+// supply and demand curves cross at.
+
+use serde::{Deserialize, Serialize};
+
+use crate::book::OrderBook;
+use crate::clock::Tick;
+use crate::entry::expected_price;
+use crate::pairs::settled_at;
+use crate::pricing::PricingRule;
+use crate::runlog::RunLog;
+use crate::{fill, Agent, AgentId, Balance, Order, Price, Trade};
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct DarkPool {
+  // of agents, lowest-numbered first, routing to the pool
+  pub share: f64,
+}
+
+impl DarkPool {
+  pub fn is_dark(&self, id: AgentId, n_agents: usize) -> bool {
+    (id as f64) < self.share * n_agents as f64
+  }
+
+  pub fn ids(&self, n_agents: usize) -> Vec<AgentId> {
+    (0..n_agents).filter(|&id| self.is_dark(id, n_agents)).collect()
+  }
+
+  // Whether `trade` went through the pool; both sides are dark if either is.
+  pub fn traded(&self, trade: &Trade, n_agents: usize) -> bool {
+    self.is_dark(trade.buyer, n_agents)
+  }
+}
+
+// The pool's best crossing at the lit midpoint if there is one, else the lit book's.
+pub fn find_next_trade(assets: &[(Agent, Balance)], book: &OrderBook, pricing: PricingRule, pool: DarkPool, now: Tick) -> Option<Trade> {
+  let by_price = |o1: &Order, o2: &Order| o1.price_per_a_in_b.partial_cmp(&o2.price_per_a_in_b).unwrap();
+  let venue = |dark| book.quotes().iter().enumerate().filter(move |&(id, _)| pool.is_dark(id, assets.len()) == dark).map(|(_, q)| q);
+  let bid = venue(false).filter_map(|q| q.0).filter(|o| pricing.admits_bid(o.price_per_a_in_b)).max_by(by_price)?;
+  let ask = venue(false).filter_map(|q| q.1).filter(|o| pricing.admits_ask(o.price_per_a_in_b)).min_by(by_price)?;
+  let mid = (bid.price_per_a_in_b + ask.price_per_a_in_b) / 2.0;
+  let dark_bid = venue(true).filter_map(|q| q.0).filter(|o| o.price_per_a_in_b > mid).max_by(by_price);
+  let dark_ask = venue(true).filter_map(|q| q.1).filter(|o| o.price_per_a_in_b < mid).min_by(by_price);
+  match (dark_bid, dark_ask) {
+    (Some(bid), Some(ask)) => {
+      let at_mid = |o: Order| Order { price_per_a_in_b: mid, ..o };
+      Some(Trade { bid_price: bid.price_per_a_in_b, ask_price: ask.price_per_a_in_b, ..fill(assets, at_mid(bid), at_mid(ask), pricing, now) })
+    }
+    _ if ask.price_per_a_in_b < bid.price_per_a_in_b => Some(fill(assets, bid, ask, pricing, now)),
+    _ => None,
+  }
+}
+
+pub struct Discovery {
+  pub lit_trades: usize,
+  // the pool's share of the A traded
+  pub dark_share: Option<f64>,
+  // the first lit trade from which lit prices stay within 1% of the last one
+  pub settled_at: Option<usize>,
+  // root mean square of each lit price relative to the curves' crossing, less 1
+  pub price_error: Option<f64>,
+}
+
+pub fn discovery(log: &RunLog, pool: Option<DarkPool>) -> Discovery {
+  let n = log.initial_assets.len();
+  let dark = |t: &Trade| pool.is_some_and(|p| p.traded(t, n));
+  let lit: Vec<Price> = log.trades.iter().filter(|t| !dark(t)).map(Trade::price_per_a_in_b).collect();
+  let volume = log.volume_a();
+  let dark_volume = log.trades.iter().filter(|t| dark(t)).fold(0.0, |v, t| v + t.amount_a);
+  let price_error = expected_price(&log.initial_assets).filter(|_| !lit.is_empty())
+    .map(|p| (lit.iter().fold(0.0, |s, x| s + (x / p - 1.0).powi(2)) / lit.len() as f64).sqrt());
+  Discovery {
+    lit_trades: lit.len(),
+    dark_share: (volume > 0.0).then(|| dark_volume / volume),
+    settled_at: settled_at(&lit),
+    price_error,
+  }
+}
+
+pub fn print_report(lit: &RunLog, run: &RunLog, pool: DarkPool) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  println!("dark pool ({}% of agents) vs an all-lit market:", 100.0 * pool.share);
+  for (label, log, pool) in [("lit", lit, None), ("with pool", run, Some(pool))] {
+    let d = discovery(log, pool);
+    let settled = d.settled_at.map_or("never".to_string(), |i| format!("from lit trade {}", i));
+    println!("  {:<9}  {} trades, {} lit, dark share {}; lit prices settled {}, error against the curves' crossing {}", label, log.trades.len(), d.lit_trades, show(d.dark_share), settled, show(d.price_error));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::dark_pool::*;
+  use crate::simulation::SimulationBuilder;
+
+  #[test]
+  fn test_dark_pool() {
+    let pool = DarkPool { share: 0.3 };
+    assert_eq!(pool.ids(10), vec![0, 1, 2]);
+    let log = SimulationBuilder::new().agents(60).seed(3).pricing(PricingRule::default().with_dark_pool(Some(pool))).build().unwrap().run();
+    // every trade stays in its venue, and dark ones go at a price inside both quotes
+    assert!(log.trades.iter().all(|t| pool.is_dark(t.buyer, 60) == pool.is_dark(t.seller, 60)));
+    let dark: Vec<&Trade> = log.trades.iter().filter(|t| pool.traded(t, 60)).collect();
+    assert!(!dark.is_empty() && dark.iter().all(|t| t.ask_price < t.price_per_a_in_b() && t.price_per_a_in_b() < t.bid_price));
+    assert!(discovery(&log, Some(pool)).dark_share.unwrap() > 0.0);
+  }
+}
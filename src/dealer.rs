@@ -0,0 +1,148 @@
+// Market makers: dealers that quote a bid and an ask at once, either side of a mid
+// price, and make their money on the spread between them. The last agents of the
+// population are recast as dealers, valuing A at about the price the initial supply
+// and demand curves cross at (see entry::expected_price), so they quote around where
+// the market should end up. Each starts with an inventory of A and the B to buy as much
+// again at the mid, and stops bidding once it holds its limit, so capital and the limit
+// between them bound how far it can lean to either side. A dealer's quote depends only
+// on its own balance and its two quotes never cross each other, so dealers can't trade
+// among themselves.
+//
+// The report sets a run with dealers against the same population without them: how
+// fast prices settle, the spread left quoted at the end, and what the dealers made.
+
+use serde::{Deserialize, Serialize};
+
+use crate::entry::expected_price;
+use crate::reporting::convergence;
+use crate::runlog::RunLog;
+use crate::strategy::Strategies;
+use crate::utility::UtilityFn;
+use crate::{Agent, AgentId, Balance, Order, OrderType, Price};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dealers {
+  pub n: usize,
+  // ask less bid, as a share of the mid
+  pub spread: f64,
+  // the A each starts with; it stops bidding once it holds twice that
+  pub inventory: f64,
+}
+
+impl Dealers {
+  // `<n>:<spread>:<inventory>`, e.g. `2:0.05:100`
+  pub fn parse(s: &str) -> Result<Dealers, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let parsed = match parts[..] {
+      [n, spread, inventory] => (|| Some(Dealers { n: n.parse().ok()?, spread: spread.parse().ok()?, inventory: inventory.parse().ok()? }))(),
+      _ => None,
+    };
+    parsed.ok_or_else(|| format!("bad dealers spec {:?} (expected <n>:<spread>:<inventory>)", s))
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{} dealers quoting a {}% spread, bidding up to {} A", self.n, 100.0 * self.spread, 2.0 * self.inventory)
+  }
+
+  // The dealers among `n_agents`: the last ones.
+  pub fn ids(&self, n_agents: usize) -> std::ops::Range<AgentId> {
+    n_agents - self.n..n_agents
+  }
+
+  // Recasts the last agents of `assets` as dealers, at the indifference price (at its
+  // holdings) of the agent the rest's curves cross at (or, if they never do, the mean
+  // of them all). The marginal agent's exactly, not the crossing a hair off it, so the
+  // two don't sample the curves out of order.
+  pub fn install(&self, assets: &mut [(Agent, Balance)]) {
+    let ids = self.ids(assets.len());
+    let rest = &assets[..ids.start];
+    let prices: Vec<Price> = rest.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).collect();
+    let mid = match expected_price(rest) {
+      Some(p) => prices.iter().copied().min_by(|x, y| (x - p).abs().total_cmp(&(y - p).abs())).unwrap(),
+      None => prices.iter().fold(0.0, |s, p| s + p) / prices.len() as f64,
+    };
+    for id in ids {
+      let agent = Agent { production_a: 0.0, production_b: 0.0, consumption_a_coeff: mid, consumption_b_coeff: 1.0, utility_fn: UtilityFn::Linear };
+      assets[id] = (agent, Balance { a: self.inventory, b: self.inventory * mid });
+    }
+  }
+
+  // A dealer's (bid, ask) around its `mid`, on whichever sides its balance and its
+  // limit allow.
+  pub fn quote(&self, id: AgentId, mid: Price, balance: &Balance) -> (Option<Order>, Option<Order>) {
+    let order = |typ, price_per_a_in_b| Order { agent_id: id, typ, price_per_a_in_b };
+    let bid = (balance.b > 0.0 && balance.a < 2.0 * self.inventory).then(|| order(OrderType::Bid, mid * (1.0 - self.spread / 2.0)));
+    let ask = (balance.a > 0.0).then(|| order(OrderType::Ask, mid * (1.0 + self.spread / 2.0)));
+    (bid, ask)
+  }
+}
+
+// The lowest ask less the highest bid from anyone else, as `strategies` would quote
+// `assets`: an agent holding both goods bids and asks the same price, which is no
+// spread anyone could trade across.
+pub fn quoted_spread(strategies: &mut Strategies, assets: &[(Agent, Balance)]) -> Option<Price> {
+  strategies.price(assets);
+  let orders = strategies.orders(assets, 0);
+  let mut bids: Vec<Order> = orders.iter().filter_map(|q| q.0).collect();
+  let mut asks: Vec<Order> = orders.iter().filter_map(|q| q.1).collect();
+  bids.sort_by(|x, y| y.price_per_a_in_b.total_cmp(&x.price_per_a_in_b));
+  asks.sort_by(|x, y| x.price_per_a_in_b.total_cmp(&y.price_per_a_in_b));
+  let gap = |ask: Option<&Order>, bid: Option<&Order>| Some(ask?.price_per_a_in_b - bid?.price_per_a_in_b);
+  match (bids.first(), asks.first()) {
+    (Some(bid), Some(ask)) if bid.agent_id == ask.agent_id => {
+      [gap(asks.get(1), Some(bid)), gap(Some(ask), bids.get(1))].iter().flatten().copied().min_by(f64::total_cmp)
+    }
+    (bid, ask) => gap(ask, bid),
+  }
+}
+
+// What the dealers made over the run, in B at their own mid.
+pub fn profit(log: &RunLog, dealers: Dealers) -> f64 {
+  let last = log.final_assets();
+  dealers.ids(last.len()).fold(0.0, |p, id| {
+    let ((agent, before), (_, after)) = (&log.initial_assets[id], &last[id]);
+    p + (after.a - before.a) * agent.indifference_price_of_a_in_b() + after.b - before.b
+  })
+}
+
+// The share of the A traded that a dealer bought or sold.
+pub fn intermediated(log: &RunLog, dealers: Dealers) -> Option<f64> {
+  let ids = dealers.ids(log.initial_assets.len());
+  let volume = log.volume_a();
+  let dealt = log.trades.iter().filter(|t| ids.contains(&t.buyer) || ids.contains(&t.seller)).fold(0.0, |v, t| v + t.amount_a);
+  (volume > 0.0).then(|| dealt / volume)
+}
+
+// `spreads` are as quoted at the end of each run, without dealers then with.
+pub fn print_report(without: &RunLog, with: &RunLog, dealers: Dealers, spreads: (Option<Price>, Option<Price>)) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  println!("{} vs none:", dealers.describe());
+  for (label, log, spread) in [("without", without, spreads.0), ("with", with, spreads.1)] {
+    let c = convergence(log);
+    let settled = c.settled_at.map_or("never".to_string(), |(i, tick)| format!("from trade {} (tick {})", i, tick));
+    println!("  {:<7}  {} trades over {} ticks, settled {}, price error {}, final spread {}", label, c.trades, c.ticks, settled, show(c.price_error), show(spread));
+  }
+  println!("  dealers' share of volume: {}, their profit: {} B", show(intermediated(with, dealers)), profit(with, dealers));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::dealer::*;
+  use crate::simulation::SimulationBuilder;
+
+  #[test]
+  fn test_dealers() {
+    assert_eq!(Dealers::parse("2:0.1:50"), Ok(Dealers { n: 2, spread: 0.1, inventory: 50.0 }));
+    assert!(Dealers::parse("2:0.1").is_err());
+    let dealers = Dealers { n: 2, spread: 0.1, inventory: 50.0 };
+    let (bid, ask) = dealers.quote(0, 2.0, &Balance { a: 100.0, b: 1.0 });
+    // at its limit it only sells
+    assert_eq!((bid, ask.map(|o| o.price_per_a_in_b)), (None, Some(2.1)));
+
+    let log = SimulationBuilder::new().agents(60).seed(2).dealers(dealers).build().unwrap().run();
+    let last = log.final_assets();
+    // the dealers never lose on a trade, and stay inside their limit but for a last fill
+    assert!(intermediated(&log, dealers).unwrap() > 0.0 && profit(&log, dealers) > 0.0);
+    assert!(dealers.ids(60).all(|id| last[id].1.a >= 0.0 && last[id].1.a < 3.0 * dealers.inventory));
+  }
+}
@@ -0,0 +1,100 @@
+// Perishable goods: a share of every agent's holdings of each good lost at the end of
+// every tick, the way food spoils or stock goes out of date, so what isn't traded
+// shrinks while it waits. The rates are per good, and either can be 0 for a good that
+// keeps. Everyone's holdings decay alike, whether or not they traded that tick, and
+// a market that stops (see stopping) stops spoiling with it.
+//
+// Spoilage isn't logged: a log carries the rates in its header and the replays (see
+// runlog::RunLog::assets_at) spoil the holdings tick by tick as the run did, so the
+// trades are still enough to replay it. Analyses that value the trades against the
+// holdings at the time (accounting, dispersion, history) replay the trades alone and
+// see holdings before spoilage; the run's final holdings and the surplus realized on
+// them are net of it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::runlog::RunLog;
+use crate::{credit, lots, short, subsidy, tax, Agent, Balance};
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Decay {
+  // the shares of each good lost per tick
+  pub a: f64,
+  pub b: f64,
+}
+
+impl Decay {
+  // `<A rate>:<B rate>`, e.g. `0.01:0` for A alone perishing at 1% a tick
+  pub fn parse(s: &str) -> Result<Decay, String> {
+    let (a, b) = s.split_once(':').ok_or_else(|| format!("bad decay {:?} (expected <A rate>:<B rate>)", s))?;
+    let rate = |r: &str| r.parse().map_err(|_| format!("can't read decay rate {:?}", r));
+    Ok(Decay { a: rate(a)?, b: rate(b)? })
+  }
+
+  pub fn is_valid(&self) -> bool {
+    (0.0..1.0).contains(&self.a) && (0.0..1.0).contains(&self.b)
+  }
+
+  // A tick's spoilage of everyone's `assets`, returning the total lost.
+  pub fn apply(&self, assets: &mut [(Agent, Balance)]) -> Balance {
+    let mut lost = Balance { a: 0.0, b: 0.0 };
+    for (_, balance) in assets.iter_mut() {
+      let (a, b) = (balance.a * self.a, balance.b * self.b);
+      balance.a -= a;
+      balance.b -= b;
+      lost.a += a;
+      lost.b += b;
+    }
+    lost
+  }
+}
+
+// What spoiled over the run `log` records: what left the ledger other than taxes,
+// subsidies and loans, before anything short was bought in.
+pub fn spoiled(log: &RunLog) -> Balance {
+  let (before, after) = (lots::totals(&log.initial_assets), lots::totals(&log.assets_at(&[log.end()])[0]));
+  let b = before.b - after.b - tax::revenue(&log.trades) + subsidy::outlay(&log.trades) + credit::net_lent(&log.trades);
+  Balance { a: before.a - after.a + short::net_shorted(&log.trades), b }
+}
+
+pub fn print_report(log: &RunLog, decay: Decay) {
+  let (endowment, lost) = (lots::totals(&log.initial_assets), spoiled(log));
+  let share = |lost: f64, total: f64| if total > 0.0 { format!("{:.2}%", 100.0 * lost / total) } else { "n/a".to_string() };
+  println!("spoilage at {} of A and {} of B a tick over {} ticks:", decay.a, decay.b, log.end());
+  // a good that keeps loses nothing but rounding
+  for (good, rate, lost, total) in [("A", decay.a, lost.a, endowment.a), ("B", decay.b, lost.b, endowment.b)] {
+    if rate > 0.0 {
+      println!("  {}: {} lost, {} of the endowment", good, lost, share(lost, total));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::Config;
+  use crate::decay::*;
+  use crate::simulate;
+  use crate::simulation::SimulationBuilder;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_decay() {
+    assert_eq!(Decay::parse("0.1:0"), Ok(Decay { a: 0.1, b: 0.0 }));
+    assert!(Decay::parse("0.1").is_err() && !Decay { a: 1.0, b: 0.0 }.is_valid());
+    let agent = linear_agent(1.0);
+    let mut assets = vec![(agent, Balance { a: 10.0, b: 4.0 })];
+    assert_eq!(Decay { a: 0.1, b: 0.5 }.apply(&mut assets), Balance { a: 1.0, b: 2.0 });
+    assert_eq!(assets[0].1, Balance { a: 9.0, b: 2.0 });
+
+    // the log replays the run's spoilage exactly, and perishing A leaves less traded
+    let config = Config { n_agents: 40, ..Config::default() };
+    let perishable = Config { pricing: config.pricing.with_decay(Some(Decay { a: 0.05, b: 0.0 })), ..config.clone() };
+    let mut simulation = SimulationBuilder::from_config(perishable.clone()).seed(3).build().unwrap();
+    while simulation.advance_round().is_none() {}
+    let final_assets = simulation.assets().to_vec();
+    let log = simulation.run();
+    assert_eq!(log.final_assets(), final_assets);
+    assert!(spoiled(&log).a > 0.0 && spoiled(&log).b.abs() < 1e-9);
+    assert!(log.volume_a() < simulate(&config, 3).volume_a());
+  }
+}
@@ -0,0 +1,140 @@
+// Nominal versus real quantities across a run. Everything is valued in B, but the
+// price of A in B moves as the market clears, so B turnover and B-valued gains from
+// different stretches of a run aren't comparable as they stand. The run is cut into
+// periods of equal length, a price index is computed from each period's trades
+// (relative to the first period that trades), and real values are the nominal ones
+// divided by it. There's one good besides B, so a fixed-basket index reduces to the
+// period's price of A; the choice is how that price is taken from the trades.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::{self, Tick};
+use crate::runlog::RunLog;
+use crate::stats::quantile;
+use crate::{settle, Agent, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceIndex {
+  // B paid per A bought over the period
+  Vwap,
+  // the median trade price, which one outsized trade can't move
+  Median,
+}
+
+impl PriceIndex {
+  pub fn parse(s: &str) -> Result<PriceIndex, String> {
+    match s {
+      "vwap" => Ok(PriceIndex::Vwap),
+      "median" => Ok(PriceIndex::Median),
+      _ => Err(format!("unknown price index {:?} (expected vwap or median)", s)),
+    }
+  }
+
+  fn level(&self, amounts: &[(f64, f64)]) -> Option<Price> {
+    if amounts.is_empty() {
+      return None;
+    }
+    Some(match self {
+      PriceIndex::Vwap => amounts.iter().map(|t| t.1).sum::<f64>() / amounts.iter().map(|t| t.0).sum::<f64>(),
+      PriceIndex::Median => quantile(&amounts.iter().map(|(a, b)| b / a).collect::<Vec<_>>(), 0.5),
+    })
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Period {
+  // [start, end)
+  pub ticks: (Tick, Tick),
+  pub trades: usize,
+  // the price level relative to the base period, carried forward through periods
+  // without trades; None before anything has traded
+  pub index: Option<f64>,
+  // B changing hands, and the trades' gains to both sides valued in B
+  pub turnover: f64,
+  pub gains: f64,
+}
+
+impl Period {
+  pub fn deflate(&self, nominal: f64) -> Option<f64> {
+    self.index.map(|index| nominal / index)
+  }
+}
+
+// Each trade's gain to its two parties, in B at each one's marginal utility of B. A
+// linear agent's is the trade's own utility; any other's is the change in its utility
+// across the trade, from its holdings then.
+fn trade_gains(log: &RunLog) -> Vec<f64> {
+  let mut assets = log.initial_assets.clone();
+  log.trades.iter().map(|t| {
+    let gain = |(agent, before): &(Agent, Balance), delta_a: f64, delta_b: f64| {
+      if agent.utility_fn.is_linear() {
+        return agent.utility(delta_a, delta_b) / agent.consumption_b_coeff;
+      }
+      let utils = agent.utility(before.a + delta_a, before.b + delta_b) - agent.utility(before.a, before.b);
+      utils / agent.marginal_utilities(before).1
+    };
+    let total = gain(&assets[t.buyer], t.amount_a, -t.amount_b) + gain(&assets[t.seller], -t.amount_a, t.amount_b);
+    settle(&mut assets, t);
+    total
+  }).collect()
+}
+
+pub fn periods(log: &RunLog, n_periods: usize, index: PriceIndex) -> Vec<Period> {
+  let per_trade = trade_gains(log);
+  let mut base = None;
+  let mut level = None;
+  let mut out = vec![];
+  for (start, end) in clock::periods(log.end(), n_periods) {
+    let in_period: Vec<usize> = (0..log.trades.len()).filter(|&i| log.trades[i].tick >= start && log.trades[i].tick < end).collect();
+    let amounts: Vec<(f64, f64)> = in_period.iter().map(|&i| (log.trades[i].amount_a, log.trades[i].amount_b)).collect();
+    if let Some(price) = index.level(&amounts) {
+      base = base.or(Some(price));
+      level = Some(price / base.unwrap());
+    }
+    let gains = in_period.iter().map(|&i| per_trade[i]).sum();
+    out.push(Period { ticks: (start, end), trades: in_period.len(), index: level, turnover: amounts.iter().map(|t| t.1).sum(), gains });
+  }
+  out
+}
+
+pub fn print_report(periods: &[Period], index: PriceIndex) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| x.to_string());
+  println!("nominal vs real (deflated by the {:?} price of A, first trading period = 1):", index);
+  for period in periods {
+    println!("  ticks {}..{}: {} trades, index {}, turnover {} B nominal / {} real, gains {} B nominal / {} real",
+      period.ticks.0, period.ticks.1, period.trades, show(period.index),
+      period.turnover, show(period.deflate(period.turnover)), period.gains, show(period.deflate(period.gains)));
+  }
+  let total = |f: fn(&Period) -> Option<f64>| periods.iter().filter_map(f).sum::<f64>();
+  println!("  total gains: {} B nominal, {} real", total(|p| Some(p.gains)), total(|p| p.deflate(p.gains)));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::deflation::*;
+  use crate::stopping::{Stop, StopReason};
+  use crate::{linear_agent, Balance, Trade};
+
+  #[test]
+  fn test_periods() {
+    let trade = |tick, amount_a, amount_b| Trade { tick, buyer: 1, seller: 0, amount_a, amount_b, bid_price: 4.0, ask_price: 1.0, ..Trade::default() };
+    let log = RunLog {
+      seed: 0,
+      initial_assets: vec![(linear_agent(1.0), Balance { a: 10.0, b: 0.0 }), (linear_agent(4.0), Balance { a: 0.0, b: 20.0 })],
+      quotes: vec![],
+      rejections: vec![],
+      // the price doubles from 1 to 2 between the first two periods; the third is quiet
+      trades: vec![trade(0, 1.0, 1.0), trade(1, 1.0, 1.0), trade(2, 1.0, 2.0), trade(3, 3.0, 6.0)],
+      stop: Some(Stop { tick: 6, reason: StopReason::Exhausted }),
+      decay: None,
+      short: None,
+    };
+    let periods = periods(&log, 3, PriceIndex::Vwap);
+    assert_eq!(periods.len(), 3);
+    assert_eq!(periods[1], Period { ticks: (2, 4), trades: 2, index: Some(2.0), turnover: 8.0, gains: 4.0 * 3.0 });
+    assert_eq!(periods[1].deflate(periods[1].turnover), Some(4.0));
+    assert_eq!((periods[2].trades, periods[2].index), (0, Some(2.0)));
+    assert!(PriceIndex::parse("cpi").is_err());
+  }
+}
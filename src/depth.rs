@@ -0,0 +1,122 @@
+// The top k bids and asks resting on the book at every match, rebuilt from the
+// logged quote changes, to show how contested the margin was: how close the
+// runners-up were to the quotes that traded, and how often the best price was tied
+// (so the choice between them came down to tie-breaking).
+
+use std::fmt::Write;
+
+use crate::clock::Tick;
+use crate::runlog::RunLog;
+use crate::stats::mean;
+use crate::{AgentId, OrderType, Price};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Depth {
+  pub tick: Tick,
+  // best first, ties in agent order
+  pub bids: Vec<(AgentId, Price)>,
+  pub asks: Vec<(AgentId, Price)>,
+}
+
+// The book just before each trade; see RunLog::books_at.
+pub fn at_trades(log: &RunLog, k: usize) -> Vec<Depth> {
+  at_ticks(log, &log.trades.iter().map(|t| t.tick).collect::<Vec<_>>(), k)
+}
+
+// The top k levels at each of `ticks` (increasing).
+pub fn at_ticks(log: &RunLog, ticks: &[Tick], k: usize) -> Vec<Depth> {
+  ticks.iter().zip(log.books_at(ticks)).map(|(&tick, book)| Depth {
+    tick,
+    bids: top(book.iter().map(|q| q.0), k, OrderType::Bid),
+    asks: top(book.iter().map(|q| q.1), k, OrderType::Ask),
+  }).collect()
+}
+
+// The k best of one side's prices, indexed by agent.
+fn top(prices: impl Iterator<Item = Option<Price>>, k: usize, side: OrderType) -> Vec<(AgentId, Price)> {
+  let mut levels: Vec<(AgentId, Price)> = prices.enumerate().filter_map(|(id, p)| p.map(|p| (id, p))).collect();
+  match side {
+    OrderType::Bid => levels.sort_by(|x, y| y.1.partial_cmp(&x.1).unwrap()),
+    OrderType::Ask => levels.sort_by(|x, y| x.1.partial_cmp(&y.1).unwrap()),
+  }
+  levels.truncate(k);
+  levels
+}
+
+pub fn csv(depths: &[Depth]) -> String {
+  let mut out = String::from("seq,tick,side,rank,agent,price\n");
+  for (seq, depth) in depths.iter().enumerate() {
+    for (side, levels) in [("bid", &depth.bids), ("ask", &depth.asks)] {
+      for (rank, (agent, price)) in levels.iter().enumerate() {
+        writeln!(out, "{},{},{},{},{},{}", seq, depth.tick, side, rank, agent, price).unwrap();
+      }
+    }
+  }
+  out
+}
+
+// |best - last shown| / best on one side, if it shows more than one level.
+fn spread_behind(levels: &[(AgentId, Price)]) -> Option<f64> {
+  match (levels.first(), levels.last()) {
+    (Some(best), Some(last)) if levels.len() > 1 => Some((best.1 - last.1).abs() / best.1),
+    _ => None,
+  }
+}
+
+fn tied_at_best(levels: &[(AgentId, Price)]) -> bool {
+  levels.len() > 1 && levels[0].1 == levels[1].1
+}
+
+pub fn print_report(depths: &[Depth], k: usize) {
+  if depths.is_empty() {
+    return;
+  }
+  let behind = |side: fn(&Depth) -> &[(AgentId, Price)]| {
+    let gaps: Vec<f64> = depths.iter().filter_map(|d| spread_behind(side(d))).collect();
+    if gaps.is_empty() { "n/a".to_string() } else { mean(&gaps).to_string() }
+  };
+  let ties = |side: fn(&Depth) -> &[(AgentId, Price)]| depths.iter().filter(|d| tied_at_best(side(d))).count();
+  println!("top-{} depth at each of {} matches:", k, depths.len());
+  println!("  mean relative distance from the best to the last of the top {}: bids {}, asks {}", k, behind(|d| &d.bids), behind(|d| &d.asks));
+  println!("  best price tied: bids at {} matches, asks at {}", ties(|d| &d.bids), ties(|d| &d.asks));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::depth::*;
+  use crate::runlog::Quote;
+  use crate::{linear_agent, Balance, Trade};
+
+  #[test]
+  fn test_at_trades() {
+    let agent = linear_agent(1.0);
+    let quote = |tick, agent_id, side, price| Quote { tick, agent_id, side, price };
+    let trade = |tick| Trade { tick, buyer: 0, seller: 2, amount_a: 1.0, amount_b: 1.0, bid_price: 0.0, ask_price: 0.0, ..Trade::default() };
+    let log = RunLog {
+      seed: 0,
+      initial_assets: vec![(agent, Balance { a: 1.0, b: 1.0 }); 3],
+      quotes: vec![
+        quote(0, 0, OrderType::Bid, Some(3.0)),
+        quote(0, 1, OrderType::Bid, Some(3.0)),
+        quote(0, 2, OrderType::Ask, Some(1.0)),
+        quote(1, 1, OrderType::Bid, None),
+        quote(1, 1, OrderType::Ask, Some(2.0)),
+      ],
+      rejections: vec![],
+      trades: vec![trade(0), trade(1)],
+      stop: None,
+      decay: None,
+      short: None,
+    };
+    let depths = at_trades(&log, 2);
+    assert_eq!(depths[0], Depth { tick: 0, bids: vec![(0, 3.0), (1, 3.0)], asks: vec![(2, 1.0)] });
+    assert_eq!(depths[1], Depth { tick: 1, bids: vec![(0, 3.0)], asks: vec![(2, 1.0), (1, 2.0)] });
+    assert!(tied_at_best(&depths[0].bids) && !tied_at_best(&depths[1].asks));
+    assert_eq!(spread_behind(&depths[1].asks), Some(1.0));
+    assert_eq!(csv(&depths).lines().count(), 1 + 3 + 3);
+
+    // any tick, traded at or not, and the book as a whole
+    assert_eq!(at_ticks(&log, &[5], 1), vec![Depth { tick: 5, bids: vec![(0, 3.0)], asks: vec![(2, 1.0)] }]);
+    assert_eq!(log.books_at(&[0])[0], vec![(Some(3.0), None), (Some(3.0), None), (None, Some(1.0))]);
+  }
+}
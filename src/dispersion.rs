@@ -0,0 +1,87 @@
+// Law-of-one-price violations: how far each trade's price was from the Walrasian
+// price of the market as it stood just before the trade.
+
+use crate::equilibrium;
+use crate::runlog::RunLog;
+use crate::stats::{mean, quantile, std_dev};
+use crate::{settle, Agent, Balance, Price};
+
+// The price at which everyone's demand for A (budget / price, from agents valuing A
+// above the price) equals the supply of A (from agents valuing it below), found by
+// bisection on the monotone excess demand. That's only how linear agents demand; with
+// any others it's the equilibrium's price, on their demand. None unless someone has A
+// and someone has B.
+pub fn walrasian_price(assets: &[(Agent, Balance)]) -> Option<Price> {
+  if assets.iter().all(|(_, balance)| balance.a == 0.0) || assets.iter().all(|(_, balance)| balance.b == 0.0) {
+    return None;
+  }
+  if !assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()) {
+    return equilibrium::solve(assets).map(|e| e.price);
+  }
+  let excess_demand = |price: Price| -> f64 {
+    assets.iter().map(|(agent, balance)| {
+      let reservation = agent.indifference_price_of_a_in_b();
+      if reservation > price { balance.b / price } else if reservation < price { -balance.a } else { 0.0 }
+    }).sum()
+  };
+  let reservations = assets.iter().map(|(agent, _)| agent.indifference_price_of_a_in_b());
+  let mut lo = reservations.clone().fold(f64::INFINITY, f64::min);
+  let mut hi = reservations.fold(0.0, f64::max);
+  for _ in 0..100 {
+    let mid = (lo + hi) / 2.0;
+    if excess_demand(mid) > 0.0 { lo = mid } else { hi = mid }
+  }
+  Some((lo + hi) / 2.0)
+}
+
+// (clearing price, Walrasian price just before the trade) for every trade.
+pub fn price_gaps(log: &RunLog) -> Vec<(Price, Price)> {
+  let mut assets = log.initial_assets.clone();
+  let mut gaps = vec![];
+  for trade in &log.trades {
+    gaps.push((trade.price_per_a_in_b(), walrasian_price(&assets).unwrap()));
+    settle(&mut assets, trade);
+  }
+  gaps
+}
+
+// Each trade's gap relative to the Walrasian price: (clearing - walrasian) / walrasian.
+pub fn relative_gaps(gaps: &[(Price, Price)]) -> Vec<f64> {
+  gaps.iter().map(|(p, w)| (p - w) / w).collect()
+}
+
+pub fn print_report(log: &RunLog) {
+  let gaps = relative_gaps(&price_gaps(log));
+  if gaps.is_empty() {
+    println!("price dispersion: no trades");
+    return;
+  }
+  let abs: Vec<f64> = gaps.iter().map(|g| g.abs()).collect();
+  println!("price vs contemporaneous Walrasian price (relative gap):");
+  println!("  mean {}, std dev {}, mean |gap| {}", mean(&gaps), std_dev(&gaps), mean(&abs));
+  println!("  quantiles: 10% {}, 50% {}, 90% {}", quantile(&gaps, 0.1), quantile(&gaps, 0.5), quantile(&gaps, 0.9));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::dispersion::*;
+  use crate::utility::UtilityFn;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_walrasian_price() {
+    // one seller of 4 A at reservation 1, one buyer with 8 B at reservation 10:
+    // demand 8/p meets supply 4 at p = 2
+    let assets = vec![(linear_agent(1.0), Balance { a: 4.0, b: 0.0 }), (linear_agent(10.0), Balance { a: 0.0, b: 8.0 })];
+    assert!((walrasian_price(&assets).unwrap() - 2.0).abs() < 1e-9);
+    // a buyer with only 1 B can't push the price above the seller's reservation
+    let assets = vec![(linear_agent(1.0), Balance { a: 4.0, b: 0.0 }), (linear_agent(10.0), Balance { a: 0.0, b: 1.0 })];
+    assert!((walrasian_price(&assets).unwrap() - 1.0).abs() < 1e-9);
+    assert_eq!(walrasian_price(&assets[..1]), None);
+    // Cobb-Douglas agents spend half their wealth on A wherever the price is:
+    // (8 + 1) / 2 B meets (4 + 1) / 2 A at 9/5, not the linear curves' crossing
+    let cobb_douglas = Agent { utility_fn: UtilityFn::CobbDouglas, ..linear_agent(1.0) };
+    let assets = vec![(cobb_douglas, Balance { a: 4.0, b: 1.0 }), (cobb_douglas, Balance { a: 1.0, b: 8.0 })];
+    assert!((walrasian_price(&assets).unwrap() - 1.8).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,91 @@
+// Endogenous participation: entering the market costs a fixed amount of B, and
+// agents who don't expect to gain at least that much stay out. Exempt agents enter
+// for free.
+
+use crate::strategy::{Strategies, Strategy};
+use crate::{supply_demand_curves, Agent, AgentId, Balance, Price};
+
+// Where the initial supply and demand curves cross, or None if they never do.
+pub fn expected_price(assets: &[(Agent, Balance)]) -> Option<Price> {
+  supply_demand_curves(assets).into_iter()
+    .find(|(_, supply, demand)| supply >= demand)
+    .map(|(price, _, _)| price)
+}
+
+// An agent's gain (in B) from trading everything it's willing to at `price`:
+// selling all its A if it values A below the price, spending all its B otherwise.
+// A non-linear agent trades what its demand says, valued at its rate halfway there.
+pub fn expected_gain(agent: &Agent, balance: &Balance, price: Price) -> f64 {
+  if !agent.utility_fn.is_linear() {
+    let q = agent.demand_for_a(balance, price);
+    let halfway = Balance { a: balance.a + q / 2.0, b: balance.b - price * q / 2.0 };
+    return q * (agent.indifference_price_at(&halfway) - price);
+  }
+  let reservation = agent.indifference_price_of_a_in_b();
+  if reservation < price {
+    balance.a * (price - reservation)
+  } else {
+    balance.b * (reservation / price - 1.0)
+  }
+}
+
+// Charges `cost` to every agent that expects to recoup it and can pay, and has the
+// rest abstain, except the `exempt`. Returns the participants.
+pub fn enter(assets: &mut [(Agent, Balance)], cost: f64, exempt: &[AgentId], strategies: &mut Strategies) -> Vec<AgentId> {
+  if cost == 0.0 {
+    return (0..assets.len()).collect();
+  }
+  let price = expected_price(assets);
+  let mut participants = vec![];
+  for (id, (agent, balance)) in assets.iter_mut().enumerate() {
+    if exempt.contains(&id) {
+      participants.push(id);
+      continue;
+    }
+    let worth_it = price.is_some_and(|p| expected_gain(agent, balance, p) >= cost);
+    if worth_it && balance.b >= cost {
+      balance.b -= cost;
+      participants.push(id);
+    } else {
+      strategies.set(id, Strategy::Abstain);
+    }
+  }
+  participants
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::entry::*;
+  use crate::utility::UtilityFn;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_enter() {
+    let mut assets = vec![
+      (linear_agent(1.0), Balance { a: 10.0, b: 1.0 }),
+      (linear_agent(3.0), Balance { a: 0.0, b: 20.0 }),
+      (linear_agent(2.1), Balance { a: 0.0, b: 1.0 }),
+    ];
+    let price = expected_price(&assets).unwrap();
+    assert!(price > 1.0 && price < 3.0);
+    let mut strategies = Strategies::truthful(3);
+    // the third agent's gain at any price near 2 is tiny
+    assert_eq!(enter(&mut assets.clone(), 0.5, &[2], &mut Strategies::truthful(3)), vec![0, 1, 2]);
+    assert_eq!(enter(&mut assets, 0.5, &[], &mut strategies), vec![0, 1]);
+    assert_eq!(strategies.agents_using(Strategy::Abstain), vec![2]);
+    assert_eq!((assets[0].1.b, assets[1].1.b, assets[2].1.b), (0.5, 19.5, 1.0));
+  }
+
+  #[test]
+  fn test_expected_gain_non_linear() {
+    let agent = Agent { production_a: 0.0, production_b: 0.0, consumption_a_coeff: 1.0, consumption_b_coeff: 1.0, utility_fn: UtilityFn::CobbDouglas };
+    let balance = Balance { a: 4.0, b: 4.0 };
+    // nothing to gain at its own rate of 1, and only from moving part way off it: not
+    // the whole 4 A's worth a linear agent would sell
+    assert_eq!(expected_gain(&agent, &balance, 1.0), 0.0);
+    for price in [0.5, 2.0] {
+      let gain = expected_gain(&agent, &balance, price);
+      assert!(gain > 0.0 && gain < 4.0 * (price - 1.0_f64).abs(), "{} at {}", gain, price);
+    }
+  }
+}
@@ -0,0 +1,190 @@
+// The competitive equilibrium of the two-good economy the market trades, solved
+// directly rather than simulated: the price of A in B at which everyone's demand
+// clears, and the bundle each agent ends up with there, so a run's outcome can be
+// measured against theory. (walras benchmarks the price alone, for N goods, by
+// iteration.)
+//
+// Under linear utility it's exact. Agents valuing A above the price spend all their
+// wealth on it and those valuing it below sell all theirs, so with the agents sorted
+// by their rate either some prefix of them buys and the price is the B they hold
+// over the A everyone else does, or one group's rate is the price and it takes
+// whatever A the keener buyers leave, in proportion to wealth. Cobb-Douglas has the
+// closed form sum(alpha b) / sum((1 - alpha) a). Anything else is solved by
+// bisection on aggregate demand between the lowest and highest rates at the
+// endowment, which assumes excess demand falls as the price rises.
+
+use crate::inequality::gini;
+use crate::utility::UtilityFn;
+use crate::{Agent, Balance, Price, Trade};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Equilibrium {
+  pub price: Price,
+  // indexed by agent
+  pub allocations: Vec<Balance>,
+}
+
+// A run's outcome against the equilibrium from its starting point.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Comparison {
+  // of the run's volume-weighted mean price from the equilibrium's, relative to it
+  pub price_gap: Option<f64>,
+  // half the total |difference| between final and equilibrium bundles, valued at the
+  // equilibrium price, as a share of total wealth: the share that would have to move
+  pub allocation_distance: f64,
+  // the run's total gain in utility over the equilibrium's; the equilibrium is
+  // efficient but needn't maximize that sum, so this can exceed 1
+  pub surplus_share: Option<f64>,
+  pub gini_equilibrium: f64,
+  pub gini_final: f64,
+}
+
+// None unless someone holds A and someone holds B.
+pub fn solve(assets: &[(Agent, Balance)]) -> Option<Equilibrium> {
+  let (total_a, total_b): (f64, f64) = assets.iter().fold((0.0, 0.0), |(a, b), (_, bal)| (a + bal.a, b + bal.b));
+  if total_a <= 0.0 || total_b <= 0.0 {
+    return None;
+  }
+  if assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()) {
+    return solve_linear(assets, total_a);
+  }
+  let price = if assets.iter().all(|(agent, _)| agent.utility_fn == UtilityFn::CobbDouglas) {
+    let alpha = |agent: &Agent| agent.consumption_a_coeff / (agent.consumption_a_coeff + agent.consumption_b_coeff);
+    let spent_on_a: f64 = assets.iter().map(|(agent, balance)| alpha(agent) * balance.b).sum();
+    let spent_on_b: f64 = assets.iter().map(|(agent, balance)| (1.0 - alpha(agent)) * balance.a).sum();
+    spent_on_a / spent_on_b
+  } else {
+    let rates = assets.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).filter(|r| r.is_finite() && *r > 0.0);
+    let (mut lo, mut hi) = rates.fold((f64::INFINITY, 0.0), |(lo, hi): (f64, f64), r| (lo.min(r), hi.max(r)));
+    if lo > hi {
+      return None;
+    }
+    for _ in 0..100 {
+      let mid = (lo * hi).sqrt();
+      let excess: f64 = assets.iter().map(|(agent, balance)| agent.demand_for_a(balance, mid)).sum();
+      if excess > 0.0 { lo = mid } else { hi = mid }
+    }
+    (lo * hi).sqrt()
+  };
+  let allocations = assets.iter().map(|(agent, balance)| {
+    let q = agent.demand_for_a(balance, price);
+    Balance { a: balance.a + q, b: balance.b - price * q }
+  }).collect();
+  Some(Equilibrium { price, allocations })
+}
+
+fn solve_linear(assets: &[(Agent, Balance)], total_a: f64) -> Option<Equilibrium> {
+  let mut order: Vec<usize> = (0..assets.len()).collect();
+  let rate = |id: usize| assets[id].0.indifference_price_of_a_in_b();
+  order.sort_by(|&i, &j| rate(j).total_cmp(&rate(i)));
+  let wealth = |id: usize, price: Price| price * assets[id].1.a + assets[id].1.b;
+  // what's held by everyone valuing A above the group being looked at
+  let (mut above_a, mut above_b) = (0.0, 0.0);
+  let mut above = f64::INFINITY;
+  let mut start = 0;
+  while start < order.len() {
+    let r = rate(order[start]);
+    let end = start + order[start..].iter().take_while(|&&id| rate(id) == r).count();
+    let group = &order[start..end];
+    // everyone above buys and everyone from here on sells
+    let price = above_b / (total_a - above_a);
+    let (buyers, marginal): (&[usize], &[usize]) = if above_b > 0.0 && price <= above && price >= r {
+      (&order[..start], &[])
+    } else if r > 0.0 && above_a + above_b / r <= total_a && above_a + (above_b + group.iter().map(|&id| wealth(id, r)).sum::<f64>()) / r >= total_a {
+      (&order[..start], group)
+    } else {
+      above_a += group.iter().map(|&id| assets[id].1.a).sum::<f64>();
+      above_b += group.iter().map(|&id| assets[id].1.b).sum::<f64>();
+      above = r;
+      start = end;
+      continue;
+    };
+    let price = if marginal.is_empty() { price } else { r };
+    // sellers keep only B, buyers take A with all they have, and the marginal group
+    // the A left over
+    let mut allocations: Vec<Balance> = (0..assets.len()).map(|id| Balance { a: 0.0, b: wealth(id, price) }).collect();
+    for &id in buyers {
+      allocations[id] = Balance { a: wealth(id, price) / price, b: 0.0 };
+    }
+    let left = total_a - buyers.iter().map(|&id| allocations[id].a).sum::<f64>();
+    let marginal_wealth: f64 = marginal.iter().map(|&id| wealth(id, price)).sum();
+    for &id in marginal {
+      let a = left * wealth(id, price) / marginal_wealth;
+      allocations[id] = Balance { a, b: wealth(id, price) - price * a };
+    }
+    return Some(Equilibrium { price, allocations });
+  }
+  None
+}
+
+pub fn compare(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], trades: &[Trade], eq: &Equilibrium) -> Comparison {
+  let p = eq.price;
+  let wealth: f64 = initial.iter().map(|(_, b)| p * b.a + b.b).sum();
+  let moved: f64 = last.iter().zip(&eq.allocations).map(|((_, f), e)| p * (f.a - e.a).abs() + (f.b - e.b).abs()).sum();
+  let volume: f64 = trades.iter().map(|t| t.amount_a).sum();
+  let mean_price = (volume > 0.0).then(|| trades.iter().map(|t| t.amount_b).sum::<f64>() / volume);
+  let gain = |bundles: &mut dyn Iterator<Item = (&Agent, &Balance)>| -> f64 {
+    bundles.zip(initial).map(|((agent, bal), (_, start))| agent.utility(bal.a, bal.b) - agent.utility(start.a, start.b)).sum()
+  };
+  let realized = gain(&mut last.iter().map(|(agent, bal)| (agent, bal)));
+  let attainable = gain(&mut initial.iter().zip(&eq.allocations).map(|((agent, _), bal)| (agent, bal)));
+  Comparison {
+    price_gap: mean_price.map(|m| (m - p) / p),
+    allocation_distance: moved / 2.0 / wealth,
+    surplus_share: (attainable > 0.0).then(|| realized / attainable),
+    gini_equilibrium: gini(&eq.allocations.iter().map(|b| p * b.a + b.b).collect::<Vec<_>>()),
+    gini_final: gini(&last.iter().map(|(_, b)| p * b.a + b.b).collect::<Vec<_>>()),
+  }
+}
+
+pub fn print_report(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], trades: &[Trade]) {
+  let Some(eq) = solve(initial) else { return };
+  let c = compare(initial, last, trades, &eq);
+  let or_na = |x: Option<f64>| x.map_or("n/a".to_string(), |x| x.to_string());
+  println!("competitive equilibrium: {} B per A", eq.price);
+  println!("  run's mean price off it by {} (relative)", or_na(c.price_gap));
+  println!("  share of wealth away from the equilibrium allocation: {}", c.allocation_distance);
+  println!("  utility gain realized, of the equilibrium's: {}", or_na(c.surplus_share));
+  println!("  gini of wealth at its price: {} at equilibrium, {} at the end of the run", c.gini_equilibrium, c.gini_final);
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::equilibrium::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_solve() {
+    let agent = |ca, utility_fn| Agent { utility_fn, ..linear_agent(ca) };
+    // two A-lovers with 5 B between them, two holding 2 A who'd rather have B
+    let assets = vec![
+      (agent(4.0, UtilityFn::Linear), Balance { a: 0.0, b: 3.0 }),
+      (agent(3.0, UtilityFn::Linear), Balance { a: 0.0, b: 2.0 }),
+      (agent(1.0, UtilityFn::Linear), Balance { a: 1.0, b: 0.0 }),
+      (agent(0.5, UtilityFn::Linear), Balance { a: 1.0, b: 0.0 }),
+    ];
+    let eq = solve(&assets).unwrap();
+    assert_eq!(eq.price, 2.5);
+    assert_eq!(eq.allocations, vec![Balance { a: 1.2, b: 0.0 }, Balance { a: 0.8, b: 0.0 }, Balance { a: 0.0, b: 2.5 }, Balance { a: 0.0, b: 2.5 }]);
+    // with only 1 B to spend the top buyer's rate is the price, and it keeps some B
+    let mut thin = assets.clone();
+    thin[0].1.b = 12.0;
+    thin.remove(1);
+    let eq = solve(&thin).unwrap();
+    assert_eq!(eq.price, 4.0);
+    assert_eq!(eq.allocations[0], Balance { a: 2.0, b: 4.0 });
+    assert_eq!(compare(&thin, &thin.iter().zip(&eq.allocations).map(|((a, _), b)| (*a, *b)).collect::<Vec<_>>(), &[], &eq).allocation_distance, 0.0);
+
+    // agrees with the bisection on the market's curves
+    let population = crate::population::generate(crate::population::Population::Uniform, 300, &mut rand::rngs::StdRng::seed_from_u64(5));
+    let walrasian = crate::dispersion::walrasian_price(&population).unwrap();
+    assert!((solve(&population).unwrap().price - walrasian).abs() / walrasian < 1e-9);
+    // and Cobb-Douglas's closed form clears the market
+    let cobb: Vec<(Agent, Balance)> = population.iter().map(|&(a, b)| (Agent { utility_fn: UtilityFn::CobbDouglas, ..a }, b)).collect();
+    let eq = solve(&cobb).unwrap();
+    let (before, after) = (cobb.iter().map(|(_, b)| b.a).sum::<f64>(), eq.allocations.iter().map(|b| b.a).sum::<f64>());
+    assert!((before - after).abs() / before < 1e-9);
+  }
+}
@@ -0,0 +1,159 @@
+// Order-entry errors: each order an agent submits has, with some probability, its
+// price multiplied or divided by a large factor. Quotes carry no size (fills are
+// sized from the balance at match time), so the price is all there is to mistype.
+// The report picks the mistyped quotes out of the log and follows them: whether the
+// risk rules stopped them, whether the price limits held their fills to the floor or
+// cap, and what the fills cost. (A limit can only keep out a bid below the floor or an
+// ask above the cap, which are the harmless mistakes.) It tells a mistyped quote by
+// its ratio to the agent's one reservation, so it needs every agent linear: any
+// other's intended quote moves with its holdings.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::crn::{Crn, Stream};
+use crate::pricing::PricingRule;
+use crate::runlog::RunLog;
+use crate::{realized_surplus, Agent, AgentId, Price};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FatFinger {
+  pub probability: f64,
+  pub factor: f64,
+}
+
+impl FatFinger {
+  // `<probability>:<factor>`, e.g. `0.01:10`
+  pub fn parse(s: &str) -> Result<FatFinger, String> {
+    let parsed = s.split_once(':').and_then(|(p, f)| Some(FatFinger { probability: p.parse().ok()?, factor: f.parse().ok()? }));
+    parsed.ok_or_else(|| format!("bad fat-finger spec {:?} (expected <probability>:<factor>)", s))
+  }
+
+  // The price as entered: usually as intended, sometimes off by the factor either way.
+  // `side` tells an agent's bid (0) from its ask (1) at the same tick.
+  pub fn enter(&self, price: Price, crn: &Crn, agent: AgentId, tick: Tick, side: u64) -> Price {
+    if !crn.chance(self.probability, Stream::FatFinger, agent, tick, side) {
+      price
+    } else if crn.chance(0.5, Stream::FatFingerDirection, agent, tick, side) {
+      price * self.factor
+    } else {
+      price / self.factor
+    }
+  }
+
+  // Whether `price` is `intended` off by the factor.
+  pub fn is_mistyped(&self, price: Price, intended: Price) -> bool {
+    let ratio = price / intended;
+    (ratio / self.factor - 1.0).abs() < 1e-9 || (ratio * self.factor - 1.0).abs() < 1e-9
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ErrorReport {
+  // mistyped quotes that reached the book
+  pub entered: usize,
+  // matches on a mistyped quote that the risk rules rejected
+  pub rejected: usize,
+  // trades on a mistyped quote, how many of them the floor or cap moved the price
+  // of, and the utility the mistyping side lost on them
+  pub filled: usize,
+  pub clamped: usize,
+  pub loss: f64,
+}
+
+// Assumes everyone otherwise quotes truthfully, at its indifference price.
+pub fn analyse(log: &RunLog, fat_finger: &FatFinger, pricing: PricingRule) -> ErrorReport {
+  let agent = |id: usize| -> Agent { log.initial_assets[id].0 };
+  let mistyped = |id: usize, price: Price| fat_finger.is_mistyped(price, agent(id).indifference_price_of_a_in_b());
+  let quotes: Vec<_> = log.quotes.iter().filter(|q| q.price.is_some_and(|p| mistyped(q.agent_id, p))).collect();
+  let unlimited = PricingRule::k_double(pricing.k);
+  let rejected = log.rejections.iter()
+    .filter(|r| mistyped(r.trade.buyer, r.trade.bid_price) || mistyped(r.trade.seller, r.trade.ask_price))
+    .count();
+  let mut filled = 0;
+  let mut clamped = 0;
+  let mut loss = 0.0;
+  for t in &log.trades {
+    let buyer_gain = agent(t.buyer).utility(t.amount_a, -t.amount_b);
+    let seller_gain = agent(t.seller).utility(-t.amount_a, t.amount_b);
+    let (bid_mistyped, ask_mistyped) = (mistyped(t.buyer, t.bid_price), mistyped(t.seller, t.ask_price));
+    if bid_mistyped || ask_mistyped {
+      filled += 1;
+      if pricing.price(t.bid_price, t.ask_price) != unlimited.price(t.bid_price, t.ask_price) {
+        clamped += 1;
+      }
+    }
+    if bid_mistyped && buyer_gain < 0.0 {
+      loss -= buyer_gain;
+    }
+    if ask_mistyped && seller_gain < 0.0 {
+      loss -= seller_gain;
+    }
+  }
+  ErrorReport { entered: quotes.len(), rejected, filled, clamped, loss }
+}
+
+// `baseline` is the same run without the errors.
+pub fn print_report(baseline: &RunLog, log: &RunLog, fat_finger: &FatFinger, pricing: PricingRule) {
+  println!("fat-finger errors (probability {}, factor {}):", fat_finger.probability, fat_finger.factor);
+  if !log.initial_assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()) {
+    println!("  can't be told from the quotes unless every agent's utility is linear");
+    return;
+  }
+  let report = analyse(log, fat_finger, pricing);
+  let total = |log: &RunLog| realized_surplus(&log.initial_assets, &log.final_assets()).iter().sum::<f64>();
+  println!("  mistyped quotes on the book: {}, matches on them rejected by the risk rules: {}", report.entered, report.rejected);
+  println!("  trades on a mistyped quote: {} ({} held to the price limits), utility lost by the side that mistyped: {}",
+    report.filled, report.clamped, report.loss);
+  println!("  total surplus: {} vs {} without errors", total(log), total(baseline));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::Config;
+  use crate::continuous::Matching;
+  use crate::fat_finger::*;
+  use crate::risk::MIN_FILL;
+  use crate::stopping::StopReason;
+  use crate::runlog::Quote;
+  use crate::{linear_agent, Balance, OrderType, Trade};
+
+  #[test]
+  fn test_analyse() {
+    let fat_finger = FatFinger::parse("1:10").unwrap();
+    let entered = fat_finger.enter(2.0, &Crn::new(0), 0, 0, 0);
+    assert!(fat_finger.is_mistyped(entered, 2.0) && !fat_finger.is_mistyped(2.0, 2.0));
+    assert!(FatFinger::parse("0.1").is_err());
+
+    // the buyer values A at 2 but bids 20; the seller asks its true 1
+    let quote = |agent_id, side, price| Quote { tick: 0, agent_id, side, price: Some(price) };
+    let log = RunLog {
+      seed: 0,
+      initial_assets: vec![(linear_agent(1.0), Balance { a: 10.0, b: 0.0 }), (linear_agent(2.0), Balance { a: 0.0, b: 21.0 })],
+      quotes: vec![quote(0, OrderType::Ask, 1.0), quote(1, OrderType::Bid, 20.0)],
+      rejections: vec![],
+      trades: vec![Trade { tick: 0, buyer: 1, seller: 0, amount_a: 2.0, amount_b: 21.0, bid_price: 20.0, ask_price: 1.0, ..Trade::default() }],
+      stop: None,
+      decay: None,
+      short: None,
+    };
+    let report = analyse(&log, &fat_finger, PricingRule::default());
+    assert_eq!(report, ErrorReport { entered: 1, rejected: 0, filled: 1, clamped: 0, loss: 17.0 });
+    // a cap would have held the price to 5 instead of 10.5
+    let capped = PricingRule::default().with_limits(None, Some(5.0));
+    assert_eq!(analyse(&log, &fat_finger, capped).clamped, 1);
+  }
+
+  #[test]
+  fn test_drained_balances() {
+    // mistyped prices drain balances until the fills are too small to tell gains in,
+    // as with these seeds; the runs end with nothing left to cross instead
+    let config = Config { n_agents: 80, fat_finger: Some(FatFinger::parse("0.2:100").unwrap()), ..Config::default() };
+    let continuous = Config { pricing: config.pricing.with_matching(Matching::Continuous), ..config.clone() };
+    for (config, seed) in [(config, 6), (continuous, 2)] {
+      let log = crate::simulate(&config, seed);
+      assert_eq!(log.stop.map(|s| s.reason), Some(StopReason::Exhausted));
+      assert!(log.trades.iter().all(|t| t.amount_a >= MIN_FILL && t.amount_b >= MIN_FILL));
+    }
+  }
+}
@@ -0,0 +1,156 @@
+// Mid-run estimates of how much surplus is still to be had, so a run can say how far
+// along it is ("97% of gains realized") and stop once enough of them are. Walrasian
+// looks at what's left to trade: it clears the residual market in one go at its
+// Walrasian price, rationing the long side pro rata, and counts what that gains.
+// Geometric only looks at the trades so far: it fits a constant decay ratio to the
+// recent per-trade gains and sums the rest of the series.
+
+use serde::{Deserialize, Serialize};
+
+use crate::dispersion::walrasian_price;
+use crate::equilibrium;
+use crate::stats::mean;
+use crate::{realized_surplus, Agent, Balance, Trade};
+
+// how many recent trades the geometric fit looks at
+const WINDOW: usize = 20;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Forecast {
+  #[default]
+  Walrasian,
+  Geometric,
+}
+
+impl Forecast {
+  pub fn parse(s: &str) -> Result<Forecast, String> {
+    match s {
+      "walrasian" => Ok(Forecast::Walrasian),
+      "geometric" => Ok(Forecast::Geometric),
+      _ => Err(format!("unknown forecast {:?} (expected walrasian or geometric)", s)),
+    }
+  }
+
+  // Utils still to be gained from trade, given the holdings now and the trades so
+  // far; None if this forecast can't tell yet.
+  pub fn remaining(&self, assets: &[(Agent, Balance)], trades: &[Trade]) -> Option<f64> {
+    match self {
+      Forecast::Walrasian => Some(clearing_gains(assets)),
+      Forecast::Geometric => geometric_tail(assets, trades),
+    }
+  }
+}
+
+// The gains from clearing `assets` at once at the Walrasian price. Under linear
+// utility a seller's gain is b_c * a * (p - r) and a buyer's b_c * (b / p) * (r - p);
+// otherwise the market clears without rationing, at the equilibrium's allocations.
+fn clearing_gains(assets: &[(Agent, Balance)]) -> f64 {
+  if !assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()) {
+    let Some(equilibrium) = equilibrium::solve(assets) else { return 0.0 };
+    return assets.iter().zip(&equilibrium.allocations).map(|((agent, before), after)| {
+      agent.utility(after.a, after.b) - agent.utility(before.a, before.b)
+    }).sum();
+  }
+  let Some(price) = walrasian_price(assets) else { return 0.0 };
+  let reservation = |agent: &Agent| agent.indifference_price_of_a_in_b();
+  let sellers = || assets.iter().filter(|(agent, _)| reservation(agent) < price);
+  let buyers = || assets.iter().filter(|(agent, _)| reservation(agent) > price);
+  let supply: f64 = sellers().map(|(_, balance)| balance.a).sum();
+  let demand: f64 = buyers().map(|(_, balance)| balance.b / price).sum();
+  let traded = supply.min(demand);
+  if traded == 0.0 {
+    return 0.0;
+  }
+  let sold: f64 = sellers().map(|(agent, balance)| agent.consumption_b_coeff * balance.a * (price - reservation(agent))).sum();
+  let bought: f64 = buyers().map(|(agent, balance)| agent.consumption_b_coeff * balance.b / price * (reservation(agent) - price)).sum();
+  sold * traded / supply + bought * traded / demand
+}
+
+// The sum of the rest of the per-trade gains, if the last WINDOW of them are decaying.
+fn geometric_tail(assets: &[(Agent, Balance)], trades: &[Trade]) -> Option<f64> {
+  if trades.len() < WINDOW {
+    return None;
+  }
+  let gains = trade_gains(assets, &trades[trades.len() - WINDOW..]);
+  let (early, late) = gains.split_at(WINDOW / 2);
+  let ratio = (mean(late) / mean(early)).powf(1.0 / (WINDOW / 2) as f64);
+  if !(ratio > 0.0 && ratio < 1.0) {
+    return None;
+  }
+  Some(gains[WINDOW - 1] * ratio / (1.0 - ratio))
+}
+
+// What each of `trades` gained its two parties, the last of them ending at `assets`.
+// A linear agent's gain is the utility of the trade itself; any other's is the change
+// in its utility across it, so its holdings are unwound from the end.
+fn trade_gains(assets: &[(Agent, Balance)], trades: &[Trade]) -> Vec<f64> {
+  let mut holdings: Vec<Balance> = assets.iter().map(|(_, balance)| *balance).collect();
+  let mut gain = |id: usize, delta_a: f64, delta_b: f64| {
+    let agent = &assets[id].0;
+    if agent.utility_fn.is_linear() {
+      return agent.utility(delta_a, delta_b);
+    }
+    let after = holdings[id];
+    holdings[id] = Balance { a: after.a - delta_a, b: after.b - delta_b };
+    agent.utility(after.a, after.b) - agent.utility(holdings[id].a, holdings[id].b)
+  };
+  let mut gains: Vec<f64> = trades.iter().rev()
+    .map(|t| gain(t.buyer, t.amount_a, -t.amount_b) + gain(t.seller, -t.amount_a, t.amount_b))
+    .collect();
+  gains.reverse();
+  gains
+}
+
+// Realized surplus as a share of realized plus forecast remaining; 1 if there was
+// nothing to gain.
+pub fn realized_share(forecast: Forecast, initial: &[(Agent, Balance)], assets: &[(Agent, Balance)], trades: &[Trade]) -> Option<f64> {
+  let realized: f64 = realized_surplus(initial, assets).iter().sum();
+  let remaining = forecast.remaining(assets, trades)?;
+  Some(if realized + remaining > 0.0 { realized / (realized + remaining) } else { 1.0 })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::forecast::*;
+  use crate::settle;
+  use crate::utility::UtilityFn;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_forecasts() {
+    let initial = vec![(linear_agent(1.0), Balance { a: 10.0, b: 0.0 }), (linear_agent(4.0), Balance { a: 0.0, b: 20.0 })];
+    // all 10 A should move to the buyer, who values each 3 more than the seller (at 2 B each)
+    assert!((Forecast::Walrasian.remaining(&initial, &[]).unwrap() - 30.0).abs() < 1e-6);
+    let trade = |amount| Trade { tick: 0, buyer: 1, seller: 0, amount_a: amount, amount_b: amount, bid_price: 4.0, ask_price: 1.0, ..Trade::default() };
+    let mut assets = initial.clone();
+    settle(&mut assets, &trade(5.0));
+    let share = realized_share(Forecast::Walrasian, &initial, &assets, &[trade(5.0)]).unwrap();
+    assert!((share - 0.5).abs() < 1e-6);
+
+    // trades halving in size each time have as much left to come as the last one gained
+    let trades: Vec<Trade> = (0..WINDOW).map(|i| trade(0.5f64.powi(i as i32))).collect();
+    assert_eq!(Forecast::Geometric.remaining(&initial, &trades[1..]), None);
+    let tail = Forecast::Geometric.remaining(&initial, &trades).unwrap();
+    assert!((tail - 3.0 * 0.5f64.powi(WINDOW as i32 - 1)).abs() < 1e-12);
+    assert!(Forecast::parse("psychic").is_err());
+  }
+
+  #[test]
+  fn test_forecasts_non_linear() {
+    let agent = Agent { production_a: 0.0, production_b: 0.0, consumption_a_coeff: 1.0, consumption_b_coeff: 1.0, utility_fn: UtilityFn::CobbDouglas };
+    let initial = vec![(agent, Balance { a: 4.0, b: 1.0 }), (agent, Balance { a: 1.0, b: 8.0 })];
+    // clearing at 9/5 leaves each with half its wealth (8.2 and 9.8 B) in each good,
+    // so sqrt(a b) is (wealth / 2) / sqrt(1.8), from the 2 and sqrt(8) they start at
+    let expected = 4.1 / 1.8f64.sqrt() - 2.0 + 4.9 / 1.8f64.sqrt() - 8f64.sqrt();
+    assert!((Forecast::Walrasian.remaining(&initial, &[]).unwrap() - expected).abs() < 1e-6);
+
+    // a trade's gain is the change in utility across it, to (3, 2) and (2, 7), not the
+    // utility of the trade
+    let trade = Trade { tick: 0, buyer: 1, seller: 0, amount_a: 1.0, amount_b: 1.0, bid_price: 8.0, ask_price: 0.25, ..Trade::default() };
+    let mut assets = initial.clone();
+    settle(&mut assets, &trade);
+    let gains = trade_gains(&assets, &[trade]);
+    assert!((gains[0] - (6f64.sqrt() - 2.0 + 14f64.sqrt() - 8f64.sqrt())).abs() < 1e-12);
+  }
+}
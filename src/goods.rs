@@ -0,0 +1,364 @@
+// `simmarket goods`: economies with any number of goods, traded by the core engine.
+// Goods are named in a registry and agents hold a Bundle, one amount per good, under
+// the config's utility; every pair of goods has its own order book, in which the
+// first good is priced in the second, and each tick makes the best trade allowed in
+// any book (see find_next_goods_trade). The config's strategies, pricing rule (tax,
+// subsidy, limits, lots, regions, a dark pool) and risk rules apply in every book as
+// they do to A and B; what needs the two-good market's own state (credit, short
+// selling, decay, matching other than batch, Poisson arrivals, entry costs) or draws
+// the population differently is refused rather than ignored. Two goods trade exactly
+// as a core run does, which the tests check.
+//
+// That's barter. With money, the registry gains one more good, held by everyone and
+// worth a util a unit to all of them (with linear utility, preferences quasi-linear in
+// it), and the only books are each good priced in money: every order is a price in
+// money, and goods trade for each other only by way of it. The same population can be
+// run both ways, so the report sets monetary exchange against barter; money's utility
+// nets out of the surplus, since every unit paid is worth as much to whoever gets it.
+//
+// Money is this command's alone, and only with --money. Nothing else has a money
+// balance: Balance holds A and B, every Order prices A in B, and every other command's
+// market trades A for B directly, B doubling as the medium of exchange. A monetary
+// experiment is a goods run; the core market isn't priced in money.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::Ordering;
+
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::continuous::Matching;
+use crate::population::Population;
+use crate::sampling::Sampling;
+use crate::stopping::StopReason;
+use crate::strategy::Strategies;
+use crate::utility::UtilityFn;
+use crate::{execute_all_goods_trades, in_book, AgentId, Bundle, Good, GoodsTrade, Preferences, Price, QUIET};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Goods {
+  pub names: Vec<String>,
+  // the good every other is priced in, if not barter
+  pub money: Option<Good>,
+}
+
+impl Goods {
+  // Distinct names, comma-separated, e.g. `a,b,c`.
+  pub fn parse(s: &str) -> Result<Goods, String> {
+    let names: Vec<String> = s.split(',').map(|n| n.trim().to_string()).collect();
+    if names.len() < 2 || names.iter().any(|n| n.is_empty()) {
+      return Err(format!("bad goods {:?} (expected at least two names, e.g. a,b,c)", s));
+    }
+    if (1..names.len()).any(|i| names[..i].contains(&names[i])) {
+      return Err(format!("goods {:?} name one good twice", s));
+    }
+    Ok(Goods { names, money: None })
+  }
+
+  // The same goods and money, the last good.
+  pub fn with_money(&self) -> Result<Goods, String> {
+    if self.index("money").is_some() {
+      return Err("a good is named money already".to_string());
+    }
+    let names: Vec<String> = self.names.iter().cloned().chain(std::iter::once("money".to_string())).collect();
+    Ok(Goods { money: Some(self.len()), names })
+  }
+
+  pub fn len(&self) -> usize {
+    self.names.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.names.is_empty()
+  }
+
+  pub fn index(&self, name: &str) -> Option<Good> {
+    self.names.iter().position(|n| n == name)
+  }
+
+  // Every book, (good, the good it's priced in), the lower-indexed good first: every
+  // pair in barter, and each good against money with it.
+  pub fn books(&self) -> Vec<(Good, Good)> {
+    match self.money {
+      Some(money) => (0..self.len()).filter(|&i| i != money).map(|i| (i, money)).collect(),
+      None => (0..self.len()).flat_map(|i| (i + 1..self.len()).map(move |j| (i, j))).collect(),
+    }
+  }
+
+  pub fn book_name(&self, (base, quote): (Good, Good)) -> String {
+    format!("{}/{}", self.names[base], self.names[quote])
+  }
+}
+
+// Agent::new_random's marginals, for any number of goods, every agent's utility `utility_fn`.
+pub fn random_population(n_goods: usize, n_agents: usize, utility_fn: UtilityFn, rng: &mut StdRng) -> Vec<(Preferences, Bundle)> {
+  let (endowment, coeff) = (Uniform::new(0.0, 1000.0), Uniform::new(0.0, 1.0));
+  (0..n_agents).map(|_| {
+    let bundle = Bundle((0..n_goods).map(|_| endowment.sample(rng)).collect());
+    (Preferences { coeffs: (0..n_goods).map(|_| coeff.sample(rng)).collect(), utility_fn }, bundle)
+  }).collect()
+}
+
+// `assets` with `endowment` money each, worth a util a unit.
+pub fn monetize(assets: &[(Preferences, Bundle)], endowment: f64) -> Vec<(Preferences, Bundle)> {
+  assets.iter().map(|(prefs, bundle)| {
+    let extend = |v: &[f64], x| v.iter().copied().chain(std::iter::once(x)).collect();
+    (Preferences { coeffs: extend(&prefs.coeffs, 1.0), ..prefs.clone() }, Bundle(extend(&bundle.0, endowment)))
+  }).collect()
+}
+
+// Refuses a config with anything an economy of goods can't honour, naming it.
+pub fn check(config: &Config) -> Result<(), String> {
+  let pricing = config.pricing;
+  let unsupported = [
+    (config.initial_state.is_some(), "an initial state"),
+    (config.population != Population::Uniform || config.copula.is_some() || config.sampling != Sampling::Independent, "populations other than uniform"),
+    (config.preference_correlation != 0.0 || config.inequality != 0.0 || config.transfer != 0.0, "reshaped endowments"),
+    (config.stress.is_some() || config.perturbation.is_some(), "shocks to the population"),
+    (config.dealers.is_some() || config.monopoly, "dealers and monopolists"),
+    (config.entry_cost != 0.0, "an entry cost"),
+    (config.arrival_rate.is_some(), "Poisson arrivals"),
+    (pricing.matching != Matching::Batch, "matching other than batch"),
+    (pricing.credit.is_some() || pricing.short.is_some(), "credit and short selling"),
+    (pricing.decay.is_some(), "decay"),
+    (pricing.subsidy.is_some() && !config.utility.is_linear(), "a subsidy to agents whose utility isn't linear"),
+    (config.convergence.is_some() || config.gains_target.is_some() || config.time_limit.is_some() || config.memory_limit.is_some(), "stopping rules other than trade and tick limits"),
+  ];
+  match unsupported.iter().find(|(set, _)| *set) {
+    Some((_, what)) => Err(format!("an economy of goods doesn't support {}", what)),
+    None => Ok(()),
+  }
+}
+
+// Runs `assets` in every book of `goods` under `config`, which `check` has passed,
+// each book's strategies drawn from `seed` alike.
+pub fn run(goods: &Goods, assets: &mut [(Preferences, Bundle)], config: &Config, seed: u64) -> (Vec<GoodsTrade>, StopReason) {
+  let books = goods.books();
+  let mut strategies: Vec<Strategies> = books.iter().map(|&book| {
+    let mut strategies = config.strategies(seed);
+    strategies.price(&in_book(assets, book));
+    strategies
+  }).collect();
+  let quiet = QUIET.swap(true, Ordering::Relaxed);
+  let run = execute_all_goods_trades(assets, &books, &mut strategies, config.pricing, &config.risk, &config.stopping());
+  QUIET.store(quiet, Ordering::Relaxed);
+  run
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BookStats {
+  pub book: (Good, Good),
+  pub trades: usize,
+  // of the book's good, and its price in the quote good over them, if any traded
+  pub volume: f64,
+  pub vwap: Option<Price>,
+}
+
+pub fn book_stats(goods: &Goods, trades: &[GoodsTrade]) -> Vec<BookStats> {
+  goods.books().into_iter().map(|book| {
+    let in_book: Vec<&GoodsTrade> = trades.iter().filter(|t| t.book == book).collect();
+    let volume = in_book.iter().fold(0.0, |v, t| v + t.trade.amount_a);
+    let paid = in_book.iter().fold(0.0, |v, t| v + t.trade.amount_b);
+    BookStats { book, trades: in_book.len(), volume, vwap: if in_book.is_empty() { None } else { Some(paid / volume) } }
+  }).collect()
+}
+
+// Cross-price effects: every book's price and volume with and without every agent's
+// taste for `shocked` scaled by `factor`, from the same endowments.
+pub fn cross_price(goods: &Goods, assets: &[(Preferences, Bundle)], shocked: Good, factor: f64, config: &Config, seed: u64) -> Vec<(BookStats, BookStats)> {
+  let baseline = run(goods, &mut assets.to_vec(), config, seed).0;
+  let mut shocked_assets = assets.to_vec();
+  shocked_assets.iter_mut().for_each(|(prefs, _)| prefs.coeffs[shocked] *= factor);
+  let shocked_trades = run(goods, &mut shocked_assets, config, seed).0;
+  book_stats(goods, &baseline).into_iter().zip(book_stats(goods, &shocked_trades)).collect()
+}
+
+// The gains from trading `initial` to `last`, in utils.
+pub fn surplus(initial: &[(Preferences, Bundle)], last: &[(Preferences, Bundle)]) -> f64 {
+  initial.iter().zip(last).fold(0.0, |s, ((prefs, before), (_, after))| s + prefs.utility(after) - prefs.utility(before))
+}
+
+// A run of an economy from `initial` to `last` by `trades`, for reports.
+pub struct GoodsRun<'a> {
+  pub initial: &'a [(Preferences, Bundle)],
+  pub last: &'a [(Preferences, Bundle)],
+  pub trades: &'a [GoodsTrade],
+  pub stop: StopReason,
+}
+
+impl GoodsRun<'_> {
+  fn limit(&self) -> &'static str {
+    if self.stop == StopReason::Exhausted { "" } else { " (stopped early)" }
+  }
+}
+
+pub fn print_report(goods: &Goods, run: &GoodsRun) {
+  println!("{} goods ({}), {} agents: {} trades, {} utils of surplus{}", goods.len(), goods.names.join(", "), run.initial.len(),
+    run.trades.len(), surplus(run.initial, run.last), run.limit());
+  for s in book_stats(goods, run.trades) {
+    println!("  {}: {} trades, {} {} traded, vwap {}", goods.book_name(s.book), s.trades, s.volume, goods.names[s.book.0],
+      s.vwap.map_or("n/a".to_string(), |p| p.to_string()));
+  }
+}
+
+// `barter` and `monetary` are the same population's runs without money and with
+// `endowment` each.
+pub fn print_monetary_comparison(barter: &GoodsRun, monetary: &GoodsRun, endowment: f64) {
+  println!("monetary exchange vs barter, with {} money each:", endowment);
+  let row = |label: &str, run: &GoodsRun| {
+    println!("  {:<8}  {} trades, {} utils of surplus{}", label, run.trades.len(), surplus(run.initial, run.last), run.limit());
+  };
+  row("barter", barter);
+  row("monetary", monetary);
+  let turnover = monetary.trades.iter().fold(0.0, |s, t| s + t.trade.amount_b);
+  println!("  money changing hands: {}, {} times the money stock", turnover, turnover / (endowment * monetary.initial.len() as f64));
+}
+
+pub fn print_cross_price(goods: &Goods, shocked: Good, factor: f64, effects: &[(BookStats, BookStats)]) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| x.to_string());
+  println!("cross-price effects of scaling every agent's taste for {} by {}:", goods.names[shocked], factor);
+  for (before, after) in effects {
+    let change = match (before.vwap, after.vwap) {
+      (Some(b), Some(a)) => format!("{:+.1}%", 100.0 * (a / b - 1.0)),
+      _ => "n/a".to_string(),
+    };
+    println!("  {}: vwap {} -> {} ({}), volume {} -> {}", goods.book_name(before.book), show(before.vwap), show(after.vwap), change, before.volume, after.volume);
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GoodsEvent {
+  Run { seed: u64, goods: Vec<String>, #[serde(default, skip_serializing_if = "Option::is_none")] money: Option<Good> },
+  Agent { id: AgentId, preferences: Preferences, bundle: Bundle },
+  Trade(GoodsTrade),
+  Stop { reason: StopReason },
+}
+
+// Newline-delimited JSON of a run, as runlog writes a core one: a header naming the
+// goods, the initial population, every trade, and why it stopped.
+pub fn write_log(path: &str, goods: &Goods, seed: u64, run: &GoodsRun) -> io::Result<()> {
+  let mut out = BufWriter::new(File::create(path)?);
+  let mut emit = |event: &GoodsEvent| -> io::Result<()> {
+    serde_json::to_writer(&mut out, event)?;
+    out.write_all(b"\n")
+  };
+  emit(&GoodsEvent::Run { seed, goods: goods.names.clone(), money: goods.money })?;
+  for (id, (preferences, bundle)) in run.initial.iter().enumerate() {
+    emit(&GoodsEvent::Agent { id, preferences: preferences.clone(), bundle: bundle.clone() })?;
+  }
+  for trade in run.trades {
+    emit(&GoodsEvent::Trade(trade.clone()))?;
+  }
+  emit(&GoodsEvent::Stop { reason: run.stop })?;
+  out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+  use crate::arrivals::Arrivals;
+  use crate::credit::Credit;
+  use crate::population;
+  use crate::pricing::PricingRule;
+  use crate::risk::RiskRules;
+  use crate::stopping::StoppingRules;
+  use crate::subsidy::Subsidy;
+  use crate::tax::Tax;
+  use crate::{find_next_goods_trade, Agent, Balance};
+
+  use crate::goods::*;
+
+  #[test]
+  fn test_three_goods() {
+    let goods = Goods::parse("a,b,c").unwrap();
+    assert_eq!(goods.books(), vec![(0, 1), (0, 2), (1, 2)]);
+    assert!(Goods::parse("a").is_err() && Goods::parse("a,b,a").is_err());
+
+    // agent 0 has only a and wants only c; agent 1 the reverse; agent 2 is indifferent
+    let linear = |coeffs: Vec<f64>| Preferences { coeffs, utility_fn: UtilityFn::Linear };
+    let assets = vec![
+      (linear(vec![1.0, 2.0, 4.0]), Bundle(vec![10.0, 0.0, 0.0])),
+      (linear(vec![4.0, 2.0, 1.0]), Bundle(vec![0.0, 0.0, 10.0])),
+      (linear(vec![1.0, 1.0, 1.0]), Bundle(vec![0.0, 5.0, 0.0])),
+    ];
+    let mut strategies: Vec<Strategies> = goods.books().iter().map(|&book| Strategies::truthful_for(&in_book(&assets, book))).collect();
+    let trade = find_next_goods_trade(&assets, &goods.books(), &mut strategies, PricingRule::default(), &RiskRules::default(), 0).unwrap();
+    // agent 1 bids 4 c per a, agent 0 asks 1/4; the a/c book crosses furthest
+    assert_eq!((trade.trade.buyer, trade.trade.seller, trade.book), (1, 0, (0, 2)));
+    let config = Config { n_agents: 3, ..Config::default() };
+    let mut after = assets.clone();
+    let (trades, stop) = run(&goods, &mut after, &config, 0);
+    assert!(stop == StopReason::Exhausted && !trades.is_empty());
+    let total = |a: &[(Preferences, Bundle)], good: Good| a.iter().fold(0.0, |s, (_, b)| s + b.0[good]);
+    for good in 0..3 {
+      assert!((total(&assets, good) - total(&after, good)).abs() < 1e-9);
+    }
+    assert!(assets.iter().zip(&after).all(|((p, before), (_, now))| p.utility(now) >= p.utility(before)));
+
+    // two goods trade exactly as a core run
+    let agent = Agent { production_a: 0.0, production_b: 0.0, consumption_a_coeff: 8.0, consumption_b_coeff: 1.0, utility_fn: UtilityFn::Log };
+    let balance = Balance { a: 3.0, b: 4.0 };
+    assert_eq!(Preferences::from(&agent).in_book((0, 1), &Bundle::from(&balance)), Agent { production_a: 3.0, production_b: 4.0, ..agent });
+    assert_eq!(Bundle::from(&balance).in_book((0, 1)), balance);
+    let mut core = population::generate(population::Population::Uniform, 30, &mut StdRng::seed_from_u64(1));
+    let mut two: Vec<(Preferences, Bundle)> = core.iter().map(|(agent, balance)| (agent.into(), balance.into())).collect();
+    let outcome = crate::execute_all_trades(&mut core, &mut Strategies::truthful(30), Arrivals::every_tick(1), PricingRule::default(), &RiskRules::default(), &StoppingRules::default(), |_| {});
+    let (trades, stop) = run(&Goods::parse("a,b").unwrap(), &mut two, &Config { n_agents: 30, ..Config::default() }, 1);
+    assert!(stop == StopReason::Exhausted && !trades.is_empty());
+    assert_eq!(trades.into_iter().map(|t| t.trade).collect::<Vec<_>>(), outcome.trades);
+    // the config's trade limit stops it early
+    let (trades, stop) = run(&goods, &mut assets.clone(), &Config { max_trades: Some(1), ..config.clone() }, 0);
+    assert!(stop == StopReason::MaxTrades && trades.len() == 1);
+
+    // the config's tax is paid in the good each book prices in, out of the economy
+    let population = random_population(3, 50, UtilityFn::Linear, &mut StdRng::seed_from_u64(0));
+    let taxed = Config { n_agents: 50, pricing: PricingRule { tax: Some(Tax::parse("ad-valorem:0.1").unwrap()), ..PricingRule::default() }, ..Config::default() };
+    let mut after = population.clone();
+    let (trades, _) = run(&goods, &mut after, &taxed, 0);
+    assert!(trades.iter().all(|t| t.trade.tax > 0.0));
+    let paid = trades.iter().filter(|t| t.book.1 == 2).fold(0.0, |s, t| s + t.trade.tax);
+    assert!((total(&population, 2) - total(&after, 2) - paid).abs() < 1e-6 * paid);
+    // and its position limit caps what anyone buys of a book's good, here a, which
+    // every book with it sells rather than pays in
+    let limited = Config { n_agents: 50, risk: RiskRules { position_limit: Some(1500.0) }, ..Config::default() };
+    let mut after = population.clone();
+    run(&goods, &mut after, &limited, 0);
+    assert!(after.iter().all(|(_, b)| b.0[0] <= 1500.0) && population.iter().zip(&after).any(|((_, x), (_, y))| y.0[0] > x.0[0]));
+
+    // any utility: Cobb-Douglas agents gain from trading all three in pairs
+    let curved = random_population(3, 20, UtilityFn::CobbDouglas, &mut StdRng::seed_from_u64(2));
+    let mut after = curved.clone();
+    let (trades, stop) = run(&goods, &mut after, &Config { n_agents: 20, ..Config::default() }, 0);
+    assert!(stop == StopReason::Exhausted && trades.iter().any(|t| t.book == (0, 2)));
+    assert!(curved.iter().zip(&after).all(|((p, before), (_, now))| p.utility(now) >= p.utility(before)) && surplus(&curved, &after) > 0.0);
+
+    let effects = cross_price(&goods, &population, 2, 2.0, &Config { n_agents: 50, ..Config::default() }, 0);
+    assert_eq!(effects.len(), 3);
+    // c dearer: more b per c, less c per a
+    assert!(effects[2].1.vwap.unwrap() < effects[2].0.vwap.unwrap());
+    assert!(effects[1].1.vwap.unwrap() < effects[1].0.vwap.unwrap());
+
+    // with money every good is priced in it, and nothing else changes hands directly
+    let monetary = goods.with_money().unwrap();
+    assert_eq!(monetary.books(), vec![(0, 3), (1, 3), (2, 3)]);
+    let initial = monetize(&population, 500.0);
+    let mut after = initial.clone();
+    let (trades, stop) = run(&monetary, &mut after, &Config { n_agents: 50, ..Config::default() }, 0);
+    assert!(stop == StopReason::Exhausted && trades.iter().all(|t| t.book.1 == 3) && surplus(&initial, &after) > 0.0);
+    for good in 0..4 {
+      assert!((total(&initial, good) - total(&after, good)).abs() < 1e-6 * total(&initial, good));
+    }
+
+    // what needs the two-good market's own state is refused
+    assert!(check(&config).is_ok());
+    assert!(check(&Config { pricing: PricingRule { credit: Some(Credit::parse("100:0.01").unwrap()), ..PricingRule::default() }, ..config.clone() }).is_err());
+    // and so, as in a core run, is a subsidy to agents who'd wash-trade it for ever
+    let subsidized = PricingRule { subsidy: Some(Subsidy::parse("0.1").unwrap()), ..PricingRule::default() };
+    assert!(check(&Config { pricing: subsidized, ..config.clone() }).is_ok());
+    assert!(check(&Config { pricing: subsidized, utility: UtilityFn::CobbDouglas, ..config }).is_err());
+  }
+}
@@ -0,0 +1,90 @@
+// Every trade of a run alongside what it did to the two sides' holdings: its
+// sequence number, the clearing price, and the buyer's and seller's balances before
+// and after, replayed from the log since the log keeps only the amounts. Written as
+// CSV (one flat row per trade) or JSON.
+
+use serde::Serialize;
+
+use crate::clock::Tick;
+use crate::runlog::RunLog;
+use crate::{settle, AgentId, Balance, Price};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TradeRecord {
+  pub seq: usize,
+  pub tick: Tick,
+  pub buyer: AgentId,
+  pub seller: AgentId,
+  pub amount_a: f64,
+  pub amount_b: f64,
+  pub price: Price, // B per A
+  pub buyer_before: Balance,
+  pub buyer_after: Balance,
+  pub seller_before: Balance,
+  pub seller_after: Balance,
+}
+
+pub fn history(log: &RunLog) -> Vec<TradeRecord> {
+  let mut assets = log.initial_assets.clone();
+  log.trades.iter().enumerate().map(|(seq, trade)| {
+    let (buyer_before, seller_before) = (assets[trade.buyer].1, assets[trade.seller].1);
+    settle(&mut assets, trade);
+    TradeRecord {
+      seq,
+      tick: trade.tick,
+      buyer: trade.buyer,
+      seller: trade.seller,
+      amount_a: trade.amount_a,
+      amount_b: trade.amount_b,
+      price: trade.price_per_a_in_b(),
+      buyer_before,
+      buyer_after: assets[trade.buyer].1,
+      seller_before,
+      seller_after: assets[trade.seller].1,
+    }
+  }).collect()
+}
+
+pub fn csv(records: &[TradeRecord]) -> String {
+  let mut out = String::from("seq,tick,buyer,seller,amount_a,amount_b,price,buyer_a_before,buyer_b_before,buyer_a_after,buyer_b_after,seller_a_before,seller_b_before,seller_a_after,seller_b_after\n");
+  for r in records {
+    out.push_str(&format!("{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+      r.seq, r.tick, r.buyer, r.seller, r.amount_a, r.amount_b, r.price,
+      r.buyer_before.a, r.buyer_before.b, r.buyer_after.a, r.buyer_after.b,
+      r.seller_before.a, r.seller_before.b, r.seller_after.a, r.seller_after.b));
+  }
+  out
+}
+
+pub fn json(records: &[TradeRecord]) -> String {
+  serde_json::to_string_pretty(records).unwrap()
+}
+
+// As JSON if `path` ends in .json, CSV otherwise.
+pub fn write(path: &str, records: &[TradeRecord]) -> std::io::Result<()> {
+  std::fs::write(path, if path.ends_with(".json") { json(records) } else { csv(records) })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::history::*;
+  use crate::simulation::SimulationBuilder;
+
+  #[test]
+  fn test_history() {
+    let log = SimulationBuilder::new().agents(20).build().unwrap().run();
+    let records = history(&log);
+    assert_eq!(records.len(), log.trades.len());
+    // each trade starts from where the sides' previous trades left them
+    let mut last: Vec<Balance> = log.initial_assets.iter().map(|(_, b)| *b).collect();
+    for r in &records {
+      assert_eq!((r.buyer_before, r.seller_before), (last[r.buyer], last[r.seller]));
+      assert_eq!(r.buyer_after.a, r.buyer_before.a + r.amount_a);
+      assert_eq!(r.seller_after.b, r.seller_before.b + r.amount_b);
+      last[r.buyer] = r.buyer_after;
+      last[r.seller] = r.seller_after;
+    }
+    assert_eq!(csv(&records).lines().count(), 1 + records.len());
+    assert!(json(&records).contains("\"buyer_before\""));
+  }
+}
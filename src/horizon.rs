@@ -0,0 +1,81 @@
+// Agents looking ahead. An agent with a horizon of more than one round (a round is a
+// tick) values A at the average of what it's worth over the rounds of the horizon:
+// its indifference price now, and in each later round the price it expects then, net
+// of spoilage. It expects the last price it can see on the tape to hold, and a
+// round's wait turns that price p into p (1 - A's rate) / (1 - B's rate) in today's
+// terms (see decay). It bids no more than that value and asks no less, so it holds
+// back from trading now at a price the later rounds would beat, and stores the A (or
+// B) for them: with linear utility all of it or none, otherwise as much as its rate
+// says to keep at the price. Two agents value the later rounds alike, so a buyer who
+// values A above a seller now still does by 1 / rounds of the gap.
+//
+// A horizon of 1 is the myopic agent, which only sees the current round. Nobody has
+// anything to expect until a trade is reported, so everyone quotes myopically until
+// then; and expectations only move with trades, so agents looking far enough ahead
+// can hold out for a first price far from where the market would clear until nothing
+// crosses.
+
+use crate::clock::Tick;
+use crate::decay::Decay;
+use crate::{Order, Price};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Horizon {
+  pub rounds: Tick,
+  pub decay: Option<Decay>,
+}
+
+impl Horizon {
+  // A's value in B over the horizon to an agent whose indifference price is
+  // `reservation`, expecting `expected` later.
+  pub fn value(&self, reservation: Price, expected: Price) -> Price {
+    let per_round = self.decay.map_or(1.0, |d| (1.0 - d.a) / (1.0 - d.b));
+    let later: Price = (1..self.rounds).map(|k| expected * per_round.powf(k as f64)).sum();
+    (reservation + later) / self.rounds as f64
+  }
+
+  // Holds `quote` back from any price that the horizon as a whole would beat.
+  pub fn hold_back(&self, (bid, ask): &mut (Option<Order>, Option<Order>), reservation: Price, expected: Price) {
+    let value = self.value(reservation, expected);
+    bid.iter_mut().for_each(|o| o.price_per_a_in_b = o.price_per_a_in_b.min(value));
+    ask.iter_mut().for_each(|o| o.price_per_a_in_b = o.price_per_a_in_b.max(value));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::horizon::*;
+  use crate::simulation::SimulationBuilder;
+  use crate::stopping::StopReason;
+  use crate::OrderType;
+
+  #[test]
+  fn test_value() {
+    assert_eq!(Horizon { rounds: 1, decay: None }.value(1.0, 4.0), 1.0);
+    assert_eq!(Horizon { rounds: 4, decay: None }.value(1.0, 5.0), 4.0);
+    // A halving each round while B keeps: 8, then 4, of today's B
+    let perishable = Horizon { rounds: 3, decay: Some(Decay { a: 0.5, b: 0.0 }) };
+    assert_eq!(perishable.value(3.0, 16.0), 5.0);
+    let order = |typ, price_per_a_in_b| Some(Order { agent_id: 0, typ, price_per_a_in_b });
+    let mut quote = (order(OrderType::Bid, 3.0), order(OrderType::Ask, 3.0));
+    perishable.hold_back(&mut quote, 3.0, 16.0);
+    // it won't sell below what it expects to get, but won't bid more than its own price
+    assert_eq!(quote, (order(OrderType::Bid, 3.0), order(OrderType::Ask, 5.0)));
+  }
+
+  #[test]
+  fn test_horizon_run() {
+    let build = || SimulationBuilder::new().agents(60).seed(4);
+    let myopic = build().horizon(1).build().unwrap().run();
+    assert_eq!(myopic.trades, build().build().unwrap().run().trades);
+    let ahead = build().horizon(3).build().unwrap().run();
+    assert_ne!(ahead.trades, myopic.trades);
+    // looking far enough ahead to trust the first price on the tape, agents hold out
+    // for it until nothing crosses
+    let further = build().horizon(20).build().unwrap().run();
+    for log in [&myopic, &ahead, &further] {
+      assert_eq!(log.stop.map(|s| s.reason), Some(StopReason::Exhausted));
+    }
+    assert!(further.volume_a() < myopic.volume_a() && further.end() < myopic.end());
+  }
+}
@@ -0,0 +1,64 @@
+// Distributional summaries of a per-agent quantity (wealth, utility, ...). Utility is
+// in raw utils, so its inequality is only as meaningful as comparing utils across
+// agents is; wealth is valued in B at a common price.
+
+use crate::summary::{utilities, wealth_in_b};
+use crate::{Agent, Balance, Price};
+
+fn sorted(values: &[f64]) -> Vec<f64> {
+  let mut result = values.to_vec();
+  result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+  result
+}
+
+// Points (cumulative population share, cumulative value share), from (0, 0) to (1, 1).
+pub fn lorenz_curve(values: &[f64]) -> Vec<(f64, f64)> {
+  let values = sorted(values);
+  let total: f64 = values.iter().sum();
+  let n = values.len() as f64;
+  let mut result = vec![(0.0, 0.0)];
+  let mut cumulative = 0.0;
+  for (i, v) in values.iter().enumerate() {
+    cumulative += v;
+    result.push(((i + 1) as f64 / n, cumulative / total));
+  }
+  result
+}
+
+pub fn gini(values: &[f64]) -> f64 {
+  let values = sorted(values);
+  let n = values.len() as f64;
+  let total: f64 = values.iter().sum();
+  let weighted: f64 = values.iter().enumerate().map(|(i, v)| (i + 1) as f64 * v).sum();
+  2.0 * weighted / (n * total) - (n + 1.0) / n
+}
+
+// Lorenz curves as CSV for plotting, one row per point of each named series.
+pub fn lorenz_csv(series: &[(&str, &[f64])]) -> String {
+  let mut out = String::from("series,population_share,value_share\n");
+  for (name, values) in series {
+    for (x, y) in lorenz_curve(values) {
+      out.push_str(&format!("{},{},{}\n", name, x, y));
+    }
+  }
+  out
+}
+
+pub fn print_report(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], price: Price) {
+  println!("inequality (gini), initial -> final:");
+  println!("  wealth at {} B per A: {} -> {}", price, gini(&wealth_in_b(initial, price)), gini(&wealth_in_b(last, price)));
+  println!("  utility: {} -> {}", gini(&utilities(initial)), gini(&utilities(last)));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::inequality::*;
+
+  #[test]
+  fn test_gini_extremes() {
+    assert_eq!(gini(&[5.0, 5.0, 5.0, 5.0]), 0.0);
+    assert_eq!(gini(&[0.0, 0.0, 0.0, 8.0]), 0.75);
+    assert_eq!(lorenz_curve(&[3.0, 1.0]), vec![(0.0, 0.0), (0.5, 0.25), (1.0, 1.0)]);
+    assert_eq!(lorenz_csv(&[("final", &[3.0, 1.0])]), "series,population_share,value_share\nfinal,0,0\nfinal,0.5,0.25\nfinal,1,1\n");
+  }
+}
@@ -0,0 +1,206 @@
+// `simmarket influence`: ceteris-paribus influence analysis. The economy is run
+// twice on one seed, once as it is and once with a single parameter of a single agent
+// changed, e.g. `3.consumption_a_coeff*1.5` or `3.b=0`, and the report traces how
+// that one change spreads: to the tape (the first trade the runs disagree on), to
+// prices, to the agent itself, and to everyone else's final holdings. The population,
+// saved (--initial-state) or generated, is the same in both runs but for the change,
+// and every random draw is a common random number (see crn), so nothing else differs.
+//
+// The change is made to the economy as it's about to trade: after any transfer or
+// stress shock, so it stays with the one agent.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::runlog::RunLog;
+use crate::summary::wealth_in_b;
+use crate::{simulate, Agent, AgentId, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Param {
+  ProductionA,
+  ProductionB,
+  ConsumptionACoeff,
+  ConsumptionBCoeff,
+  // holdings
+  A,
+  B,
+}
+
+impl Param {
+  pub fn parse(s: &str) -> Result<Param, String> {
+    match s {
+      "production_a" => Ok(Param::ProductionA),
+      "production_b" => Ok(Param::ProductionB),
+      "consumption_a_coeff" => Ok(Param::ConsumptionACoeff),
+      "consumption_b_coeff" => Ok(Param::ConsumptionBCoeff),
+      "a" => Ok(Param::A),
+      "b" => Ok(Param::B),
+      _ => Err(format!("unknown agent parameter {:?} (expected production_a, production_b, consumption_a_coeff, consumption_b_coeff, a or b)", s)),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Param::ProductionA => "production_a",
+      Param::ProductionB => "production_b",
+      Param::ConsumptionACoeff => "consumption_a_coeff",
+      Param::ConsumptionBCoeff => "consumption_b_coeff",
+      Param::A => "a",
+      Param::B => "b",
+    }
+  }
+
+  fn of<'a>(&self, agent: &'a mut Agent, balance: &'a mut Balance) -> &'a mut f64 {
+    match self {
+      Param::ProductionA => &mut agent.production_a,
+      Param::ProductionB => &mut agent.production_b,
+      Param::ConsumptionACoeff => &mut agent.consumption_a_coeff,
+      Param::ConsumptionBCoeff => &mut agent.consumption_b_coeff,
+      Param::A => &mut balance.a,
+      Param::B => &mut balance.b,
+    }
+  }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+  Set(f64),
+  Scale(f64),
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Perturbation {
+  pub agent: AgentId,
+  pub param: Param,
+  pub change: Change,
+}
+
+impl Perturbation {
+  // `<id>.<param>=<value>` or `<id>.<param>*<factor>`, e.g. `3.consumption_a_coeff*1.5`
+  pub fn parse(s: &str) -> Result<Perturbation, String> {
+    let bad = || format!("bad perturbation {:?} (expected <id>.<param>=<value> or <id>.<param>*<factor>)", s);
+    let (id, rest) = s.split_once('.').ok_or_else(bad)?;
+    let agent = id.parse().map_err(|_| format!("can't read agent id {:?}", id))?;
+    let (param, change) = match (rest.split_once('='), rest.split_once('*')) {
+      (Some((param, value)), None) => (param, value.parse().map(Change::Set)),
+      (None, Some((param, factor))) => (param, factor.parse().map(Change::Scale)),
+      _ => return Err(bad()),
+    };
+    Ok(Perturbation { agent, param: Param::parse(param)?, change: change.map_err(|_| bad())? })
+  }
+
+  pub fn describe(&self) -> String {
+    match self.change {
+      Change::Set(value) => format!("agent {}'s {} set to {}", self.agent, self.param.name(), value),
+      Change::Scale(factor) => format!("agent {}'s {} scaled by {}", self.agent, self.param.name(), factor),
+    }
+  }
+
+  // The parameter's value before and after the change, in `assets`.
+  pub fn values(&self, assets: &[(Agent, Balance)]) -> (f64, f64) {
+    let (mut agent, mut balance) = assets[self.agent];
+    let before = *self.param.of(&mut agent, &mut balance);
+    (before, match self.change {
+      Change::Set(value) => value,
+      Change::Scale(factor) => before * factor,
+    })
+  }
+
+  pub fn apply(&self, assets: &mut [(Agent, Balance)]) {
+    let after = self.values(assets).1;
+    let (agent, balance) = &mut assets[self.agent];
+    *self.param.of(agent, balance) = after;
+  }
+
+  // A copy of `assets`, perturbed.
+  pub fn cloned(&self, assets: &[(Agent, Balance)]) -> Vec<(Agent, Balance)> {
+    let mut clone = assets.to_vec();
+    self.apply(&mut clone);
+    clone
+  }
+}
+
+// The runs without and with `perturbation`.
+pub fn run(config: &Config, seed: u64, perturbation: Perturbation) -> (RunLog, RunLog) {
+  let baseline = simulate(&Config { perturbation: None, ..config.clone() }, seed);
+  (baseline, simulate(&Config { perturbation: Some(perturbation), ..config.clone() }, seed))
+}
+
+// The index of the first trade the runs disagree on, None if the tapes are the same.
+pub fn divergence(baseline: &RunLog, perturbed: &RunLog) -> Option<usize> {
+  let same = baseline.trades.iter().zip(&perturbed.trades).take_while(|(x, y)| x == y).count();
+  (same < baseline.trades.len().max(perturbed.trades.len())).then_some(same)
+}
+
+// Each agent's change in final wealth at `price`.
+pub fn wealth_changes(baseline: &RunLog, perturbed: &RunLog, price: Price) -> Vec<f64> {
+  let (before, after) = (wealth_in_b(&baseline.final_assets(), price), wealth_in_b(&perturbed.final_assets(), price));
+  before.iter().zip(&after).map(|(x, y)| y - x).collect()
+}
+
+pub fn print_report(baseline: &RunLog, perturbed: &RunLog, perturbation: Perturbation, top_k: usize) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  let (before, after) = perturbation.values(&baseline.initial_assets);
+  println!("influence of {} ({} -> {}):", perturbation.describe(), before, after);
+  println!("  trades: {} -> {}", baseline.trades.len(), perturbed.trades.len());
+  match divergence(baseline, perturbed) {
+    Some(i) => {
+      let tick = baseline.trades.get(i).or(perturbed.trades.get(i)).map(|t| t.tick).unwrap();
+      println!("  the tapes part at trade {} (tick {})", i, tick);
+    }
+    None => println!("  the tapes are the same"),
+  }
+  let (p0, p1) = (baseline.mean_price(), perturbed.mean_price());
+  let change = p0.zip(p1).map(|(p0, p1)| format!(" ({:+.4}%)", 100.0 * (p1 / p0 - 1.0))).unwrap_or_default();
+  println!("  mean price: {} -> {}{}", show(p0), show(p1), change);
+
+  let id = perturbation.agent;
+  let (g0, g1) = (baseline.gains(), perturbed.gains());
+  let (last0, last1) = (baseline.final_assets()[id].1, perturbed.final_assets()[id].1);
+  println!("  agent {}: ends with {} A and {} B -> {} A and {} B, gaining {} -> {} (own utils)", id, last0.a, last0.b, last1.a, last1.b, g0[id], g1[id]);
+
+  // wealth at the unperturbed mean price, so a change in price alone moves nobody
+  let changes = wealth_changes(baseline, perturbed, p0.unwrap_or(1.0));
+  let others: Vec<(AgentId, f64)> = changes.iter().copied().enumerate().filter(|&(other, _)| other != id).collect();
+  let moved = others.iter().filter(|(other, _)| baseline.final_assets()[*other].1 != perturbed.final_assets()[*other].1).count();
+  let others_gains = |g: &[f64]| g.iter().enumerate().filter(|&(other, _)| other != id).fold(0.0, |s, (_, x)| s + x);
+  let mean_abs = others.iter().fold(0.0, |s, (_, x)| s + x.abs()) / others.len().max(1) as f64;
+  println!("  the other {}: {} end with other holdings, by a mean {:.6} B of wealth; their gains {} -> {}", others.len(), moved, mean_abs, others_gains(&g0), others_gains(&g1));
+  let mut most = others;
+  most.sort_by(|x, y| y.1.abs().total_cmp(&x.1.abs()));
+  let most: Vec<String> = most.iter().take(top_k).filter(|(_, x)| *x != 0.0).map(|(other, x)| format!("agent {} ({:+.4} B)", other, x)).collect();
+  if !most.is_empty() {
+    println!("  most affected: {}", most.join(", "));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::influence::*;
+
+  #[test]
+  fn test_influence() {
+    let p = Perturbation::parse("3.consumption_a_coeff*1.5").unwrap();
+    assert_eq!(p, Perturbation { agent: 3, param: Param::ConsumptionACoeff, change: Change::Scale(1.5) });
+    assert!(Perturbation::parse("3.height=2").is_err() && Perturbation::parse("3.a").is_err());
+
+    let config = Config { n_agents: 40, ..Config::default() };
+    let assets = config.initial_assets(1, &mut rand::SeedableRng::seed_from_u64(1));
+    let clone = Perturbation::parse("5.b=0").unwrap().cloned(&assets);
+    // only the one agent differs
+    assert!((0..40).all(|id| (clone[id] == assets[id]) == (id != 5 || assets[5].1.b == 0.0)));
+    assert_eq!(clone[5].1, Balance { b: 0.0, ..assets[5].1 });
+
+    // no change, no influence; a keener agent moves the tape
+    let unchanged = Perturbation { agent: 5, param: Param::A, change: Change::Scale(1.0) };
+    let (baseline, same) = run(&config, 1, unchanged);
+    assert_eq!(divergence(&baseline, &same), None);
+    let (baseline, perturbed) = run(&config, 1, Perturbation { agent: 5, param: Param::ConsumptionACoeff, change: Change::Scale(3.0) });
+    assert!(divergence(&baseline, &perturbed).is_some());
+    assert_eq!(perturbed.initial_assets[5].0.consumption_a_coeff, 3.0 * baseline.initial_assets[5].0.consumption_a_coeff);
+    assert!(wealth_changes(&baseline, &perturbed, 1.0).iter().enumerate().any(|(id, x)| id != 5 && *x != 0.0));
+  }
+}
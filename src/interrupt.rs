@@ -0,0 +1,109 @@
+// Ctrl-C. The first SIGINT raises a signal every run in the process stops on at its
+// next tick, as for a stop file (see stopping), and the command writes out what it has
+// so far, flagged as truncated, before exiting; a second kills it as usual. Only the
+// binary installs the handler, so a run embedded elsewhere is never stopped by it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+static SIGNAL: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+// apart from the signal, which a stop file raises too
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod sys {
+  use std::os::raw::c_int;
+
+  pub const SIGINT: c_int = 2;
+  pub const SIG_DFL: usize = 0;
+
+  extern "C" {
+    pub fn signal(signum: c_int, handler: usize) -> usize;
+  }
+}
+
+// Only atomics, which are safe to touch in a signal handler.
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: std::os::raw::c_int) {
+  INTERRUPTED.store(true, Ordering::Relaxed);
+  if let Some(signal) = SIGNAL.get() {
+    signal.store(true, Ordering::Relaxed);
+  }
+  // SAFETY: signal is async-signal-safe, and restores the default handler
+  unsafe { sys::signal(sys::SIGINT, sys::SIG_DFL) };
+}
+
+// Installs the handler, once; elsewhere than unix, nothing ever raises the signal.
+pub fn install() {
+  if SIGNAL.set(Arc::new(AtomicBool::new(false))).is_ok() {
+    // SAFETY: the handler only stores to atomics and reinstates the default
+    #[cfg(unix)]
+    unsafe { sys::signal(sys::SIGINT, on_interrupt as extern "C" fn(_) as usize) };
+  }
+}
+
+// The signal a Ctrl-C raises, once the handler is installed.
+pub fn signal() -> Option<Arc<AtomicBool>> {
+  SIGNAL.get().cloned()
+}
+
+pub fn interrupted() -> bool {
+  INTERRUPTED.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+  use rand::rngs::StdRng;
+
+  use crate::arrivals::Arrivals;
+  use crate::interrupt::*;
+  use crate::lots;
+  use crate::market::Market;
+  use crate::population::{self, Population};
+  use crate::pricing::PricingRule;
+  use crate::risk::RiskRules;
+  use crate::runlog::{self, Event};
+  use crate::stopping::{StopReason, StoppingRules};
+  use crate::strategy::Strategies;
+  use crate::summary::Summary;
+
+  #[test]
+  fn test_interrupted_run() {
+    // with the handler never installed, a Ctrl-C is only noted: there's no signal for
+    // it to raise
+    #[cfg(unix)]
+    {
+      on_interrupt(sys::SIGINT);
+      assert!(interrupted() && signal().is_none());
+    }
+
+    // the handler's signal, raised as it would be after the fifth trade; not the
+    // process's own, which would stop every other test's runs too
+    let signal = Arc::new(AtomicBool::new(false));
+    let stopping = StoppingRules { signal: Some(signal.clone()), ..StoppingRules::default() };
+    let assets = population::generate(Population::Uniform, 40, &mut StdRng::seed_from_u64(1));
+    let mut market = Market::new(assets, Arrivals::every_tick(1), PricingRule::default(), RiskRules::default(), stopping);
+    let mut traded = 0;
+    let stop = market.run(&mut Strategies::truthful_for(&market.assets), |event| {
+      if let Event::Trade(_) = event {
+        traded += 1;
+        if traded == 5 {
+          signal.store(true, Ordering::Relaxed);
+        }
+      }
+    });
+    assert_eq!((stop.tick, stop.reason), (5, StopReason::Signal));
+    let log = market.into_outcome().into_log(1, vec![]);
+
+    // what it has so far is written out, flagged as truncated, and replays to holdings
+    // with every good accounted for
+    let path = std::env::temp_dir().join("simmarket_interrupt_test.ndjson");
+    runlog::write(path.to_str().unwrap(), &log).unwrap();
+    let written = runlog::read(path.to_str().unwrap()).unwrap();
+    assert_eq!((written.trades.len(), written.stop), (5, Some(stop)));
+    assert_eq!(written.final_assets(), log.final_assets());
+    lots::check_conservation(lots::totals(&written.initial_assets), lots::totals(&written.final_assets()));
+    assert_eq!(Summary::of(&written).metric("truncated"), Some(1.0));
+  }
+}
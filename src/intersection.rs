@@ -0,0 +1,140 @@
+// Where the discrete step supply and demand curves cross. Each truthful order from
+// generate_orders is one step: an ask offers all of the agent's A at its price, and a
+// bid spends its whole budget at any price up to its own, so demand falls as 1/p. The
+// crossing is the Walrasian price of dispersion, found exactly rather than by
+// bisection. Those steps are linear agents' orders, so the report is left out unless
+// every agent is linear.
+
+use crate::{all_orders, Agent, Balance, Price};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Intersection {
+  // the prices at which the curves cross, as an interval: wider than a point when
+  // they cross on a vertical segment (between two prices)
+  pub price_low: Price,
+  pub price_high: Price,
+  // the quantities of A that clear at `price()`: wider than a point when the curves
+  // overlap on a flat segment (several orders at exactly that price)
+  pub quantity_low: f64,
+  pub quantity_high: f64,
+}
+
+impl Intersection {
+  pub fn price(&self) -> Price {
+    (self.price_low + self.price_high) / 2.0
+  }
+}
+
+// (price, size) for each order on one side of the book: the B budgeted for a bid, the
+// A offered for an ask
+pub type Steps = Vec<(Price, f64)>;
+
+// (bids, asks)
+pub fn steps(assets: &[(Agent, Balance)]) -> (Steps, Steps) {
+  let mut bids = vec![];
+  let mut asks = vec![];
+  for (bid, ask) in all_orders(assets) {
+    if let Some(bid) = bid {
+      bids.push((bid.price_per_a_in_b, assets[bid.agent_id].1.b));
+    }
+    if let Some(ask) = ask {
+      asks.push((ask.price_per_a_in_b, assets[ask.agent_id].1.a));
+    }
+  }
+  (bids, asks)
+}
+
+// None if either side of the book is empty. If nothing crosses, the quantity is 0
+// and the price interval is the spread. Demand at a price p is what every bid at or
+// above p budgets, divided by p, as in supply_demand_curves, so it falls continuously
+// between steps and the curves cross at a single price.
+pub fn solve(bids: &[(Price, f64)], asks: &[(Price, f64)]) -> Option<Intersection> {
+  if bids.is_empty() || asks.is_empty() {
+    return None;
+  }
+  // with `strict`, only the orders strictly willing at `price`
+  let supply = |price: Price, strict: bool| asks.iter().filter(|a| if strict { a.0 < price } else { a.0 <= price }).fold(0.0, |s, a| s + a.1);
+  let budget = |price: Price, strict: bool| bids.iter().filter(|b| if strict { b.0 > price } else { b.0 >= price }).fold(0.0, |d, b| d + b.1);
+  let demand = |price: Price, strict: bool| budget(price, strict) / price;
+  let at = |price_low: Price, price_high: Price| {
+    let price = (price_low + price_high) / 2.0;
+    Intersection {
+      price_low,
+      price_high,
+      quantity_low: supply(price, true).max(demand(price, true)),
+      quantity_high: supply(price, false).min(demand(price, false)),
+    }
+  };
+
+  let highest_bid = bids.iter().fold(f64::NEG_INFINITY, |m, b| m.max(b.0));
+  let lowest_ask = asks.iter().fold(f64::INFINITY, |m, a| m.min(a.0));
+  if highest_bid < lowest_ask {
+    return Some(Intersection { price_low: highest_bid, price_high: lowest_ask, quantity_low: 0.0, quantity_high: 0.0 });
+  }
+  // the curves cross either on a step, where one jumps past the other, or between
+  // two, where demand B/p meets the flat supply S at p = B/S
+  let mut prices: Vec<Price> = bids.iter().chain(asks).map(|x| x.0).collect();
+  prices.sort_by(|x, y| x.total_cmp(y));
+  prices.dedup();
+  for (k, &price) in prices.iter().enumerate() {
+    if demand(price, false) >= supply(price, true) && demand(price, true) <= supply(price, false) {
+      return Some(at(price, price));
+    }
+    let next = prices.get(k + 1).copied().unwrap_or(f64::INFINITY);
+    let (budgeted, offered) = (budget(next, false), supply(price, false));
+    let between = budgeted / offered;
+    if offered > 0.0 && budgeted > 0.0 && price <= between && between <= next {
+      return Some(at(between, between));
+    }
+  }
+  unreachable!("demand starts above supply and ends below it")
+}
+
+pub fn print_report(assets: &[(Agent, Balance)]) {
+  if !assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()) {
+    return;
+  }
+  let (bids, asks) = steps(assets);
+  match solve(&bids, &asks) {
+    Some(x) => println!("step curves cross at price {} (range {}..{}), quantity {} (range {}..{})",
+      x.price(), x.price_low, x.price_high, x.quantity_high, x.quantity_low, x.quantity_high),
+    None => println!("step curves don't cross: one side of the book is empty"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::Config;
+  use crate::dispersion::walrasian_price;
+  use crate::intersection::*;
+
+  #[test]
+  fn test_solve_edge_cases() {
+    // between steps: the bid's 6 B buys the 2 A offered below 4 at 3
+    let x = solve(&[(5.0, 6.0)], &[(1.0, 2.0), (4.0, 10.0)]).unwrap();
+    assert_eq!((x.price_low, x.price_high, x.quantity_low, x.quantity_high), (3.0, 3.0, 2.0, 2.0));
+    // on a step: supply jumps past demand at 2, where the 6 B buy 3 A
+    let x = solve(&[(5.0, 6.0)], &[(1.0, 1.0), (2.0, 10.0)]).unwrap();
+    assert_eq!((x.price_low, x.price_high, x.quantity_low, x.quantity_high), (2.0, 2.0, 3.0, 3.0));
+    // flat: both curves have a step at 3, pinning the price there; nobody is strictly
+    // willing, and at most the 1 A offered clears
+    let x = solve(&[(3.0, 6.0)], &[(3.0, 1.0)]).unwrap();
+    assert_eq!((x.price_low, x.price_high, x.quantity_low, x.quantity_high), (3.0, 3.0, 0.0, 1.0));
+    // no crossing: the spread, with nothing traded
+    let x = solve(&[(1.0, 1.0)], &[(2.0, 1.0)]).unwrap();
+    assert_eq!((x.price_low, x.price_high, x.quantity_high), (1.0, 2.0, 0.0));
+    assert_eq!(solve(&[], &[(2.0, 1.0)]), None);
+  }
+
+  #[test]
+  fn test_crossing_is_walrasian() {
+    let config = Config { n_agents: 50, ..Config::default() };
+    let assets = config.initial_assets(1, &mut rand::SeedableRng::seed_from_u64(1));
+    let (bids, asks) = steps(&assets);
+    let (x, walrasian) = (solve(&bids, &asks).unwrap(), walrasian_price(&assets).unwrap());
+    assert!((x.price() - walrasian).abs() < 1e-9 * walrasian, "{} vs {}", x.price(), walrasian);
+    // what clears is what's offered at or below the price
+    let offered = asks.iter().filter(|a| a.0 <= x.price()).fold(0.0, |s, a| s + a.1);
+    assert!(x.quantity_high <= offered && x.quantity_low <= x.quantity_high);
+  }
+}
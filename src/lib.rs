@@ -0,0 +1,861 @@
+// The simulation core: agents and their holdings, quotes, the matching engine
+// (find_next_trade, execute_one_trade, and market::Market, which steps or runs a
+// whole market; find_next_goods_trade matches the same way book by book, for
+// economies of more goods than two), and the analyses built on its output. The
+// simmarket binary is a command line over it; anything else can embed or benchmark
+// it the same way.
+
+use rand::rngs::StdRng;
+use rand::distributions::{Distribution, Uniform};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set to silence the play-by-play from the matching loop, e.g. when scanning many runs.
+pub static QUIET: AtomicBool = AtomicBool::new(false);
+
+macro_rules! trace {
+  ($($arg:tt)*) => { if !QUIET.load(Ordering::Relaxed) { println!($($arg)*); } }
+}
+
+pub mod accounting;
+pub mod activity;
+pub mod allocation;
+pub mod anonymize;
+pub mod arrivals;
+pub mod bilateral;
+pub mod book;
+pub mod bootstrap;
+pub mod checkpoint;
+pub mod budget_share;
+pub mod call;
+pub mod cli;
+pub mod clock;
+pub mod cohort;
+pub mod community;
+pub mod copula;
+pub mod crn;
+pub mod curve_fit;
+pub mod curves;
+pub mod config;
+pub mod continuous;
+pub mod credit;
+pub mod dark_pool;
+pub mod dealer;
+pub mod decay;
+pub mod deflation;
+pub mod depth;
+pub mod dispersion;
+pub mod entry;
+pub mod equilibrium;
+pub mod fat_finger;
+pub mod forecast;
+pub mod goods;
+pub mod history;
+pub mod horizon;
+pub mod inequality;
+pub mod influence;
+pub mod limits;
+pub mod lots;
+pub mod interrupt;
+pub mod intersection;
+pub mod market;
+pub mod market_power;
+pub mod mobility;
+pub mod network;
+pub mod nonconvergence;
+pub mod numeraire;
+pub mod outcome;
+pub mod outdir;
+pub mod pairs;
+pub mod parallel;
+pub mod pareto;
+pub mod population;
+pub mod pricing;
+pub mod planner;
+pub mod privacy;
+pub mod query;
+pub mod recommend;
+pub mod regions;
+pub mod privilege;
+pub mod report;
+pub mod reporting;
+pub mod risk;
+pub mod runlog;
+pub mod sampling;
+pub mod schema;
+pub mod seeds;
+pub mod session;
+pub mod short;
+pub mod simulation;
+pub mod stats;
+pub mod steady_state;
+pub mod stopping;
+pub mod strategy;
+pub mod stress;
+pub mod subsidy;
+pub mod summary;
+pub mod svg;
+pub mod tax;
+pub mod thesis;
+pub mod timing;
+pub mod trade_stream;
+pub mod transfers;
+pub mod transparency;
+pub mod utility;
+pub mod walras;
+pub mod welfare;
+pub mod zero_intelligence;
+pub mod zip;
+
+// A run with no output, for when only the outcome matters. The config is checked as
+// the builder checks it, and panics if it's invalid.
+pub fn simulate(config: &config::Config, seed: u64) -> runlog::RunLog {
+  let simulation = simulation::SimulationBuilder::from_config(config.clone()).seed(seed).build();
+  simulation.unwrap_or_else(|e| panic!("invalid config: {}", e)).run()
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Agent {
+    // Production ability per time unit of each commodity
+    pub production_a: f64,
+    pub production_b: f64,
+
+    pub consumption_a_coeff: f64,
+    pub consumption_b_coeff: f64,
+
+    // states and logs from before this was configurable are linear
+    #[serde(default, skip_serializing_if = "utility::UtilityFn::is_linear")]
+    pub utility_fn: utility::UtilityFn,
+}
+
+impl Agent {
+  pub fn utility(&self, consumption_a: f64, consumption_b: f64) -> f64 {
+    self.utility_fn.level(self.coeffs(), consumption_a, consumption_b)
+  }
+
+  // Whether trading `delta_a` A for `delta_b` B leaves an agent holding `before` better
+  // off. Judged on the trade itself, since rounding swamps a dust-sized fill's effect
+  // on the utility of a large balance: under linear utility by its value, and
+  // otherwise by the agent's rate halfway through it, which is above the price paid
+  // for A (below the price received) for any fill that stops where the rate meets it.
+  pub fn gains(&self, before: &Balance, delta_a: f64, delta_b: f64) -> bool {
+    if self.utility_fn.is_linear() {
+      self.utility(delta_a, delta_b) > 0.0
+    } else {
+      let halfway = Balance { a: before.a + delta_a / 2.0, b: before.b + delta_b / 2.0 };
+      self.indifference_price_at(&halfway) * delta_a + delta_b > 0.0
+    }
+  }
+
+  // The indifference price at the agent's production. Under anything but linear
+  // utility it moves with what the agent holds; see indifference_price_at.
+  pub fn indifference_price_of_a_in_b(&self) -> f64 {
+    self.utility_fn.marginal_rate(self.coeffs(), self.production_a, self.production_b)
+  }
+
+  pub fn indifference_price_at(&self, balance: &Balance) -> f64 {
+    self.utility_fn.marginal_rate(self.coeffs(), balance.a, balance.b)
+  }
+
+  pub fn marginal_utilities(&self, balance: &Balance) -> (f64, f64) {
+    self.utility_fn.marginal_utilities(self.coeffs(), balance.a, balance.b)
+  }
+
+  // The A the agent would buy at `price`, or sell if negative; see UtilityFn::demand.
+  pub fn demand_for_a(&self, balance: &Balance, price: Price) -> f64 {
+    self.utility_fn.demand(self.coeffs(), balance, price)
+  }
+
+  fn coeffs(&self) -> (f64, f64) {
+    (self.consumption_a_coeff, self.consumption_b_coeff)
+  }
+
+  pub fn new_random(rng: &mut StdRng) -> Agent {
+    let prod_dist = Uniform::new(0.0,1000.0);
+    let coeff_dist = Uniform::new(0.0,1.0);
+    
+    Agent {
+      production_a: prod_dist.sample(rng),
+      production_b: prod_dist.sample(rng),
+
+      consumption_a_coeff: coeff_dist.sample(rng),
+      consumption_b_coeff: coeff_dist.sample(rng),
+
+      utility_fn: utility::UtilityFn::Linear,
+    }
+  }
+
+  // The agent at the given quantiles of new_random's marginals, in field order.
+  pub fn from_quantiles(u: [f64; 4]) -> Agent {
+    Agent {
+      production_a: 1000.0 * u[0],
+      production_b: 1000.0 * u[1],
+
+      consumption_a_coeff: u[2],
+      consumption_b_coeff: u[3],
+
+      utility_fn: utility::UtilityFn::Linear,
+    }
+  }
+}
+
+// A linear agent producing nothing and valuing A at `a_coeff` B, the fixture most
+// tests build their populations from.
+#[cfg(test)]
+pub(crate) fn linear_agent(a_coeff: f64) -> Agent {
+  Agent { production_a: 0.0, production_b: 0.0, consumption_a_coeff: a_coeff, consumption_b_coeff: 1.0, utility_fn: utility::UtilityFn::Linear }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::*;
+
+  #[test]
+  fn test_indifference_price() {
+    let agent = Agent {
+      production_a: 10.0,
+      production_b: 10.0,
+      consumption_a_coeff: 1.0,
+      consumption_b_coeff: 5.0,
+      utility_fn: utility::UtilityFn::Linear,
+    };
+    assert_eq!(agent.indifference_price_of_a_in_b(), 0.20);
+
+    let price_a_in_b = agent.indifference_price_of_a_in_b();
+    let amount_a_bought = 1.0;
+
+    let consumption_a = agent.production_a + amount_a_bought;
+    let consumption_b = agent.production_b - amount_a_bought*price_a_in_b;
+
+
+    assert_eq!(
+      agent.utility(agent.production_a, agent.production_b),
+      agent.utility(consumption_a, consumption_b),
+    );
+  }
+
+  #[test]
+  fn test_find_next_trade() {
+    let mut assets = vec![
+      (
+        Agent {
+          production_a: 0.0,
+          production_b: 0.0,
+          consumption_a_coeff: 1.0,
+          consumption_b_coeff: 5.0,
+          utility_fn: utility::UtilityFn::Linear,
+        },
+        Balance {
+          a: 1.0,
+          b: 2.0,
+        },
+      ),
+      (
+        Agent {
+          production_a: 0.0,
+          production_b: 0.0,
+          consumption_a_coeff: 8.0,
+          consumption_b_coeff: 1.0,
+          utility_fn: utility::UtilityFn::Linear,
+        },
+        Balance {
+          a: 3.0,
+          b: 4.0,
+        },
+      ),
+    ];
+
+    // clearing price is the midpoint (8.0 + 0.2) / 2 = 4.1, so the buyer's 4.0 B
+    // runs out before the seller's 1.0 A does
+    let mut strategies = strategy::Strategies::truthful_for(&assets);
+    assert_eq!(
+      find_next_trade(&assets, &book::OrderBook::from_quotes(&strategies.orders(&assets, 0)), pricing::PricingRule::default(), &[], 3).unwrap(),
+      Trade{
+        tick: 3,
+        buyer: 1,
+        seller: 0,
+        amount_a: 0.9756097560975611,
+        amount_b: 4.0,
+        bid_price: 8.0,
+        ask_price: 0.2,
+        ..Trade::default()
+      }
+    );
+
+    let orders = strategies.orders(&assets, 0);
+    execute_one_trade(&mut assets, &book::OrderBook::from_quotes(&orders), pricing::PricingRule::default(), &risk::RiskRules::default(), &[], 3, |_| {});
+
+    // the only remaining bid (agent 0's, at 0.2) is below every remaining ask
+    assert_eq!(find_next_trade(&assets, &book::OrderBook::from_quotes(&strategies.orders(&assets, 0)), pricing::PricingRule::default(), &[], 4), None);
+  }
+
+  #[test]
+  fn test_resting_quotes_withdrawn_when_unbacked() {
+    // with slow arrivals quotes rest for many ticks, so a side a trade drains is taken
+    // off the book in the same tick, not left there until its agent next requotes
+    let assets = population::generate(population::Population::Uniform, 30, &mut StdRng::seed_from_u64(0));
+    let arrivals = arrivals::Arrivals::poisson(assets.len(), (0.05, 0.05), 0);
+    let mut market = market::Market::new(assets, arrivals, pricing::PricingRule::default(), risk::RiskRules::default(), stopping::StoppingRules::default());
+    let mut strategies = strategy::Strategies::truthful_for(&market.assets);
+    let mut drained = 0;
+    while let Some(trade) = market.step_to_trade(&mut strategies, |_| {}).cloned() {
+      let book = market.state().book;
+      if market.assets[trade.buyer].1.b == 0.0 {
+        assert_eq!(book[trade.buyer].0, None, "{:?}", trade);
+        drained += 1;
+      }
+      if market.assets[trade.seller].1.a == 0.0 {
+        assert_eq!(book[trade.seller].1, None, "{:?}", trade);
+        drained += 1;
+      }
+    }
+    assert!(drained > 0);
+  }
+
+  #[test]
+  fn test_realized_surplus() {
+    let agent = Agent {
+      production_a: 0.0,
+      production_b: 0.0,
+      consumption_a_coeff: 2.0,
+      consumption_b_coeff: 1.0,
+      utility_fn: utility::UtilityFn::Linear,
+    };
+    let initial = vec![(agent, Balance { a: 1.0, b: 1.0 })];
+    let last = vec![(agent, Balance { a: 2.0, b: 0.5 })];
+    assert_eq!(realized_surplus(&initial, &last), vec![1.5]);
+  }
+
+  #[test]
+  fn test_supply_demand_curves_non_linear() {
+    let agent = Agent { production_a: 0.0, production_b: 0.0, consumption_a_coeff: 1.0, consumption_b_coeff: 1.0, utility_fn: utility::UtilityFn::CobbDouglas };
+    // rates 1/4 and 8; with half of each one's wealth on A the market clears at 9/5
+    let assets = vec![(agent, Balance { a: 4.0, b: 1.0 }), (agent, Balance { a: 1.0, b: 8.0 })];
+    let curves = supply_demand_curves(&assets);
+    assert_eq!(curves.len(), 4);
+    // each agent only starts to trade past its own rate, and then only a little
+    assert!(curves.iter().all(|&(_, supply, demand)| supply < 4.0 && demand < 8.0 / 0.25));
+    let (supply, demand) = supply_demand_at(&assets, 1.8);
+    assert!(supply > 0.0 && (supply - demand).abs() < 1e-9);
+  }
+
+}
+
+// What an agent holds of A and B. It stays two goods: an economy of more holds a
+// Bundle, and each of its books sees this view of it (Bundle::in_book).
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Balance {
+  pub a: f64,
+  pub b: f64,
+}
+
+pub type AgentId = usize;
+
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Trade {
+  // logs from before the event clock have no ticks
+  #[serde(default)]
+  pub tick: clock::Tick,
+  pub buyer: AgentId,
+  pub seller: AgentId,
+
+  pub amount_a: f64, // transferred from seller to buyer
+  pub amount_b: f64, // transferred from buyer to seller
+
+  // the matched quotes; logs from before these were recorded have 0 here
+  #[serde(default)]
+  pub bid_price: Price,
+  #[serde(default)]
+  pub ask_price: Price,
+  // B the buyer paid in tax on top of amount_b, left out of untaxed logs; see tax
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub tax: f64,
+  // B paid to either side from outside the market; see subsidy
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub buyer_subsidy: f64,
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub seller_subsidy: f64,
+  // B lent to the buyer toward paying, and taken from the seller once paid to pay
+  // down its debt; see credit
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub borrowed: f64,
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub repaid: f64,
+  // A lent to the seller toward delivering, and taken from the buyer once delivered to
+  // cover what it owes; see short
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub shorted: f64,
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub covered: f64,
+}
+
+fn is_zero(x: &f64) -> bool {
+  *x == 0.0
+}
+
+impl Trade {
+  pub fn price_per_a_in_b(&self) -> f64 {
+    self.amount_b / self.amount_a
+  }
+}
+
+// Economies of more goods than A and B (see goods) index them in a registry. An
+// agent holds a Bundle, an amount of each, and weighs them by its Preferences, one
+// coefficient each under a UtilityFn. In a book trading one good priced in another,
+// every agent is the two-good agent holding just those, the book's good as A and the
+// one it's priced in as B: under every UtilityFn the rate between two goods depends
+// on nothing else, so that agent quotes, trades and gains exactly as the whole one.
+pub type Good = usize;
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Bundle(pub Vec<f64>);
+
+impl Bundle {
+  pub fn in_book(&self, (base, quote): (Good, Good)) -> Balance {
+    Balance { a: self.0[base], b: self.0[quote] }
+  }
+}
+
+impl From<&Balance> for Bundle {
+  fn from(balance: &Balance) -> Bundle {
+    Bundle(vec![balance.a, balance.b])
+  }
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct Preferences {
+  pub coeffs: Vec<f64>,
+  #[serde(default, skip_serializing_if = "utility::UtilityFn::is_linear")]
+  pub utility_fn: utility::UtilityFn,
+}
+
+impl Preferences {
+  pub fn utility(&self, bundle: &Bundle) -> f64 {
+    self.utility_fn.level_of(&self.coeffs, &bundle.0)
+  }
+
+  // The two-good agent holding `bundle`'s amounts of the book's goods.
+  pub fn in_book(&self, (base, quote): (Good, Good), bundle: &Bundle) -> Agent {
+    Agent {
+      production_a: bundle.0[base],
+      production_b: bundle.0[quote],
+      consumption_a_coeff: self.coeffs[base],
+      consumption_b_coeff: self.coeffs[quote],
+      utility_fn: self.utility_fn,
+    }
+  }
+}
+
+impl From<&Agent> for Preferences {
+  fn from(agent: &Agent) -> Preferences {
+    Preferences { coeffs: vec![agent.consumption_a_coeff, agent.consumption_b_coeff], utility_fn: agent.utility_fn }
+  }
+}
+
+// Everyone as the two-good engine sees them in `book`.
+pub fn in_book(assets: &[(Preferences, Bundle)], book: (Good, Good)) -> Vec<(Agent, Balance)> {
+  assets.iter().map(|(prefs, bundle)| (prefs.in_book(book, bundle), bundle.in_book(book))).collect()
+}
+
+// A trade in one book: `trade`'s A is the book's first good and its B the second.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct GoodsTrade {
+  pub book: (Good, Good),
+  #[serde(flatten)]
+  pub trade: Trade,
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+  Bid,
+  Ask,
+}
+
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Order {
+  pub agent_id: AgentId,
+  
+  pub typ: OrderType,
+
+  // in a book of more goods (see GoodsTrade), the book's first good priced in its second
+  pub price_per_a_in_b: f64,
+}
+
+// An agent's truthful (bid, ask), through strategy::Truthful.
+pub fn generate_orders(agent_id: AgentId, agent: &Agent, balance: &Balance) -> (Option<Order>, Option<Order>) {
+  use strategy::TradingStrategy;
+  let seen = strategy::Observation { id: agent_id, agent, balance, reservation: agent.indifference_price_at(balance), now: 0, last_price: None };
+  strategy::Truthful.quote(&seen)
+}
+
+// quote_at, but with a non-linear agent's bid and ask either side of `price`; see
+// utility::SETTLED.
+pub fn quote_around(agent_id: AgentId, agent: &Agent, price: Price, balance: &Balance) -> (Option<Order>, Option<Order>) {
+  let (mut bid, mut ask) = quote_at(agent_id, price, balance);
+  if !agent.utility_fn.is_linear() {
+    bid.iter_mut().for_each(|o| o.price_per_a_in_b *= 1.0 - utility::SETTLED);
+    ask.iter_mut().for_each(|o| o.price_per_a_in_b *= 1.0 + utility::SETTLED);
+  }
+  (bid, ask)
+}
+
+// A bid and an ask at `price`, on whichever sides the balance can back.
+pub fn quote_at(agent_id: AgentId, price: Price, balance: &Balance) -> (Option<Order>, Option<Order>) {
+  let bid = {
+    if balance.b > 0.0 {
+      Some(Order {
+        agent_id,
+        typ: OrderType::Bid,
+        price_per_a_in_b: price,
+      })
+    } else {
+      None
+    }
+  };
+
+  let ask = {
+    if balance.a > 0.0 {
+      Some(Order {
+        agent_id,
+        typ: OrderType::Ask,
+        price_per_a_in_b: price,
+      })
+    } else {
+      None
+    }
+  };
+
+  (bid, ask)
+}
+
+pub fn all_orders(assets: &[(Agent, Balance)]) -> Vec<(Option<Order>, Option<Order>)> {
+  assets.iter().enumerate()
+    .map(|(id, (agent, balance))| generate_orders(id, agent, balance))
+    .collect()
+}
+
+// Highest bid price and lowest ask price among truthful quotes, whether or not they cross.
+pub fn best_quotes(assets: &[(Agent, Balance)]) -> (Option<Price>, Option<Price>) {
+  let orders = all_orders(assets);
+  let highest_bid = orders.iter().filter_map(|(bid, _)| bid.map(|o| o.price_per_a_in_b)).fold(None, |m: Option<f64>, p| Some(m.map_or(p, |m| m.max(p))));
+  let lowest_ask = orders.iter().filter_map(|(_, ask)| ask.map(|o| o.price_per_a_in_b)).fold(None, |m: Option<f64>, p| Some(m.map_or(p, |m| m.min(p))));
+  (highest_bid, lowest_ask)
+}
+
+// Matches the highest bid with the lowest ask below it, except that a crossing order
+// from an agent in `priority` goes ahead of any better-priced one. With regions each
+// has its own book, and a dark pool is matched apart from the lit book; see regions
+// and dark_pool.
+pub fn find_next_trade(assets : &[(Agent, Balance)], book: &book::OrderBook, pricing: pricing::PricingRule, priority: &[AgentId], now: clock::Tick) -> Option<Trade> {
+  if let Some(regions) = pricing.regions {
+    return regions::find_next_trade(assets, book, pricing, regions, now);
+  }
+  if let Some(pool) = pricing.dark_pool {
+    return dark_pool::find_next_trade(assets, book, pricing, pool, now);
+  }
+  let by_price = |o1: &Order, o2: &Order| o1.price_per_a_in_b.partial_cmp(&o2.price_per_a_in_b).unwrap();
+  // the privileged agents' orders, in agent order as the book breaks ties
+  let mut privileged = priority.to_vec();
+  privileged.sort_unstable();
+  privileged.dedup();
+  let lowest_ask = book.lowest_ask(pricing, None);
+  let highest_bid = privileged.iter()
+    .filter_map(|&id| book.quotes()[id].0)
+    .filter(|o| pricing.admits_bid(o.price_per_a_in_b) && lowest_ask.is_some_and(|ask| ask.price_per_a_in_b < pricing.net_bid(o.price_per_a_in_b)))
+    .max_by(by_price)
+    .or_else(|| book.highest_bid(pricing));
+  let below = highest_bid.map(|o| pricing.net_bid(o.price_per_a_in_b));
+  let lowest_acceptable_ask = privileged.iter()
+    .filter_map(|&id| book.quotes()[id].1)
+    .filter(|o| pricing.admits_ask(o.price_per_a_in_b) && below.is_none_or(|bid| o.price_per_a_in_b < bid))
+    .min_by(by_price)
+    .or_else(|| book.lowest_ask(pricing, below));
+
+  match (highest_bid, lowest_acceptable_ask) {
+    (Some(bid), Some(ask)) => Some(timing::time(timing::Phase::Clearing, || fill(assets, bid, ask, pricing, now))),
+    _ => None,
+  }
+}
+
+// The trade between a crossing `bid` and `ask`, at the pricing rule's price.
+pub fn fill(assets: &[(Agent, Balance)], bid: Order, ask: Order, pricing: pricing::PricingRule, now: clock::Tick) -> Trade {
+  trace!("matching bid {:?} against ask {:?}", bid, ask);
+  let (buyer, buyer_balance) = &assets[bid.agent_id];
+  let (seller, seller_balance) = &assets[ask.agent_id];
+  trace!("  (balances: bidder {:?}, seller {:?})", buyer_balance, seller_balance);
+  // what the seller gets and the buyer pays for each A, taxes and subsidies included,
+  // either side of the price they trade at; a seller's subsidy never takes it below 0
+  let (buyer_subsidy, seller_subsidy) = pricing.subsidies();
+  let received = pricing.price(pricing.net_bid(bid.price_per_a_in_b), ask.price_per_a_in_b.max(seller_subsidy));
+  let clearing_price = received - seller_subsidy;
+  let paid = pricing.gross(received);
+  let tax_on = |amount_a: f64| pricing.tax.map_or(0.0, |tax| tax.on(amount_a, clearing_price));
+  // all the buyer can afford and all the seller has, unless a side's utility isn't
+  // linear, when it trades only as far as its rate meets the price
+  let amount_a_buyer_wants = if buyer.utility_fn.is_linear() {
+    buyer_balance.b / paid
+  } else {
+    buyer.demand_for_a(buyer_balance, paid).max(0.0)
+  };
+  let amount_a_seller_offers = if seller.utility_fn.is_linear() {
+    seller_balance.a
+  } else {
+    (-seller.demand_for_a(seller_balance, received)).max(0.0)
+  };
+  let (amount_a, amount_b) = if amount_a_buyer_wants < amount_a_seller_offers {
+    // spending all its B, the buyer pays exactly that (less the tax, plus its subsidy)
+    // rather than a rounded product
+    let all_b = amount_a_buyer_wants == buyer_balance.b / paid;
+    (amount_a_buyer_wants, if all_b { buyer_balance.b - tax_on(amount_a_buyer_wants) + buyer_subsidy * amount_a_buyer_wants } else { clearing_price * amount_a_buyer_wants })
+  } else {
+    (amount_a_seller_offers, clearing_price * amount_a_seller_offers)
+  };
+  // rounded, a side whose utility isn't linear still trades only as far as its rate
+  // meets what it then pays (gets) for each A
+  let want = |a: f64, b: f64| {
+    (buyer.utility_fn.is_linear() || a <= buyer.demand_for_a(buyer_balance, (b + tax_on(a) - buyer_subsidy * a) / a))
+      && (seller.utility_fn.is_linear() || a <= -seller.demand_for_a(seller_balance, (b + seller_subsidy * a) / a))
+  };
+  // possibly (0, 0), which the risk rules reject
+  let (amount_a, amount_b) = match pricing.lots {
+    Some(lots) => lots.fill(amount_a, clearing_price, bid.price_per_a_in_b, ask.price_per_a_in_b, buyer_balance.b, want),
+    None => (amount_a, amount_b),
+  };
+  Trade {
+    tick: now,
+    buyer: bid.agent_id,
+    seller: ask.agent_id,
+    amount_a,
+    amount_b,
+    bid_price: bid.price_per_a_in_b,
+    ask_price: ask.price_per_a_in_b,
+    tax: tax_on(amount_a),
+    buyer_subsidy: buyer_subsidy * amount_a,
+    seller_subsidy: seller_subsidy * amount_a,
+    ..Trade::default()
+  }
+}
+
+pub fn execute_one_trade(assets: &mut [(Agent, Balance)], book: &book::OrderBook, pricing: pricing::PricingRule, risk: &risk::RiskRules, priority: &[AgentId], now: clock::Tick, on_reject: impl FnMut(risk::Rejection)) -> Option<Trade> /* None when done */ {
+  trace!("in execute_one_trade");
+  match risk::find_allowed_trade(assets, book, pricing, risk, priority, now, on_reject) {
+    None => {
+      trace!("no more trades are possible");
+      None
+    }
+    Some(trade) => {
+      execute(assets, &trade);
+      Some(trade)
+    }
+  }
+}
+
+// Settles `trade`, checking that neither side regrets it.
+pub fn execute(assets: &mut [(Agent, Balance)], trade: &Trade) {
+  trace!("executing {:?}", trade);
+  let ((buyer, buyer_before), (seller, seller_before)) = (assets[trade.buyer], assets[trade.seller]);
+  timing::time(timing::Phase::Settlement, || settle(assets, trade));
+  // Only a bid above the buyer's indifference price (an ask below the seller's) can
+  // lose its side utility, and only an order-entry error quotes one.
+  timing::time(timing::Phase::InvariantChecks, || {
+    if trade.bid_price <= buyer.indifference_price_at(&buyer_before) {
+      assert!(buyer.gains(&buyer_before, trade.amount_a, -trade.amount_b - trade.tax + trade.buyer_subsidy), "buyer's remorse");
+    }
+    if trade.ask_price >= seller.indifference_price_at(&seller_before) {
+      assert!(seller.gains(&seller_before, -trade.amount_a, trade.amount_b + trade.seller_subsidy), "seller's remorse");
+    }
+  });
+}
+
+// Moves the traded goods between the two parties' balances, the buyer's tax out of
+// the economy and any subsidy in, and with credit the buyer's loan in and the
+// seller's repayment out, and with short selling the same for the seller's loan of A
+// and the buyer's return. The tax comes off and the loans and the buyer's subsidy go
+// on first, so a buyer spending all its B (a seller delivering all its A) ends at 0.
+pub fn settle(assets: &mut [(Agent, Balance)], trade: &Trade) {
+  assets[trade.buyer] .1.b -= trade.tax;
+  assets[trade.buyer] .1.b += trade.buyer_subsidy;
+  assets[trade.buyer] .1.b += trade.borrowed;
+  assets[trade.seller].1.b += trade.seller_subsidy;
+  assets[trade.seller].1.a += trade.shorted;
+  assets[trade.buyer] .1.a += trade.amount_a;
+  assets[trade.buyer] .1.a -= trade.covered;  if assets[trade.buyer] .1.a < 0.0 {panic!("oh no")}
+  assets[trade.seller].1.a -= trade.amount_a; if assets[trade.seller].1.a < 0.0 {panic!("oh no")}
+  assets[trade.buyer] .1.b -= trade.amount_b; if assets[trade.buyer] .1.b < 0.0 {panic!("oh no")}
+  assets[trade.seller].1.b += trade.amount_b;
+  assets[trade.seller].1.b -= trade.repaid;  if assets[trade.seller].1.b < 0.0 {panic!("oh no")}
+}
+
+// Trades until no more trades are possible or a stopping rule fires, one matching
+// pass per tick, handing each quote change, rejection, trade, and finally the stop to
+// `on_event` as soon as it happens, and returning them all (but the quotes) at the
+// end. Agents requote as `arrivals` allows; the market is exhausted once nothing
+// crosses even with everyone requoted, or every cross is rejected by the risk rules.
+pub fn execute_all_trades(assets: &mut [(Agent, Balance)], strategies: &mut strategy::Strategies, arrivals: arrivals::Arrivals, pricing: pricing::PricingRule, risk: &risk::RiskRules, stopping: &stopping::StoppingRules, mut on_event: impl FnMut(&runlog::Event)) -> outcome::SimulationOutcome {
+  strategies.price(assets);
+  let mut market = market::Market::new(assets.to_vec(), arrivals, pricing, *risk, stopping.clone());
+  market.run(strategies, &mut on_event);
+  let outcome = market.into_outcome();
+  assets.copy_from_slice(&outcome.final_assets);
+  outcome
+}
+
+// The best trade allowed in any of `books` at `now`, each matched as find_next_trade
+// matches A in B, with the pricing and risk rules, on the quotes of its own
+// strategies (`strategies[k]` quotes in `books[k]`, since reservations differ by
+// book). Of the books' best trades, the one whose bid is furthest over its ask.
+pub fn find_next_goods_trade(assets: &[(Preferences, Bundle)], books: &[(Good, Good)], strategies: &mut [strategy::Strategies], pricing: pricing::PricingRule, risk: &risk::RiskRules, now: clock::Tick) -> Option<GoodsTrade> {
+  let mut best: Option<GoodsTrade> = None;
+  for (&book, strategies) in books.iter().zip(strategies.iter_mut()) {
+    let pair = in_book(assets, book);
+    let quotes = strategies.orders(&pair, now);
+    let book_quotes = book::OrderBook::from_quotes(&strategies.submit(&quotes, now));
+    let trade = match risk::find_allowed_trade(&pair, &book_quotes, pricing, risk, strategies.priority(), now, |_| {}) {
+      Some(trade) => trade,
+      None => continue,
+    };
+    if best.as_ref().is_none_or(|b| trade.bid_price / trade.ask_price > b.trade.bid_price / b.trade.ask_price) {
+      best = Some(GoodsTrade { book, trade });
+    }
+  }
+  best
+}
+
+// Settles `trade` in its book, checking as execute does that neither side regrets it.
+pub fn execute_goods_trade(assets: &mut [(Preferences, Bundle)], trade: &GoodsTrade) {
+  let mut pair = in_book(assets, trade.book);
+  execute(&mut pair, &trade.trade);
+  for id in [trade.trade.buyer, trade.trade.seller] {
+    let (base, quote) = trade.book;
+    assets[id].1 .0[base] = pair[id].1.a;
+    assets[id].1 .0[quote] = pair[id].1.b;
+  }
+}
+
+// Trades until nothing is allowed in any book and no report is awaited, one trade a
+// tick, or until the signal, trade limit or tick limit of `stopping`. Each book's
+// strategies see the tape of that book's trades.
+pub fn execute_all_goods_trades(assets: &mut [(Preferences, Bundle)], books: &[(Good, Good)], strategies: &mut [strategy::Strategies], pricing: pricing::PricingRule, risk: &risk::RiskRules, stopping: &stopping::StoppingRules) -> (Vec<GoodsTrade>, stopping::StopReason) {
+  use stopping::StopReason;
+  let mut trades: Vec<GoodsTrade> = vec![];
+  let mut tapes: Vec<Vec<Trade>> = vec![vec![]; books.len()];
+  let mut now = 0;
+  loop {
+    if stopping.signal.as_ref().is_some_and(|s| s.load(Ordering::Relaxed)) {
+      return (trades, StopReason::Signal);
+    } else if stopping.max_trades.is_some_and(|n| trades.len() >= n) {
+      return (trades, StopReason::MaxTrades);
+    } else if stopping.max_ticks.is_some_and(|n| now >= n) {
+      return (trades, StopReason::MaxTicks);
+    }
+    for (strategies, tape) in strategies.iter_mut().zip(&tapes) {
+      strategies.observe(tape, now);
+    }
+    match find_next_goods_trade(assets, books, strategies, pricing, risk, now) {
+      Some(trade) => {
+        execute_goods_trade(assets, &trade);
+        tapes[books.iter().position(|&b| b == trade.book).unwrap()].push(trade.trade.clone());
+        trades.push(trade);
+      }
+      None if !strategies.iter().any(|s| s.awaiting_reports()) => return (trades, StopReason::Exhausted),
+      None => {}
+    }
+    now += 1;
+  }
+}
+
+// Cancels whichever sides of an agent's resting quote its balance can no longer back.
+// Quotes carry no size (fills are sized from the balance at match time), so a
+// partially filled quote needs no resizing, only withdrawal once a side runs dry.
+// With lots, a side runs dry below one lot of A or one tick of B.
+pub fn withdraw_unbacked(quote: &mut (Option<Order>, Option<Order>), balance: &Balance, lots: Option<lots::Lots>) {
+  let (lot, tick) = lots.map_or((0.0, 0.0), |l| (l.a, l.b));
+  if balance.b == 0.0 || balance.b < tick { quote.0 = None; }
+  if balance.a == 0.0 || balance.a < lot { quote.1 = None; }
+}
+
+// Change in each agent's utility between two snapshots of the same population.
+pub fn realized_surplus(initial: &[(Agent, Balance)], last: &[(Agent, Balance)]) -> Vec<f64> {
+  initial.iter().zip(last.iter())
+    .map(|((agent, before), (_, after))| agent.utility(after.a, after.b) - agent.utility(before.a, before.b))
+    .collect()
+}
+
+pub fn sanity_check_endpoint(assets: &[(Agent, Balance)]) {
+  let mut local = assets.to_vec();
+  local.sort_by(|(agent_1,_), (agent_2, _)| {
+    agent_1.indifference_price_of_a_in_b().partial_cmp(
+      &agent_2.indifference_price_of_a_in_b()
+    ).unwrap()
+  });
+
+  let remainder = local.iter()
+    .skip_while(|(_, balance)| {    balance.a == 0.0  })
+    .skip_while(|(_, balance)| {    balance.a > 0.0 && balance.b > 0.0  })
+    .skip_while(|(_, balance)| {    balance.b == 0.0  })
+    .collect::<Vec<_>>();
+  // println!("Agents:");
+  // for (agent, balance) in local.iter() {
+  //   println!("  ({}, {}, {}), {:?}", agent.indifference_price_of_a_in_b(), balance.a, balance.b, agent);
+  // }
+  // println!("Remainder:");
+  // for (agent, balance) in remainder.iter() {
+  //   println!("  ({}, {}, {}), {:?}", agent.indifference_price_of_a_in_b(), balance.a, balance.b, agent);
+  // }
+  assert!(remainder.is_empty(), "{:?} ({} elems)", remainder, remainder.len());
+}
+
+pub type Price = f64;
+
+// (supply, demand) of A at `price`. A linear agent supplies all its A at or above its
+// reservation and spends all its B at or below it; any other supplies or demands
+// what its demand says, from its holdings.
+pub fn supply_demand_at(assets: &[(Agent, Balance)], price: Price) -> (f64, f64) {
+  assets.iter().fold((0.0, 0.0), |(supply, demand), (agent, balance)| {
+    if !agent.utility_fn.is_linear() {
+      let q = agent.demand_for_a(balance, price);
+      return (supply + (-q).max(0.0), demand + q.max(0.0));
+    }
+    let reservation = agent.indifference_price_of_a_in_b();
+    (
+      supply + if reservation <= price { balance.a } else { 0.0 },
+      demand + if reservation >= price { balance.b / price } else { 0.0 },
+    )
+  })
+}
+
+// Sampled either side of every agent's rate at its holdings: where a linear agent's
+// step is, and where any other's demand changes sign.
+pub fn supply_demand_curves(assets: &[(Agent, Balance)]) -> Vec<(Price, f64, f64)> {
+  let mut interesting_prices: Vec<f64> = assets.iter()
+    .map(|(agent, balance)| agent.indifference_price_at(balance))
+    .filter(|r| r.is_finite() && *r > 0.0)
+    .collect();
+  interesting_prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+  // agents sharing a price would otherwise interleave their sample points out of order
+  interesting_prices.dedup();
+
+  let mut result = vec![];
+  for discontinuity_price in interesting_prices {
+    let eps = 2_f64.powf(-30.0);
+    for price in [discontinuity_price*(1.0-eps), discontinuity_price*(1.0+eps)] {
+      let (supply, demand) = supply_demand_at(assets, price);
+      result.push((price, supply, demand));
+    }
+  }
+
+  // sanity check; only linear curves are sure to be monotone, since a non-linear
+  // agent's supply can bend backwards
+  if assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()) {
+    for i in 1..result.len() {
+      assert!(result[i].1 >= result[i-1].1, "{:?} -> {:?}", result[i-1], result[i]);
+      assert!(result[i].2 <= result[i-1].2, "{:?} -> {:?}", result[i-1], result[i]);
+    }
+  }
+
+  result
+}
@@ -0,0 +1,90 @@
+// Price floors and caps: how orders on the wrong side of one are treated, and the
+// shortage or surplus a binding one leaves. By default a limit clamps the clearing
+// price, and only orders that can trade at the clamped price take part (bids above a
+// floor, asks below a cap). Rejecting instead turns away every order priced outside
+// the band, so sellers can't undercut a floor nor buyers outbid a cap at all. Either
+// way, once the run is over the curves at the limit show what it holds back: at a
+// floor, A its holders would sell there that nobody will buy (a surplus); at a cap,
+// A wanted there that nobody will sell (a shortage).
+
+use serde::{Deserialize, Serialize};
+
+use crate::pricing::PricingRule;
+use crate::thesis::Limit;
+use crate::{supply_demand_at, Agent, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Enforcement {
+  #[default]
+  Clamp,
+  Reject,
+}
+
+impl Enforcement {
+  pub fn parse(s: &str) -> Result<Enforcement, String> {
+    match s {
+      "clamp" => Ok(Enforcement::Clamp),
+      "reject" => Ok(Enforcement::Reject),
+      _ => Err(format!("unknown limit enforcement {:?} (expected clamp or reject)", s)),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Imbalance {
+  pub limit: Limit,
+  pub price: Price,
+  // of A at the limit
+  pub supply: f64,
+  pub demand: f64,
+}
+
+impl Imbalance {
+  // The surplus at a floor or the shortage at a cap, 0 if the limit doesn't bind.
+  pub fn excess(&self) -> f64 {
+    match self.limit {
+      Limit::Floor => (self.supply - self.demand).max(0.0),
+      Limit::Cap => (self.demand - self.supply).max(0.0),
+    }
+  }
+}
+
+pub fn imbalances(assets: &[(Agent, Balance)], pricing: PricingRule) -> Vec<Imbalance> {
+  [(Limit::Floor, pricing.floor), (Limit::Cap, pricing.cap)].iter()
+    .filter_map(|&(limit, price)| price.map(|price| {
+      let (supply, demand) = supply_demand_at(assets, price);
+      Imbalance { limit, price, supply, demand }
+    }))
+    .collect()
+}
+
+pub fn print_report(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], pricing: PricingRule) {
+  for (start, end) in imbalances(initial, pricing).iter().zip(imbalances(last, pricing)) {
+    let kind = if end.limit == Limit::Floor { "surplus" } else { "shortage" };
+    println!("{:?} at {} ({:?}): {} of {} A at the start, {} A left at the end ({} A supplied, {} demanded)",
+      end.limit, end.price, pricing.enforcement, kind, start.excess(), end.excess(), end.supply, end.demand);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::limits::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_enforcement() {
+    let clamp = PricingRule::default().with_limits(Some(2.0), Some(4.0));
+    let reject = clamp.with_enforcement(Enforcement::Reject);
+    // an ask under the floor trades at the floor when clamped, and not at all rejected
+    assert!(clamp.admits_ask(1.0) && !reject.admits_ask(1.0));
+    assert!(clamp.admits_bid(5.0) && !reject.admits_bid(5.0));
+    assert!(reject.admits_bid(3.0) && reject.admits_ask(3.0));
+
+    let assets = vec![(linear_agent(1.0), Balance { a: 10.0, b: 0.0 }), (linear_agent(3.0), Balance { a: 0.0, b: 8.0 })];
+    // at the floor of 2 the seller offers 10 A and the buyer takes 4; at the cap of 4
+    // the buyer has dropped out
+    let found = imbalances(&assets, clamp);
+    assert_eq!((found[0].excess(), found[1].excess()), (6.0, 0.0));
+  }
+}
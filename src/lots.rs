@@ -0,0 +1,121 @@
+// Settlement in whole units: A changes hands in lots and B in ticks, with the
+// direction B is rounded in chosen explicitly. Each good's rounded amount is debited
+// from one side and credited to the other as the same number, so nothing is created
+// or destroyed, and the ledger check at the end of a run confirms it. Rounding never
+// pushes a trade past either side's quote or budget, or past where its rate meets the
+// rounded price: if it would, the fill shrinks a lot at a time, and a fill that rounds
+// to nothing isn't made.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Agent, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+  // the buyer's payment rounds up, in the seller's favor
+  Up,
+  Down,
+  Nearest,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Lots {
+  pub a: f64,
+  pub b: f64,
+  pub rounding: Rounding,
+}
+
+impl Lots {
+  // `<lot of A>:<tick of B>` or `<lot>:<tick>:<up|down|nearest>`, e.g. `0.1:0.01:up`
+  pub fn parse(s: &str) -> Result<Lots, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let rounding = match parts.get(2).copied() {
+      None | Some("nearest") => Rounding::Nearest,
+      Some("up") => Rounding::Up,
+      Some("down") => Rounding::Down,
+      Some(other) => return Err(format!("unknown rounding {:?} (expected up, down or nearest)", other)),
+    };
+    match (parts.len(), parts[0].parse(), parts.get(1).map(|b| b.parse())) {
+      (2 | 3, Ok(a), Some(Ok(b))) => Ok(Lots { a, b, rounding }),
+      _ => Err(format!("bad lots {:?} (expected <lot>:<tick>[:<rounding>])", s)),
+    }
+  }
+
+  fn round_b(&self, amount_b: f64) -> f64 {
+    let ticks = amount_b / self.b;
+    let ticks = match self.rounding {
+      Rounding::Up => ticks.ceil(),
+      Rounding::Down => ticks.floor(),
+      Rounding::Nearest => ticks.round(),
+    };
+    ticks * self.b
+  }
+
+  // The largest whole-lot fill of up to `amount_a` at `price` whose rounded payment
+  // stays within the buyer's `budget` and between `ask` and `bid`, and that both sides
+  // still `want` at that payment; (0, 0) if none.
+  pub fn fill(&self, amount_a: f64, price: Price, bid: Price, ask: Price, budget: f64, want: impl Fn(f64, f64) -> bool) -> (f64, f64) {
+    let mut lots = (amount_a / self.a).floor();
+    while lots > 0.0 {
+      let a = (lots * self.a).min(amount_a);
+      let b = self.round_b(a * price);
+      if b <= budget && b > a * ask && b < a * bid && want(a, b) {
+        return (a, b);
+      }
+      lots -= 1.0;
+    }
+    (0.0, 0.0)
+  }
+}
+
+// Everyone's holdings added up.
+pub fn totals(assets: &[(Agent, Balance)]) -> Balance {
+  assets.iter().fold(Balance { a: 0.0, b: 0.0 }, |total, (_, balance)| Balance { a: total.a + balance.a, b: total.b + balance.b })
+}
+
+// Panics unless each good's total is unchanged, up to floating-point error.
+pub fn check_conservation(before: Balance, after: Balance) {
+  let close = |x: f64, y: f64| (x - y).abs() <= 1e-9 * x.abs().max(y.abs());
+  assert!(close(before.a, after.a) && close(before.b, after.b), "ledger doesn't balance: {:?} before, {:?} after", before, after);
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::Config;
+  use crate::continuous::Matching;
+  use crate::lots::*;
+  use crate::pricing::PricingRule;
+  use crate::utility::UtilityFn;
+
+  #[test]
+  fn test_fill() {
+    let lots = Lots::parse("0.5:0.1:up").unwrap();
+    assert_eq!(Lots::parse("1:1").unwrap().rounding, Rounding::Nearest);
+    assert!(Lots::parse("1:1:sideways").is_err() && Lots::parse("1").is_err());
+    // 1.7 A rounds down to 1.5; 1.5 * 1.01 = 1.515 B rounds up to 1.6
+    let (a, b) = lots.fill(1.7, 1.01, 2.0, 0.5, 10.0, |_, _| true);
+    assert_eq!(a, 1.5);
+    assert!((b - 1.6).abs() < 1e-12);
+    // a budget of 1.55 B can't cover 1.6, so a lot comes off
+    assert_eq!(lots.fill(1.7, 1.01, 2.0, 0.5, 1.55, |_, _| true).0, 1.0);
+    // rounding 0.5 * 1.01 up to 0.6 B would pay more than the 1.1 bid
+    assert_eq!(lots.fill(0.7, 1.01, 1.1, 0.5, 10.0, |_, _| true), (0.0, 0.0));
+    assert_eq!(lots.fill(0.4, 1.01, 2.0, 0.5, 10.0, |_, _| true), (0.0, 0.0));
+    // a side wanting no more than 1.2 A at the rounded price gets a lot less
+    assert_eq!(lots.fill(1.7, 1.01, 2.0, 0.5, 10.0, |a, _| a <= 1.2).0, 1.0);
+  }
+
+  #[test]
+  fn test_fill_past_rate() {
+    // rounding 1 A's payment up to 0.8 B would take these Cobb-Douglas buyers past
+    // where their rate meets the price paid, and trading would leave them worse off
+    let pricing = PricingRule::default().with_lots(Some(Lots::parse("1:0.1").unwrap()));
+    let config = Config { n_agents: 80, utility: UtilityFn::CobbDouglas, pricing, ..Config::default() };
+    let continuous = Config { pricing: pricing.with_matching(Matching::Continuous), ..config.clone() };
+    for (config, seed) in [(config, 1), (continuous, 0)] {
+      let log = crate::simulate(&config, seed);
+      assert!(!log.trades.is_empty() && log.trades.iter().all(|t| t.amount_a % 1.0 == 0.0));
+    }
+  }
+}
@@ -11,354 +11,585 @@ Try imposing a price floor (or cap) and check whether what we get is readily rel
 Try the above for both binding and non-binding floor (cap).
 */
 
-use rand::Rng;
 use rand::rngs::StdRng;
-use rand::distributions::{Distribution, Uniform};
-use std::collections::HashMap;
-use std::cmp::{min, max};
 use rand::SeedableRng;
+use std::sync::atomic::Ordering;
+
+use simmarket::*;
+use simmarket::cli::{flag_parsed, flag_value, flag_values, flag_with, n_periods, Command};
 
 fn main() {
   let args: Vec<String> = std::env::args().collect();
-  let seed: u64 = args[1].parse::<u64>().unwrap();
-
-  let mut rng: StdRng = StdRng::seed_from_u64(seed);
-
-  let mut agents = Vec::new();
-
-  println!("setting up agent pool");
-  for i in 0..1000 {
-    agents.push(Agent::new_random(&mut rng));
-  }
-
-  let mut assets = Vec::new();
-  for agent in agents {
-    let a = agent.production_a;
-    let b = agent.production_b;
-    assets.push(
-      (
-        agent,
-        Balance{
-          a: a,
-          b: b,
-        }
-      )
-    );
+  interrupt::install();
+  if let Err(e) = Command::parse(&args).and_then(|command| dispatch(command, &args)) {
+    eprintln!("error: {}", e);
+    eprintln!("see `simmarket help` for usage");
+    std::process::exit(2);
   }
-
-  for (price, supply, demand) in supply_demand_curves(&assets) {
-    println!(r#"[ {}, {{ "supply":{}, "demand":{} }}]"#, price, supply, demand);
+  if interrupt::interrupted() {
+    eprintln!("interrupted");
+    std::process::exit(130);
   }
-  execute_all_trades(&mut assets);
-
-  println!("done with main");
 }
 
-#[derive(PartialEq, Debug, Copy, Clone)]
-struct Agent {
-    // Production ability per time unit of each commodity
-    production_a: f64,
-    production_b: f64,
-
-    consumption_a_coeff: f64,
-    consumption_b_coeff: f64,
+// An io::Error as a message saying what it was doing to which file.
+fn io_err<'a>(action: &'a str, path: &'a str) -> impl Fn(std::io::Error) -> String + 'a {
+  move |e| format!("{} {}: {}", action, path, e)
 }
 
-impl Agent {
-  fn utility(&self, consumption_a: f64, consumption_b: f64) -> f64 {
-    return self.consumption_a_coeff*consumption_a + self.consumption_b_coeff*consumption_b
-  }
+fn seed_flag(args: &[String]) -> Result<u64, String> {
+  flag_parsed(args, "--seed").map(|seed| seed.unwrap_or(0))
+}
 
-  fn indifference_price_of_a_in_b(&self) -> f64 {
-    return self.consumption_a_coeff / self.consumption_b_coeff;
+fn jobs_flag(args: &[String]) -> Result<usize, String> {
+  match flag_parsed(args, "--jobs")? {
+    Some(0) => Err("--jobs must be at least 1".to_string()),
+    jobs => Ok(jobs.unwrap_or_else(parallel::default_jobs)),
   }
+}
 
-  fn new_random(rng: &mut StdRng) -> Agent {
-    let prod_dist = Uniform::new(0.0,1000.0);
-    let coeff_dist = Uniform::new(0.0,1.0);
-    
-    return Agent {
-      production_a: prod_dist.sample(rng),
-      production_b: prod_dist.sample(rng),
-
-      consumption_a_coeff: coeff_dist.sample(rng),
-      consumption_b_coeff: coeff_dist.sample(rng),
+fn dispatch(command: Command, args: &[String]) -> Result<(), String> {
+  match command {
+    Command::Help => println!("{}", cli::USAGE),
+    Command::Run { seeds } => run(args, &seeds)?,
+    Command::Sweep { path, values, seeds } => sweep(args, &path, &values, &seeds)?,
+    Command::Batch { seeds } => {
+      // only the aggregates, over seeds run --jobs at a time
+      let config = config::Config::from_args(args)?;
+      let jobs = jobs_flag(args)?;
+      QUIET.store(true, Ordering::Relaxed);
+      let summaries = batch(&config, &seeds, jobs);
+      sampling::print_batch(&summaries, config.sampling);
+      if let Some(out) = flag_value(args, "-o") {
+        let rows: Vec<(&str, summary::Summary)> = summaries.into_iter().map(|s| ("", s)).collect();
+        write_summaries(args, out, None, &rows)?;
+      }
+    }
+    Command::Plot { seed } => {
+      let out = flag_value(args, "-o").unwrap_or("plot.html");
+      let config = config::Config::from_args(args)?;
+      QUIET.store(true, Ordering::Relaxed);
+      std::fs::write(out, report::html(&simulate(&config, seed), config.numeraire)).map_err(io_err("writing", out))?;
+      println!("wrote {}", out);
+    }
+    Command::Report { log } => {
+      let log = runlog::read(&log).map_err(io_err("reading", &log))?;
+      let out = flag_value(args, "-o").unwrap_or("report.html");
+      let numeraire = flag_with(args, "--numeraire", numeraire::Numeraire::parse)?.unwrap_or(numeraire::Numeraire::B);
+      std::fs::write(out, report::html(&log, numeraire)).map_err(io_err("writing", out))?;
+      // the book at chosen ticks, rebuilt from the log, e.g. --depth-at 0,50,100 --top-k 3
+      if let Some(mut ticks) = flag_with(args, "--depth-at", |ts| ts.split(',').map(|t| t.parse().map_err(|_| format!("can't read tick {:?}", t))).collect::<Result<Vec<_>, _>>())? {
+        ticks.sort_unstable();
+        print!("{}", depth::csv(&depth::at_ticks(&log, &ticks, flag_parsed(args, "--top-k")?.unwrap_or(5))));
+      }
+    }
+    Command::Anonymize { log } => {
+      let log = runlog::read(&log).map_err(io_err("reading", &log))?;
+      let out = flag_value(args, "-o").unwrap_or("shared.ndjson");
+      let anonymizer = anonymize::Anonymizer::new(flag_parsed(args, "--jitter")?.unwrap_or(0.1))?;
+      let salt = flag_parsed(args, "--salt")?.unwrap_or_else(rand::random);
+      runlog::write(out, &anonymizer.apply(&log, &mut StdRng::seed_from_u64(salt))).map_err(io_err("writing", out))?;
+      println!("wrote {}", out);
+    }
+    Command::Query { query } => {
+      let query = query::Query::parse(&query)?;
+      let archive = flag_value(args, "--archive").unwrap_or(".");
+      let rows = query::rows(archive).map_err(io_err("reading", archive))?;
+      let (columns, table) = query.run(&rows)?;
+      print!("{}", if args.iter().any(|a| a == "--csv") { query::csv(&columns, &table) } else { query::table(&columns, &table) });
+    }
+    Command::FindSeeds => {
+      let seeds = cli::parse_seeds(flag_value(args, "--seeds").unwrap_or("0..100"))?;
+      let predicates = flag_values(args, "--where").iter()
+        .map(|p| seeds::Predicate::parse(p).map_err(|e| format!("--where: {}", e)))
+        .collect::<Result<Vec<_>, String>>()?;
+      let limit = flag_parsed(args, "--limit")?;
+      let config = config::Config::from_args(args)?;
+      QUIET.store(true, Ordering::Relaxed);
+      seeds::find_seeds(&seeds, &predicates, limit, |seed| summary::Summary::reported(&simulate(&config, seed), &config)).map_err(|e| format!("--where: {}", e))?;
+    }
+    Command::Watch => watch(args, simulation::SimulationBuilder::from_config(config::Config::from_args(args)?).seed(seed_flag(args)?).build()?)?,
+    Command::Resume { checkpoint } => {
+      let checkpoint = checkpoint::load_checkpoint(&checkpoint).map_err(io_err("reading", &checkpoint))?;
+      let overrides = flag_values(args, "--set");
+      let config = if overrides.is_empty() { checkpoint.config.clone() } else { checkpoint.config.with_overrides(&overrides).map_err(|e| format!("--set: {}", e))? };
+      watch(args, simulation::Simulation::from_checkpoint(checkpoint, config))?
+    }
+    Command::GainsMatrix => {
+      // every pair's potential surplus against who actually traded, for small populations
+      let seed = seed_flag(args)?;
+      let config = config::Config::from_args(args)?;
+      if config.n_agents > bilateral::MAX_AGENTS {
+        return Err(format!("the pairwise matrix is for at most {} agents", bilateral::MAX_AGENTS));
+      }
+      let log = simulate(&config, seed);
+      bilateral::print_report(&log, config.pricing);
+      if let Some(out) = flag_value(args, "-o") {
+        let potential = bilateral::potential(&log.initial_assets, config.pricing);
+        std::fs::write(out, bilateral::csv(&potential, &bilateral::traded(config.n_agents, &log))).map_err(io_err("writing", out))?;
+      }
+    }
+    Command::Goods => {
+      // an economy of any number of goods, one book per pair, e.g. --goods a,b,c --shock c:1.5
+      let seed = seed_flag(args)?;
+      let goods = flag_with(args, "--goods", goods::Goods::parse)?.map_or_else(|| goods::Goods::parse("a,b,c"), Ok)?;
+      let config = config::Config::from_args(args)?;
+      let shock = flag_with(args, "--shock", |s| {
+        let (name, factor) = s.split_once(':').ok_or_else(|| format!("expected <good>:<factor>, got {:?}", s))?;
+        let shocked = goods.index(name).ok_or_else(|| format!("no good named {:?}", name))?;
+        factor.parse::<f64>().map(|factor| (shocked, factor)).map_err(|_| format!("can't read factor {:?}", factor))
+      })?;
+      goods::check(&config)?;
+      // each agent's money, to price every good in it rather than barter
+      let money: Option<f64> = flag_parsed(args, "--money")?;
+      let initial = goods::random_population(goods.len(), config.n_agents, config.utility, &mut StdRng::seed_from_u64(seed));
+      let mut assets = initial.clone();
+      let (trades, stop) = goods::run(&goods, &mut assets, &config, seed);
+      let barter = goods::GoodsRun { initial: &initial, last: &assets, trades: &trades, stop };
+      goods::print_report(&goods, &barter);
+      pairs::print_report(&goods.names, &pairs::goods_pair_stats(&goods, &assets, &trades));
+      walras::print_goods_report(&goods, &initial);
+      if let Some(path) = flag_value(args, "--log") {
+        goods::write_log(path, &goods, seed, &barter).map_err(io_err("writing", path))?;
+      }
+      if let Some((shocked, factor)) = shock {
+        goods::print_cross_price(&goods, shocked, factor, &goods::cross_price(&goods, &initial, shocked, factor, &config, seed));
+      }
+      if let Some(endowment) = money {
+        if !(endowment > 0.0 && endowment.is_finite()) {
+          return Err(format!("--money: need a positive endowment, got {}", endowment));
+        }
+        let monetary = goods.with_money()?;
+        let with_money = goods::monetize(&initial, endowment);
+        let mut assets = with_money.clone();
+        let (money_trades, stop) = goods::run(&monetary, &mut assets, &config, seed);
+        let monetary_run = goods::GoodsRun { initial: &with_money, last: &assets, trades: &money_trades, stop };
+        goods::print_report(&monetary, &monetary_run);
+        goods::print_monetary_comparison(&barter, &monetary_run, endowment);
+      }
+    }
+    Command::Thesis => {
+      let seed = seed_flag(args)?;
+      let out = flag_value(args, "-o").unwrap_or("thesis.html");
+      let config = config::Config::from_args(args)?;
+      let thesis = thesis::Thesis::run(&config, seed);
+      thesis.print();
+      std::fs::write(out, thesis.html()).map_err(io_err("writing", out))?;
+    }
+    Command::Transfers => {
+      let seed = seed_flag(args)?;
+      let shares = flag_with(args, "--transfers", |s| s.split(',').map(|v| v.parse::<f64>().map_err(|_| format!("can't read share {:?}", v))).collect())?
+        .unwrap_or_else(|| vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+      if let Some(share) = shares.iter().find(|s| !(0.0..=1.0).contains(*s)) {
+        return Err(format!("--transfers: each share must be in [0, 1], got {}", share));
+      }
+      let config = config::Config::from_args(args)?;
+      transfers::print_report(&transfers::run(&config, seed, &shares));
+    }
+    Command::Stress => {
+      let seed = seed_flag(args)?;
+      let scenarios = flag_with(args, "--scenarios", |s| s.split(',').map(stress::Scenario::parse).collect())?.unwrap_or_else(|| stress::ALL.to_vec());
+      let config = config::Config::from_args(args)?;
+      let (baseline, shocked) = stress::run(&config, seed, &scenarios);
+      stress::print_report(&baseline, &shocked);
+    }
+    Command::Recommend => {
+      let seeds = cli::parse_seeds(flag_value(args, "--seeds").unwrap_or("0..10"))?;
+      let targets = flag_values(args, "--target").iter()
+        .map(|p| seeds::Predicate::parse(p).map_err(|e| format!("--target: {}", e)))
+        .collect::<Result<Vec<_>, String>>()?;
+      let objective = flag_value(args, "--objective").unwrap_or("utility_final");
+      let known = summary::Summary::names();
+      if !known.contains(&objective) {
+        return Err(format!("--objective: no metric {:?}", objective));
+      }
+      if let Some(t) = targets.iter().find(|t| !known.contains(&t.metric.as_str())) {
+        return Err(format!("--target: no metric {:?}", t.metric));
+      }
+      let grid = |flag, default: Vec<f64>| flag_with(args, flag, |s| s.split(',').map(|v| v.parse::<f64>().map_err(|_| format!("can't read {:?}", v))).collect()).map(|g| g.unwrap_or(default));
+      let default = recommend::Space::default();
+      let space = recommend::Space { taxes: grid("--taxes", default.taxes)?, transfers: grid("--transfers", default.transfers)?, floors: grid("--floors", default.floors)? };
+      let config = config::Config::from_args(args)?;
+      let jobs = jobs_flag(args)?;
+      QUIET.store(true, Ordering::Relaxed);
+      let evaluations = recommend::search(&config, &seeds, jobs, &space, objective, &targets);
+      recommend::print_report(&evaluations, objective, &targets, seeds.len());
+    }
+    Command::Influence => {
+      let seed = seed_flag(args)?;
+      let top_k = flag_parsed(args, "--top-k")?.unwrap_or(5);
+      let config = config::Config::from_args(args)?;
+      let perturbation = config.perturbation.ok_or("influence needs a --perturb <id>.<param>=<value> or <id>.<param>*<factor>")?;
+      let (baseline, perturbed) = influence::run(&config, seed, perturbation);
+      influence::print_report(&baseline, &perturbed, perturbation, top_k);
     }
   }
+  Ok(())
 }
 
-mod tests {
-  use crate::*;
-
-  #[test]
-  fn test_indifference_price() {
-    let agent = Agent {
-      production_a: 10.0,
-      production_b: 10.0,
-      consumption_a_coeff: 1.0,
-      consumption_b_coeff: 5.0,
-    };
-    assert_eq!(agent.indifference_price_of_a_in_b(), 0.20);
-
-    let price_a_in_b = agent.indifference_price_of_a_in_b();
-    let amount_a_bought = 1.0;
-
-    let consumption_a = agent.production_a + amount_a_bought;
-    let consumption_b = agent.production_b - amount_a_bought*price_a_in_b;
-
-
-    assert_eq!(
-      agent.utility(agent.production_a, agent.production_b),
-      agent.utility(consumption_a, consumption_b),
-    );
+// The run driven one trade at a time, as an embedding event loop would.
+fn watch(args: &[String], mut simulation: simulation::Simulation) -> Result<(), String> {
+  let forecast = flag_with(args, "--forecast", forecast::Forecast::parse)?.unwrap_or_default();
+  while let Some(trade) = simulation.advance_trade().cloned() {
+    let progress = simulation.realized_share(forecast).map_or(String::new(), |s| format!(" ({:.1}% of gains realized)", 100.0 * s));
+    println!("tick {}: agent {} buys {} A from agent {} at {}{}", trade.tick, trade.buyer, trade.amount_a, trade.seller, trade.price_per_a_in_b(), progress);
   }
+  let stop = simulation.stop().unwrap();
+  println!("{} trades; stopped at tick {}: {:?}; final quotes {:?}", simulation.trades().len(), stop.tick, stop.reason, best_quotes(simulation.assets()));
+  if let Some(path) = flag_value(args, "--checkpoint") {
+    checkpoint::save_checkpoint(path, &simulation).map_err(io_err("writing", path))?;
+    println!("wrote {}", path);
+  }
+  Ok(())
+}
 
-  #[test]
-  fn test_find_next_trade() {
-    let mut assets = vec![
-      (
-        Agent {
-          production_a: 0.0,
-          production_b: 0.0,
-          consumption_a_coeff: 1.0,
-          consumption_b_coeff: 5.0,
-        },
-        Balance {
-          a: 1.0,
-          b: 2.0,
-        },
-      ),
-      (
-        Agent {
-          production_a: 0.0,
-          production_b: 0.0,
-          consumption_a_coeff: 8.0,
-          consumption_b_coeff: 1.0,
-        },
-        Balance {
-          a: 3.0,
-          b: 4.0,
-        },
-      ),
-    ];
-
-    assert_eq!(
-      find_next_trade(&assets).unwrap(),
-      Trade{
-        buyer: 1,
-        seller: 0,
-        amount_a: 0.5,
-        amount_b: 0.12195121951219513,
-      }
-    );
-
-    execute_one_trade(&mut assets);
-
-    assert_eq!(
-      find_next_trade(&assets).unwrap(),
-      Trade{
-        buyer: 1,
-        seller: 0,
-        amount_a: 0.5,
-        amount_b: 0.12195121951219513,
-      }
-    );
+fn run(args: &[String], seeds: &[u64]) -> Result<(), String> {
+  let config = config::Config::from_args(args)?;
+  let out_dir = flag_value(args, "--out-dir");
+  if seeds.len() == 1 {
+    let mut dir = out_dir.map(|base| outdir::RunDir::create_timestamped(base, &format!("seed{}", seeds[0])).map_err(io_err("creating a run directory in", base))).transpose()?;
+    run_seed(args, &config, seeds[0], dir.as_mut())?;
+    return Ok(());
   }
 
+  if ["--log", "--trade-stream", "--trades"].iter().any(|f| flag_value(args, f).is_some()) {
+    return Err("--log, --trade-stream and --trades take a single seed; use --out-dir for multi-seed runs".to_string());
+  }
+  let sweep_dir = out_dir.map(|base| outdir::RunDir::create_timestamped(base, "sweep").map_err(io_err("creating a run directory in", base))).transpose()?;
+  let mut cells = vec![];
+  let mut summaries = vec![];
+  for &seed in seeds {
+    let cell = format!("seed{}", seed);
+    let mut dir = sweep_dir.as_ref().map(|d| d.cell(&cell).map_err(|e| format!("creating {}: {}", cell, e))).transpose()?;
+    summaries.push(run_seed(args, &config, seed, dir.as_mut())?);
+    cells.push(cell);
+    if interrupt::interrupted() {
+      break;
+    }
+  }
+  sampling::print_batch(&summaries, config.sampling);
+  if let Some(dir) = sweep_dir {
+    dir.write_sweep_manifest(args, &config, &cells).map_err(|e| format!("writing the sweep manifest: {}", e))?;
+    println!("wrote {}", dir.path().display());
+  }
+  Ok(())
 }
 
-#[derive(PartialEq, Debug, Copy, Clone)]
-struct Balance {
-  a: f64,
-  b: f64,
+// The summaries of `config`'s runs over `seeds`, `jobs` at a time. After an interrupt,
+// only the runs already begun, cut short.
+fn batch(config: &config::Config, seeds: &[u64], jobs: usize) -> Vec<summary::Summary> {
+  let summaries = parallel::map(seeds, jobs, |&seed| (!interrupt::interrupted()).then(|| summary::Summary::reported(&simulate(config, seed), config)));
+  summaries.into_iter().flatten().collect()
 }
 
-type AgentId = usize;
-
-#[derive(Debug, PartialEq)]
-struct Trade {
-  buyer: AgentId,
-  seller: AgentId,
-
-  amount_a: f64, // transferred from seller to buyer
-  amount_b: f64, // transferred from buyer to seller
+// One batch over `seeds` per value of the config field at `path`, as --set would
+// assign it, printing only each batch's intervals.
+fn sweep(args: &[String], path: &str, values: &[String], seeds: &[u64]) -> Result<(), String> {
+  let base = config::Config::from_args(args)?;
+  let jobs = jobs_flag(args)?;
+  // e.g. --pareto total_surplus:max,gini_final:min
+  let objectives = flag_with(args, "--pareto", |s| s.split(',').map(pareto::Objective::parse).collect::<Result<Vec<_>, String>>())?.unwrap_or_default();
+  QUIET.store(true, Ordering::Relaxed);
+  let mut rows = vec![];
+  let mut scores = vec![];
+  for value in values {
+    let config = base.with_overrides(&[&format!("{}={}", path, value)]).map_err(|e| format!("--vary: {}", e))?;
+    let summaries = batch(&config, seeds, jobs);
+    println!("{} = {}:", path, value);
+    sampling::print_batch(&summaries, config.sampling);
+    scores.push(objectives.iter().map(|o| pareto::mean(&summaries, &o.metric)).collect::<Vec<_>>());
+    rows.extend(summaries.into_iter().map(|s| (value.as_str(), s)));
+    if interrupt::interrupted() {
+      break;
+    }
+  }
+  if !objectives.is_empty() {
+    if let Some(o) = objectives.iter().find(|o| rows.first().is_some_and(|(_, s)| !s.metrics().iter().any(|(name, _, _)| *name == o.metric))) {
+      return Err(format!("--pareto: no metric {:?}", o.metric));
+    }
+    let senses: Vec<pareto::Sense> = objectives.iter().map(|o| o.sense).collect();
+    let efficient = pareto::efficient(&scores, &senses);
+    let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| x.to_string());
+    let described: Vec<String> = objectives.iter().map(|o| o.describe()).collect();
+    println!("pareto-efficient values of {} ({}), by their means:", path, described.join(", "));
+    let mut csv = format!("{},{}\n", path, objectives.iter().map(|o| o.metric.as_str()).collect::<Vec<_>>().join(","));
+    for &i in &efficient {
+      let means: Vec<String> = objectives.iter().zip(&scores[i]).map(|(o, &x)| format!("{} {}", o.metric, show(x))).collect();
+      println!("  {} = {}: {}", path, values[i], means.join(", "));
+      csv += &format!("{},{}\n", values[i], scores[i].iter().map(|x| x.map_or(String::new(), |x| x.to_string())).collect::<Vec<_>>().join(","));
+    }
+    if let Some(out) = flag_value(args, "--pareto-csv") {
+      std::fs::write(out, csv).map_err(io_err("writing", out))?;
+      println!("wrote {}", out);
+    }
+  }
+  if let Some(out) = flag_value(args, "-o") {
+    write_summaries(args, out, Some(path), &rows)?;
+  }
+  Ok(())
 }
 
-#[derive(PartialEq, Eq, Debug, Copy, Clone)]
-enum OrderType {
-  Bid,
-  Ask,
+// Each run's summary as a CSV row, after a first column of the value the run was for
+// if there's a `key` naming it. With --dp-epsilon the metrics go through the
+// differentially private release, --dp-sensitivity saying which and how much.
+fn write_summaries(args: &[String], out: &str, key: Option<&str>, rows: &[(&str, summary::Summary)]) -> Result<(), String> {
+  let Some((_, first)) = rows.first() else { return Ok(()) };
+  let names: Vec<&str> = first.metrics().iter().map(|(name, _, _)| *name).collect();
+  let mut table: privacy::Table = rows.iter().map(|(_, s)| s.metrics().into_iter().map(|(name, _, v)| (name, v)).collect()).collect();
+  if let Some(epsilon) = flag_parsed(args, "--dp-epsilon")? {
+    let sensitivities = flag_value(args, "--dp-sensitivity").ok_or("--dp-epsilon needs --dp-sensitivity <metric>=<s>,...")?;
+    let privacy = privacy::Privacy::parse(epsilon, sensitivities, &names).map_err(|e| format!("--dp-sensitivity: {}", e))?;
+    let salt = flag_parsed(args, "--dp-salt")?.unwrap_or_else(rand::random);
+    table = privacy.release(&table, &mut StdRng::seed_from_u64(salt));
+  }
+  let mut csv = key.map_or(String::new(), |k| format!("{},", k)) + &names.join(",") + "\n";
+  for ((value, _), row) in rows.iter().zip(&table) {
+    let metrics: Vec<String> = row.iter().map(|(_, v)| v.map_or(String::new(), |v| v.to_string())).collect();
+    csv += &key.map_or(String::new(), |_| format!("{},", value));
+    csv += &(metrics.join(",") + "\n");
+  }
+  std::fs::write(out, csv).map_err(io_err("writing", out))?;
+  println!("wrote {}", out);
+  Ok(())
 }
 
+fn run_seed(args: &[String], config: &config::Config, seed: u64, out_dir: Option<&mut outdir::RunDir>) -> Result<summary::Summary, String> {
+  // the report flags, read up front so a bad one fails before the run rather than after
+  let batch_size = flag_parsed(args, "--stream-batch")?.unwrap_or(trade_stream::DEFAULT_BATCH_SIZE);
+  let top_k: Option<usize> = flag_parsed(args, "--top-k")?;
+  let n_periods = n_periods(args)?;
+  let cohorts = flag_values(args, "--cohort").into_iter()
+    .map(|c| cohort::Cohort::parse(c).map(|cohort| (c, cohort)).map_err(|e| format!("--cohort: {}", e)))
+    .collect::<Result<Vec<(&str, cohort::Cohort)>, String>>()?;
+  // `<index>`, or `<index>:<periods>` to override --periods
+  let deflate = flag_with(args, "--deflate", |spec| {
+    let (index, periods) = spec.split_once(':').map_or(Ok((spec, n_periods)), |(i, n)| n.parse().map(|n| (i, n)).map_err(|_| format!("can't read periods {:?}", n)))?;
+    deflation::PriceIndex::parse(index).map(|index| (index, periods))
+  })?;
+  // a comma-separated subset of utilitarian, rawlsian and nash; all of them by default
+  let welfare_fns = flag_with(args, "--welfare", |w| w.split(',').map(welfare::Welfare::parse).collect())?.unwrap_or_else(|| welfare::ALL.to_vec());
+  // `endowment` by default, or money-metric with --welfare-in b; or `money-metric:<price>`
+  // or `raw`
+  let norm = flag_with(args, "--normalize", welfare::Normalization::parse)?;
+  // resamples for within-run intervals, off by default
+  let n_resamples: Option<usize> = flag_parsed(args, "--bootstrap")?;
+  // <prefix>_start.csv and <prefix>_end.csv, on --curves-grid or one spanning the
+  // starting rates
+  let curves_prefix = flag_value(args, "--curves-csv");
+  let curves_grid = flag_with(args, "--curves-grid", curves::PriceGrid::parse)?;
+  // trades to leave out of the steady-state moments, or `auto`
+  let burn_in = flag_with(args, "--burn-in", steady_state::BurnIn::parse)?;
 
-#[derive(PartialEq, Debug, Copy, Clone)]
-struct Order {
-  agent_id: AgentId,
-  
-  typ: OrderType,
+  let mut rng: StdRng = StdRng::seed_from_u64(seed);
 
-  price_per_a_in_b: f64,
-}
+  println!("setting up agent pool");
+  let mut assets = config.initial_assets(seed, &mut rng);
+  if config.preference_correlation != 0.0 {
+    let production: Vec<f64> = assets.iter().map(|(agent, _)| population::production_mix(agent)).collect();
+    let preference: Vec<f64> = assets.iter().map(|(agent, _)| population::preference_mix(agent)).collect();
+    println!("production/preference mix correlation: {:?}", stats::pearson(&production, &preference));
+  }
+  let mut strategies = config.strategies(seed);
+  if config.entry_cost > 0.0 {
+    let participants = entry::enter(&mut assets, config.entry_cost, config.privilege.exempt_agents(), &mut strategies);
+    println!("participation at entry cost {}: {} of {} agents ({}%)",
+      config.entry_cost, participants.len(), assets.len(), 100.0 * participants.len() as f64 / assets.len() as f64);
+  }
+  strategies.price(&assets);
 
-fn generate_orders(agent_id: AgentId, agent: &Agent, balance: &Balance) -> (Option<Order>, Option<Order>) {
-  let bid = {
-    if balance.b > 0.0 {
-      Some(Order {
-        agent_id: agent_id,
-        typ: OrderType::Bid,
-        price_per_a_in_b: agent.indifference_price_of_a_in_b(),
-      })
-    } else {
-      None
+  for (price, supply, demand) in supply_demand_curves(&assets) {
+    println!(r#"[ {}, {{ "supply":{}, "demand":{} }}]"#, price, supply, demand);
+  }
+  curve_fit::print_report("start", &curve_fit::fit_market(&assets));
+  intersection::print_report(&assets);
+  walras::print_report(&assets);
+  let stream_path = flag_value(args, "--trade-stream");
+  let mut stream = trade_stream::TradeStream::open(stream_path, batch_size).map_err(io_err("opening", stream_path.unwrap_or("")))?;
+  let mut stream_err = None;
+  let mut stopping = config.stopping();
+  if let Some(path) = flag_value(args, "--stop-file") {
+    stopping::watch_stop_file(path.into(), stopping.signal.get_or_insert_with(Default::default).clone());
+  }
+  let mut quotes = vec![];
+  let outcome = execute_all_trades(&mut assets, &mut strategies, config.arrivals(seed), config.pricing, &config.risk, &stopping, |event| match event {
+    runlog::Event::Quote(quote) => quotes.push(quote.clone()),
+    runlog::Event::Trade(trade) => if let Err(e) = stream.push(trade) { stream_err.get_or_insert(e); },
+    _ => {}
+  });
+  stream_err.map_or(Ok(()), Err).and_then(|_| stream.finish()).map_err(io_err("writing", stream_path.unwrap_or("")))?;
+  println!("stopped at tick {}: {:?}", outcome.stop.tick, outcome.stop.reason);
+  if let Some(flag) = nonconvergence::detect(&outcome.trades) {
+    println!("warning: run looks non-convergent ({:?})", flag);
+  }
+  if !outcome.rejections.is_empty() {
+    println!("{} matched trades rejected by the risk rules", outcome.rejections.len());
+  }
+  if args.iter().any(|a| a == "--timings") {
+    timing::print_report(&outcome.timings, outcome.final_assets.len());
+  }
+  let summary = outcome.summary(seed);
+  let final_assets = outcome.final_assets.clone();
+  let log = outcome.into_log(seed, quotes);
+  let summary = summary.in_currency(&log, config.welfare_currency);
+  let b_price = config.welfare_currency.price(summary.valuation_price);
+  let norm = norm.unwrap_or_else(|| b_price.map_or_else(Default::default, |price| welfare::Normalization::MoneyMetric { price }));
+  if interrupt::interrupted() {
+    // the comparisons would run the economy again; only what this run has is written
+    println!("interrupted; the run as far as it got:");
+    let summary = summary.in_numeraire(config.numeraire);
+    for (name, _, value) in summary.metrics() {
+      println!("  {}: {}", name, value.map_or("n/a".to_string(), |v| v.to_string()));
     }
-  };
+    write_run(args, config, &log, out_dir)?;
+    return Ok(summary);
+  }
 
-  let ask = {
-    if balance.a > 0.0 {
-      Some(Order {
-        agent_id: agent_id,
-        typ: OrderType::Ask,
-        price_per_a_in_b: agent.indifference_price_of_a_in_b(),
-      })
-    } else {
-      None
+  if !strategies.is_truthful() {
+    let baseline = simulation::Simulation::with_strategies(config.clone(), seed, strategy::Strategies::truthful(config.n_agents)).run();
+    if let Some(fat_finger) = &config.fat_finger {
+      fat_finger::print_report(&baseline, &log, fat_finger, config.pricing);
     }
-  };
-
-  return (bid, ask)
-}
-
-fn find_next_trade(assets : &Vec<(Agent, Balance)>) -> Option<Trade> {
-  let orders: Vec<(Option<Order>, Option<Order>)> =
-    assets.iter().enumerate()
-    .map(|(id, (agent, balance))| generate_orders(id, agent, balance))
-    .collect();
-
-  let highest_bid = orders.iter()
-    .map(|(bid, _)| bid)
-    .filter(|o| o.is_some()).map(|o| o.unwrap())
-    .max_by(|o1, o2| o1.price_per_a_in_b.partial_cmp(&o2.price_per_a_in_b).unwrap());
-  let lowest_acceptable_ask = orders.iter()
-    .map(|(_, ask)| ask)
-    .filter(|o| o.is_some()).map(|o| o.unwrap())
-    .filter(|o| !highest_bid.is_some() || o.price_per_a_in_b < highest_bid.unwrap().price_per_a_in_b)
-    .min_by(|o1, o2| o1.price_per_a_in_b.partial_cmp(&o2.price_per_a_in_b).unwrap());
-
-  match (highest_bid, lowest_acceptable_ask) {
-    (Some(bid), Some(ask)) => { 
-      println!("matching bid {:?} against ask {:?}", bid, ask);
-      let (buyer, buyer_balance) = &assets[bid.agent_id];
-      let (seller, seller_balance) = &assets[ask.agent_id];
-      println!("  (balances: bidder {:?}, seller {:?})", buyer_balance, seller_balance);
-      let clearing_price = (bid.price_per_a_in_b + ask.price_per_a_in_b) / 2.0;
-      let amount_a_buyer_can_afford = buyer_balance.b / clearing_price;
-      let (amount_a, amount_b) = if amount_a_buyer_can_afford < seller_balance.a {
-        // amount_a_buyer_can_afford is known to be < seller_balance.a due to the if
-        // statement above
-        (amount_a_buyer_can_afford, buyer_balance.b)
-      } else {
-        (seller_balance.a, clearing_price * seller_balance.a)
-      };
-      return Some(Trade {
-        buyer: bid.agent_id,
-        seller: ask.agent_id,
-        amount_a: amount_a,
-        amount_b: amount_b,
-      });
+    if config.monopoly {
+      let sellers = strategies.agents_using(strategy::Strategy::Monopolist);
+      market_power::print_comparison("monopoly", &baseline, &log, &sellers);
     }
-    _ => { return None; }
-  }    
-}
-
-fn execute_one_trade(assets: &mut Vec<(Agent, Balance)>) -> bool /* done? */ {
-  println!("in execute_one_trade");
-  match find_next_trade(assets) {
-    None => { 
-      println!("no more trades are possible");
-      return true;
+    for coalition in strategies.coalitions() {
+      market_power::print_comparison("cartel", &baseline, &log, &coalition.members);
+      println!("  defections: {}", coalition.defections);
+    }
+    if let Some(zi) = &config.zero_intelligence {
+      zero_intelligence::print_report(&baseline, &log, zi);
     }
-    Some(trade) => {
-      println!("executing {:?}", trade);
-      let (initial_buyer_utility, initial_seller_utility) = {
-        let (buyer, mut buyer_balance) = assets[trade.buyer];
-        let (seller, mut seller_balance) = assets[trade.seller];
-        (
-          buyer.utility(buyer_balance.a, buyer_balance.b),
-          seller.utility(seller_balance.a, seller_balance.b)
-        )
-      };
-      assets[trade.buyer] .1.a += trade.amount_a; if assets[trade.buyer] .1.a < 0.0 {panic!("oh no")}
-      assets[trade.seller].1.a -= trade.amount_a; if assets[trade.seller].1.a < 0.0 {panic!("oh no")}
-      assets[trade.buyer] .1.b -= trade.amount_b; if assets[trade.buyer] .1.b < 0.0 {panic!("oh no")}
-      assets[trade.seller].1.b += trade.amount_b; if assets[trade.seller].1.b < 0.0 {panic!("oh no")}
-      let (final_buyer_utility, final_seller_utility) = {
-        let (buyer, mut buyer_balance) = assets[trade.buyer];
-        let (seller, mut seller_balance) = assets[trade.seller];
-        (
-          buyer.utility(buyer_balance.a, buyer_balance.b),
-          seller.utility(seller_balance.a, seller_balance.b)
-        )
-      };
-      // println!("buyer {:?}", buyer);
-      // println!("  util {} -> {}", initial_buyer_utility, final_buyer_utility);
-      // println!("seller {:?}", seller);
-      // println!("  util {} -> {}", initial_seller_utility, final_seller_utility);
-      assert!(final_buyer_utility > initial_buyer_utility, "buyer's remorse");
-      assert!(final_seller_utility > initial_seller_utility, "seller's remorse");
-      return false;
+    if let Some(zip) = &config.zip {
+      zip::print_report(&baseline, &log, zip);
+    }
+    if config.transparency != transparency::Transparency::Full {
+      let lit = config::Config { transparency: transparency::Transparency::Full, ..config.clone() };
+      let strategic: Vec<AgentId> = (0..config.n_agents).filter(|&id| !matches!(strategies.get(id), strategy::Strategy::Truthful | strategy::Strategy::Dealer)).collect();
+      transparency::print_report(&simulate(&lit, seed), &log, config.transparency, &strategic);
     }
   }
-}
+  if !config.privilege.agents.is_empty() {
+    let unprivileged = config::Config { privilege: privilege::Privilege::default(), ..config.clone() };
+    let fees_waived = config.entry_cost * config.privilege.exempt_agents().len() as f64;
+    privilege::print_transfer(&simulate(&unprivileged, seed), &log, &config.privilege, fees_waived);
+  }
+  if config.pricing.matching != continuous::Matching::Batch {
+    let batch = config::Config { pricing: config.pricing.with_matching(continuous::Matching::Batch), ..config.clone() };
+    continuous::print_comparison(config.pricing.matching, &simulate(&batch, seed), &log);
+  }
+  if let continuous::Matching::Sessions { continuous } = config.pricing.matching {
+    session::print_report(&log.trades, continuous);
+  }
+  if let Some(dealers) = config.dealers {
+    let without = config::Config { dealers: None, ..config.clone() };
+    let spread = |config: &config::Config, log: &runlog::RunLog| dealer::quoted_spread(&mut config.strategies(seed), &log.final_assets());
+    let baseline = simulate(&without, seed);
+    dealer::print_report(&baseline, &log, dealers, (spread(&without, &baseline), spread(config, &log)));
+  }
+  if config.reporting_delay > 0 {
+    let immediate = config::Config { reporting_delay: 0, ..config.clone() };
+    reporting::print_report(&simulate(&immediate, seed), &log, config.reporting_delay);
+  }
+  if let Some(tax) = config.pricing.tax {
+    let untaxed = config::Config { pricing: config.pricing.with_tax(None), ..config.clone() };
+    tax::print_report(&simulate(&untaxed, seed), &log, tax);
+  }
+  if let Some(subsidy) = config.pricing.subsidy {
+    let unsubsidized = config::Config { pricing: config.pricing.with_subsidy(None), ..config.clone() };
+    subsidy::print_report(&simulate(&unsubsidized, seed), &log, subsidy);
+  }
+  if let Some(credit) = config.pricing.credit {
+    let uncredited = config::Config { pricing: config.pricing.with_credit(None), ..config.clone() };
+    credit::print_report(&simulate(&uncredited, seed), &log, credit);
+  }
+  if let Some(short) = config.pricing.short {
+    let unshorted = config::Config { pricing: config.pricing.with_short(None), ..config.clone() };
+    short::print_report(&simulate(&unshorted, seed), &log, short);
+  }
+  if let Some(pool) = config.pricing.dark_pool {
+    let lit = config::Config { pricing: config.pricing.with_dark_pool(None), ..config.clone() };
+    dark_pool::print_report(&simulate(&lit, seed), &log, pool);
+  }
+  if let Some(regions) = config.pricing.regions.filter(|r| r.tariff.is_some()) {
+    let free = config::Config { pricing: config.pricing.with_regions(Some(regions::Regions { tariff: None, ..regions })), ..config.clone() };
+    regions::print_report(&simulate(&free, seed), &log, regions);
+  }
 
-fn execute_all_trades(assets: &mut Vec<(Agent, Balance)>) {
-  while !execute_one_trade(assets) {}
-  sanity_check_endpoint(assets);
-}
+  let surplus = realized_surplus(&log.initial_assets, &final_assets);
+  let trade_network = network::TradeNetwork::from_trades(final_assets.len(), &log.trades);
+  network::print_report(&trade_network, &surplus);
+  community::print_report(&trade_network, &log.trades, &mut rng);
+  activity::print_report(&log, &strategies);
+  dispersion::print_report(&log);
+  mobility::print_report(&log, n_periods);
+  pairs::print_report(&["A", "B"], &pairs::pair_stats(&log));
+  if let Some(k) = top_k {
+    depth::print_report(&depth::at_trades(&log, k), k);
+  }
+  if !cohorts.is_empty() {
+    cohort::print_report(&log, &cohorts, n_periods);
+  }
+  if let Some((index, n_periods)) = deflate {
+    deflation::print_report(&deflation::periods(&log, n_periods, index), index);
+  }
+  pricing::print_report(&log.trades);
+  limits::print_report(&log.initial_assets, &final_assets, config.pricing);
+  if let Some(decay) = config.pricing.decay {
+    decay::print_report(&log, decay);
+  }
+  let accounts = b_price.map_or_else(|| accounting::of(&log.initial_assets, &log.trades), |p| accounting::in_b(&log.initial_assets, &log.trades, p));
+  accounting::print_report(&accounts, &config.welfare_currency.unit(summary.valuation_price));
+  welfare::print_report(&log.initial_assets, &final_assets, &welfare_fns, norm);
+  equilibrium::print_report(&log.initial_assets, &final_assets, &log.trades);
+  allocation::print_report(&log.initial_assets, &final_assets, &welfare_fns, norm, summary.valuation_price, &mut StdRng::seed_from_u64(seed));
+  budget_share::print_report(&log.initial_assets, &final_assets, summary.valuation_price);
+  inequality::print_report(&log.initial_assets, &final_assets, summary.valuation_price);
+  if let Some(burn_in) = burn_in {
+    steady_state::print_report(steady_state::analyse(&log.trades, burn_in, config.numeraire).as_ref());
+  }
+  if let Some(n) = n_resamples {
+    bootstrap::print_report(&bootstrap::intervals(&log, n, &mut StdRng::seed_from_u64(seed)), n);
+  }
 
-fn sanity_check_endpoint(assets: &Vec<(Agent, Balance)>) {
-  let mut local = assets.clone();
-  local.sort_by(|(agent_1,_), (agent_2, _)| {
-    agent_1.indifference_price_of_a_in_b().partial_cmp(
-      &agent_2.indifference_price_of_a_in_b()
-    ).unwrap()
-  });
+  if let Some(prefix) = curves_prefix {
+    let grid = curves_grid.or_else(|| curves::PriceGrid::spanning(&log.initial_assets)).ok_or("--curves-csv: every agent shares one rate; give a --curves-grid")?;
+    for (state, assets) in [("start", &log.initial_assets), ("end", &final_assets)] {
+      let path = format!("{}_{}.csv", prefix, state);
+      std::fs::write(&path, curves::csv(&curves::on_grid(assets, grid), config.numeraire)).map_err(io_err("writing", &path))?;
+      println!("wrote {}", path);
+    }
+  }
+  if let Some(path) = flag_value(args, "--lorenz-csv") {
+    let price = summary.valuation_price;
+    let (wealth, utility) = (|assets| summary::wealth_in_b(assets, price), summary::utilities);
+    let series = [
+      ("wealth_initial", wealth(&log.initial_assets)),
+      ("wealth_final", wealth(&final_assets)),
+      ("utility_initial", utility(&log.initial_assets)),
+      ("utility_final", utility(&final_assets)),
+    ];
+    let series: Vec<(&str, &[f64])> = series.iter().map(|(name, values)| (*name, &values[..])).collect();
+    std::fs::write(path, inequality::lorenz_csv(&series)).map_err(io_err("writing", path))?;
+    println!("wrote {}", path);
+  }
+  write_run(args, config, &log, out_dir)?;
 
-  let remainder = local.iter()
-    .skip_while(|(_, balance)| {    balance.a == 0.0  })
-    .skip_while(|(_, balance)| {    balance.a > 0.0 && balance.b > 0.0  })
-    .skip_while(|(_, balance)| {    balance.b == 0.0  })
-    .collect::<Vec<_>>();
-  // println!("Agents:");
-  // for (agent, balance) in local.iter() {
-  //   println!("  ({}, {}, {}), {:?}", agent.indifference_price_of_a_in_b(), balance.a, balance.b, agent);
-  // }
-  // println!("Remainder:");
-  // for (agent, balance) in remainder.iter() {
-  //   println!("  ({}, {}, {}), {:?}", agent.indifference_price_of_a_in_b(), balance.a, balance.b, agent);
-  // }
-  assert!(remainder.is_empty(), "{:?} ({} elems)", remainder, remainder.len());
+  println!("done with main");
+  Ok(summary.in_numeraire(config.numeraire))
 }
 
-type Price = f64;
-fn supply_demand_curves(assets: &Vec<(Agent, Balance)>) -> Vec<(Price, f64, f64)> {
-  let mut interesting_prices: Vec<f64> = assets.iter().map(|(agent, _)| agent.indifference_price_of_a_in_b()).collect();
-  interesting_prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-  let mut result = vec![];
-  for discontinuity_price in interesting_prices {
-    let eps = 2_f64.powf(-30.0);
-    for price in vec![discontinuity_price*(1.0-eps), discontinuity_price*(1.0+eps)] {
-      let supply = assets.iter().map(|(agent, balance)| if agent.indifference_price_of_a_in_b() > price {0.0} else {balance.a        }).sum();
-      let demand = assets.iter().map(|(agent, balance)| if agent.indifference_price_of_a_in_b() < price {0.0} else {balance.b / price}).sum();
-      result.push((price, supply, demand));
-    }
+// The run's trades, log and directory, as asked for.
+fn write_run(args: &[String], config: &config::Config, log: &runlog::RunLog, out_dir: Option<&mut outdir::RunDir>) -> Result<(), String> {
+  if let Some(path) = flag_value(args, "--trades") {
+    history::write(path, &history::history(log)).map_err(io_err("writing", path))?;
   }
-
-  // sanity check
-  for i in 1..result.len() {
-    assert!(result[i].1 >= result[i-1].1, "{:?} -> {:?}", result[i-1], result[i]);
-    assert!(result[i].2 <= result[i-1].2, "{:?} -> {:?}", result[i-1], result[i]);
+  if let Some(path) = flag_value(args, "--log") {
+    runlog::write(path, log).map_err(io_err("writing", path))?;
   }
-
-  result
+  if let Some(dir) = out_dir {
+    dir.write_run(args, config, log).map_err(|e| format!("writing {}: {}", dir.path().display(), e))?;
+    println!("wrote {}", dir.path().display());
+  }
+  Ok(())
 }
@@ -0,0 +1,550 @@
+// The matching loop as a state machine, so a run can be driven a tick at a time from
+// someone else's event loop instead of running to completion in one call, and its
+// state saved and picked up again later (see checkpoint).
+
+use serde::{Deserialize, Serialize};
+
+use crate::arrivals::Arrivals;
+use crate::book::OrderBook;
+use crate::call;
+use crate::clock::{Clock, Tick};
+use crate::continuous::{self, Matching};
+use crate::credit;
+use crate::forecast::{self, Forecast};
+use crate::lots;
+use crate::outcome::SimulationOutcome;
+use crate::pricing::PricingRule;
+use crate::risk::{self, Rejection, RiskRules};
+use crate::runlog::{Event, Quote, QuoteTracker};
+use crate::session::Session;
+use crate::short;
+use crate::stopping::{Stop, StopReason, StoppingRules};
+use crate::strategy::Strategies;
+use crate::subsidy;
+use crate::tax;
+use crate::timing::{self, Phase, Timings};
+use crate::{execute, execute_one_trade, sanity_check_endpoint, withdraw_unbacked, Agent, AgentId, Balance, Order, Trade};
+
+// Everything about a market that changes as it runs; the rules it runs by come from
+// its config. Not whether it has stopped: a restored market checks its stopping
+// rules afresh, so one paused by them resumes under new ones.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MarketState {
+  pub initial_assets: Vec<(Agent, Balance)>,
+  pub assets: Vec<(Agent, Balance)>,
+  pub clock: Clock,
+  pub quotes: QuoteTracker,
+  pub book: Vec<(Option<Order>, Option<Order>)>,
+  // when each order on the book was entered, with continuous matching
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub entered: Option<Vec<(u64, u64)>>,
+  pub trades: Vec<Trade>,
+  pub rejections: Vec<Rejection>,
+  // what has perished so far, with decay
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub spoiled: Option<Balance>,
+  // what each agent owes, with credit
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub debts: Option<Vec<f64>>,
+  // what each agent owes in A, with short selling
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub shorts: Option<Vec<f64>>,
+}
+
+pub struct Market {
+  pub assets: Vec<(Agent, Balance)>,
+  initial_assets: Vec<(Agent, Balance)>,
+  arrivals: Arrivals,
+  pricing: PricingRule,
+  risk: RiskRules,
+  stopping: StoppingRules,
+  clock: Clock,
+  quotes: QuoteTracker,
+  book: OrderBook,
+  // who to quote again before the next match, when only the last trade's parties
+  // can have changed their quotes (see Strategies::quotes_independently); None when
+  // everyone must be
+  requote: Option<Vec<AgentId>>,
+  trades: Vec<Trade>,
+  rejections: Vec<Rejection>,
+  spoiled: Balance,
+  debts: Vec<f64>,
+  shorts: Vec<f64>,
+  stop: Option<Stop>,
+  timings: Timings,
+  // quote changes announced, for the memory limit; like the timings, not saved
+  quoted: usize,
+}
+
+impl Market {
+  pub fn new(assets: Vec<(Agent, Balance)>, arrivals: Arrivals, pricing: PricingRule, risk: RiskRules, stopping: StoppingRules) -> Market {
+    let n_agents = assets.len();
+    Market {
+      initial_assets: assets.clone(),
+      assets,
+      arrivals,
+      pricing,
+      risk,
+      stopping,
+      clock: Clock::default(),
+      quotes: QuoteTracker::new(n_agents),
+      book: match pricing.matching {
+        Matching::Batch | Matching::Call => OrderBook::new(n_agents),
+        Matching::Continuous | Matching::Sessions { .. } => OrderBook::with_time_priority(n_agents),
+      },
+      requote: None,
+      trades: vec![],
+      rejections: vec![],
+      spoiled: Balance { a: 0.0, b: 0.0 },
+      debts: vec![0.0; n_agents],
+      shorts: vec![0.0; n_agents],
+      stop: None,
+      timings: Timings::default(),
+      quoted: 0,
+    }
+  }
+
+  pub fn from_state(state: MarketState, arrivals: Arrivals, pricing: PricingRule, risk: RiskRules, stopping: StoppingRules) -> Market {
+    let n_agents = state.assets.len();
+    Market {
+      initial_assets: state.initial_assets,
+      assets: state.assets,
+      arrivals,
+      pricing,
+      risk,
+      stopping,
+      clock: state.clock,
+      quotes: state.quotes,
+      book: match &state.entered {
+        Some(entered) => OrderBook::restore(&state.book, entered),
+        None => OrderBook::from_quotes(&state.book),
+      },
+      requote: None,
+      trades: state.trades,
+      rejections: state.rejections,
+      spoiled: state.spoiled.unwrap_or(Balance { a: 0.0, b: 0.0 }),
+      debts: state.debts.unwrap_or_else(|| vec![0.0; n_agents]),
+      shorts: state.shorts.unwrap_or_else(|| vec![0.0; n_agents]),
+      stop: None,
+      timings: Timings::default(),
+      quoted: 0,
+    }
+  }
+
+  pub fn state(&self) -> MarketState {
+    MarketState {
+      initial_assets: self.initial_assets.clone(),
+      assets: self.assets.clone(),
+      clock: self.clock,
+      quotes: self.quotes.clone(),
+      book: self.book.quotes().to_vec(),
+      entered: self.book.entered().map(<[_]>::to_vec),
+      trades: self.trades.clone(),
+      rejections: self.rejections.clone(),
+      spoiled: self.pricing.decay.map(|_| self.spoiled),
+      debts: self.pricing.credit.map(|_| self.debts.clone()),
+      shorts: self.pricing.short.map(|_| self.shorts.clone()),
+    }
+  }
+
+  pub fn trades(&self) -> &[Trade] {
+    &self.trades
+  }
+
+  pub fn stop(&self) -> Option<Stop> {
+    self.stop
+  }
+
+  pub fn debts(&self) -> &[f64] {
+    &self.debts
+  }
+
+  pub fn shorts(&self) -> &[f64] {
+    &self.shorts
+  }
+
+  // Where the run's steps have spent their time so far.
+  pub fn timings(&self) -> Timings {
+    self.timings
+  }
+
+  pub fn realized_share(&self, forecast: Forecast) -> Option<f64> {
+    forecast::realized_share(forecast, &self.initial_assets, &self.assets, &self.trades)
+  }
+
+  // One tick: stopping rules, requotes, then at most one trade. Returns the stop once
+  // the run is over, after which further calls do nothing. Exhaustion is judged on the
+  // orders as intended, so an entry error can't end the run, and not while trades are
+  // still to be reported, which could move an adaptive agent's quotes.
+  pub fn step(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Option<Stop> {
+    if self.stop.is_some() {
+      return self.stop;
+    }
+    let mut quoted = 0;
+    let counted = |event: &Event| {
+      quoted += matches!(event, Event::Quote(_)) as usize;
+      on_event(event)
+    };
+    let (stop, timings) = timing::scoped(|| self.tick(strategies, counted));
+    self.timings += timings;
+    self.quoted += quoted;
+    stop
+  }
+
+  // An estimate of the bytes the run's records take: its trades and rejections, and
+  // the quote changes a log keeps.
+  pub fn footprint(&self) -> usize {
+    self.trades.len() * size_of::<Trade>() + self.rejections.len() * size_of::<Rejection>() + self.quoted * size_of::<Quote>()
+  }
+
+  fn tick(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Option<Stop> {
+    let reason = self.stopping.check(self.clock.now(), &self.initial_assets, &self.assets, &self.trades);
+    if let Some(reason) = reason.or_else(|| self.stopping.over_budget(self.timings.total, self.footprint())) {
+      return Some(self.finish(strategies, reason, on_event));
+    }
+    // trades already executed reach the strategies only as they're reported
+    for report in strategies.observe(&self.trades, self.clock.now()) {
+      on_event(&Event::Report(report));
+    }
+    match self.pricing.matching {
+      Matching::Batch => {}
+      Matching::Continuous => return self.step_continuous(strategies, on_event),
+      Matching::Call => return self.step_call(strategies, on_event),
+      Matching::Sessions { continuous } => return self.step_session(continuous, strategies, on_event),
+    }
+    let now = self.clock.now();
+    // what everyone can spend, when some may borrow, worked out once for the tick
+    let lent = self.lent();
+    // the orders as intended, when they aren't simply the book
+    let mut fresh = None;
+    let changes = timing::time(Phase::OrderGeneration, || {
+      match self.requote.take() {
+        Some(mut ids) if strategies.quotes_independently() && matches!(self.arrivals, Arrivals::EveryTick { .. }) => {
+          ids.sort_unstable();
+          ids.dedup();
+          let assets = lent.as_deref().unwrap_or(&self.assets);
+          for &id in &ids {
+            let mut quote = strategies.quote(id, assets, now);
+            withdraw_unbacked(&mut quote, &assets[id].1, self.pricing.lots);
+            self.book.set(id, quote);
+          }
+          self.quotes.update_agents(now, ids, self.book.quotes())
+        }
+        _ => {
+          let assets = lent.as_deref().unwrap_or(&self.assets);
+          let mut orders = strategies.orders(assets, now);
+          for (quote, (_, balance)) in orders.iter_mut().zip(assets.iter()) {
+            withdraw_unbacked(quote, balance, self.pricing.lots);
+          }
+          let submitted = strategies.submit(&orders, now);
+          for (id, quote) in submitted.iter().enumerate() {
+            if self.arrivals.arrives(id, now) && self.book.quotes()[id] != *quote {
+              self.book.set(id, *quote);
+            }
+          }
+          if orders != self.book.quotes() {
+            fresh = Some(OrderBook::from_quotes(&orders));
+          }
+          self.quotes.update(now, self.book.quotes())
+        }
+      }
+    });
+    for quote in changes {
+      on_event(&Event::Quote(quote));
+    }
+    self.requote = Some(vec![]);
+    let rejections = &mut self.rejections;
+    let on_reject = |rejection: Rejection| {
+      on_event(&Event::Rejection(rejection.clone()));
+      rejections.push(rejection);
+    };
+    let found = match (self.pricing.credit, self.pricing.short) {
+      (None, None) => execute_one_trade(&mut self.assets, &self.book, self.pricing, &self.risk, strategies.priority(), now, on_reject),
+      // matched against what everyone can spend, then lent what it takes
+      (credit, short) => risk::find_allowed_trade(lent.as_ref().unwrap(), &self.book, self.pricing, &self.risk, strategies.priority(), now, on_reject).map(|mut trade| {
+        if let Some(credit) = credit {
+          credit.finance(&mut trade, &self.assets, &mut self.debts);
+        }
+        if let Some(short) = short {
+          short.finance(&mut trade, &self.assets, &mut self.shorts);
+        }
+        execute(&mut self.assets, &trade);
+        trade
+      }),
+    };
+    match found {
+      Some(trade) => {
+        for id in [trade.buyer, trade.seller] {
+          let mut quote = self.book.quotes()[id];
+          withdraw_unbacked(&mut quote, &self.spendable(id), self.pricing.lots);
+          self.book.set(id, quote);
+        }
+        self.requote = Some(vec![trade.buyer, trade.seller]);
+        on_event(&Event::Trade(trade.clone()));
+        self.trades.push(trade);
+      }
+      None if !strategies.awaiting_reports() && risk::find_allowed_trade(lent.as_deref().unwrap_or(&self.assets), fresh.as_ref().unwrap_or(&self.book), self.pricing, &self.risk, strategies.priority(), now, |_| {}).is_none() => {
+        return Some(self.finish(strategies, StopReason::Exhausted, on_event));
+      }
+      None => {}
+    }
+    self.end_tick();
+    None
+  }
+
+  // One tick of continuous matching: the arrivals come in one by one, each trading
+  // as far as it crosses before the next. Exhausted as for step.
+  fn step_continuous(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Option<Stop> {
+    let now = self.clock.now();
+    let (orders, submitted) = timing::time(Phase::OrderGeneration, || {
+      let mut orders = strategies.orders(&self.assets, now);
+      for (quote, (_, balance)) in orders.iter_mut().zip(&self.assets) {
+        withdraw_unbacked(quote, balance, self.pricing.lots);
+      }
+      let submitted = strategies.submit(&orders, now);
+      (orders, submitted)
+    });
+    let traded = self.trades.len();
+    for id in self.arrivals.queue(self.assets.len(), now) {
+      // less what it can no longer back after trading earlier in the tick
+      let changes = timing::time(Phase::OrderGeneration, || {
+        let mut quote = submitted[id];
+        withdraw_unbacked(&mut quote, &self.assets[id].1, self.pricing.lots);
+        self.book.set(id, quote);
+        self.quotes.update_agents(now, [id], self.book.quotes())
+      });
+      for quote in changes {
+        on_event(&Event::Quote(quote));
+      }
+      while self.stopping.max_trades.is_none_or(|n| self.trades.len() < n) {
+        let rejections = &mut self.rejections;
+        let on_reject = |rejection: Rejection| {
+          on_event(&Event::Rejection(rejection.clone()));
+          rejections.push(rejection);
+        };
+        let Some(trade) = continuous::match_incoming(&self.assets, &self.book, id, self.pricing, &self.risk, now, on_reject) else {
+          break;
+        };
+        execute(&mut self.assets, &trade);
+        for party in [trade.buyer, trade.seller] {
+          let mut quote = self.book.quotes()[party];
+          withdraw_unbacked(&mut quote, &self.assets[party].1, self.pricing.lots);
+          self.book.set(party, quote);
+        }
+        for quote in self.quotes.update_agents(now, [trade.buyer, trade.seller], self.book.quotes()) {
+          on_event(&Event::Quote(quote));
+        }
+        on_event(&Event::Trade(trade.clone()));
+        self.trades.push(trade);
+      }
+    }
+    if self.trades.len() == traded && !strategies.awaiting_reports() && risk::find_allowed_trade(&self.assets, &OrderBook::from_quotes(&orders), self.pricing, &self.risk, strategies.priority(), now, |_| {}).is_none() {
+      return Some(self.finish(strategies, StopReason::Exhausted, on_event));
+    }
+    self.end_tick();
+    None
+  }
+
+  // The call auction: everyone's orders at once, every fill at one price, and done.
+  fn step_call(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Option<Stop> {
+    self.call_auction(strategies, &mut on_event);
+    self.end_tick();
+    Some(self.finish(strategies, StopReason::Cleared, on_event))
+  }
+
+  // One tick of a trading session: a call at the open and close of each round and
+  // continuous matching between. Exhausted as for step, by a call that finds nothing
+  // to trade as much as by a continuous tick.
+  fn step_session(&mut self, continuous: Tick, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Option<Stop> {
+    let now = self.clock.now();
+    if Session::at(now, continuous) == Session::Continuous {
+      return self.step_continuous(strategies, on_event);
+    }
+    let traded = self.trades.len();
+    let orders = self.call_auction(strategies, &mut on_event);
+    if self.trades.len() == traded && !strategies.awaiting_reports() && risk::find_allowed_trade(&self.assets, &OrderBook::from_quotes(&orders), self.pricing, &self.risk, strategies.priority(), now, |_| {}).is_none() {
+      return Some(self.finish(strategies, StopReason::Exhausted, on_event));
+    }
+    self.end_tick();
+    None
+  }
+
+  // Collects everyone's orders onto the book and clears them at one price, returning
+  // the orders as intended.
+  fn call_auction(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Vec<(Option<Order>, Option<Order>)> {
+    let now = self.clock.now();
+    let (orders, changes) = timing::time(Phase::OrderGeneration, || {
+      let mut orders = strategies.orders(&self.assets, now);
+      for (quote, (_, balance)) in orders.iter_mut().zip(&self.assets) {
+        withdraw_unbacked(quote, balance, self.pricing.lots);
+      }
+      for (id, quote) in strategies.submit(&orders, now).into_iter().enumerate() {
+        self.book.set(id, quote);
+      }
+      (orders, self.quotes.update(now, self.book.quotes()))
+    });
+    for quote in changes {
+      on_event(&Event::Quote(quote));
+    }
+    let trades = timing::time(Phase::BestPriceSearch, || call::clear(&self.assets, self.book.quotes(), self.pricing, now));
+    for trade in trades {
+      if self.stopping.max_trades.is_some_and(|n| self.trades.len() >= n) {
+        break;
+      }
+      if let Err(reason) = timing::time(Phase::Clearing, || self.risk.check(&trade, &self.assets)) {
+        let rejection = Rejection { tick: now, trade, reason };
+        on_event(&Event::Rejection(rejection.clone()));
+        self.rejections.push(rejection);
+        continue;
+      }
+      execute(&mut self.assets, &trade);
+      // what a party fills still rests on the book until its next order
+      for party in [trade.buyer, trade.seller] {
+        let mut quote = self.book.quotes()[party];
+        withdraw_unbacked(&mut quote, &self.assets[party].1, self.pricing.lots);
+        self.book.set(party, quote);
+      }
+      for quote in self.quotes.update_agents(now, [trade.buyer, trade.seller], self.book.quotes()) {
+        on_event(&Event::Quote(quote));
+      }
+      on_event(&Event::Trade(trade.clone()));
+      self.trades.push(trade);
+    }
+    orders
+  }
+
+  // Everyone's holdings as they can spend them, if anyone can borrow (see spendable).
+  fn lent(&self) -> Option<Vec<(Agent, Balance)>> {
+    (self.pricing.credit.is_some() || self.pricing.short.is_some()).then(|| self.assets.iter().enumerate().map(|(id, &(agent, _))| (agent, self.spendable(id))).collect())
+  }
+
+  // What `id` can spend: what it holds plus what it could still borrow, in B given
+  // credit and in A given short selling.
+  fn spendable(&self, id: AgentId) -> Balance {
+    let mut balance = self.assets[id].1;
+    if let Some(credit) = self.pricing.credit {
+      balance = credit.spendable(balance, self.debts[id]);
+    }
+    if let Some(short) = self.pricing.short {
+      balance = short.spendable(balance, self.shorts[id]);
+    }
+    balance
+  }
+
+  // Spoils what's held, with decay, charges interest on what's owed, with credit, and
+  // moves on to the next tick.
+  fn end_tick(&mut self) {
+    if let Some(decay) = self.pricing.decay {
+      let lost = decay.apply(&mut self.assets);
+      self.spoiled = Balance { a: self.spoiled.a + lost.a, b: self.spoiled.b + lost.b };
+    }
+    if let Some(credit) = self.pricing.credit {
+      credit.accrue(&mut self.debts);
+    }
+    self.clock.advance();
+  }
+
+  // Steps until the run is over.
+  pub fn run(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Stop {
+    loop {
+      if let Some(stop) = self.step(strategies, &mut on_event) {
+        return stop;
+      }
+    }
+  }
+
+  // Steps until the next trade, returning it, or None once the run is over.
+  pub fn step_to_trade(&mut self, strategies: &mut Strategies, mut on_event: impl FnMut(&Event)) -> Option<&Trade> {
+    let before = self.trades.len();
+    while self.trades.len() == before {
+      if self.step(strategies, &mut on_event).is_some() {
+        return None;
+      }
+    }
+    self.trades.last()
+  }
+
+  fn finish(&mut self, strategies: &Strategies, reason: StopReason, mut on_event: impl FnMut(&Event)) -> Stop {
+    timing::time(Phase::InvariantChecks, || {
+      // the government's revenue, what spoiled and what was repaid are still in the
+      // ledger, and the subsidies and loans are not
+      let after = lots::totals(&self.assets);
+      let b = after.b + tax::revenue(&self.trades) - subsidy::outlay(&self.trades) - credit::net_lent(&self.trades) + self.spoiled.b;
+      let a = after.a - short::net_shorted(&self.trades) + self.spoiled.a;
+      lots::check_conservation(lots::totals(&self.initial_assets), Balance { a, b });
+      // strategic quoting and rejected trades can legitimately leave gains from trade on
+      // the table, and so can limits shutting out or clamping the keenest orders, a tax or tariff
+      // the smallest gains (or a subsidy make some that lose), a dark pool the trades
+      // across venues, credit and short selling those left to borrowers at their limit,
+      // and non-linear agents settle short of a corner
+      let linear = self.assets.iter().all(|(agent, _)| agent.utility_fn.is_linear());
+      let untaxed = self.pricing.tax.is_none() && self.pricing.subsidy.is_none() && self.pricing.regions.is_none_or(|r| r.tariff.is_none());
+      let unlimited = self.pricing.floor.is_none() && self.pricing.cap.is_none() && untaxed && self.pricing.dark_pool.is_none() && self.pricing.credit.is_none() && self.pricing.short.is_none();
+      if strategies.is_truthful() && self.rejections.is_empty() && reason == StopReason::Exhausted && linear && unlimited {
+        sanity_check_endpoint(&self.assets);
+      }
+    });
+    let stop = Stop { tick: self.clock.now(), reason };
+    on_event(&Event::Stop(stop));
+    self.stop = Some(stop);
+    stop
+  }
+
+  // The run's outcome, with any positions still short bought in.
+  pub fn into_outcome(mut self) -> SimulationOutcome {
+    if let (Some(_), Some(price)) = (self.pricing.short, short::closing_price(&self.initial_assets, &self.trades)) {
+      short::buy_in(&mut self.assets, &self.shorts, price);
+    }
+    SimulationOutcome {
+      initial_assets: self.initial_assets,
+      final_assets: self.assets,
+      trades: self.trades,
+      rejections: self.rejections,
+      stop: self.stop.expect("the run hasn't stopped yet"),
+      timings: self.timings,
+      decay: self.pricing.decay,
+      short: self.pricing.short,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+  use rand::rngs::StdRng;
+
+  use crate::lots;
+  use crate::market::*;
+  use crate::population::{self, Population};
+
+  fn market(matching: Matching) -> Market {
+    let assets = population::generate(Population::Uniform, 30, &mut StdRng::seed_from_u64(1));
+    Market::new(assets, Arrivals::every_tick(1), PricingRule::default().with_matching(matching), RiskRules::default(), StoppingRules::default())
+  }
+
+  #[test]
+  fn test_step_to_trade() {
+    // stepping to a few trades and then running on is the same run as one call
+    let mut whole = market(Matching::Batch);
+    let stop = whole.run(&mut Strategies::truthful_for(&whole.assets), |_| {});
+    let mut stepped = market(Matching::Batch);
+    let mut strategies = Strategies::truthful_for(&stepped.assets);
+    for n in 1..=3 {
+      assert_eq!(stepped.step_to_trade(&mut strategies, |_| {}), Some(&whole.trades()[n - 1]));
+    }
+    assert_eq!(stepped.run(&mut strategies, |_| {}), stop);
+    assert_eq!(stepped.trades(), whole.trades());
+    assert_eq!(stepped.step_to_trade(&mut strategies, |_| {}), None);
+  }
+
+  #[test]
+  fn test_modes_conserve_goods() {
+    for matching in [Matching::Batch, Matching::Continuous, Matching::Call, Matching::Sessions { continuous: 3 }] {
+      let mut market = market(matching);
+      market.run(&mut Strategies::truthful_for(&market.assets), |_| {});
+      assert!(!market.trades().is_empty(), "{:?}", matching);
+      let outcome = market.into_outcome();
+      lots::check_conservation(lots::totals(&outcome.initial_assets), lots::totals(&outcome.final_assets));
+    }
+  }
+}
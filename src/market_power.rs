@@ -0,0 +1,70 @@
+// Comparing a run where some sellers quote strategically against the competitive
+// (everyone truthful) run on the same population.
+
+use crate::runlog::RunLog;
+use crate::{realized_surplus, AgentId};
+
+// A group of sellers' side of a run.
+struct SellerOutcome {
+  sold_a: f64,
+  received_b: f64,
+  group_surplus: f64,
+  others_surplus: f64,
+}
+
+impl SellerOutcome {
+  fn of(log: &RunLog, group: &[AgentId]) -> SellerOutcome {
+    let sales: Vec<_> = log.trades.iter().filter(|t| group.contains(&t.seller)).collect();
+    let surplus = realized_surplus(&log.initial_assets, &log.final_assets());
+    SellerOutcome {
+      sold_a: sales.iter().map(|t| t.amount_a).sum(),
+      received_b: sales.iter().map(|t| t.amount_b).sum(),
+      group_surplus: group.iter().map(|&i| surplus[i]).sum(),
+      others_surplus: (0..surplus.len()).filter(|i| !group.contains(i)).map(|i| surplus[i]).sum(),
+    }
+  }
+
+  fn price(&self) -> f64 {
+    self.received_b / self.sold_a
+  }
+}
+
+pub fn print_comparison(label: &str, competitive: &RunLog, strategic: &RunLog, group: &[AgentId]) {
+  let c = SellerOutcome::of(competitive, group);
+  let s = SellerOutcome::of(strategic, group);
+  println!("{} ({} sellers) vs competitive baseline:", label, group.len());
+  println!("  A sold by the group:       {} vs {}", s.sold_a, c.sold_a);
+  println!("  mean price of their sales: {} vs {}", s.price(), c.price());
+  println!("  group's surplus (utils):   {} vs {}", s.group_surplus, c.group_surplus);
+  println!("  everyone else's surplus:   {} vs {}", s.others_surplus, c.others_surplus);
+  let total_s = s.group_surplus + s.others_surplus;
+  let total_c = c.group_surplus + c.others_surplus;
+  println!("  total surplus:             {} vs {} (deadweight loss {})", total_s, total_c, total_c - total_s);
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arrivals::Arrivals;
+  use crate::market::Market;
+  use crate::market_power::*;
+  use crate::pricing::PricingRule;
+  use crate::risk::RiskRules;
+  use crate::stopping::StoppingRules;
+  use crate::strategy::Strategies;
+  use crate::{linear_agent, Balance};
+
+  #[test]
+  fn test_seller_outcome() {
+    // a buyer valuing A at 3 B with 10 B and two sellers valuing it at 1 with 2 A each:
+    // the buyer takes all 4 at 2, each seller gaining 2 and the buyer 4
+    let assets = vec![(linear_agent(3.0), Balance { a: 0.0, b: 10.0 }), (linear_agent(1.0), Balance { a: 2.0, b: 0.0 }), (linear_agent(1.0), Balance { a: 2.0, b: 0.0 })];
+    let mut market = Market::new(assets, Arrivals::every_tick(0), PricingRule::default(), RiskRules::default(), StoppingRules::default());
+    market.run(&mut Strategies::truthful_for(&market.assets), |_| {});
+    let log = market.into_outcome().into_log(0, vec![]);
+    let group = SellerOutcome::of(&log, &[1]);
+    assert_eq!((group.sold_a, group.received_b, group.price()), (2.0, 4.0, 2.0));
+    assert_eq!((group.group_surplus, group.others_surplus), (2.0, 6.0));
+    let everyone = SellerOutcome::of(&log, &[1, 2]);
+    assert_eq!((everyone.sold_a, everyone.group_surplus, everyone.others_surplus), (4.0, 4.0, 4.0));
+  }
+}
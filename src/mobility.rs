@@ -0,0 +1,151 @@
+// Inequality and mobility over a run: the Gini of wealth at the end of every period,
+// and how agents move between wealth quintiles from one period to the next, with
+// Shorrocks' index, (n - trace) / (n - 1), as the one-number summary of each
+// transition matrix (0 when nobody changes quintile).
+
+use std::fmt::Write;
+
+use crate::clock::{self, Tick};
+use crate::inequality::gini;
+use crate::runlog::RunLog;
+use crate::summary::{wealth_in_b, Summary};
+use crate::Price;
+
+pub const QUINTILES: usize = 5;
+
+// Each agent's quintile of `values` (e.g. wealth), 0 the lowest; ties go by agent order.
+pub fn quintiles(values: &[f64]) -> Vec<usize> {
+  let n = values.len();
+  let mut ranked: Vec<usize> = (0..n).collect();
+  ranked.sort_by(|&x, &y| values[x].partial_cmp(&values[y]).unwrap());
+  let mut out = vec![0; n];
+  for (rank, id) in ranked.into_iter().enumerate() {
+    out[id] = rank * QUINTILES / n;
+  }
+  out
+}
+
+// Row q is where the agents starting in quintile q end up, as shares of the row.
+pub fn transitions(from: &[usize], to: &[usize]) -> [[f64; QUINTILES]; QUINTILES] {
+  let mut counts = [[0.0; QUINTILES]; QUINTILES];
+  for (&q, &r) in from.iter().zip(to) {
+    counts[q][r] += 1.0;
+  }
+  for row in &mut counts {
+    let total: f64 = row.iter().sum();
+    if total > 0.0 {
+      row.iter_mut().for_each(|x| *x /= total);
+    }
+  }
+  counts
+}
+
+pub fn shorrocks(matrix: &[[f64; QUINTILES]; QUINTILES]) -> f64 {
+  let trace: f64 = (0..QUINTILES).map(|q| matrix[q][q]).sum();
+  (QUINTILES as f64 - trace) / (QUINTILES as f64 - 1.0)
+}
+
+pub struct Mobility {
+  // the start of the run, then the end of every period
+  pub ticks: Vec<Tick>,
+  pub gini: Vec<f64>,
+  // between consecutive ticks
+  pub transitions: Vec<[[f64; QUINTILES]; QUINTILES]>,
+  // from the start to the end
+  pub overall: [[f64; QUINTILES]; QUINTILES],
+}
+
+// Wealth is valued at `price` throughout, so only holdings move it.
+pub fn analyse(log: &RunLog, n_periods: usize, price: Price) -> Mobility {
+  let mut ticks = vec![0];
+  ticks.extend(clock::periods(log.end(), n_periods).into_iter().map(|(_, end)| end));
+  let wealth: Vec<Vec<f64>> = log.assets_at(&ticks).iter().map(|assets| wealth_in_b(assets, price)).collect();
+  let ranks: Vec<Vec<usize>> = wealth.iter().map(|w| quintiles(w)).collect();
+  Mobility {
+    gini: wealth.iter().map(|w| gini(w)).collect(),
+    transitions: ranks.windows(2).map(|w| transitions(&w[0], &w[1])).collect(),
+    overall: transitions(&ranks[0], &ranks[ranks.len() - 1]),
+    ticks,
+  }
+}
+
+pub fn gini_csv(mobility: &Mobility) -> String {
+  let mut out = String::from("tick,gini\n");
+  for (tick, g) in mobility.ticks.iter().zip(&mobility.gini) {
+    writeln!(out, "{},{}", tick, g).unwrap();
+  }
+  out
+}
+
+// One row per (period, from, to) cell; `from_tick` is the period's start.
+pub fn transitions_csv(mobility: &Mobility) -> String {
+  let mut out = String::from("from_tick,to_tick,from_quintile,to_quintile,share\n");
+  for (ticks, matrix) in mobility.ticks.windows(2).zip(&mobility.transitions) {
+    for (q, row) in matrix.iter().enumerate() {
+      for (r, share) in row.iter().enumerate() {
+        writeln!(out, "{},{},{},{},{}", ticks[0], ticks[1], q, r, share).unwrap();
+      }
+    }
+  }
+  out
+}
+
+pub fn print_report(log: &RunLog, n_periods: usize) {
+  let price = Summary::of(log).valuation_price;
+  let mobility = analyse(log, n_periods, price);
+  println!("inequality and mobility (wealth valued at {}):", price);
+  let series: Vec<String> = mobility.ticks.iter().zip(&mobility.gini).map(|(t, g)| format!("{}: {:.4}", t, g)).collect();
+  println!("  Gini by tick: {}", series.join(", "));
+  let indices: Vec<String> = mobility.transitions.iter().map(|m| format!("{:.3}", shorrocks(m))).collect();
+  println!("  Shorrocks mobility per period: {}", indices.join(", "));
+  println!("  quintile transitions, start to end (rows: from poorest; Shorrocks {:.3}):", shorrocks(&mobility.overall));
+  for row in &mobility.overall {
+    let row: Vec<String> = row.iter().map(|x| format!("{:.2}", x)).collect();
+    println!("    [{}]", row.join(", "));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mobility::*;
+  use crate::{linear_agent, Balance, Trade};
+
+  #[test]
+  fn test_mobility() {
+    assert_eq!(quintiles(&[5.0, 1.0, 4.0, 2.0, 3.0]), vec![4, 0, 3, 1, 2]);
+    let same = transitions(&[0, 1, 2, 3, 4], &[0, 1, 2, 3, 4]);
+    assert_eq!(shorrocks(&same), 0.0);
+    let swapped = transitions(&[0, 1, 2, 3, 4], &[4, 3, 2, 1, 0]);
+    assert_eq!(shorrocks(&swapped), 1.0);
+
+    // the poorest agent buys everything the richest has for all its B, and trades places
+    let agent = linear_agent(1.0);
+    let balance = |a, b| (agent, Balance { a, b });
+    let log = RunLog {
+      seed: 0,
+      initial_assets: vec![balance(0.0, 1.0), balance(2.0, 0.0), balance(3.0, 0.0), balance(4.0, 0.0), balance(5.0, 0.0)],
+      quotes: vec![],
+      rejections: vec![],
+      trades: vec![Trade { tick: 1, buyer: 0, seller: 4, amount_a: 5.0, amount_b: 1.0, bid_price: 1.0, ask_price: 1.0, ..Trade::default() }],
+      stop: None,
+      decay: None,
+      short: None,
+    };
+    let mobility = analyse(&log, 2, 1.0);
+    assert_eq!(mobility.ticks, vec![0, 1, 2]);
+    assert_eq!(mobility.transitions[0], same);
+    assert_eq!(mobility.overall, swapped_ends());
+    assert!(mobility.gini[2] > 0.0);
+    assert_eq!(gini_csv(&mobility).lines().count(), 4);
+    assert_eq!(transitions_csv(&mobility).lines().count(), 1 + 2 * 25);
+  }
+
+  // agents 0 and 4 swap the top and bottom quintiles; the middle stays put
+  fn swapped_ends() -> [[f64; QUINTILES]; QUINTILES] {
+    let mut m = [[0.0; QUINTILES]; QUINTILES];
+    for (q, r) in [(0, 4), (1, 1), (2, 2), (3, 3), (4, 0)] {
+      m[q][r] = 1.0;
+    }
+    m
+  }
+}
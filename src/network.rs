@@ -0,0 +1,167 @@
+// The realized trade network: agents are nodes, and two agents share an edge if
+// they traded with each other at least once (in either direction).
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{AgentId, Trade};
+use crate::stats::pearson;
+
+pub struct TradeNetwork {
+  // neighbors[i] maps each counterparty of agent i to the number of trades between them
+  neighbors: Vec<BTreeMap<AgentId, usize>>,
+}
+
+impl TradeNetwork {
+  pub fn from_trades(n_agents: usize, trades: &[Trade]) -> TradeNetwork {
+    let mut neighbors = vec![BTreeMap::new(); n_agents];
+    for trade in trades {
+      *neighbors[trade.buyer].entry(trade.seller).or_insert(0) += 1;
+      *neighbors[trade.seller].entry(trade.buyer).or_insert(0) += 1;
+    }
+    TradeNetwork { neighbors }
+  }
+
+  pub fn n_agents(&self) -> usize {
+    self.neighbors.len()
+  }
+
+  pub fn n_edges(&self) -> usize {
+    self.neighbors.iter().map(|ns| ns.len()).sum::<usize>() / 2
+  }
+
+  pub fn neighbors(&self, agent: AgentId) -> impl Iterator<Item=AgentId> + '_ {
+    self.neighbors[agent].keys().copied()
+  }
+
+  // (counterparty, number of trades with them)
+  pub fn weighted_neighbors(&self, agent: AgentId) -> impl Iterator<Item=(AgentId, usize)> + '_ {
+    self.neighbors[agent].iter().map(|(&v, &w)| (v, w))
+  }
+
+  pub fn degree(&self, agent: AgentId) -> usize {
+    self.neighbors[agent].len()
+  }
+
+  // degree -> number of agents with that degree
+  pub fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+    let mut result = BTreeMap::new();
+    for agent in 0..self.n_agents() {
+      *result.entry(self.degree(agent)).or_insert(0) += 1;
+    }
+    result
+  }
+
+  // Fraction of pairs of the agent's counterparties that also traded with each other.
+  pub fn clustering(&self, agent: AgentId) -> f64 {
+    let ns: Vec<AgentId> = self.neighbors(agent).collect();
+    if ns.len() < 2 {
+      return 0.0;
+    }
+    let mut links = 0;
+    for (i, &u) in ns.iter().enumerate() {
+      for &v in &ns[i+1..] {
+        if self.neighbors[u].contains_key(&v) {
+          links += 1;
+        }
+      }
+    }
+    2.0 * links as f64 / (ns.len() * (ns.len() - 1)) as f64
+  }
+
+  // Unweighted betweenness centrality (Brandes' algorithm), normalized by the number
+  // of pairs of other nodes.
+  pub fn betweenness(&self) -> Vec<f64> {
+    let n = self.n_agents();
+    let mut centrality = vec![0.0; n];
+    for source in 0..n {
+      let mut stack = vec![];
+      let mut predecessors: Vec<Vec<AgentId>> = vec![vec![]; n];
+      let mut n_paths = vec![0.0; n];
+      let mut distance: Vec<Option<usize>> = vec![None; n];
+      n_paths[source] = 1.0;
+      distance[source] = Some(0);
+      let mut queue = VecDeque::new();
+      queue.push_back(source);
+      while let Some(v) = queue.pop_front() {
+        stack.push(v);
+        let dv = distance[v].unwrap();
+        for w in self.neighbors(v) {
+          if distance[w].is_none() {
+            distance[w] = Some(dv + 1);
+            queue.push_back(w);
+          }
+          if distance[w] == Some(dv + 1) {
+            n_paths[w] += n_paths[v];
+            predecessors[w].push(v);
+          }
+        }
+      }
+      let mut dependency = vec![0.0; n];
+      while let Some(w) = stack.pop() {
+        for &v in &predecessors[w] {
+          dependency[v] += n_paths[v] / n_paths[w] * (1.0 + dependency[w]);
+        }
+        if w != source {
+          centrality[w] += dependency[w];
+        }
+      }
+    }
+    // each undirected path was counted from both ends
+    let pairs = if n > 2 { ((n - 1) * (n - 2)) as f64 } else { 1.0 };
+    centrality.iter().map(|c| c / pairs).collect()
+  }
+
+  pub fn triangles(&self) -> usize {
+    let mut result = 0;
+    for u in 0..self.n_agents() {
+      for v in self.neighbors(u).filter(|&v| v > u) {
+        result += self.neighbors(v).filter(|&w| w > v && self.neighbors[u].contains_key(&w)).count();
+      }
+    }
+    result
+  }
+}
+
+pub fn print_report(network: &TradeNetwork, surplus: &[f64]) {
+  println!("trade network: {} agents, {} edges, {} triangles", network.n_agents(), network.n_edges(), network.triangles());
+  println!("degree distribution:");
+  for (degree, count) in network.degree_distribution() {
+    println!("  degree {}: {} agents", degree, count);
+  }
+
+  let degree: Vec<f64> = (0..network.n_agents()).map(|i| network.degree(i) as f64).collect();
+  let clustering: Vec<f64> = (0..network.n_agents()).map(|i| network.clustering(i)).collect();
+  let betweenness = network.betweenness();
+  println!("average clustering: {}", crate::stats::mean(&clustering));
+
+  println!("correlation with realized surplus:");
+  for (name, metric) in [("degree", &degree), ("clustering", &clustering), ("betweenness", &betweenness)] {
+    match pearson(metric, surplus) {
+      Some(r) => println!("  {}: {}", name, r),
+      None => println!("  {}: undefined (no variance)", name),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::network::*;
+
+  fn trade(buyer: AgentId, seller: AgentId) -> Trade {
+    Trade { tick: 0, buyer, seller, amount_a: 1.0, amount_b: 1.0, bid_price: 1.0, ask_price: 1.0, ..Trade::default() }
+  }
+
+  #[test]
+  fn test_triangle_plus_pendant() {
+    // 0-1-2 triangle, with 3 hanging off of 2
+    let network = TradeNetwork::from_trades(4, &[trade(0, 1), trade(1, 2), trade(2, 0), trade(3, 2), trade(2, 3)]);
+    assert_eq!(network.n_edges(), 4);
+    assert_eq!(network.triangles(), 1);
+    assert_eq!(network.degree_distribution().into_iter().collect::<Vec<_>>(), vec![(1, 1), (2, 2), (3, 1)]);
+    assert_eq!(network.clustering(0), 1.0);
+    assert_eq!(network.clustering(2), 1.0 / 3.0);
+    assert_eq!(network.clustering(3), 0.0);
+    // every shortest path into 3 goes through 2: pairs (0,3) and (1,3) out of 3 pairs
+    assert_eq!(network.betweenness(), vec![0.0, 0.0, 2.0 / 3.0, 0.0]);
+  }
+}
@@ -0,0 +1,67 @@
+// Flags runs that look pathological rather than settled: the price is still moving
+// and either keeps swinging back and forth without damping, or trades stop getting
+// smaller. Only the second half of the trades is examined, and short runs are never
+// flagged.
+
+use crate::stats::mean;
+use crate::Trade;
+
+const MIN_TRADES: usize = 20;
+// a price moving less than this (relative) per trade has settled, whatever else happens
+const SETTLED: f64 = 0.01;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum NonConvergence {
+  // most consecutive price moves reverse direction, and the moves aren't shrinking
+  UndampedOscillation,
+  // trades late in the run are as big as earlier ones
+  SizesNotShrinking,
+}
+
+pub fn detect(trades: &[Trade]) -> Option<NonConvergence> {
+  if trades.len() < MIN_TRADES {
+    return None;
+  }
+  let tail = &trades[trades.len() / 2..];
+  let prices: Vec<f64> = tail.iter().map(|t| t.price_per_a_in_b()).collect();
+  let moves: Vec<f64> = prices.windows(2).map(|w| w[1] - w[0]).collect();
+  let reversals = moves.windows(2).filter(|w| w[0] * w[1] < 0.0).count();
+  let (early, late) = moves.split_at(moves.len() / 2);
+  let amplitude = |ms: &[f64]| mean(&ms.iter().map(|m| m.abs()).collect::<Vec<_>>());
+  if amplitude(late) < SETTLED * mean(&prices) {
+    return None;
+  }
+  if 2 * reversals >= moves.len() - 1 && amplitude(late) >= amplitude(early) {
+    return Some(NonConvergence::UndampedOscillation);
+  }
+
+  let sizes: Vec<f64> = tail.iter().map(|t| t.amount_b).collect();
+  let (early, late) = sizes.split_at(sizes.len() / 2);
+  if mean(late) >= mean(early) {
+    return Some(NonConvergence::SizesNotShrinking);
+  }
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::nonconvergence::*;
+
+  #[test]
+  fn test_detect() {
+    let trade = |price: f64, size: f64| Trade { tick: 0, buyer: 0, seller: 1, amount_a: size / price, amount_b: size, bid_price: price, ask_price: price, ..Trade::default() };
+    // damped oscillation with shrinking trades: fine
+    let settling: Vec<Trade> = (0..40).map(|i| trade(2.0 + (-0.9f64).powi(i), 100.0 - i as f64)).collect();
+    assert_eq!(detect(&settling), None);
+    // a steady two-cycle
+    let cycling: Vec<Trade> = (0..40).map(|i| trade(if i % 2 == 0 { 1.0 } else { 3.0 }, 100.0 - i as f64)).collect();
+    assert_eq!(detect(&cycling), Some(NonConvergence::UndampedOscillation));
+    // still drifting, and trades aren't getting any smaller
+    let constant_size: Vec<Trade> = (0..40).map(|i| trade(2.0 + 0.1 * i as f64, 10.0)).collect();
+    assert_eq!(detect(&constant_size), Some(NonConvergence::SizesNotShrinking));
+    // settled to within a fraction of a percent, so sizes don't matter
+    let settled: Vec<Trade> = (0..40).map(|i| trade(2.0 + 0.001 * (i % 2) as f64, 10.0)).collect();
+    assert_eq!(detect(&settled), None);
+    assert_eq!(detect(&cycling[..10]), None);
+  }
+}
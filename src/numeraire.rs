@@ -0,0 +1,65 @@
+// Which good reported prices are quoted in. Internally every price is B per A; with A
+// as the numeraire, metrics, exports, and plots quote the price of B in A instead.
+// Surplus is in utils (or B; see welfare::Currency) and wealth Ginis are scale-free,
+// so neither changes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Price;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Numeraire {
+  A,
+  #[default]
+  B,
+}
+
+impl Numeraire {
+  pub fn parse(s: &str) -> Result<Numeraire, String> {
+    match s {
+      "a" | "A" => Ok(Numeraire::A),
+      "b" | "B" => Ok(Numeraire::B),
+      _ => Err(format!("unknown numeraire {:?} (expected a or b)", s)),
+    }
+  }
+
+  // A price of A in B, quoted in this numeraire.
+  pub fn price(&self, b_per_a: Price) -> Price {
+    match self {
+      Numeraire::A => 1.0 / b_per_a,
+      Numeraire::B => b_per_a,
+    }
+  }
+
+  // The best (bid, ask) for the good being priced. Quoted in A, a bid for A is an
+  // ask for B, so the sides swap.
+  pub fn quotes(&self, bid: Option<Price>, ask: Option<Price>) -> (Option<Price>, Option<Price>) {
+    match self {
+      Numeraire::A => (ask.map(|p| self.price(p)), bid.map(|p| self.price(p))),
+      Numeraire::B => (bid, ask),
+    }
+  }
+
+  pub fn unit(&self) -> &'static str {
+    match self {
+      Numeraire::A => "A per B",
+      Numeraire::B => "B per A",
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::numeraire::*;
+
+  #[test]
+  fn test_quotes_in_a() {
+    assert_eq!(Numeraire::parse("a"), Ok(Numeraire::A));
+    assert!(Numeraire::parse("c").is_err());
+    assert_eq!(Numeraire::B.quotes(Some(2.0), Some(4.0)), (Some(2.0), Some(4.0)));
+    // bidding 2 B for an A is offering an A for 2 B, i.e. asking 1/2 A per B
+    assert_eq!(Numeraire::A.quotes(Some(2.0), Some(4.0)), (Some(0.25), Some(0.5)));
+    assert_eq!(Numeraire::A.quotes(Some(2.0), None), (None, Some(0.5)));
+  }
+}
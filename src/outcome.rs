@@ -0,0 +1,44 @@
+// What execute_all_trades hands back: everything needed to analyse a run without
+// having listened to its events.
+
+use crate::decay::Decay;
+use crate::risk::Rejection;
+use crate::runlog::{Quote, RunLog};
+use crate::short::Short;
+use crate::stopping::Stop;
+use crate::summary::Summary;
+use crate::timing::Timings;
+use crate::{Agent, Balance, Trade};
+
+#[derive(Debug, Clone)]
+pub struct SimulationOutcome {
+  pub initial_assets: Vec<(Agent, Balance)>,
+  pub final_assets: Vec<(Agent, Balance)>,
+  pub trades: Vec<Trade>,
+  pub rejections: Vec<Rejection>,
+  pub stop: Stop,
+  pub timings: Timings,
+  pub decay: Option<Decay>,
+  pub short: Option<Short>,
+}
+
+impl SimulationOutcome {
+  // Quotes aren't kept here since most callers don't need them; pass any collected
+  // from the Quote events.
+  pub fn into_log(self, seed: u64, quotes: Vec<Quote>) -> RunLog {
+    RunLog {
+      seed,
+      initial_assets: self.initial_assets,
+      quotes,
+      rejections: self.rejections,
+      trades: self.trades,
+      stop: Some(self.stop),
+      decay: self.decay,
+      short: self.short,
+    }
+  }
+
+  pub fn summary(&self, seed: u64) -> Summary {
+    Summary::of(&self.clone().into_log(seed, vec![]))
+  }
+}
@@ -0,0 +1,204 @@
+// Output layout for runs. Each run gets its own timestamped directory:
+//
+//   <out-dir>/<timestamp>-seed<N>/
+//     manifest.json       args and config, crate version, and the files below
+//     run.ndjson          the replayable run log
+//     trades.csv
+//     initial_state.json, final_state.json
+//     curves_start.csv, curves_end.csv
+//     curve_fits.json     parametric fits to both
+//     report.html, plots/*.svg
+//     gini.csv, mobility.csv  the Gini and quintile transitions by period
+//     depth.csv           with --top-k, the top of the book at every match
+//
+// A multi-seed sweep gets `<out-dir>/<timestamp>-sweep/` with one such
+// subdirectory per cell and a manifest listing the cells.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::numeraire::Numeraire;
+use crate::runlog::{self, RunLog};
+use crate::summary::Summary;
+use crate::cli::{flag_parsed, n_periods};
+use crate::{curve_fit, depth, dispersion, mobility, report, supply_demand_curves, Agent, Balance};
+
+pub struct RunDir {
+  path: PathBuf,
+  files: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RunManifest<'a> {
+  simmarket_version: &'a str,
+  created: &'a str,
+  args: &'a [String],
+  config: &'a Config,
+  seed: u64,
+  n_agents: usize,
+  n_trades: usize,
+  files: &'a [String],
+}
+
+#[derive(Serialize)]
+struct SweepManifest<'a> {
+  simmarket_version: &'a str,
+  created: &'a str,
+  args: &'a [String],
+  config: &'a Config,
+  cells: &'a [String],
+}
+
+fn timestamp() -> String {
+  format_utc(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64)
+}
+
+// Seconds since the epoch as UTC, formatted like 20261014T132600Z.
+fn format_utc(secs: i64) -> String {
+  let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+  // civil-from-days, per Howard Hinnant's date algorithms
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+impl RunDir {
+  // Creates `<base>/<timestamp>-<label>`, adding a numeric suffix if that's taken.
+  pub fn create_timestamped(base: &str, label: &str) -> io::Result<RunDir> {
+    fs::create_dir_all(base)?;
+    let stem = format!("{}-{}", timestamp(), label);
+    let mut path = Path::new(base).join(&stem);
+    let mut suffix = 1;
+    while path.exists() {
+      suffix += 1;
+      path = Path::new(base).join(format!("{}-{}", stem, suffix));
+    }
+    RunDir::create(path)
+  }
+
+  fn create(path: PathBuf) -> io::Result<RunDir> {
+    fs::create_dir_all(&path)?;
+    Ok(RunDir { path, files: vec![] })
+  }
+
+  // A subdirectory for one cell of a sweep.
+  pub fn cell(&self, name: &str) -> io::Result<RunDir> {
+    RunDir::create(self.path.join(name))
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  // Path for a new file in this directory, remembered for the manifest.
+  pub fn file(&mut self, name: &str) -> io::Result<PathBuf> {
+    let path = self.path.join(name);
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    self.files.push(name.to_string());
+    Ok(path)
+  }
+
+  pub fn write(&mut self, name: &str, contents: &str) -> io::Result<()> {
+    let path = self.file(name)?;
+    fs::write(path, contents)
+  }
+
+  pub fn write_run(&mut self, args: &[String], config: &Config, log: &RunLog) -> io::Result<()> {
+    let final_assets = log.final_assets();
+
+    let path = self.file("run.ndjson")?;
+    runlog::write(path.to_str().unwrap(), log)?;
+
+    // the quotes stay B per A; `price` is the trade price in the config's numeraire
+    let numeraire = config.numeraire;
+    let mut trades_csv = String::from("seq,tick,buyer,seller,amount_a,amount_b,price_per_a_in_b,bid_price,ask_price,walrasian_price,price\n");
+    for (i, (t, (_, walrasian))) in log.trades.iter().zip(dispersion::price_gaps(log)).enumerate() {
+      trades_csv.push_str(&format!("{},{},{},{},{},{},{},{},{},{},{}\n", i, t.tick, t.buyer, t.seller, t.amount_a, t.amount_b, t.price_per_a_in_b(), t.bid_price, t.ask_price, walrasian, numeraire.price(t.price_per_a_in_b())));
+    }
+    self.write("trades.csv", &trades_csv)?;
+
+    self.write("initial_state.json", &state_json(&log.initial_assets))?;
+    self.write("final_state.json", &state_json(&final_assets))?;
+    self.write("curves_start.csv", &curves_csv(&log.initial_assets, numeraire))?;
+    self.write("curves_end.csv", &curves_csv(&final_assets, numeraire))?;
+    let fits = serde_json::json!({
+      "start": curve_fit::fit_market(&log.initial_assets),
+      "end": curve_fit::fit_market(&final_assets),
+    });
+    self.write("curve_fits.json", &serde_json::to_string_pretty(&fits)?)?;
+
+    let n_periods = n_periods(args).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mobility = mobility::analyse(log, n_periods, Summary::of(log).valuation_price);
+    self.write("gini.csv", &mobility::gini_csv(&mobility))?;
+    self.write("mobility.csv", &mobility::transitions_csv(&mobility))?;
+    if let Some(k) = flag_parsed(args, "--top-k").map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))? {
+      self.write("depth.csv", &depth::csv(&depth::at_trades(log, k)))?;
+    }
+    self.write("report.html", &report::html(log, numeraire))?;
+    for (name, chart) in report::charts(log, numeraire) {
+      self.write(&format!("plots/{}.svg", name), &chart.to_svg())?;
+    }
+
+    let manifest = RunManifest {
+      simmarket_version: env!("CARGO_PKG_VERSION"),
+      created: &timestamp(),
+      args,
+      config,
+      seed: log.seed,
+      n_agents: log.initial_assets.len(),
+      n_trades: log.trades.len(),
+      files: &self.files,
+    };
+    fs::write(self.path.join("manifest.json"), serde_json::to_string_pretty(&manifest)?)
+  }
+
+  pub fn write_sweep_manifest(&self, args: &[String], config: &Config, cells: &[String]) -> io::Result<()> {
+    let manifest = SweepManifest {
+      simmarket_version: env!("CARGO_PKG_VERSION"),
+      created: &timestamp(),
+      args,
+      config,
+      cells,
+    };
+    fs::write(self.path.join("manifest.json"), serde_json::to_string_pretty(&manifest)?)
+  }
+}
+
+fn state_json(assets: &[(Agent, Balance)]) -> String {
+  serde_json::to_string_pretty(&runlog::states(assets)).unwrap()
+}
+
+// Supply and demand of A, against its price in the numeraire.
+fn curves_csv(assets: &[(Agent, Balance)], numeraire: Numeraire) -> String {
+  let mut out = String::from("price,supply,demand\n");
+  for (price, supply, demand) in supply_demand_curves(assets) {
+    out.push_str(&format!("{},{},{}\n", numeraire.price(price), supply, demand));
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::outdir::*;
+
+  #[test]
+  fn test_format_utc() {
+    assert_eq!(format_utc(0), "19700101T000000Z");
+    assert_eq!(format_utc(951782400), "20000229T000000Z");
+    assert_eq!(format_utc(1791984645), "20261014T133045Z");
+  }
+}
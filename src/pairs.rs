@@ -0,0 +1,251 @@
+// Statistics per traded pair, how the pairs' prices move together, and whether they
+// are consistent: for any three goods, the price of x in y times y in z should be the
+// price of x in z, or there's a triangular arbitrage. Goods are keyed by their index
+// in a registry of names. A core run trades A (good 0) against B (good 1) alone, so it
+// has one pair, a 1x1 correlation matrix and no triangles; an economy of more goods
+// (see goods) has a pair per book, and at most one trade a tick.
+
+use crate::clock::Tick;
+use crate::goods::Goods;
+use crate::runlog::RunLog;
+use crate::stats::{mean, pearson};
+use crate::{best_quotes, in_book, Bundle, Good, GoodsTrade, Preferences, Price};
+
+// a price within this (relative) of the last price has settled
+const SETTLED: f64 = 0.01;
+// a triangle whose cross rates disagree by more than this (in logs) is an arbitrage
+const ARBITRAGE: f64 = 0.01;
+
+pub struct PairStats {
+  // prices are in `quote` per `base`
+  pub base: Good,
+  pub quote: Good,
+  pub trades: usize,
+  pub volume_base: f64,
+  pub volume_quote: f64,
+  pub final_price: Option<Price>,
+  pub final_spread: Option<Price>,
+  // the first trade from which every later price stays within SETTLED of the last one
+  pub settled_at: Option<usize>,
+  // (tick, price) of every trade
+  pub path: Vec<(Tick, Price)>,
+}
+
+pub fn settled_at(prices: &[Price]) -> Option<usize> {
+  let last = *prices.last()?;
+  let unsettled = prices.iter().rposition(|p| (p - last).abs() > SETTLED * last);
+  Some(unsettled.map_or(0, |i| i + 1))
+}
+
+pub fn pair_stats(log: &RunLog) -> Vec<PairStats> {
+  let path: Vec<(Tick, Price)> = log.trades.iter().map(|t| (t.tick, t.price_per_a_in_b())).collect();
+  let prices: Vec<Price> = path.iter().map(|(_, p)| *p).collect();
+  let (bid, ask) = best_quotes(&log.final_assets());
+  vec![PairStats {
+    base: 0,
+    quote: 1,
+    trades: log.trades.len(),
+    volume_base: log.trades.iter().map(|t| t.amount_a).sum(),
+    volume_quote: log.trades.iter().map(|t| t.amount_b).sum(),
+    final_price: prices.last().copied(),
+    final_spread: ask.zip(bid).map(|(ask, bid)| ask - bid),
+    settled_at: settled_at(&prices),
+    path,
+  }]
+}
+
+// Every book's statistics over the `trades` of an economy of `goods`, ending with
+// `assets`, as pair_stats has them for a core run.
+pub fn goods_pair_stats(goods: &Goods, assets: &[(Preferences, Bundle)], trades: &[GoodsTrade]) -> Vec<PairStats> {
+  goods.books().into_iter().map(|(base, quote)| {
+    let in_pair: Vec<&GoodsTrade> = trades.iter().filter(|t| t.book == (base, quote)).collect();
+    let path: Vec<(Tick, Price)> = in_pair.iter().map(|t| (t.trade.tick, t.trade.price_per_a_in_b())).collect();
+    let prices: Vec<Price> = path.iter().map(|(_, p)| *p).collect();
+    let (bid, ask) = best_quotes(&in_book(assets, (base, quote)));
+    PairStats {
+      base,
+      quote,
+      trades: in_pair.len(),
+      volume_base: in_pair.iter().fold(0.0, |v, t| v + t.trade.amount_a),
+      volume_quote: in_pair.iter().fold(0.0, |v, t| v + t.trade.amount_b),
+      final_price: prices.last().copied(),
+      final_spread: ask.zip(bid).map(|(ask, bid)| ask - bid),
+      settled_at: settled_at(&prices),
+      path,
+    }
+  }).collect()
+}
+
+// Log prices at every tick, carrying each pair's last price forward, from the first
+// tick where every pair has traded. Every path must be non-empty.
+fn tick_levels(paths: &[&[(Tick, Price)]]) -> (Tick, Vec<Vec<f64>>) {
+  let start = paths.iter().map(|p| p[0].0).max().unwrap_or(0);
+  let end = paths.iter().map(|p| p[p.len() - 1].0).max().unwrap_or(0);
+  let levels = paths.iter().map(|path| {
+    let mut i = 0;
+    let mut levels = vec![];
+    for tick in start..=end {
+      while i + 1 < path.len() && path[i + 1].0 <= tick {
+        i += 1;
+      }
+      levels.push(path[i].1.ln());
+    }
+    levels
+  }).collect();
+  (start, levels)
+}
+
+// Correlation of tick-to-tick price changes between every two pairs, None where a
+// pair's price never moves.
+pub fn price_correlations(pairs: &[PairStats]) -> Vec<Vec<Option<f64>>> {
+  let paths: Vec<&[(Tick, Price)]> = pairs.iter().map(|p| &p.path[..]).collect();
+  if paths.iter().any(|p| p.is_empty()) {
+    return vec![vec![None; pairs.len()]; pairs.len()];
+  }
+  let returns: Vec<Vec<f64>> = tick_levels(&paths).1.iter().map(|levels| levels.windows(2).map(|w| w[1] - w[0]).collect()).collect();
+  returns.iter().map(|xs| returns.iter().map(|ys| pearson(xs, ys)).collect()).collect()
+}
+
+pub struct Triangle {
+  pub goods: [Good; 3],
+  // (tick, ln(price of x in y * y in z / x in z)), from when all three pairs have traded
+  pub violations: Vec<(Tick, f64)>,
+}
+
+// Every three goods whose three pairs have all traded, with how far their cross rates
+// disagree at each tick.
+pub fn triangles(pairs: &[PairStats]) -> Vec<Triangle> {
+  let mut goods: Vec<Good> = pairs.iter().flat_map(|p| [p.base, p.quote]).collect();
+  goods.sort_unstable();
+  goods.dedup();
+  // the pair quoting x in y, and whether it's quoted the other way round
+  let find = |x: Good, y: Good| pairs.iter().find_map(|p| match (p.base, p.quote) {
+    (b, q) if (b, q) == (x, y) => Some((p, false)),
+    (b, q) if (b, q) == (y, x) => Some((p, true)),
+    _ => None,
+  }).filter(|(p, _)| !p.path.is_empty());
+  let mut out = vec![];
+  for (i, &x) in goods.iter().enumerate() {
+    for (j, &y) in goods.iter().enumerate().skip(i + 1) {
+      for &z in &goods[j + 1..] {
+        let (Some(xy), Some(yz), Some(xz)) = (find(x, y), find(y, z), find(x, z)) else { continue };
+        let (start, levels) = tick_levels(&[&xy.0.path, &yz.0.path, &xz.0.path]);
+        let sign = |inverted: bool| if inverted { -1.0 } else { 1.0 };
+        let violations = (0..levels[0].len())
+          .map(|t| (start + t as Tick, sign(xy.1) * levels[0][t] + sign(yz.1) * levels[1][t] - sign(xz.1) * levels[2][t]))
+          .collect();
+        out.push(Triangle { goods: [x, y, z], violations });
+      }
+    }
+  }
+  out
+}
+
+// The `pairs` of goods named by `names`, e.g. ["A", "B"] for a core run.
+pub fn print_report(names: &[impl AsRef<str>], pairs: &[PairStats]) {
+  let show = |p: Option<f64>| p.map_or("none".to_string(), |p| p.to_string());
+  let name = |good: Good| names[good].as_ref();
+  println!("per-pair statistics:");
+  for pair in pairs {
+    println!("  {}/{}: {} trades, {} {} for {} {}, final price {}, final spread {}, settled from trade {}",
+      name(pair.base), name(pair.quote), pair.trades, pair.volume_base, name(pair.base), pair.volume_quote, name(pair.quote),
+      show(pair.final_price), show(pair.final_spread), pair.settled_at.map_or("none".to_string(), |i| i.to_string()));
+  }
+  println!("  cross-price correlations of per-tick log returns:");
+  for (pair, row) in pairs.iter().zip(price_correlations(pairs)) {
+    let row: Vec<String> = row.into_iter().map(show).collect();
+    println!("    {}/{}: [{}]", name(pair.base), name(pair.quote), row.join(", "));
+  }
+  let triangles = triangles(pairs);
+  if triangles.is_empty() {
+    println!("  no-arbitrage check: no three goods have all traded with each other");
+  }
+  for triangle in &triangles {
+    let [x, y, z] = triangle.goods.map(name);
+    let abs: Vec<f64> = triangle.violations.iter().map(|(_, v)| v.abs()).collect();
+    let (early, late) = abs.split_at(abs.len() / 2);
+    let arbitrage = abs.iter().filter(|&&v| v > ARBITRAGE).count();
+    println!("  no-arbitrage {}/{} * {}/{} vs {}/{}: mean |log gap| {} (first half {}, second half {}), final {}, {} of {} ticks beyond {}",
+      x, y, y, z, x, z, mean(&abs), mean(early), mean(late), triangle.violations.last().unwrap().1, arbitrage, abs.len(), ARBITRAGE);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
+
+  use crate::config::Config;
+  use crate::goods::{book_stats, random_population, run};
+  use crate::pairs::*;
+  use crate::utility::UtilityFn;
+  use crate::Trade;
+
+  #[test]
+  fn test_pairs() {
+    assert_eq!(settled_at(&[1.0, 3.0, 2.0, 2.01, 2.0]), Some(2));
+    assert_eq!(settled_at(&[2.0]), Some(0));
+    assert_eq!(settled_at(&[]), None);
+
+    let pair = |path: Vec<(Tick, Price)>| PairStats {
+      base: 0, quote: 1, trades: path.len(), volume_base: 0.0, volume_quote: 0.0,
+      final_price: None, final_spread: None, settled_at: None, path,
+    };
+    // the second pair moves at the same ticks by twice as much in logs; the third jumps once
+    let pairs = vec![
+      pair(vec![(0, 1.0), (1, 2.0), (3, 1.0), (4, 4.0)]),
+      pair(vec![(0, 1.0), (1, 4.0), (3, 1.0), (4, 16.0)]),
+      pair(vec![(0, 5.0), (2, 6.0)]),
+    ];
+    let correlations = price_correlations(&pairs);
+    assert!((correlations[0][1].unwrap() - 1.0).abs() < 1e-12);
+    assert!(correlations[0][2].unwrap() < 0.0);
+    assert_eq!(price_correlations(&[pair(vec![(0, 1.0)])]), vec![vec![None]]);
+
+    // 2 B per A and 3 C per B are consistent with 6 C per A, quoted here as A per C
+    let quoted = |base, quote, path| PairStats { base, quote, ..pair(path) };
+    let pairs = vec![
+      quoted(0, 1, vec![(0, 2.0)]),
+      quoted(1, 2, vec![(0, 3.0), (2, 3.3)]),
+      quoted(2, 0, vec![(1, 1.0 / 6.0)]),
+    ];
+    let triangles = triangles(&pairs);
+    assert_eq!(triangles.len(), 1);
+    assert_eq!(triangles[0].goods, [0, 1, 2]);
+    let violations: Vec<f64> = triangles[0].violations.iter().map(|(_, v)| *v).collect();
+    assert_eq!(triangles[0].violations[0].0, 1);
+    assert!(violations[0].abs() < 1e-12);
+    assert!((violations[1] - 1.1f64.ln()).abs() < 1e-12);
+    assert!(super::triangles(&pairs[..2]).is_empty());
+  }
+
+  #[test]
+  fn test_goods_pairs() {
+    // a pair per book of a three-good economy, keyed by good, its trades in order
+    let goods = Goods::parse("a,b,c").unwrap();
+    let mut assets = random_population(3, 30, UtilityFn::Linear, &mut StdRng::seed_from_u64(0));
+    let (trades, _) = run(&goods, &mut assets, &Config { n_agents: 30, ..Config::default() }, 0);
+    let pairs = goods_pair_stats(&goods, &assets, &trades);
+    assert_eq!(pairs.iter().map(|p| (p.base, p.quote)).collect::<Vec<_>>(), goods.books());
+    assert_eq!(pairs.iter().fold(0, |n, p| n + p.trades), trades.len());
+    for (pair, book) in pairs.iter().zip(book_stats(&goods, &trades)) {
+      assert_eq!((pair.trades, pair.final_price.is_some()), (book.trades, book.vwap.is_some()));
+      assert!((pair.volume_base - book.volume).abs() < 1e-9 * book.volume.max(1.0));
+      // nothing crosses once the market has run dry
+      assert!(pair.final_spread.is_none_or(|s| s >= 0.0));
+    }
+    assert_eq!(triangles(&pairs).len(), 1);
+
+    // a cycle with a known arbitrage: 2 b per a and 3 c per b, but only 5 c per a, until
+    // a/c trades again at 6
+    let trade = |tick, book, amount_b| GoodsTrade { book, trade: Trade { tick, buyer: 0, seller: 1, amount_a: 1.0, amount_b, bid_price: amount_b, ask_price: amount_b, ..Trade::default() } };
+    let trades = vec![trade(0, (0, 1), 2.0), trade(1, (1, 2), 3.0), trade(2, (0, 2), 5.0), trade(3, (0, 2), 6.0)];
+    let triangles = triangles(&goods_pair_stats(&goods, &assets, &trades));
+    assert_eq!(triangles.len(), 1);
+    assert_eq!(triangles[0].goods, [0, 1, 2]);
+    let violations = &triangles[0].violations;
+    assert_eq!(violations.iter().map(|(t, _)| *t).collect::<Vec<_>>(), vec![2, 3]);
+    assert!((violations[0].1 - 1.2f64.ln()).abs() < 1e-12 && violations[0].1 > ARBITRAGE);
+    assert!(violations[1].1.abs() < 1e-12);
+  }
+}
@@ -0,0 +1,49 @@
+// Independent runs spread over threads. Each simulation owns its RNG and shares
+// nothing but the config, so a batch of seeds parallelizes as it stands: `map` hands
+// the items out to `jobs` scoped threads one at a time, as each thread frees up, and
+// gives the results back in the items' order, so a parallel batch reports exactly what
+// the same batch run one seed after another would. It's std's scoped threads rather
+// than rayon, which isn't among the dependencies the crate can build with.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// One job per core, or one if that can't be told.
+pub fn default_jobs() -> usize {
+  thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+pub fn map<T: Sync, R: Send>(items: &[T], jobs: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+  let next = AtomicUsize::new(0);
+  let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+  thread::scope(|scope| {
+    for _ in 0..jobs.clamp(1, items.len().max(1)) {
+      scope.spawn(|| loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        let Some(item) = items.get(i) else { break };
+        let result = f(item);
+        results.lock().unwrap()[i] = Some(result);
+      });
+    }
+  });
+  results.into_inner().unwrap().into_iter().map(|r| r.expect("every item is taken by some thread")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::config::Config;
+  use crate::parallel::*;
+  use crate::summary::Summary;
+
+  #[test]
+  fn test_map() {
+    assert_eq!(map(&[3, 1, 2], 8, |x| x * 10), vec![30, 10, 20]);
+    assert_eq!(map(&[] as &[u64], 4, |x| *x), Vec::<u64>::new());
+    // runs in parallel are the runs in sequence, in order
+    let config = Config { n_agents: 30, ..Config::default() };
+    let seeds: Vec<u64> = (0..6).collect();
+    let summary = |&seed: &u64| Summary::of(&crate::simulate(&config, seed)).metrics().iter().map(|(_, _, v)| *v).collect::<Vec<_>>();
+    assert_eq!(map(&seeds, 3, summary), seeds.iter().map(summary).collect::<Vec<_>>());
+  }
+}
@@ -0,0 +1,76 @@
+// Pareto efficiency across configurations scored on several objectives at once, such
+// as efficiency against equality: the configurations no other does at least as well
+// as on every objective and better on one. An objective a configuration has no score
+// for (a metric undefined for all its runs) neither counts for nor against it.
+
+use crate::summary::Summary;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Sense {
+  Max,
+  Min,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Objective {
+  pub metric: String,
+  pub sense: Sense,
+}
+
+impl Objective {
+  // `<metric>:max` or `<metric>:min`, e.g. `gini_final:min`
+  pub fn parse(s: &str) -> Result<Objective, String> {
+    let sense = match s.rsplit_once(':') {
+      Some((_, "max")) => Sense::Max,
+      Some((_, "min")) => Sense::Min,
+      _ => return Err(format!("bad objective {:?} (expected <metric>:max or <metric>:min)", s)),
+    };
+    Ok(Objective { metric: s.rsplit_once(':').unwrap().0.to_string(), sense })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{} {}", if self.sense == Sense::Max { "maximizing" } else { "minimizing" }, self.metric)
+  }
+}
+
+// The mean of `metric` over the `summaries` it's defined for.
+pub fn mean(summaries: &[Summary], metric: &str) -> Option<f64> {
+  let values: Vec<f64> = summaries.iter().filter_map(|s| s.metric(metric)).collect();
+  (!values.is_empty()).then(|| values.iter().fold(0.0, |s, x| s + x) / values.len() as f64)
+}
+
+// Whether scores `y` are at least as good as `x` on every objective and better on one.
+pub fn dominates(y: &[Option<f64>], x: &[Option<f64>], senses: &[Sense]) -> bool {
+  let mut better = false;
+  for ((&x, &y), sense) in x.iter().zip(y).zip(senses) {
+    let (Some(x), Some(y)) = (x, y) else { continue };
+    let (x, y) = if *sense == Sense::Max { (x, y) } else { (-x, -y) };
+    if y < x {
+      return false;
+    }
+    better |= y > x;
+  }
+  better
+}
+
+// The indices of the undominated `scores`, in order.
+pub fn efficient(scores: &[Vec<Option<f64>>], senses: &[Sense]) -> Vec<usize> {
+  (0..scores.len()).filter(|&i| !scores.iter().any(|other| dominates(other, &scores[i], senses))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::pareto::*;
+
+  #[test]
+  fn test_pareto() {
+    assert_eq!(Objective::parse("gini_final:min"), Ok(Objective { metric: "gini_final".to_string(), sense: Sense::Min }));
+    assert!(Objective::parse("gini_final").is_err());
+    let senses = [Sense::Max, Sense::Min];
+    // (surplus, gini): the second trades surplus for equality, the third is worse than
+    // the first on both, and the last ties the first
+    let scores = vec![vec![Some(10.0), Some(0.3)], vec![Some(8.0), Some(0.1)], vec![Some(9.0), Some(0.4)], vec![Some(10.0), Some(0.3)]];
+    assert_eq!(efficient(&scores, &senses), vec![0, 1, 3]);
+    assert!(!dominates(&[None, Some(0.0)], &[Some(1.0), Some(0.0)], &senses));
+  }
+}
@@ -0,0 +1,153 @@
+// The planner's optimum: the allocation of the economy's goods that maximizes total
+// utility, each agent's normalized as welfare normalizes it, so the utilitarian
+// welfare it reaches is the ceiling on what any mechanism could. Normalizing by the
+// endowment or by nothing weighs each agent's utility by a constant; money-metric
+// utility is linear in utility only when utility is homogeneous, so under log utility
+// it has no planner. Normalizing by the endowment makes a unit of utility count most
+// for those who started with least, and under homogeneous utility nothing diminishes
+// that, so the planner hands them nearly everything.
+//
+// Separable utilities (linear and log, in any mix) are solved exactly, one good at a
+// time: each good goes to whoever values it most at the margin, water-filling at a
+// shadow price where log agents take c / price - 1 and linear agents with their
+// coefficient above it take the lot. Anything else is solved by exponentiated
+// gradient ascent from equal division: each agent's share of each good is scaled by
+// (its weighted marginal utility over the share-weighted mean) ^ STEP, which leaves
+// the shares on the simplex and converges where every agent holding a good values it
+// alike.
+
+use crate::utility::UtilityFn;
+use crate::welfare::{money_metric, Normalization};
+use crate::{Agent, Balance, Price};
+
+const ITERATIONS: usize = 5000;
+const STEP: f64 = 0.5;
+// the least share ascent leaves an agent, where Cobb-Douglas's and CES's marginal
+// utility is infinite
+const MIN_SHARE: f64 = 1e-200;
+
+// What each agent's utility counts for under `norm`; None if normalizing isn't a
+// scaling for someone.
+pub fn weights(initial: &[(Agent, Balance)], norm: Normalization) -> Option<Vec<f64>> {
+  initial.iter().map(|(agent, balance)| match norm {
+    Normalization::Endowment => {
+      let base = agent.utility(balance.a, balance.b);
+      (base > 0.0).then(|| 1.0 / base)
+    }
+    Normalization::MoneyMetric { price } => {
+      // the B a util is worth, from any bundle
+      let probe = Balance { a: 1.0, b: 1.0 };
+      (agent.utility_fn != UtilityFn::Log).then(|| money_metric(agent, &probe, price) / agent.utility(probe.a, probe.b))
+    }
+    Normalization::Raw => Some(1.0),
+  }).collect()
+}
+
+// Everyone's holdings at the optimum, indexed by agent.
+pub fn solve(initial: &[(Agent, Balance)], norm: Normalization) -> Option<Vec<Balance>> {
+  let weights = weights(initial, norm)?;
+  let total = |good: fn(&Balance) -> f64| initial.iter().map(|(_, balance)| good(balance)).sum::<f64>();
+  let (total_a, total_b) = (total(|b| b.a), total(|b| b.b));
+  if initial.iter().all(|(agent, _)| matches!(agent.utility_fn, UtilityFn::Linear | UtilityFn::Log)) {
+    let a = fill(initial, |i| weights[i] * initial[i].0.consumption_a_coeff, total_a);
+    let b = fill(initial, |i| weights[i] * initial[i].0.consumption_b_coeff, total_b);
+    return Some(a.into_iter().zip(b).map(|(a, b)| Balance { a, b }).collect());
+  }
+  Some(ascend(initial, &weights, total_a, total_b))
+}
+
+// The split of `amount` of one good among separable agents maximizing the sum of
+// their weighted utilities, where agent i's weighted coefficient on it is `coeff(i)`.
+fn fill(assets: &[(Agent, Balance)], coeff: impl Fn(usize) -> f64, amount: f64) -> Vec<f64> {
+  let is_log = |i: usize| assets[i].0.utility_fn == UtilityFn::Log;
+  let logs = |price: Price| -> Vec<f64> {
+    (0..assets.len()).map(|i| if is_log(i) { (coeff(i) / price - 1.0).max(0.0) } else { 0.0 }).collect()
+  };
+  let linear = (0..assets.len()).filter(|&i| !is_log(i)).map(&coeff).fold(0.0, f64::max);
+  if linear > 0.0 && logs(linear).iter().sum::<f64>() < amount {
+    // the keenest linear agents take whatever the log agents leave at their rate
+    let mut shares = logs(linear);
+    let keenest: Vec<usize> = (0..assets.len()).filter(|&i| !is_log(i) && coeff(i) == linear).collect();
+    let left = (amount - shares.iter().sum::<f64>()) / keenest.len() as f64;
+    for i in keenest {
+      shares[i] = left;
+    }
+    return shares;
+  }
+  let (mut lo, mut hi) = (linear, (0..assets.len()).map(&coeff).fold(0.0, f64::max));
+  for _ in 0..100 {
+    let mid = (lo + hi) / 2.0;
+    if logs(mid).iter().sum::<f64>() > amount { lo = mid } else { hi = mid }
+  }
+  // hand out the bisection's last bit of slack in proportion
+  let shares = logs(hi);
+  let given: f64 = shares.iter().sum();
+  if given > 0.0 { shares.iter().map(|s| s * amount / given).collect() } else { shares }
+}
+
+fn ascend(assets: &[(Agent, Balance)], weights: &[f64], total_a: f64, total_b: f64) -> Vec<Balance> {
+  let n = assets.len();
+  let mut shares = vec![(1.0 / n as f64, 1.0 / n as f64); n];
+  let held = |shares: &[(f64, f64)]| -> Vec<Balance> { shares.iter().map(|&(sa, sb)| Balance { a: sa * total_a, b: sb * total_b }).collect() };
+  for _ in 0..ITERATIONS {
+    let marginal: Vec<(f64, f64)> = assets.iter().zip(held(&shares)).zip(weights)
+      .map(|(((agent, _), balance), w)| {
+        let (ma, mb) = agent.marginal_utilities(&balance);
+        (w * ma, w * mb)
+      })
+      .collect();
+    let mean_a: f64 = shares.iter().zip(&marginal).map(|(s, m)| s.0 * m.0).sum();
+    let mean_b: f64 = shares.iter().zip(&marginal).map(|(s, m)| s.1 * m.1).sum();
+    for (s, m) in shares.iter_mut().zip(&marginal) {
+      *s = ((s.0 * (m.0 / mean_a).powf(STEP)).max(MIN_SHARE), (s.1 * (m.1 / mean_b).powf(STEP)).max(MIN_SHARE));
+    }
+    let (sum_a, sum_b) = shares.iter().fold((0.0, 0.0), |(a, b), s| (a + s.0, b + s.1));
+    for s in shares.iter_mut() {
+      *s = (s.0 / sum_a, s.1 / sum_b);
+    }
+  }
+  held(&shares)
+}
+
+// The weighted total utility at `allocation`.
+pub fn total_utility(initial: &[(Agent, Balance)], weights: &[f64], allocation: &[Balance]) -> f64 {
+  initial.iter().zip(weights).zip(allocation).map(|(((agent, _), w), b)| w * agent.utility(b.a, b.b)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::planner::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_planner() {
+    let agent = |ca, utility_fn| Agent { utility_fn, ..linear_agent(ca) };
+    let initial = vec![
+      (agent(3.0, UtilityFn::Linear), Balance { a: 0.0, b: 4.0 }),
+      (agent(1.0, UtilityFn::Log), Balance { a: 2.0, b: 0.0 }),
+      (agent(7.0, UtilityFn::Log), Balance { a: 4.0, b: 2.0 }),
+    ];
+    // A: the log agent keen on it takes 7 / 3 - 1 before its marginal utility falls to
+    // the linear agent's 3, which then takes the rest. B: the log agents' marginal
+    // utility starts at 1, the linear agent's, so it all goes to the linear agent
+    let planned = solve(&initial, Normalization::Raw).unwrap();
+    assert!((planned[2].a - 4.0 / 3.0).abs() < 1e-12 && (planned[0].a - 14.0 / 3.0).abs() < 1e-12 && planned[1].a == 0.0);
+    assert_eq!(planned.iter().map(|b| b.b).collect::<Vec<_>>(), vec![6.0, 0.0, 0.0]);
+    // without a linear agent it's water-filling: 1 / p - 1 + 7 / p - 1 = 6 at p = 1
+    let planned = solve(&initial[1..], Normalization::Raw).unwrap();
+    assert!((planned[0].a - 0.0).abs() < 1e-9 && (planned[1].a - 6.0).abs() < 1e-9, "{:?}", planned);
+    assert_eq!(weights(&initial, Normalization::MoneyMetric { price: 1.0 }), None);
+
+    // for Cobb-Douglas, ascent matches a brute-force search over how two agents split
+    // the goods
+    let cobb = vec![(agent(4.0, UtilityFn::CobbDouglas), Balance { a: 1.0, b: 2.0 }), (agent(0.5, UtilityFn::CobbDouglas), Balance { a: 2.0, b: 1.0 })];
+    let w = weights(&cobb, Normalization::Endowment).unwrap();
+    let best = (0..=400).flat_map(|i| (0..=400).map(move |j| (i as f64 / 400.0 * 3.0, j as f64 / 400.0 * 3.0)))
+      .map(|(a, b)| total_utility(&cobb, &w, &[Balance { a, b }, Balance { a: 3.0 - a, b: 3.0 - b }]))
+      .fold(0.0, f64::max);
+    let planned = solve(&cobb, Normalization::Endowment).unwrap();
+    let reached = total_utility(&cobb, &w, &planned);
+    assert!(reached >= best - 1e-9 && reached < best + 1e-3, "{} against {}", reached, best);
+    assert!((planned[0].a + planned[1].a - 3.0).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,259 @@
+// Generators for the starting population. `Uniform` is the default; the others are
+// textbook edge cases (no gains from trade, monopoly, ...) for stress-testing the
+// mechanism.
+
+use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::crn::{Crn, Stream};
+use crate::{Agent, AgentId, Balance};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Population {
+  // independent uniform productions and preferences; everyone is endowed with their production
+  Uniform,
+  // everyone shares one set of preferences, so there are no gains from trade
+  Identical,
+  // two preference clusters, one that mostly wants A and one that mostly wants B
+  Bimodal,
+  // agents 0..sellers split all of the A evenly; everyone else holds only B
+  Monopolist { sellers: usize },
+  // half the agents hold only A, the other half only B
+  Complementary,
+}
+
+impl Population {
+  pub fn parse(s: &str) -> Result<Population, String> {
+    match s {
+      "uniform" => Ok(Population::Uniform),
+      "identical" => Ok(Population::Identical),
+      "bimodal" => Ok(Population::Bimodal),
+      "monopolist" => Ok(Population::Monopolist { sellers: 1 }),
+      "complementary" => Ok(Population::Complementary),
+      _ => match s.strip_prefix("oligopoly:").map(|k| k.parse::<usize>()) {
+        Some(Ok(sellers)) if sellers > 0 => Ok(Population::Monopolist { sellers }),
+        _ => Err(format!("unknown population {:?} (expected uniform, identical, bimodal, monopolist, oligopoly:<k>, or complementary)", s)),
+      },
+    }
+  }
+
+  // The agents holding all of good A, for populations built that way.
+  pub fn sellers(&self) -> Option<Vec<AgentId>> {
+    match self {
+      Population::Monopolist { sellers } => Some((0..*sellers).collect()),
+      _ => None,
+    }
+  }
+}
+
+fn endowed_with_production(agents: Vec<Agent>) -> Vec<(Agent, Balance)> {
+  let mut assets = Vec::new();
+  for agent in agents {
+    let a = agent.production_a;
+    let b = agent.production_b;
+    assets.push(
+      (
+        agent,
+        Balance{
+          a,
+          b,
+        }
+      )
+    );
+  }
+  assets
+}
+
+pub fn generate(population: Population, n_agents: usize, rng: &mut StdRng) -> Vec<(Agent, Balance)> {
+  let agents = (0..n_agents).map(|_| Agent::new_random(rng)).collect();
+  shape(population, agents, rng)
+}
+
+// Turns freshly sampled agents into the given population, endowed with their production.
+pub fn shape(population: Population, mut agents: Vec<Agent>, rng: &mut StdRng) -> Vec<(Agent, Balance)> {
+  let n_agents = agents.len();
+  match population {
+    Population::Uniform => {}
+    Population::Identical => {
+      let template = agents[0];
+      for agent in agents.iter_mut() {
+        agent.consumption_a_coeff = template.consumption_a_coeff;
+        agent.consumption_b_coeff = template.consumption_b_coeff;
+      }
+    }
+    Population::Bimodal => {
+      let jitter = Uniform::new(-0.05, 0.05);
+      for (i, agent) in agents.iter_mut().enumerate() {
+        let (a, b) = if i % 2 == 0 { (0.8, 0.2) } else { (0.2, 0.8) };
+        agent.consumption_a_coeff = a + jitter.sample(rng);
+        agent.consumption_b_coeff = b + jitter.sample(rng);
+      }
+    }
+    Population::Monopolist { sellers } => {
+      assert!(sellers <= n_agents, "more sellers than agents");
+      let total_a: f64 = agents.iter().map(|agent| agent.production_a).sum();
+      for (i, agent) in agents.iter_mut().enumerate() {
+        agent.production_a = if i < sellers { total_a / sellers as f64 } else { 0.0 };
+      }
+    }
+    Population::Complementary => {
+      for (i, agent) in agents.iter_mut().enumerate() {
+        if i % 2 == 0 {
+          agent.production_b = 0.0;
+        } else {
+          agent.production_a = 0.0;
+        }
+      }
+    }
+  }
+  endowed_with_production(agents)
+}
+
+// Share of an agent's production that's A.
+pub fn production_mix(agent: &Agent) -> f64 {
+  agent.production_a / (agent.production_a + agent.production_b)
+}
+
+// Share of an agent's marginal utility weight that's on A.
+pub fn preference_mix(agent: &Agent) -> f64 {
+  agent.consumption_a_coeff / (agent.consumption_a_coeff + agent.consumption_b_coeff)
+}
+
+// Correlates what agents make with what they like, without changing either
+// marginal: a random |rho| of the agents swap preferences among themselves so that
+// their preference ranks follow their production-mix ranks (reversed if rho < 0).
+pub fn correlate_preferences(assets: &mut [(Agent, Balance)], rho: f64, rng: &mut StdRng) {
+  assert!((-1.0..=1.0).contains(&rho), "preference correlation must be in [-1, 1], got {}", rho);
+  let mut chosen: Vec<usize> = (0..assets.len()).filter(|_| rng.gen_bool(rho.abs())).collect();
+  let mut preferences: Vec<(f64, f64)> = chosen.iter()
+    .map(|&i| (assets[i].0.consumption_a_coeff, assets[i].0.consumption_b_coeff))
+    .collect();
+  let by = |x: f64, y: f64| x.partial_cmp(&y).unwrap();
+  chosen.sort_by(|&i, &j| by(production_mix(&assets[i].0), production_mix(&assets[j].0)));
+  preferences.sort_by(|p, q| by(p.0 / (p.0 + p.1), q.0 / (q.0 + q.1)));
+  if rho < 0.0 {
+    preferences.reverse();
+  }
+  for (i, (a_coeff, b_coeff)) in chosen.into_iter().zip(preferences) {
+    assets[i].0.consumption_a_coeff = a_coeff;
+    assets[i].0.consumption_b_coeff = b_coeff;
+  }
+}
+
+// Spreads out the scale of agents' endowments without touching their preferences or
+// the mix of goods they hold: each agent's production and balance are multiplied by
+// a lognormal factor exp(sigma z - sigma^2 / 2), of mean 1, so sigma 0 leaves them
+// as they are and larger sigma concentrates wealth in fewer hands. The z are common
+// random numbers (see crn), the same for a seed whatever sigma is, so a sweep over
+// sigma moves only the inequality.
+pub fn spread_endowments(assets: &mut [(Agent, Balance)], sigma: f64, seed: u64) {
+  assert!(sigma >= 0.0 && sigma.is_finite(), "endowment inequality must be a non-negative sigma, got {}", sigma);
+  let crn = Crn::new(seed);
+  for (id, (agent, balance)) in assets.iter_mut().enumerate() {
+    // Box-Muller, on 1 - u so the log is finite
+    let u1 = 1.0 - crn.uniform(Stream::Endowment, &[id as u64, 0]);
+    let u2 = crn.uniform(Stream::Endowment, &[id as u64, 1]);
+    let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+    let scale = (sigma * z - sigma * sigma / 2.0).exp();
+    agent.production_a *= scale;
+    agent.production_b *= scale;
+    balance.a *= scale;
+    balance.b *= scale;
+  }
+}
+
+// A lump-sum redistribution: `share` of every agent's endowment, and of its
+// production to match, is pooled and split equally, so 0 changes nothing and 1 is
+// equal division. It doesn't depend on preferences, only on what agents hold.
+pub fn redistribute(assets: &mut [(Agent, Balance)], share: f64) {
+  assert!((0.0..=1.0).contains(&share), "transfer must be a share of endowments in [0, 1], got {}", share);
+  let n = assets.len() as f64;
+  let pooled = |f: &dyn Fn(&(Agent, Balance)) -> f64| share * assets.iter().map(f).sum::<f64>() / n;
+  let (a, b) = (pooled(&|(agent, _)| agent.production_a), pooled(&|(agent, _)| agent.production_b));
+  let (held_a, held_b) = (pooled(&|(_, balance)| balance.a), pooled(&|(_, balance)| balance.b));
+  for (agent, balance) in assets.iter_mut() {
+    agent.production_a = (1.0 - share) * agent.production_a + a;
+    agent.production_b = (1.0 - share) * agent.production_b + b;
+    balance.a = (1.0 - share) * balance.a + held_a;
+    balance.b = (1.0 - share) * balance.b + held_b;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::population::*;
+
+  #[test]
+  fn test_monopolist_holds_all_a() {
+    let uniform = generate(Population::Uniform, 10, &mut StdRng::seed_from_u64(3));
+    let monopoly = generate(Population::Monopolist { sellers: 1 }, 10, &mut StdRng::seed_from_u64(3));
+    let total_a: f64 = uniform.iter().map(|(_, balance)| balance.a).sum();
+    assert_eq!(monopoly[0].1.a, total_a);
+    assert!(monopoly[1..].iter().all(|(_, balance)| balance.a == 0.0));
+    // preferences and B are untouched
+    assert!(uniform.iter().zip(monopoly.iter()).all(|((u, ub), (m, mb))| u.consumption_a_coeff == m.consumption_a_coeff && ub.b == mb.b));
+  }
+
+  #[test]
+  fn test_correlate_preferences() {
+    let mixes = |assets: &[(Agent, Balance)]| -> (Vec<f64>, Vec<f64>) {
+      (assets.iter().map(|(a, _)| production_mix(a)).collect(), assets.iter().map(|(a, _)| preference_mix(a)).collect())
+    };
+    let mut rng = StdRng::seed_from_u64(1);
+    let base = generate(Population::Uniform, 2000, &mut rng);
+    for rho in [1.0, -1.0, 0.5] {
+      let mut assets = base.clone();
+      correlate_preferences(&mut assets, rho, &mut rng);
+      let (production, preference) = mixes(&assets);
+      let r = crate::stats::pearson(&production, &preference).unwrap();
+      assert!(r * rho > 0.0 && r.abs() > 0.8 * rho.abs(), "rho {} gave {}", rho, r);
+      // preferences are only permuted
+      let mut before = mixes(&base).1;
+      let mut after = preference;
+      before.sort_by(|x, y| x.partial_cmp(y).unwrap());
+      after.sort_by(|x, y| x.partial_cmp(y).unwrap());
+      assert_eq!(before, after);
+    }
+  }
+
+  #[test]
+  fn test_spread_endowments() {
+    let base = generate(Population::Uniform, 2000, &mut StdRng::seed_from_u64(2));
+    let gini = |assets: &[(Agent, Balance)]| crate::inequality::gini(&assets.iter().map(|(_, b)| b.a + b.b).collect::<Vec<_>>());
+    let spread = |sigma| {
+      let mut assets = base.clone();
+      spread_endowments(&mut assets, sigma, 2);
+      assets
+    };
+    assert_eq!(spread(0.0), base);
+    let (low, high) = (spread(0.5), spread(1.5));
+    assert!(gini(&base) < gini(&low) && gini(&low) < gini(&high));
+    // preferences and mixes stay, and the same agents are the rich ones
+    assert!(base.iter().zip(&high).all(|((b, _), (h, _))| b.consumption_a_coeff == h.consumption_a_coeff && (production_mix(b) - production_mix(h)).abs() < 1e-12));
+    let ranks = |spread: &[(Agent, Balance)]| {
+      let mut ids: Vec<usize> = (0..base.len()).collect();
+      ids.sort_by(|&i, &j| (spread[i].1.a / base[i].1.a).total_cmp(&(spread[j].1.a / base[j].1.a)));
+      ids
+    };
+    assert_eq!(ranks(&low), ranks(&high));
+  }
+
+  #[test]
+  fn test_redistribute() {
+    let base = generate(Population::Uniform, 100, &mut StdRng::seed_from_u64(4));
+    let total = |assets: &[(Agent, Balance)]| assets.iter().fold((0.0, 0.0), |(a, b), (_, bal)| (a + bal.a, b + bal.b));
+    let mut half = base.clone();
+    redistribute(&mut half, 0.5);
+    let ((a0, b0), (a1, b1)) = (total(&base), total(&half));
+    assert!((a0 - a1).abs() < 1e-9 && (b0 - b1).abs() < 1e-9);
+    assert!((half[0].1.a - (base[0].1.a / 2.0 + a0 / 200.0)).abs() < 1e-12);
+    let mut equal = base.clone();
+    redistribute(&mut equal, 1.0);
+    assert!(equal.iter().all(|(_, b)| (b.a - a0 / 100.0).abs() < 1e-12 && (b.b - b0 / 100.0).abs() < 1e-12));
+  }
+}
@@ -0,0 +1,198 @@
+// Where between a crossing bid and ask a trade clears, and who gets the surplus.
+
+use serde::{Deserialize, Serialize};
+
+use crate::continuous::Matching;
+use crate::credit::Credit;
+use crate::dark_pool::DarkPool;
+use crate::decay::Decay;
+use crate::limits::Enforcement;
+use crate::lots::Lots;
+use crate::stats::mean;
+use crate::regions::Regions;
+use crate::short::Short;
+use crate::subsidy::Subsidy;
+use crate::tax::Tax;
+use crate::{Price, Trade};
+
+// The k-double auction: the price is k of the way from the ask to the bid, so k near
+// 1 gives nearly all the surplus to the seller and k near 0 to the buyer. The ends
+// are excluded so both sides strictly gain. The default 0.5 is the midpoint.
+//
+// An optional floor and cap clamp the price. Only bids above the floor and asks
+// below the cap can trade, so both sides still strictly gain at the clamped price;
+// or, enforced by rejection, only orders inside the band (see limits). With lots,
+// fills are rounded to whole units (see lots::Lots). Orders are matched in one pass
+// per tick, or continuously as they arrive (see continuous). With a tax or a subsidy,
+// the price is set from what the bid leaves the seller once it's paid, and limits
+// apply to that too (see tax and subsidy). Perishable goods spoil between ticks (see
+// decay), with credit buyers may borrow to pay (see credit), and with short selling
+// sellers may borrow to deliver (see short).
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PricingRule {
+  pub k: f64,
+  pub floor: Option<Price>,
+  pub cap: Option<Price>,
+  pub lots: Option<Lots>,
+  #[serde(default)]
+  pub enforcement: Enforcement,
+  #[serde(default)]
+  pub matching: Matching,
+  #[serde(default)]
+  pub tax: Option<Tax>,
+  #[serde(default)]
+  pub subsidy: Option<Subsidy>,
+  // two order books, with a tariff between them; see regions
+  #[serde(default)]
+  pub regions: Option<Regions>,
+  // a second, hidden venue; see dark_pool
+  #[serde(default)]
+  pub dark_pool: Option<DarkPool>,
+  #[serde(default)]
+  pub decay: Option<Decay>,
+  #[serde(default)]
+  pub credit: Option<Credit>,
+  #[serde(default)]
+  pub short: Option<Short>,
+}
+
+impl Default for PricingRule {
+  fn default() -> PricingRule {
+    PricingRule { k: 0.5, floor: None, cap: None, lots: None, enforcement: Enforcement::Clamp, matching: Matching::Batch, tax: None, subsidy: None, regions: None, dark_pool: None, decay: None, credit: None, short: None }
+  }
+}
+
+impl PricingRule {
+  pub fn k_double(k: f64) -> PricingRule {
+    assert!(k > 0.0 && k < 1.0, "k must be strictly between 0 and 1, got {}", k);
+    PricingRule { k, ..PricingRule::default() }
+  }
+
+  pub fn with_limits(self, floor: Option<Price>, cap: Option<Price>) -> PricingRule {
+    if let (Some(floor), Some(cap)) = (floor, cap) {
+      assert!(floor < cap, "price floor {} must be below the cap {}", floor, cap);
+    }
+    PricingRule { floor, cap, ..self }
+  }
+
+  pub fn with_lots(self, lots: Option<Lots>) -> PricingRule {
+    PricingRule { lots, ..self }
+  }
+
+  pub fn with_enforcement(self, enforcement: Enforcement) -> PricingRule {
+    PricingRule { enforcement, ..self }
+  }
+
+  pub fn with_matching(self, matching: Matching) -> PricingRule {
+    PricingRule { matching, ..self }
+  }
+
+  pub fn with_tax(self, tax: Option<Tax>) -> PricingRule {
+    PricingRule { tax, ..self }
+  }
+
+  pub fn with_subsidy(self, subsidy: Option<Subsidy>) -> PricingRule {
+    PricingRule { subsidy, ..self }
+  }
+
+  pub fn with_regions(self, regions: Option<Regions>) -> PricingRule {
+    PricingRule { regions, ..self }
+  }
+
+  pub fn with_dark_pool(self, dark_pool: Option<DarkPool>) -> PricingRule {
+    PricingRule { dark_pool, ..self }
+  }
+
+  pub fn with_decay(self, decay: Option<Decay>) -> PricingRule {
+    PricingRule { decay, ..self }
+  }
+
+  pub fn with_credit(self, credit: Option<Credit>) -> PricingRule {
+    PricingRule { credit, ..self }
+  }
+
+  pub fn with_short(self, short: Option<Short>) -> PricingRule {
+    PricingRule { short, ..self }
+  }
+
+  // The subsidy per A to (the buyer, the seller).
+  pub fn subsidies(&self) -> (f64, f64) {
+    self.subsidy.map_or((0.0, 0.0), |s| s.per_unit())
+  }
+
+  // What a bid leaves the seller once the tax is paid, and with the subsidies.
+  pub fn net_bid(&self, bid: Price) -> Price {
+    let (to_buyer, to_seller) = self.subsidies();
+    self.tax.map_or(bid + to_buyer, |tax| tax.net(bid + to_buyer)) + to_seller
+  }
+
+  // What the buyer pays per A for the seller to get `received`: net_bid's inverse.
+  pub fn gross(&self, received: Price) -> Price {
+    let (to_buyer, to_seller) = self.subsidies();
+    self.tax.map_or(received - to_seller, |tax| tax.gross(received - to_seller)) - to_buyer
+  }
+
+  // Whether a limit turns away orders outright, rather than just clamping the price.
+  pub fn rejects_orders(&self) -> bool {
+    self.enforcement == Enforcement::Reject && (self.floor.is_some() || self.cap.is_some())
+  }
+
+  pub fn admits_bid(&self, bid: Price) -> bool {
+    let bid = self.net_bid(bid);
+    let under_cap = self.enforcement == Enforcement::Clamp || self.cap.is_none_or(|cap| bid <= cap);
+    under_cap && self.floor.is_none_or(|floor| bid > floor)
+  }
+
+  pub fn admits_ask(&self, ask: Price) -> bool {
+    let over_floor = self.enforcement == Enforcement::Clamp || self.floor.is_none_or(|floor| ask >= floor);
+    over_floor && self.cap.is_none_or(|cap| ask < cap)
+  }
+
+  pub fn price(&self, bid: Price, ask: Price) -> Price {
+    let price = ask + self.k * (bid - ask);
+    let price = self.floor.map_or(price, |floor| price.max(floor));
+    self.cap.map_or(price, |cap| price.min(cap))
+  }
+}
+
+// How one trade's surplus, (bid - ask) * quantity in B, was split.
+pub fn price_improvement(trade: &Trade) -> (f64, f64) {
+  let price = trade.price_per_a_in_b();
+  ((trade.bid_price - price) * trade.amount_a, (price - trade.ask_price) * trade.amount_a)
+}
+
+// (buyers' total improvement, sellers' total improvement), in B.
+pub fn total_improvement(trades: &[Trade]) -> (f64, f64) {
+  trades.iter().map(price_improvement).fold((0.0, 0.0), |(b, s), (tb, ts)| (b + tb, s + ts))
+}
+
+pub fn print_report(trades: &[Trade]) {
+  if trades.is_empty() {
+    return;
+  }
+  let (buyers, sellers) = total_improvement(trades);
+  let per_trade: Vec<(f64, f64)> = trades.iter().map(price_improvement).collect();
+  println!("price improvement (B) vs own quote:");
+  println!("  buyers:  total {}, mean per trade {}", buyers, mean(&per_trade.iter().map(|p| p.0).collect::<Vec<_>>()));
+  println!("  sellers: total {}, mean per trade {}", sellers, mean(&per_trade.iter().map(|p| p.1).collect::<Vec<_>>()));
+  println!("  buyers' share of quoted surplus: {}", buyers / (buyers + sellers));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::pricing::*;
+
+  #[test]
+  fn test_surplus_split() {
+    let rule = PricingRule::k_double(0.25);
+    let price = rule.price(5.0, 1.0);
+    assert_eq!(price, 2.0);
+    let trade = Trade { tick: 0, buyer: 0, seller: 1, amount_a: 3.0, amount_b: 6.0, bid_price: 5.0, ask_price: 1.0, ..Trade::default() };
+    // surplus (5 - 1) * 3 = 12, of which 3/4 goes to the buyer
+    assert_eq!(price_improvement(&trade), (9.0, 3.0));
+    assert_eq!(PricingRule::default().price(5.0, 1.0), 3.0);
+    let floored = PricingRule::default().with_limits(Some(4.0), None);
+    assert_eq!(floored.price(5.0, 1.0), 4.0);
+    assert!(!floored.admits_bid(4.0) && floored.admits_ask(4.0));
+  }
+}
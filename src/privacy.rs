@@ -0,0 +1,87 @@
+// Differentially private release of exported summaries, for when the agents are
+// calibrated to something sensitive and the numbers leave the building. The Laplace
+// mechanism: each released value gets noise of scale sensitivity / ε, where the
+// sensitivity, how far one agent's data can move that metric, is the caller's to say
+// per metric, since it depends on the calibration's bounds and not on anything a run
+// can see. Metrics given no sensitivity are withheld rather than released exact, and
+// the budget ε is split evenly over every value released (basic composition), so the
+// whole table costs ε. Seeds are identifiers, not data, and pass through.
+//
+// Only the exports are noised; the simulation and what's printed to the terminal are
+// untouched.
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+// A table of summaries, as from Summary::metrics.
+pub type Table = Vec<Vec<(&'static str, Option<f64>)>>;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Privacy {
+  pub epsilon: f64,
+  pub sensitivities: Vec<(String, f64)>,
+}
+
+impl Privacy {
+  // e.g. `mean_price=0.05,total_surplus=100`, against the metrics there are
+  pub fn parse(epsilon: f64, sensitivities: &str, known: &[&str]) -> Result<Privacy, String> {
+    if !(epsilon > 0.0 && epsilon.is_finite()) {
+      return Err(format!("epsilon must be positive, got {}", epsilon));
+    }
+    let sensitivities = sensitivities.split(',').map(|s| {
+      let (name, value) = s.split_once('=').ok_or_else(|| format!("expected <metric>=<sensitivity>, got {:?}", s))?;
+      if !known.contains(&name) {
+        return Err(format!("no metric {:?}", name));
+      }
+      match value.parse::<f64>() {
+        Ok(v) if v >= 0.0 => Ok((name.to_string(), v)),
+        _ => Err(format!("can't read sensitivity {:?}", value)),
+      }
+    }).collect::<Result<Vec<_>, String>>()?;
+    Ok(Privacy { epsilon, sensitivities })
+  }
+
+  // `table`, noised, with unprotected metrics blanked.
+  pub fn release(&self, table: &Table, rng: &mut StdRng) -> Table {
+    let released = table.len() * self.sensitivities.iter().filter(|(name, _)| name != "seed").count();
+    let per_value = self.epsilon / released.max(1) as f64;
+    table.iter().map(|row| row.iter().map(|&(name, value)| {
+      if name == "seed" {
+        return (name, value);
+      }
+      let sensitivity = self.sensitivities.iter().find(|(n, _)| n == name).map(|&(_, s)| s);
+      (name, value.zip(sensitivity).map(|(v, s)| v + laplace(s / per_value, rng)))
+    }).collect()).collect()
+  }
+}
+
+// A draw from the Laplace distribution of mean 0 and scale `scale`, by inverting its CDF.
+pub fn laplace(scale: f64, rng: &mut StdRng) -> f64 {
+  let u: f64 = rng.gen_range(-0.5, 0.5);
+  -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::SeedableRng;
+
+  use crate::privacy::*;
+
+  #[test]
+  fn test_release() {
+    let mut rng = StdRng::seed_from_u64(0);
+    // the mean absolute deviation of a Laplace draw is its scale
+    let draws: Vec<f64> = (0..20000).map(|_| laplace(2.0, &mut rng)).collect();
+    assert!((crate::stats::mean(&draws.iter().map(|d| d.abs()).collect::<Vec<_>>()) - 2.0).abs() < 0.1);
+
+    let known = ["seed", "mean_price", "total_surplus"];
+    assert!(Privacy::parse(1.0, "volume_a=1", &known).is_err());
+    assert!(Privacy::parse(0.0, "mean_price=1", &known).is_err());
+    let privacy = Privacy::parse(1.0, "mean_price=0.01", &known).unwrap();
+    let table: Table = vec![vec![("seed", Some(7.0)), ("mean_price", Some(1.5)), ("total_surplus", Some(100.0))]];
+    let released = privacy.release(&table, &mut rng);
+    assert_eq!((released[0][0], released[0][2]), (("seed", Some(7.0)), ("total_surplus", None)));
+    let price = released[0][1].1.unwrap();
+    assert!(price != 1.5 && (price - 1.5).abs() < 0.2, "{}", price);
+  }
+}
@@ -0,0 +1,99 @@
+// Regulatory favoritism: a policy tagging some agents as privileged, who are matched
+// ahead of everyone else whenever their quote crosses, or pay no entry cost, or both.
+// The transfer it generates is measured against the same run without the policy.
+
+use serde::{Deserialize, Serialize};
+
+use crate::runlog::RunLog;
+use crate::{realized_surplus, AgentId};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Privilege {
+  pub agents: Vec<AgentId>,
+  // a crossing privileged bid (ask) is matched before any higher bid (lower ask)
+  pub priority: bool,
+  // pays no entry cost, and so always takes part
+  pub fee_exempt: bool,
+}
+
+impl Privilege {
+  // `<ids>` or `<ids>:<grants>`, where grants is a comma-separated subset of
+  // `priority` and `fee_exempt` (both if omitted), e.g. `0,3:priority`.
+  pub fn parse(s: &str) -> Result<Privilege, String> {
+    let (ids, grants) = s.split_once(':').unwrap_or((s, "priority,fee_exempt"));
+    let agents = ids.split(',').map(|id| id.parse().map_err(|_| format!("bad agent id {:?}", id))).collect::<Result<_, _>>()?;
+    let mut privilege = Privilege { agents, priority: false, fee_exempt: false };
+    for grant in grants.split(',') {
+      match grant {
+        "priority" => privilege.priority = true,
+        "fee_exempt" => privilege.fee_exempt = true,
+        _ => return Err(format!("unknown privilege {:?} (expected priority or fee_exempt)", grant)),
+      }
+    }
+    Ok(privilege)
+  }
+
+  pub fn priority_agents(&self) -> &[AgentId] {
+    if self.priority { &self.agents } else { &[] }
+  }
+
+  pub fn exempt_agents(&self) -> &[AgentId] {
+    if self.fee_exempt { &self.agents } else { &[] }
+  }
+}
+
+// Surplus (utils) of the privileged group and of everyone else.
+pub fn group_surplus(log: &RunLog, group: &[AgentId]) -> (f64, f64) {
+  let surplus = realized_surplus(&log.initial_assets, &log.final_assets());
+  (0..surplus.len()).fold((0.0, 0.0), |(inside, outside), i| {
+    if group.contains(&i) { (inside + surplus[i], outside) } else { (inside, outside + surplus[i]) }
+  })
+}
+
+// `fees_waived` is the entry cost (B) the exempt agents didn't pay; surplus is gross
+// of entry costs, so it isn't counted there.
+pub fn print_transfer(baseline: &RunLog, privileged: &RunLog, privilege: &Privilege, fees_waived: f64) {
+  let (group, others) = group_surplus(privileged, &privilege.agents);
+  let (group_base, others_base) = group_surplus(baseline, &privilege.agents);
+  println!("privilege for {} agents (priority: {}, fee exempt: {}) vs no privilege:", privilege.agents.len(), privilege.priority, privilege.fee_exempt);
+  println!("  privileged agents' surplus (utils): {} vs {} (transfer to them {})", group, group_base, group - group_base);
+  println!("  everyone else's surplus:            {} vs {} (change {})", others, others_base, others - others_base);
+  println!("  total surplus change:               {}", group + others - group_base - others_base);
+  if fees_waived > 0.0 {
+    println!("  entry fees waived: {} B", fees_waived);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::pricing::PricingRule;
+  use crate::privilege::*;
+  use crate::book::OrderBook;
+  use crate::{all_orders, find_next_trade, linear_agent, Balance};
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(Privilege::parse("0,3").unwrap(), Privilege { agents: vec![0, 3], priority: true, fee_exempt: true });
+    assert_eq!(Privilege::parse("2:fee_exempt").unwrap().priority_agents(), &[] as &[AgentId]);
+    assert_eq!(Privilege::parse("2:fee_exempt").unwrap().exempt_agents(), &[2]);
+    assert!(Privilege::parse("2:bribes").is_err());
+    assert!(Privilege::parse("x").is_err());
+  }
+
+  #[test]
+  fn test_priority_jumps_the_queue() {
+    let assets = vec![
+      (linear_agent(1.0), Balance { a: 10.0, b: 0.0 }),
+      (linear_agent(2.0), Balance { a: 10.0, b: 0.0 }),
+      (linear_agent(4.0), Balance { a: 0.0, b: 10.0 }),
+      (linear_agent(3.0), Balance { a: 0.0, b: 10.0 }),
+      (linear_agent(0.5), Balance { a: 0.0, b: 10.0 }),
+    ];
+    let orders = all_orders(&assets);
+    let parties = |priority: &[AgentId]| find_next_trade(&assets, &OrderBook::from_quotes(&orders), PricingRule::default(), priority, 0).map(|t| (t.buyer, t.seller));
+    assert_eq!(parties(&[]), Some((2, 0)));
+    assert_eq!(parties(&[1, 3]), Some((3, 1)));
+    // a bid below every ask gets nothing from priority
+    assert_eq!(parties(&[4]), Some((2, 0)));
+  }
+}
@@ -0,0 +1,283 @@
+// `simmarket query`: a small SQL-like filter over an archive of runs, for quick
+// questions during a sweep without loading the results into another tool. The
+// archive is any directory tree of out-dir runs (see outdir); each run is a row whose
+// columns are its config fields, by dotted path as --set names them (`pricing.k`,
+// `n_agents`), its summary metrics (`mean_price`, `gini_final`, ...), and `run`, its
+// directory. The one table is `runs`:
+//
+//   select <columns> | * from runs [where <cond> [and <cond>]...]
+//     [order by <column> [asc | desc]] [limit <n>]
+//
+// A condition is `<column> <op> <value>` with any of the comparisons of find-seeds;
+// numbers compare as numbers, anything else as text. Keywords are read in any case.
+//
+// There's no SQLite or Parquet store behind it, for want of either crate here: the
+// runs are read from their out-dirs' JSON as they are, on every query.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::runlog;
+use crate::seeds::Comparison;
+use crate::summary::Summary;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Condition {
+  pub column: String,
+  pub comparison: Comparison,
+  pub value: Value,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Query {
+  // empty for every column
+  pub columns: Vec<String>,
+  pub conditions: Vec<Condition>,
+  // and whether descending
+  pub order_by: Option<(String, bool)>,
+  pub limit: Option<usize>,
+}
+
+// A run's columns, in order.
+pub type Row = Vec<(String, Value)>;
+
+impl Condition {
+  pub fn parse(s: &str) -> Result<Condition, String> {
+    // two-character operators first, so `>=` isn't read as `>`
+    let ops = [("<=", Comparison::Le), (">=", Comparison::Ge), ("==", Comparison::Eq), ("!=", Comparison::Ne),
+               ("<", Comparison::Lt), (">", Comparison::Gt), ("=", Comparison::Eq)];
+    for (op, comparison) in ops {
+      if let Some((column, value)) = s.split_once(op) {
+        let value = value.trim().trim_matches('\'');
+        let value = value.parse::<f64>().map_or_else(|_| Value::String(value.to_string()), Value::from);
+        return Ok(Condition { column: column.trim().to_string(), comparison, value });
+      }
+    }
+    Err(format!("no comparison operator in {:?}", s))
+  }
+
+  // Columns a row doesn't have, or has as null, never match.
+  pub fn holds(&self, row: &Row) -> bool {
+    let ordering = match (cell(row, &self.column), &self.value) {
+      (Some(Value::Number(x)), Value::Number(v)) => x.as_f64().partial_cmp(&v.as_f64()),
+      (Some(Value::Null), _) | (None, _) => None,
+      (Some(x), v) => Some(text(x).cmp(&text(v))),
+    };
+    let Some(ordering) = ordering else { return false };
+    match self.comparison {
+      Comparison::Lt => ordering.is_lt(),
+      Comparison::Le => ordering.is_le(),
+      Comparison::Gt => ordering.is_gt(),
+      Comparison::Ge => ordering.is_ge(),
+      Comparison::Eq => ordering.is_eq(),
+      Comparison::Ne => ordering.is_ne(),
+    }
+  }
+}
+
+// Where the ASCII `keyword` first appears in `s`, in any case. Matching on the bytes
+// of `s` itself keeps the offset one into `s`, as lowercasing it first wouldn't where
+// that changes a character's length.
+fn find_keyword(s: &str, keyword: &str) -> Option<usize> {
+  s.as_bytes().windows(keyword.len()).position(|w| w.eq_ignore_ascii_case(keyword.as_bytes()))
+}
+
+impl Query {
+  pub fn parse(s: &str) -> Result<Query, String> {
+    let select = find_keyword(s, "select ").filter(|&i| i == s.len() - s.trim_start().len())
+      .ok_or("a query starts with `select`")?;
+    let from = find_keyword(s, " from runs").ok_or("a query selects `from runs`")?;
+    let rest = &s[from + " from runs".len()..];
+    // the clauses after the table, each up to the next
+    let clause = |k: &str| find_keyword(rest, k).map(|i| (i, i + k.len()));
+    let (where_at, order_at, limit_at) = (clause("where "), clause("order by "), clause("limit "));
+    let end_of = |start: usize| [where_at, order_at, limit_at].iter().flatten().map(|&(i, _)| i).filter(|&i| i > start).min().unwrap_or(rest.len());
+    let text_of = |at: Option<(usize, usize)>| at.map(|(i, j)| rest[j..end_of(i)].trim());
+
+    let columns = s[select + "select ".len()..from].split(',').map(|c| c.trim().to_string()).collect::<Vec<_>>();
+    let columns = if columns == ["*"] { vec![] } else { columns };
+    if columns.iter().any(|c| c.is_empty()) {
+      return Err("empty column in the select list".to_string());
+    }
+    let conditions = match text_of(where_at) {
+      Some(w) => split_and(w).iter().map(|c| Condition::parse(c)).collect::<Result<Vec<_>, _>>()?,
+      None => vec![],
+    };
+    let order_by = text_of(order_at).map(|o| {
+      let mut words = o.split_whitespace();
+      let column = words.next().ok_or("order by needs a column")?.to_string();
+      match words.next() {
+        None => Ok((column, false)),
+        Some(d) if d.eq_ignore_ascii_case("asc") => Ok((column, false)),
+        Some(d) if d.eq_ignore_ascii_case("desc") => Ok((column, true)),
+        Some(other) => Err(format!("expected asc or desc after order by {}, got {:?}", column, other)),
+      }
+    }).transpose()?;
+    let limit = text_of(limit_at).map(|l| l.parse().map_err(|_| format!("can't read limit {:?}", l))).transpose()?;
+    let leftover = rest[..where_at.or(order_at).or(limit_at).map_or(rest.len(), |(i, _)| i)].trim();
+    if !leftover.is_empty() {
+      return Err(format!("unexpected {:?} after `from runs`", leftover));
+    }
+    Ok(Query { columns, conditions, order_by, limit })
+  }
+
+  // The selected columns of every matching row, with their names.
+  pub fn run(&self, rows: &[Row]) -> Result<(Vec<String>, Vec<Vec<Value>>), String> {
+    let known = |c: &str| rows.iter().any(|row| cell(row, c).is_some());
+    let columns = if self.columns.is_empty() {
+      rows.first().map_or(vec![], |row| row.iter().map(|(name, _)| name.clone()).collect())
+    } else {
+      self.columns.clone()
+    };
+    let mentioned = columns.iter().chain(self.conditions.iter().map(|c| &c.column)).chain(self.order_by.iter().map(|(c, _)| c));
+    if !rows.is_empty() {
+      if let Some(unknown) = mentioned.into_iter().find(|c| !known(c)) {
+        return Err(format!("no column {:?}; there are {}", unknown, rows[0].iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>().join(", ")));
+      }
+    }
+    let mut matching: Vec<&Row> = rows.iter().filter(|row| self.conditions.iter().all(|c| c.holds(row))).collect();
+    if let Some((column, descending)) = &self.order_by {
+      // nulls last either way
+      matching.sort_by(|x, y| {
+        let key = |row: &Row| cell(row, column).and_then(|v| v.as_f64());
+        match (key(x), key(y)) {
+          (Some(a), Some(b)) if *descending => b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal),
+          (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+          (a, b) => a.is_none().cmp(&b.is_none()),
+        }
+      });
+    }
+    matching.truncate(self.limit.unwrap_or(usize::MAX));
+    let table = matching.iter().map(|row| columns.iter().map(|c| cell(row, c).cloned().unwrap_or(Value::Null)).collect()).collect();
+    Ok((columns, table))
+  }
+}
+
+// `a=1 and b>2`, on `and` in any case.
+fn split_and(s: &str) -> Vec<String> {
+  let mut conditions = vec![String::new()];
+  for word in s.split_whitespace() {
+    if word.eq_ignore_ascii_case("and") {
+      conditions.push(String::new());
+    } else {
+      let last = conditions.last_mut().unwrap();
+      if !last.is_empty() {
+        last.push(' ');
+      }
+      last.push_str(word);
+    }
+  }
+  conditions
+}
+
+fn cell<'a>(row: &'a Row, column: &str) -> Option<&'a Value> {
+  row.iter().find(|(name, _)| name == column).map(|(_, v)| v)
+}
+
+fn text(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    other => other.to_string(),
+  }
+}
+
+// Nested objects as dotted paths, e.g. {"pricing": {"k": 0.5}} as pricing.k.
+fn flatten(prefix: &str, value: &Value, out: &mut Row) {
+  match value {
+    Value::Object(fields) => {
+      for (key, v) in fields {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        flatten(&path, v, out);
+      }
+    }
+    other => out.push((prefix.to_string(), other.clone())),
+  }
+}
+
+// One row per run directory (holding a manifest.json and run.ndjson) anywhere under
+// `archive`, in path order.
+pub fn rows(archive: &str) -> io::Result<Vec<Row>> {
+  let mut dirs = vec![];
+  find_runs(Path::new(archive), &mut dirs)?;
+  dirs.sort();
+  dirs.iter().map(|dir| {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", dir.display(), e));
+    let manifest: Map<String, Value> = serde_json::from_str(&fs::read_to_string(dir.join("manifest.json"))?)?;
+    let config_json = manifest.get("config").cloned().unwrap_or(Value::Null);
+    let config: Config = serde_json::from_value(config_json.clone()).map_err(|e| invalid(e.to_string()))?;
+    let log = runlog::read(dir.join("run.ndjson").to_str().unwrap())?;
+    let mut row = vec![("run".to_string(), Value::from(dir.strip_prefix(archive).unwrap_or(dir).display().to_string()))];
+    flatten("", &config_json, &mut row);
+    for (name, _, value) in Summary::reported(&log, &config).metrics() {
+      row.push((name.to_string(), value.map_or(Value::Null, Value::from)));
+    }
+    Ok(row)
+  }).collect()
+}
+
+fn find_runs(dir: &Path, found: &mut Vec<std::path::PathBuf>) -> io::Result<()> {
+  if dir.join("manifest.json").exists() && dir.join("run.ndjson").exists() {
+    found.push(dir.to_path_buf());
+  }
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      find_runs(&path, found)?;
+    }
+  }
+  Ok(())
+}
+
+pub fn csv(columns: &[String], table: &[Vec<Value>]) -> String {
+  let mut out = columns.join(",") + "\n";
+  for row in table {
+    out += &row.iter().map(|v| if v.is_null() { String::new() } else { text(v) }).collect::<Vec<_>>().join(",");
+    out += "\n";
+  }
+  out
+}
+
+// Columns padded to their widest value.
+pub fn table(columns: &[String], table: &[Vec<Value>]) -> String {
+  let cells: Vec<Vec<String>> = table.iter().map(|row| row.iter().map(|v| if v.is_null() { "-".to_string() } else { text(v) }).collect()).collect();
+  let widths: Vec<usize> = columns.iter().enumerate().map(|(i, c)| cells.iter().map(|r| r[i].len()).chain([c.len()]).max().unwrap()).collect();
+  let line = |values: &[String]| values.iter().zip(&widths).map(|(v, w)| format!("{:<w$}", v, w = w)).collect::<Vec<_>>().join("  ").trim_end().to_string() + "\n";
+  let mut out = line(columns);
+  for row in &cells {
+    out += &line(row);
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::query::*;
+
+  #[test]
+  fn test_query() {
+    let q = Query::parse("select seed, mean_price from runs where pricing.k >= 0.5 and population = uniform order by mean_price desc limit 2").unwrap();
+    assert_eq!(q.columns, vec!["seed", "mean_price"]);
+    assert_eq!(q.conditions[1], Condition { column: "population".to_string(), comparison: Comparison::Eq, value: Value::from("uniform") });
+    assert_eq!((q.order_by.clone(), q.limit), (Some(("mean_price".to_string(), true)), Some(2)));
+    assert!(Query::parse("select * from trades").is_err());
+    // keywords in any case, around text whose lowercase is longer than it is
+    let shouted = Query::parse("SELECT run FROM runs WHERE run = 'İİİ' Order By run DESC LIMIT 1").unwrap();
+    assert_eq!(shouted.conditions, vec![Condition { column: "run".to_string(), comparison: Comparison::Eq, value: Value::from("İİİ") }]);
+    assert_eq!((shouted.order_by, shouted.limit), (Some(("run".to_string(), true)), Some(1)));
+
+    let row = |seed: u64, k: f64, price: Option<f64>| -> Row {
+      vec![("seed".to_string(), Value::from(seed)), ("pricing.k".to_string(), Value::from(k)), ("population".to_string(), Value::from("uniform")),
+           ("mean_price".to_string(), price.map_or(Value::Null, Value::from))]
+    };
+    let rows = vec![row(0, 0.5, Some(1.0)), row(1, 0.8, Some(3.0)), row(2, 0.2, Some(9.0)), row(3, 0.9, None), row(4, 0.6, Some(2.0))];
+    let (columns, table) = q.run(&rows).unwrap();
+    assert_eq!(columns, vec!["seed", "mean_price"]);
+    assert_eq!(table, vec![vec![Value::from(1), Value::from(3.0)], vec![Value::from(4), Value::from(2.0)]]);
+    assert!(Query::parse("select nope from runs").unwrap().run(&rows).is_err());
+    assert_eq!(csv(&columns, &table[..1]), "seed,mean_price\n1,3.0\n");
+  }
+}
@@ -0,0 +1,185 @@
+// `simmarket recommend`: policy design as search. Every combination of an
+// ad-valorem tax rate, a lump-sum transfer share (see population::redistribute) and
+// a price floor in a grid is run over a batch of seeds, and each policy scored by the
+// batch means of an objective metric (total utility after trading, by default) and
+// of the metrics in the targets, e.g. `gini_final<=0.4`. Floors are given as
+// multiples of the mean price with no intervention, so one grid suits any market; a
+// floor the config's cap leaves no room for, or a policy the config can't be run
+// with, is left out.
+//
+// The report is the frontier (see pareto): the policies no other beats on the
+// objective and on every target metric at once, each target metric counted better in
+// the direction its comparison points (lower for `<`). Those meeting every target are marked, and the
+// best of them is recommended, with its loss on the objective against doing nothing.
+
+use crate::config::Config;
+use crate::parallel;
+use crate::pareto::{self, mean, Sense};
+use crate::seeds::{Comparison, Predicate};
+use crate::simulation::SimulationBuilder;
+use crate::summary::Summary;
+use crate::tax::Tax;
+use crate::{simulate, Price};
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Policy {
+  // ad-valorem; 0 for none
+  pub tax: f64,
+  pub transfer: f64,
+  pub floor: Option<Price>,
+}
+
+pub const NONE: Policy = Policy { tax: 0.0, transfer: 0.0, floor: None };
+
+impl Policy {
+  pub fn describe(&self) -> String {
+    let tax = if self.tax > 0.0 { format!("a {}% tax", 100.0 * self.tax) } else { "no tax".to_string() };
+    let floor = self.floor.map_or("no floor".to_string(), |f| format!("a floor at {:.4}", f));
+    format!("{}, {} of endowments transferred, {}", tax, self.transfer, floor)
+  }
+
+  // `config` with the policy in place of its own tax, transfer and floor, if it can
+  // be run.
+  pub fn apply(&self, config: &Config) -> Option<Config> {
+    if let (Some(floor), Some(cap)) = (self.floor, config.pricing.cap) {
+      if floor >= cap {
+        return None;
+      }
+    }
+    let tax = (self.tax > 0.0).then_some(Tax::AdValorem { rate: self.tax });
+    let pricing = config.pricing.with_tax(tax).with_limits(self.floor, config.pricing.cap);
+    SimulationBuilder::from_config(Config { transfer: self.transfer, pricing, ..config.clone() }).validate().ok()
+  }
+}
+
+// The grid searched, floors as multiples of the mean price with no intervention.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Space {
+  pub taxes: Vec<f64>,
+  pub transfers: Vec<f64>,
+  pub floors: Vec<f64>,
+}
+
+impl Default for Space {
+  fn default() -> Space {
+    Space { taxes: vec![0.0, 0.1, 0.2], transfers: vec![0.0, 0.25, 0.5, 0.75, 1.0], floors: vec![1.1, 1.25] }
+  }
+}
+
+impl Space {
+  // Every policy in the grid, no intervention first; no floor is always among them.
+  pub fn policies(&self, price: Option<Price>) -> Vec<Policy> {
+    let floors: Vec<Option<Price>> = std::iter::once(None).chain(price.into_iter().flat_map(|p| self.floors.iter().map(move |m| Some(m * p)))).collect();
+    let mut policies = vec![NONE];
+    for &tax in &self.taxes {
+      for &transfer in &self.transfers {
+        for &floor in &floors {
+          let policy = Policy { tax, transfer, floor };
+          if !policies.contains(&policy) {
+            policies.push(policy);
+          }
+        }
+      }
+    }
+    policies
+  }
+}
+
+pub struct Evaluation {
+  pub policy: Policy,
+  // batch means, over the seeds the metric is defined for
+  pub objective: Option<f64>,
+  pub targets: Vec<Option<f64>>,
+}
+
+impl Evaluation {
+  pub fn meets(&self, targets: &[Predicate]) -> bool {
+    targets.iter().zip(&self.targets).all(|(t, x)| x.is_some_and(|x| t.admits(x)))
+  }
+
+  // The objective and then the target metrics, for pareto, those compared for
+  // (in)equality left out.
+  fn scores(&self, targets: &[Predicate]) -> Vec<Option<f64>> {
+    let directed = self.targets.iter().zip(targets).map(|(&x, t)| x.filter(|_| !matches!(t.comparison, Comparison::Eq | Comparison::Ne)));
+    std::iter::once(self.objective).chain(directed).collect()
+  }
+}
+
+// Every policy in `space` run over `seeds`, `jobs` at a time; no intervention first.
+pub fn search(config: &Config, seeds: &[u64], jobs: usize, space: &Space, objective: &str, targets: &[Predicate]) -> Vec<Evaluation> {
+  let batch = |config: &Config| parallel::map(seeds, jobs, |&seed| Summary::reported(&simulate(config, seed), config));
+  let evaluate = |policy: Policy, summaries: &[Summary]| Evaluation {
+    policy,
+    objective: mean(summaries, objective),
+    targets: targets.iter().map(|t| mean(summaries, &t.metric)).collect(),
+  };
+  let Some(unregulated) = NONE.apply(config).map(|c| batch(&c)) else { return vec![] };
+  let policies = space.policies(mean(&unregulated, "mean_price"));
+  let mut evaluations = vec![evaluate(NONE, &unregulated)];
+  evaluations.extend(policies.into_iter().skip(1).filter_map(|policy| Some(evaluate(policy, &batch(&policy.apply(config)?)))));
+  evaluations
+}
+
+// The evaluations nobody dominates, best on the objective first.
+pub fn frontier<'a>(evaluations: &'a [Evaluation], targets: &[Predicate]) -> Vec<&'a Evaluation> {
+  let senses: Vec<Sense> = std::iter::once(Sense::Max).chain(targets.iter().map(|t| if matches!(t.comparison, Comparison::Lt | Comparison::Le) { Sense::Min } else { Sense::Max })).collect();
+  let scores: Vec<Vec<Option<f64>>> = evaluations.iter().map(|e| e.scores(targets)).collect();
+  let mut frontier: Vec<&Evaluation> = pareto::efficient(&scores, &senses).into_iter().map(|i| &evaluations[i]).collect();
+  frontier.sort_by(|x, y| y.objective.unwrap_or(f64::NEG_INFINITY).total_cmp(&x.objective.unwrap_or(f64::NEG_INFINITY)));
+  frontier
+}
+
+// The policy meeting every target that does best on the objective.
+pub fn recommend<'a>(evaluations: &'a [Evaluation], targets: &[Predicate]) -> Option<&'a Evaluation> {
+  evaluations.iter().filter(|e| e.meets(targets) && e.objective.is_some()).max_by(|x, y| x.objective.unwrap().total_cmp(&y.objective.unwrap()))
+}
+
+pub fn print_report(evaluations: &[Evaluation], objective: &str, targets: &[Predicate], n_seeds: usize) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.4}", x));
+  let goals: Vec<String> = targets.iter().map(|t| t.describe()).collect();
+  println!("{} policies over {} seeds, maximizing {} subject to {}:", evaluations.len(), n_seeds, objective, if goals.is_empty() { "nothing".to_string() } else { goals.join(", ") });
+  let mut header = format!("  {:>6} {:>8} {:>9} {:>14}", "tax", "transfer", "floor", objective);
+  let widths: Vec<usize> = targets.iter().map(|t| t.metric.len().max(14)).collect();
+  targets.iter().zip(&widths).for_each(|(t, &w)| header += &format!(" {:>w$}", t.metric));
+  println!("{}  (* meets every target)", header);
+  for e in frontier(evaluations, targets) {
+    let mut row = format!("  {:>6} {:>8} {:>9} {:>14}", e.policy.tax, e.policy.transfer, show(e.policy.floor), show(e.objective));
+    e.targets.iter().zip(&widths).for_each(|(&x, &w)| row += &format!(" {:>w$}", show(x)));
+    println!("{}{}", row, if e.meets(targets) { "  *" } else { "" });
+  }
+  let unregulated = evaluations.iter().find(|e| e.policy == NONE);
+  if let Some(none) = unregulated {
+    let targets: Vec<String> = targets.iter().zip(&none.targets).map(|(t, &x)| format!(", {} {}", t.metric, show(x))).collect();
+    println!("no intervention: {} {}{}", objective, show(none.objective), targets.concat());
+  }
+  match recommend(evaluations, targets) {
+    Some(best) => {
+      let loss = unregulated.and_then(|none| none.objective.zip(best.objective)).map(|(none, best)| none - best);
+      println!("recommended: {}; {} {}, a loss of {} against no intervention", best.policy.describe(), objective, show(best.objective), show(loss));
+    }
+    None => println!("no policy searched meets every target"),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::recommend::*;
+
+  #[test]
+  fn test_recommend() {
+    let space = Space { taxes: vec![0.0, 0.2], transfers: vec![0.0, 1.0], floors: vec![1.5] };
+    assert_eq!(space.policies(Some(2.0)).len(), 8);
+    assert_eq!(space.policies(None)[0], NONE);
+
+    let config = Config { n_agents: 40, ..Config::default() };
+    let targets = vec![Predicate::parse("gini_final<=0.1").unwrap()];
+    let evaluations = search(&config, &[0, 1], 2, &space, "utility_final", &targets);
+    assert_eq!(evaluations.len(), 8);
+    // only an even split of endowments gets inequality that low, and the best of those
+    // levies no tax
+    assert!(!evaluations[0].meets(&targets) && evaluations.iter().filter(|e| e.meets(&targets)).all(|e| e.policy.transfer == 1.0));
+    let best = recommend(&evaluations, &targets).unwrap();
+    assert_eq!(best.policy.tax, 0.0);
+    assert!(frontier(&evaluations, &targets).iter().any(|e| e.policy == best.policy));
+  }
+}
@@ -0,0 +1,167 @@
+// Two regions, home and foreign, for studying trade policy. The lowest-numbered share
+// of agents live at home and the rest abroad, and each region keeps its own order
+// book. A tick matches whichever of the four pairings of a region's best bid with a
+// region's best ask (its own, or the other's) crosses by the most, and a trade across
+// regions pays an import tariff: the buyer pays it as it would a tax (see tax), so a
+// foreign ask has to beat a home one by the tariff to win the bid, and the ledger
+// check at the end of a run counts the tariff revenue back in.
+//
+// The report sets a run with a tariff against the same regions trading freely: how
+// much trade moves from imports to domestic sellers (trade diversion), the prices in
+// each region, and the importers' share of the tariff as the rise in what they pay
+// for imports over the wedge it drives.
+
+use serde::{Deserialize, Serialize};
+
+use crate::book::OrderBook;
+use crate::pricing::PricingRule;
+use crate::runlog::RunLog;
+use crate::tax::{self, Tax};
+use crate::{fill, Agent, AgentId, Balance, Order, Price, Trade};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Region {
+  Home,
+  Foreign,
+}
+
+pub const ALL: [Region; 2] = [Region::Home, Region::Foreign];
+
+impl Region {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Region::Home => "home",
+      Region::Foreign => "foreign",
+    }
+  }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Regions {
+  // the share of agents, lowest-numbered first, at home
+  pub home: f64,
+  // on trades across regions; None trades freely
+  pub tariff: Option<Tax>,
+}
+
+impl Regions {
+  pub fn region(&self, id: AgentId, n_agents: usize) -> Region {
+    if (id as f64) < self.home * n_agents as f64 { Region::Home } else { Region::Foreign }
+  }
+
+  // Whether `trade` is an import into the buyer's region.
+  pub fn crosses(&self, trade: &Trade, n_agents: usize) -> bool {
+    self.region(trade.buyer, n_agents) != self.region(trade.seller, n_agents)
+  }
+
+  pub fn describe(&self) -> String {
+    let tariff = self.tariff.map_or("free trade".to_string(), |t| format!("a {} tariff", t.describe()));
+    format!("{}% of agents at home, {}", 100.0 * self.home, tariff)
+  }
+}
+
+// The best crossing bid and ask with the agents split into `regions`, the bidder
+// paying the tariff if the ask is from the other region; ties go to the home
+// region's bid, then its ask.
+pub fn find_next_trade(assets: &[(Agent, Balance)], book: &OrderBook, pricing: PricingRule, regions: Regions, now: crate::clock::Tick) -> Option<Trade> {
+  let by_price = |o1: &Order, o2: &Order| o1.price_per_a_in_b.partial_cmp(&o2.price_per_a_in_b).unwrap();
+  let in_region = |region| book.quotes().iter().enumerate().filter(move |&(id, _)| regions.region(id, assets.len()) == region).map(|(_, q)| q);
+  let mut best: Option<(Price, Order, Order, PricingRule)> = None;
+  for buyers in ALL {
+    for sellers in ALL {
+      let pricing = if buyers == sellers { pricing } else { pricing.with_tax(regions.tariff) };
+      let bid = in_region(buyers).filter_map(|q| q.0).filter(|o| pricing.admits_bid(o.price_per_a_in_b)).max_by(by_price);
+      let ask = in_region(sellers).filter_map(|q| q.1).filter(|o| pricing.admits_ask(o.price_per_a_in_b)).min_by(by_price);
+      if let (Some(bid), Some(ask)) = (bid, ask) {
+        let surplus = pricing.net_bid(bid.price_per_a_in_b) - ask.price_per_a_in_b;
+        if surplus > 0.0 && best.is_none_or(|(most, ..)| surplus > most) {
+          best = Some((surplus, bid, ask, pricing));
+        }
+      }
+    }
+  }
+  best.map(|(_, bid, ask, pricing)| fill(assets, bid, ask, pricing, now))
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct RegionStats {
+  pub region: Region,
+  // A bought by the region's agents from their own region and from the other
+  pub domestic_a: f64,
+  pub imported_a: f64,
+  // volume-weighted, of the domestic trades
+  pub mean_price: Option<Price>,
+}
+
+pub fn stats(log: &RunLog, regions: Regions) -> Vec<RegionStats> {
+  let n = log.initial_assets.len();
+  ALL.iter().map(|&region| {
+    let bought: Vec<&Trade> = log.trades.iter().filter(|t| regions.region(t.buyer, n) == region).collect();
+    let (domestic, imported): (Vec<&Trade>, Vec<&Trade>) = bought.into_iter().partition(|t| !regions.crosses(t, n));
+    let domestic_a = domestic.iter().fold(0.0, |v, t| v + t.amount_a);
+    RegionStats {
+      region,
+      domestic_a,
+      imported_a: imported.iter().fold(0.0, |v, t| v + t.amount_a),
+      mean_price: (domestic_a > 0.0).then(|| domestic.iter().fold(0.0, |b, t| b + t.amount_b) / domestic_a),
+    }
+  }).collect()
+}
+
+// Importers' mean payment per A, tariff included, and exporters' mean receipt.
+pub fn import_prices(log: &RunLog, regions: Regions) -> Option<(Price, Price)> {
+  let n = log.initial_assets.len();
+  let imports: Vec<&Trade> = log.trades.iter().filter(|t| regions.crosses(t, n)).collect();
+  let volume_a = imports.iter().fold(0.0, |v, t| v + t.amount_a);
+  (volume_a > 0.0).then(|| {
+    let received = imports.iter().fold(0.0, |b, t| b + t.amount_b) / volume_a;
+    (received + imports.iter().fold(0.0, |r, t| r + t.tax) / volume_a, received)
+  })
+}
+
+pub fn print_report(free: &RunLog, tariffed: &RunLog, regions: Regions) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  println!("regions ({}) vs free trade:", regions.describe());
+  let free_regions = Regions { tariff: None, ..regions };
+  for (label, log, regions) in [("free", free, free_regions), ("tariff", tariffed, regions)] {
+    let imported = stats(log, regions).iter().fold(0.0, |v, s| v + s.imported_a);
+    let volume = log.volume_a();
+    println!("  {:<6}  {} trades, {} A, {} imported ({})", label, log.trades.len(), volume, imported, show((volume > 0.0).then(|| imported / volume)));
+    for s in stats(log, regions) {
+      println!("    {:<7}  bought {} A at home and {} A abroad, domestic price {}", s.region.name(), s.domestic_a, s.imported_a, show(s.mean_price));
+    }
+  }
+  let (before, after) = (stats(free, free_regions), stats(tariffed, regions));
+  let diverted = after.iter().zip(&before).fold(0.0, |d, (a, b)| d + (a.domestic_a - b.domestic_a).max(0.0));
+  println!("  trade diverted from imports to domestic sellers: {} A", diverted);
+  let incidence = match (import_prices(free, free_regions), import_prices(tariffed, regions)) {
+    (Some((price, _)), Some((paid, received))) if paid > received => Some((paid - price) / (paid - received)),
+    _ => None,
+  };
+  let c = tax::compare(free, tariffed);
+  println!("  tariff revenue: {} B, importers' share of the tariff: {}", tax::revenue(&tariffed.trades), show(incidence));
+  println!("  deadweight loss: {} B", show(c.map(|c| c.deadweight_loss)));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::regions::*;
+  use crate::simulation::SimulationBuilder;
+
+  #[test]
+  fn test_regions() {
+    let regions = Regions { home: 0.25, tariff: None };
+    assert_eq!((regions.region(4, 20), regions.region(5, 20)), (Region::Home, Region::Foreign));
+
+    let run = |tariff| SimulationBuilder::new().agents(80).seed(5).pricing(PricingRule::default().with_regions(Some(Regions { home: 0.5, tariff }))).build().unwrap().run();
+    let (free, tariffed) = (run(None), run(Some(Tax::PerUnit { b: 0.3 })));
+    // only imports pay, and they pay the tariff on every unit
+    let n = free.initial_assets.len();
+    let regions = Regions { home: 0.5, tariff: None };
+    assert!(tariffed.trades.iter().all(|t| if regions.crosses(t, n) { (t.tax - 0.3 * t.amount_a).abs() < 1e-12 } else { t.tax == 0.0 }));
+    let imported = |log: &RunLog| stats(log, regions).iter().fold(0.0, |v, s| v + s.imported_a);
+    assert!(imported(&free) > 0.0 && imported(&tariffed) < imported(&free));
+    let (paid, received) = import_prices(&tariffed, regions).unwrap();
+    assert!((paid - received - 0.3).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,140 @@
+// Standalone HTML summary of a logged run: a table of headline numbers plus
+// embedded SVG charts, so one file can be shared per run.
+
+use std::fmt::Write;
+
+use crate::inequality::lorenz_curve;
+use crate::numeraire::Numeraire;
+use crate::runlog::RunLog;
+use crate::summary::{utilities, wealth_in_b, Summary};
+use crate::book::OrderBook;
+use crate::svg::{escape, Chart};
+use crate::{settle, supply_demand_curves, Agent, Balance};
+
+type Points = Vec<(f64, f64)>;
+
+// (supply, demand) of A as chartable (price, quantity) points
+fn curve_series(assets: &[(Agent, Balance)], numeraire: Numeraire) -> (Points, Points) {
+  let curves = supply_demand_curves(assets);
+  (
+    curves.iter().map(|(p, s, _)| (numeraire.price(*p), *s)).collect(),
+    curves.iter().map(|(p, _, d)| (numeraire.price(*p), *d)).collect(),
+  )
+}
+
+// Replays the run, recording the best bid and ask before each trade. Only each
+// trade's parties need quoting again after it.
+fn quote_paths(log: &RunLog, numeraire: Numeraire) -> (Points, Points) {
+  let mut bids = vec![];
+  let mut asks = vec![];
+  let mut assets = log.initial_assets.clone();
+  let mut book = OrderBook::truthful(&assets);
+  for (i, trade) in log.trades.iter().enumerate() {
+    let (bid, ask) = book.best();
+    let (bid, ask) = numeraire.quotes(bid, ask);
+    if let Some(bid) = bid { bids.push((i as f64, bid)); }
+    if let Some(ask) = ask { asks.push((i as f64, ask)); }
+    settle(&mut assets, trade);
+    book.requote(trade.buyer, &assets);
+    book.requote(trade.seller, &assets);
+  }
+  (bids, asks)
+}
+
+// The report's charts, each with a short name usable as a file stem.
+pub fn charts(log: &RunLog, numeraire: Numeraire) -> Vec<(&'static str, Chart)> {
+  let final_assets = log.final_assets();
+  let prices: Points = log.trades.iter().enumerate().map(|(i, t)| (i as f64, numeraire.price(t.price_per_a_in_b()))).collect();
+  let (bids, asks) = quote_paths(log, numeraire);
+  let (start_supply, start_demand) = curve_series(&log.initial_assets, numeraire);
+  let (end_supply, end_demand) = curve_series(&final_assets, numeraire);
+  let valuation_price = Summary::of(log).valuation_price;
+  let price_label = format!("price ({})", numeraire.unit());
+  vec![
+    ("price_path", Chart::new("Price path", "trade", &price_label).log_y()
+      .series("price", prices)),
+    ("curves", Chart::new("Supply and demand of A", &price_label, "quantity of A").log_x()
+      .series("supply (start)", start_supply)
+      .series("demand (start)", start_demand)
+      .series("supply (end)", end_supply)
+      .series("demand (end)", end_demand)),
+    ("quotes", Chart::new("Best bid and ask", "trade", &price_label).log_y()
+      .series("best bid", bids)
+      .series("best ask", asks)),
+    ("lorenz", Chart::new("Lorenz curve of wealth", "share of agents", "share of wealth")
+      .series("equality", vec![(0.0, 0.0), (1.0, 1.0)])
+      .series("initial", lorenz_curve(&wealth_in_b(&log.initial_assets, valuation_price)))
+      .series("final", lorenz_curve(&wealth_in_b(&final_assets, valuation_price)))),
+    ("lorenz_utility", Chart::new("Lorenz curve of utility", "share of agents", "share of utility")
+      .series("equality", vec![(0.0, 0.0), (1.0, 1.0)])
+      .series("initial", lorenz_curve(&utilities(&log.initial_assets)))
+      .series("final", lorenz_curve(&utilities(&final_assets)))),
+  ]
+}
+
+pub fn html(log: &RunLog, numeraire: Numeraire) -> String {
+  let summary = Summary::of(log).in_numeraire(numeraire);
+  let mut out = String::new();
+  writeln!(out, "<!DOCTYPE html>").unwrap();
+  writeln!(out, "<html><head><meta charset=\"utf-8\"><title>simmarket run {}</title>", log.seed).unwrap();
+  writeln!(out, "<style>body {{ font-family: sans-serif; max-width: 700px; margin: 2em auto; }} td {{ padding: 2px 12px 2px 0; }} td:last-child {{ font-family: monospace; }}</style>").unwrap();
+  writeln!(out, "</head><body>").unwrap();
+  writeln!(out, "<h1>simmarket run {}</h1>", log.seed).unwrap();
+  writeln!(out, "<table>").unwrap();
+  let stop = summary.stop.map_or("unknown".to_string(), |s| format!("{:?} at tick {}", s.reason, s.tick));
+  writeln!(out, "<tr><td>stopped because</td><td>{}</td></tr>", escape(&stop)).unwrap();
+  writeln!(out, "<tr><td>prices in</td><td>{}</td></tr>", numeraire.unit()).unwrap();
+  if let Some(flag) = summary.non_convergence {
+    writeln!(out, "<tr><td>non-convergence</td><td>{:?}</td></tr>", flag).unwrap();
+  }
+  for (_, label, value) in summary.metrics() {
+    let value = value.map_or("none".to_string(), |v| v.to_string());
+    writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", escape(label), escape(&value)).unwrap();
+  }
+  writeln!(out, "</table>").unwrap();
+  for (_, chart) in charts(log, numeraire) {
+    writeln!(out, "<div>{}</div>", chart.to_svg()).unwrap();
+  }
+  writeln!(out, "</body></html>").unwrap();
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arrivals::Arrivals;
+  use crate::market::Market;
+  use crate::pricing::PricingRule;
+  use crate::report::*;
+  use crate::risk::RiskRules;
+  use crate::stopping::StoppingRules;
+  use crate::strategy::Strategies;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_report() {
+    // a buyer valuing A at 3 B with 10 B and a seller valuing it at 1 with 4 A, who
+    // trade all 4 at 2
+    let assets = vec![(linear_agent(3.0), Balance { a: 0.0, b: 10.0 }), (linear_agent(1.0), Balance { a: 4.0, b: 0.0 })];
+    let mut market = Market::new(assets, Arrivals::every_tick(0), PricingRule::default(), RiskRules::default(), StoppingRules::default());
+    market.run(&mut Strategies::truthful_for(&market.assets), |_| {});
+    let log = market.into_outcome().into_log(7, vec![]);
+
+    let in_b = charts(&log, Numeraire::B);
+    assert_eq!(in_b.iter().map(|(name, _)| *name).collect::<Vec<_>>(), vec!["price_path", "curves", "quotes", "lorenz", "lorenz_utility"]);
+    assert_eq!(in_b[0].1.series[0].points, vec![(0.0, 2.0)]);
+    // the quotes the trade was matched from
+    assert_eq!(in_b[2].1.series.iter().map(|s| s.points.clone()).collect::<Vec<_>>(), vec![vec![(0.0, 3.0)], vec![(0.0, 1.0)]]);
+    // and priced in A
+    assert_eq!(charts(&log, Numeraire::A)[0].1.series[0].points, vec![(0.0, 0.5)]);
+
+    // a row for the stop, the unit and every metric, and every chart
+    let html = html(&log, Numeraire::B);
+    assert!(html.contains("<title>simmarket run 7</title>"));
+    assert!(html.contains("<tr><td>stopped because</td><td>Exhausted at tick 1</td></tr>"));
+    assert!(html.contains("<tr><td>prices in</td><td>B per A</td></tr>"));
+    assert!(html.contains("<tr><td>volume of A traded</td><td>4</td></tr>"));
+    assert!(html.contains("<tr><td>buyers' share of the quoted surplus</td><td>0.5</td></tr>"));
+    assert_eq!(html.matches("<tr>").count(), 2 + Summary::of(&log).metrics().len());
+    assert_eq!(html.matches("<svg").count(), 5);
+  }
+}
@@ -0,0 +1,134 @@
+// Trade reporting: when an executed trade becomes public. Execution and dissemination
+// are separate steps, so trades settle as soon as they match but reach the tape, the
+// record agents' strategies quote from, only once they're reported. Each trade is
+// reported at the start of a tick after the one it executed in, with a random delay
+// of up to some number of ticks on top, drawn as a common random number keyed by the
+// trade (see crn); with no delay every trade reaches the next tick's quotes. Reports
+// come out in the order they're due, so a late report of an old trade can follow a
+// newer one onto the tape, as on a real one.
+//
+// Only adaptive agents (see strategy) and ZIP agents (see zip) read the tape. The
+// report sets a run with delayed reporting against the same run reported
+// immediately, to show what the stale information costs them in convergence.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::crn::{Crn, Stream};
+use crate::pairs::settled_at;
+use crate::runlog::RunLog;
+use crate::{Price, Trade};
+
+// A trade reaching the tape.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Report {
+  pub tick: Tick,
+  // its index among the run's trades
+  pub trade: usize,
+}
+
+pub struct Tape {
+  max_delay: Tick,
+  crn: Crn,
+  // how many of the run's trades have been seen
+  recorded: usize,
+  // (due, trade index, price), for trades executed but not yet reported
+  pending: Vec<(Tick, usize, Price)>,
+  last_price: Option<Price>,
+}
+
+impl Tape {
+  pub fn new(max_delay: Tick, seed: u64) -> Tape {
+    Tape { max_delay, crn: Crn::new(seed), recorded: 0, pending: vec![], last_price: None }
+  }
+
+  // The extra ticks the `trade`th trade waits to be reported, uniform on 0..=max_delay.
+  pub fn delay(&self, trade: usize) -> Tick {
+    (self.crn.uniform(Stream::ReportingDelay, &[trade as u64]) * (self.max_delay + 1) as f64) as Tick
+  }
+
+  // Takes in the trades executed since the last call and returns those due by `now`,
+  // in the order they're reported.
+  pub fn observe(&mut self, trades: &[Trade], now: Tick) -> Vec<Report> {
+    for (i, trade) in trades.iter().enumerate().skip(self.recorded) {
+      self.pending.push((trade.tick + 1 + self.delay(i), i, trade.price_per_a_in_b()));
+    }
+    self.recorded = trades.len();
+    self.pending.sort_by_key(|&(due, i, _)| (due, i));
+    let due: Vec<(Tick, usize, Price)> = self.pending.drain(..self.pending.partition_point(|&(due, _, _)| due <= now)).collect();
+    if let Some(&(_, _, price)) = due.last() {
+      self.last_price = Some(price);
+    }
+    due.iter().map(|&(tick, trade, _)| Report { tick, trade }).collect()
+  }
+
+  // Whether any trade executed is still to be reported.
+  pub fn is_pending(&self) -> bool {
+    !self.pending.is_empty()
+  }
+
+  // The price of the last trade reported.
+  pub fn last_price(&self) -> Option<Price> {
+    self.last_price
+  }
+}
+
+pub struct Convergence {
+  pub trades: usize,
+  pub ticks: Tick,
+  // the first trade from which prices stay within 1% of the last one, and its tick
+  pub settled_at: Option<(usize, Tick)>,
+  pub final_price: Option<Price>,
+  // root mean square of each trade's price relative to the final one, less 1
+  pub price_error: Option<f64>,
+}
+
+pub fn convergence(log: &RunLog) -> Convergence {
+  let prices: Vec<Price> = log.trades.iter().map(Trade::price_per_a_in_b).collect();
+  let final_price = prices.last().copied();
+  Convergence {
+    trades: log.trades.len(),
+    ticks: log.stop.map_or(0, |s| s.tick),
+    settled_at: settled_at(&prices).map(|i| (i, log.trades.get(i).map_or(0, |t| t.tick))),
+    final_price,
+    price_error: final_price.map(|last| (prices.iter().fold(0.0, |s, p| s + (p / last - 1.0).powi(2)) / prices.len() as f64).sqrt()),
+  }
+}
+
+pub fn print_report(immediate: &RunLog, delayed: &RunLog, max_delay: Tick) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  println!("adaptive quoting with trades reported up to {} ticks late vs immediately:", max_delay);
+  for (label, log) in [("immediate", immediate), ("delayed", delayed)] {
+    let c = convergence(log);
+    let settled = c.settled_at.map_or("never".to_string(), |(i, tick)| format!("from trade {} (tick {})", i, tick));
+    println!("  {:<9}  {} trades over {} ticks, settled {}, final price {}, price error {}", label, c.trades, c.ticks, settled, show(c.final_price), show(c.price_error));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::reporting::*;
+  use crate::simulation::SimulationBuilder;
+
+  #[test]
+  fn test_reporting_delay() {
+    let trade = |tick| Trade { tick, buyer: 0, seller: 1, amount_a: 1.0, amount_b: tick as f64 + 1.0, bid_price: 9.0, ask_price: 0.0, ..Trade::default() };
+    let trades: Vec<Trade> = (0..50).map(trade).collect();
+    let mut tape = Tape::new(0, 1);
+    // without a delay each trade is on the next tick's tape
+    assert_eq!(tape.observe(&trades[..1], 1), vec![Report { tick: 1, trade: 0 }]);
+    assert_eq!((tape.observe(&trades[..1], 2), tape.last_price()), (vec![], Some(1.0)));
+
+    let mut tape = Tape::new(3, 1);
+    let reports: Vec<Report> = (0..60).flat_map(|now| tape.observe(&trades[..(now as usize).min(50)], now)).collect();
+    assert_eq!(reports.len(), 50);
+    assert!(reports.iter().all(|r| (trades[r.trade].tick + 1..=trades[r.trade].tick + 4).contains(&r.tick)));
+    assert!(reports.windows(2).any(|w| w[1].trade < w[0].trade));
+
+    // adaptive agents still trade the market out with a delay
+    let run = |delay| SimulationBuilder::new().agents(60).seed(4).adaptive(true).reporting_delay(delay).build().unwrap().run();
+    let (immediate, delayed) = (run(0), run(5));
+    assert_ne!(immediate.trades, delayed.trades);
+    assert!([&immediate, &delayed].iter().all(|log| convergence(log).settled_at.is_some()));
+  }
+}
@@ -0,0 +1,128 @@
+// Pre-trade checks. Every matched trade is validated before it settles; one that
+// fails is rejected, the order responsible is set aside for the rest of the tick, and
+// the book is matched again.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::book::OrderBook;
+use crate::clock::Tick;
+use crate::pricing::PricingRule;
+use crate::timing::{self, Phase};
+use crate::{find_next_trade, Agent, AgentId, Balance, OrderType, Trade};
+
+#[derive(Default, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct RiskRules {
+  // the most A any agent may hold after buying
+  pub position_limit: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RejectReason {
+  SelfTrade,
+  InsufficientBalance { agent: AgentId },
+  PositionLimit { agent: AgentId },
+  // the fill rounds to less than a lot, or is too small to move anything (see
+  // MIN_FILL); `agent` is the side with less to trade
+  BelowLot { agent: AgentId },
+}
+
+// The least either leg of a fill may be: below it the amounts are subnormal or zero,
+// too imprecise to tell whether the trade gains either side anything. A fill that
+// leaves what a party receives unchanged where it's added is no trade either, so
+// balances drained by mistyped prices aren't matched on in ever smaller fills.
+pub const MIN_FILL: f64 = f64::MIN_POSITIVE;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Rejection {
+  pub tick: Tick,
+  pub trade: Trade,
+  pub reason: RejectReason,
+}
+
+impl RejectReason {
+  // The order to take off the book before re-matching. A self-trade drops the ask.
+  fn offending_order(&self, trade: &Trade) -> (AgentId, OrderType) {
+    match *self {
+      RejectReason::SelfTrade => (trade.seller, OrderType::Ask),
+      RejectReason::InsufficientBalance { agent } if agent == trade.buyer => (agent, OrderType::Bid),
+      RejectReason::InsufficientBalance { agent } => (agent, OrderType::Ask),
+      RejectReason::PositionLimit { agent } => (agent, OrderType::Bid),
+      RejectReason::BelowLot { agent } if agent == trade.buyer => (agent, OrderType::Bid),
+      RejectReason::BelowLot { agent } => (agent, OrderType::Ask),
+    }
+  }
+}
+
+impl RiskRules {
+  pub fn check(&self, trade: &Trade, assets: &[(Agent, Balance)]) -> Result<(), RejectReason> {
+    let buyer = assets[trade.buyer].1;
+    let seller = assets[trade.seller].1;
+    if trade.buyer == trade.seller {
+      Err(RejectReason::SelfTrade)
+    } else if trade.amount_a < MIN_FILL || trade.amount_b < MIN_FILL || buyer.a + trade.amount_a == buyer.a || seller.b + trade.amount_b == seller.b {
+      // valued at the ask, which both sides are willing to trade at
+      let smaller = if seller.a * trade.ask_price < buyer.b { trade.seller } else { trade.buyer };
+      Err(RejectReason::BelowLot { agent: smaller })
+    } else if buyer.b - trade.tax + trade.buyer_subsidy < trade.amount_b {
+      Err(RejectReason::InsufficientBalance { agent: trade.buyer })
+    } else if seller.a < trade.amount_a {
+      Err(RejectReason::InsufficientBalance { agent: trade.seller })
+    } else if self.position_limit.is_some_and(|limit| buyer.a + trade.amount_a > limit) {
+      Err(RejectReason::PositionLimit { agent: trade.buyer })
+    } else {
+      Ok(())
+    }
+  }
+}
+
+// The best trade on the book that passes the rules, reporting each rejected match
+// along the way. The book is copied, to take out the rejected orders, only once one is.
+pub fn find_allowed_trade(assets: &[(Agent, Balance)], book: &OrderBook, pricing: PricingRule, rules: &RiskRules, priority: &[AgentId], now: Tick, on_reject: impl FnMut(Rejection)) -> Option<Trade> {
+  find_allowed(assets, book, rules, now, |book| find_next_trade(assets, book, pricing, priority, now), on_reject)
+}
+
+// The same, for trades matched by `find` rather than as the best bid against the best ask.
+pub fn find_allowed(assets: &[(Agent, Balance)], book: &OrderBook, rules: &RiskRules, now: Tick, find: impl Fn(&OrderBook) -> Option<Trade>, mut on_reject: impl FnMut(Rejection)) -> Option<Trade> {
+  let mut book = Cow::Borrowed(book);
+  loop {
+    let trade = timing::time(Phase::BestPriceSearch, || find(&book))?;
+    match timing::time(Phase::Clearing, || rules.check(&trade, assets)) {
+      Ok(()) => return Some(trade),
+      Err(reason) => {
+        match reason.offending_order(&trade) {
+          (agent, OrderType::Bid) => book.to_mut().set_bid(agent, None),
+          (agent, OrderType::Ask) => book.to_mut().set_ask(agent, None),
+        }
+        on_reject(Rejection { tick: now, trade, reason });
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::generate_orders;
+  use crate::risk::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_position_limit_rematches() {
+    let assets = vec![
+      (linear_agent(1.0), Balance { a: 10.0, b: 0.0 }),
+      (linear_agent(4.0), Balance { a: 5.0, b: 10.0 }),
+      (linear_agent(3.0), Balance { a: 0.0, b: 10.0 }),
+    ];
+    let orders: Vec<_> = assets.iter().enumerate().map(|(id, (agent, balance))| generate_orders(id, agent, balance)).collect();
+    let rules = RiskRules { position_limit: Some(6.0) };
+    let mut rejections = vec![];
+    let trade = find_allowed_trade(&assets, &OrderBook::from_quotes(&orders), PricingRule::default(), &rules, &[], 0, |r| rejections.push(r)).unwrap();
+    // agent 1 bids highest but already holds 5 A
+    assert_eq!(rejections.len(), 1);
+    assert_eq!(rejections[0].reason, RejectReason::PositionLimit { agent: 1 });
+    assert_eq!((trade.buyer, trade.seller), (2, 0));
+    assert_eq!(rules.check(&trade, &assets), Ok(()));
+  }
+}
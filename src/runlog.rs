@@ -0,0 +1,326 @@
+// Newline-delimited JSON record of a run: a header, the initial population, then
+// every quote change, rejected match, and executed trade in tick order. The trades alone are enough
+// to replay the run to any point, with the decay rates in the header if goods perish
+// (see decay) and the margin limit if agents sell short (see short). The header carries the schema version, and older
+// logs and states are migrated as they're read; see schema.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::decay::Decay;
+use crate::reporting::Report;
+use crate::risk::Rejection;
+use crate::schema::{self, StateFile};
+use crate::short::{self, Short};
+use crate::stopping::Stop;
+use crate::{realized_surplus, settle, Agent, AgentId, Balance, Order, OrderType, Price, Trade};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+  Run {
+    seed: u64,
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    decay: Option<Decay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    short: Option<Short>,
+  },
+  Agent { id: AgentId, agent: Agent, balance: Balance },
+  Quote(Quote),
+  Rejection(Rejection),
+  Trade(Trade),
+  // an executed trade made public; see reporting
+  Report(Report),
+  Stop(Stop),
+}
+
+// An agent's bid or ask changing at some tick; `price` is None when it's withdrawn.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Quote {
+  pub tick: Tick,
+  pub agent_id: AgentId,
+  pub side: OrderType,
+  pub price: Option<Price>,
+}
+
+// Turns the full book quoted each tick into just the changes since the last tick.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QuoteTracker {
+  last: Vec<(Option<Price>, Option<Price>)>,
+}
+
+impl QuoteTracker {
+  pub fn new(n_agents: usize) -> QuoteTracker {
+    QuoteTracker { last: vec![(None, None); n_agents] }
+  }
+
+  pub fn update(&mut self, tick: Tick, orders: &[(Option<Order>, Option<Order>)]) -> Vec<Quote> {
+    self.update_agents(tick, 0..orders.len(), orders)
+  }
+
+  // update, looking only at `agents`, in the order given, when nobody else's quote can
+  // have changed.
+  pub fn update_agents(&mut self, tick: Tick, agents: impl IntoIterator<Item = AgentId>, orders: &[(Option<Order>, Option<Order>)]) -> Vec<Quote> {
+    let mut changes = vec![];
+    for agent_id in agents {
+      let (bid, ask) = orders[agent_id];
+      let now = (bid.map(|o| o.price_per_a_in_b), ask.map(|o| o.price_per_a_in_b));
+      let last = &mut self.last[agent_id];
+      if now.0 != last.0 {
+        changes.push(Quote { tick, agent_id, side: OrderType::Bid, price: now.0 });
+      }
+      if now.1 != last.1 {
+        changes.push(Quote { tick, agent_id, side: OrderType::Ask, price: now.1 });
+      }
+      *last = now;
+    }
+    changes
+  }
+}
+
+// One agent's entry in a saved state snapshot.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AgentState {
+  pub id: AgentId,
+  pub agent: Agent,
+  pub balance: Balance,
+}
+
+pub fn states(assets: &[(Agent, Balance)]) -> StateFile {
+  let agents = assets.iter().enumerate().map(|(id, (agent, balance))| AgentState { id, agent: *agent, balance: *balance }).collect();
+  StateFile { version: schema::VERSION, agents }
+}
+
+fn invalid(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Reads a snapshot written from `states`, e.g. an out-dir's final_state.json, at
+// this or any earlier schema version.
+pub fn read_state(path: &str) -> io::Result<Vec<(Agent, Balance)>> {
+  let file = schema::read_state(serde_json::from_reader(BufReader::new(File::open(path)?))?).map_err(invalid)?;
+  let mut assets = vec![];
+  for state in file.agents {
+    if state.id != assets.len() {
+      return Err(invalid(format!("agent {} out of order", state.id)));
+    }
+    assets.push((state.agent, state.balance));
+  }
+  Ok(assets)
+}
+
+// Every agent's (bid, ask), indexed by agent; None where it has none resting.
+pub type Book = Vec<(Option<Price>, Option<Price>)>;
+
+pub struct RunLog {
+  pub seed: u64,
+  pub initial_assets: Vec<(Agent, Balance)>,
+  pub quotes: Vec<Quote>,
+  pub rejections: Vec<Rejection>,
+  pub trades: Vec<Trade>,
+  // missing from logs of unfinished runs, and from before stopping rules
+  pub stop: Option<Stop>,
+  pub decay: Option<Decay>,
+  pub short: Option<Short>,
+}
+
+impl RunLog {
+  // The tick after the last thing that happened.
+  pub fn end(&self) -> Tick {
+    self.stop.map_or(0, |s| s.tick).max(self.trades.last().map_or(0, |t| t.tick + 1))
+  }
+
+  // Everyone's holdings as of each of `ticks` (increasing), after every trade before it
+  // and, with decay, the spoilage at the end of every tick before it.
+  pub fn assets_at(&self, ticks: &[Tick]) -> Vec<Vec<(Agent, Balance)>> {
+    let mut assets = self.initial_assets.clone();
+    let mut trades = self.trades.iter().peekable();
+    let mut spoiled_through = 0;
+    ticks.iter().map(|&tick| {
+      match self.decay {
+        None => while let Some(trade) = trades.next_if(|t| t.tick < tick) {
+          settle(&mut assets, trade);
+        },
+        Some(decay) => for now in spoiled_through..tick {
+          while let Some(trade) = trades.next_if(|t| t.tick <= now) {
+            settle(&mut assets, trade);
+          }
+          decay.apply(&mut assets);
+        },
+      }
+      spoiled_through = spoiled_through.max(tick);
+      assets.clone()
+    }).collect()
+  }
+
+  // The book as quoted at each of `ticks` (increasing), rebuilt from the quote changes
+  // up to and including it: quotes change at the start of a tick, so this is the book
+  // the tick's trades matched against, not what they left.
+  pub fn books_at(&self, ticks: &[Tick]) -> Vec<Book> {
+    let mut book: Book = vec![(None, None); self.initial_assets.len()];
+    let mut quotes = self.quotes.iter().peekable();
+    ticks.iter().map(|&tick| {
+      while let Some(quote) = quotes.next_if(|q| q.tick <= tick) {
+        match quote.side {
+          OrderType::Bid => book[quote.agent_id].0 = quote.price,
+          OrderType::Ask => book[quote.agent_id].1 = quote.price,
+        }
+      }
+      book.clone()
+    }).collect()
+  }
+
+  // A traded over the run.
+  pub fn volume_a(&self) -> f64 {
+    self.trades.iter().fold(0.0, |v, t| v + t.amount_a)
+  }
+
+  // B paid per A over the run, None if nothing traded.
+  pub fn mean_price(&self) -> Option<Price> {
+    let volume_a = self.volume_a();
+    (volume_a > 0.0).then(|| self.trades.iter().fold(0.0, |b, t| b + t.amount_b) / volume_a)
+  }
+
+  // Every agent's surplus realized on its final holdings (see realized_surplus).
+  pub fn gains(&self) -> Vec<f64> {
+    realized_surplus(&self.initial_assets, &self.final_assets())
+  }
+
+  pub fn total_gains(&self) -> f64 {
+    self.gains().iter().fold(0.0, |s, x| s + x)
+  }
+
+  // As of the end, and with short selling once what's still short is bought in.
+  pub fn final_assets(&self) -> Vec<(Agent, Balance)> {
+    let mut assets = if self.decay.is_some() {
+      self.assets_at(&[self.end()]).pop().unwrap()
+    } else {
+      let mut assets = self.initial_assets.clone();
+      for trade in &self.trades {
+        settle(&mut assets, trade);
+      }
+      assets
+    };
+    if let (Some(_), Some(price)) = (self.short, short::closing_price(&self.initial_assets, &self.trades)) {
+      short::buy_in(&mut assets, &short::owed(self.initial_assets.len(), &self.trades), price);
+    }
+    assets
+  }
+}
+
+pub fn write(path: &str, log: &RunLog) -> io::Result<()> {
+  let mut out = BufWriter::new(File::create(path)?);
+  let mut emit = |event: &Event| -> io::Result<()> {
+    serde_json::to_writer(&mut out, event)?;
+    out.write_all(b"\n")
+  };
+  emit(&Event::Run { seed: log.seed, version: schema::VERSION, decay: log.decay, short: log.short })?;
+  for (id, (agent, balance)) in log.initial_assets.iter().enumerate() {
+    emit(&Event::Agent { id, agent: *agent, balance: *balance })?;
+  }
+  // each tick's quotes come before its rejections, and those before its trade
+  let mut quotes = log.quotes.iter().peekable();
+  let mut rejections = log.rejections.iter().peekable();
+  let mut emit_through = |tick: Tick, emit: &mut dyn FnMut(&Event) -> io::Result<()>| -> io::Result<()> {
+    loop {
+      let next_quote = quotes.peek().map(|q| q.tick).filter(|&t| t <= tick);
+      let next_rejection = rejections.peek().map(|r| r.tick).filter(|&t| t <= tick);
+      let quote_first = match (next_quote, next_rejection) {
+        (None, None) => return Ok(()),
+        (Some(q), Some(r)) => q <= r,
+        (q, _) => q.is_some(),
+      };
+      if quote_first {
+        emit(&Event::Quote(quotes.next().unwrap().clone()))?;
+      } else {
+        emit(&Event::Rejection(rejections.next().unwrap().clone()))?;
+      }
+    }
+  };
+  for trade in &log.trades {
+    emit_through(trade.tick, &mut emit)?;
+    emit(&Event::Trade(trade.clone()))?;
+  }
+  emit_through(Tick::MAX, &mut emit)?;
+  if let Some(stop) = log.stop {
+    emit(&Event::Stop(stop))?;
+  }
+  out.flush()
+}
+
+pub fn read(path: &str) -> io::Result<RunLog> {
+  let mut log = RunLog { seed: 0, initial_assets: vec![], quotes: vec![], rejections: vec![], trades: vec![], stop: None, decay: None, short: None };
+  let mut version = 1;
+  for line in BufReader::new(File::open(path)?).lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let value: serde_json::Value = serde_json::from_str(&line)?;
+    if value["type"] == "run" {
+      version = schema::log_version(&value);
+    }
+    match schema::read_event(value, version).map_err(invalid)? {
+      Event::Run { seed, decay, short, .. } => (log.seed, log.decay, log.short) = (seed, decay, short),
+      Event::Agent { id, agent, balance } => {
+        if id != log.initial_assets.len() {
+          return Err(invalid(format!("agent {} out of order", id)));
+        }
+        log.initial_assets.push((agent, balance));
+      }
+      Event::Quote(quote) => log.quotes.push(quote),
+      Event::Rejection(rejection) => log.rejections.push(rejection),
+      Event::Trade(trade) => log.trades.push(trade),
+      // follow from the trades and the config, so a log doesn't keep them
+      Event::Report(_) => {}
+      Event::Stop(stop) => log.stop = Some(stop),
+    }
+  }
+  Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::runlog::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_roundtrip_replays_exactly() {
+    let agent = |ca, cb| Agent { consumption_b_coeff: cb, ..linear_agent(ca) };
+    let mut assets = vec![
+      (agent(1.0, 5.0), Balance { a: 1.0, b: 2.0 }),
+      (agent(8.0, 1.0), Balance { a: 3.0, b: 4.0 }),
+    ];
+    let initial_assets = assets.clone();
+    let mut quotes = vec![];
+    let rules = crate::stopping::StoppingRules::default();
+    let outcome = crate::execute_all_trades(&mut assets, &mut crate::strategy::Strategies::truthful(2), crate::arrivals::Arrivals::every_tick(0), crate::pricing::PricingRule::default(), &crate::risk::RiskRules::default(), &rules, |event| {
+      if let Event::Quote(quote) = event {
+        quotes.push(quote.clone());
+      }
+    });
+    let (trades, stop) = (outcome.trades, outcome.stop);
+    // both agents quote both sides at tick 0; after the trade agent 1 is out of B
+    assert_eq!(quotes.len(), 5);
+    assert_eq!(quotes[4], Quote { tick: 1, agent_id: 1, side: OrderType::Bid, price: None });
+
+    let rejections = vec![Rejection { tick: 0, trade: trades[0].clone(), reason: crate::risk::RejectReason::SelfTrade }];
+    let path = std::env::temp_dir().join("simmarket_runlog_test.ndjson");
+    write(path.to_str().unwrap(), &RunLog { seed: 7, initial_assets, quotes: quotes.clone(), rejections: rejections.clone(), trades: trades.clone(), stop: Some(stop), decay: None, short: None }).unwrap();
+    let log = read(path.to_str().unwrap()).unwrap();
+    assert_eq!(log.seed, 7);
+    assert_eq!(log.quotes, quotes);
+    assert_eq!(log.rejections, rejections);
+    assert_eq!(log.trades, trades);
+    assert_eq!(log.stop, Some(stop));
+    assert_eq!(log.final_assets(), assets);
+
+    let state_path = std::env::temp_dir().join("simmarket_state_test.json");
+    std::fs::write(&state_path, serde_json::to_string(&states(&assets)).unwrap()).unwrap();
+    assert_eq!(read_state(state_path.to_str().unwrap()).unwrap(), assets);
+  }
+}
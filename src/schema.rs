@@ -0,0 +1,83 @@
+// Versions of the on-disk formats, and the migrations that bring old files up to date
+// so saved states and run logs keep loading as the structs change. A file records the
+// version it was written at (files from before versioning count as version 1), and
+// reading it runs every migration from there to VERSION on the raw JSON before it's
+// deserialized. Changing a saved struct means bumping VERSION and adding one migration
+// per format that fills in the new shape from the old.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::runlog::{AgentState, Event};
+
+pub const VERSION: u32 = 2;
+
+// A saved population, e.g. an out-dir's final_state.json.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct StateFile {
+  pub version: u32,
+  pub agents: Vec<AgentState>,
+}
+
+type Migration = fn(Value) -> Value;
+
+// MIGRATIONS[i] takes a file from version i + 1 to i + 2
+const STATE_MIGRATIONS: [Migration; 1] = [state_v1_to_v2];
+const EVENT_MIGRATIONS: [Migration; 1] = [event_v1_to_v2];
+
+// v1 states were a bare array of agents
+fn state_v1_to_v2(agents: Value) -> Value {
+  json!({ "version": 2, "agents": agents })
+}
+
+// v1 run headers had no version
+fn event_v1_to_v2(mut event: Value) -> Value {
+  if event["type"] == "run" {
+    event["version"] = json!(2);
+  }
+  event
+}
+
+fn upgrade(value: Value, from: u32, migrations: &[Migration]) -> Result<Value, String> {
+  if from == 0 || from > VERSION {
+    return Err(format!("schema version {} isn't one this simmarket (version {}) knows", from, VERSION));
+  }
+  Ok(migrations[from as usize - 1..].iter().fold(value, |value, migrate| migrate(value)))
+}
+
+pub fn read_state(value: Value) -> Result<StateFile, String> {
+  let version = match &value {
+    Value::Array(_) => 1,
+    _ => value["version"].as_u64().ok_or("state file has no version")? as u32,
+  };
+  serde_json::from_value(upgrade(value, version, &STATE_MIGRATIONS)?).map_err(|e| e.to_string())
+}
+
+// One line of a run log written at `version`.
+pub fn read_event(value: Value, version: u32) -> Result<Event, String> {
+  serde_json::from_value(upgrade(value, version, &EVENT_MIGRATIONS)?).map_err(|e| e.to_string())
+}
+
+// The version a run log's header says it was written at.
+pub fn log_version(header: &Value) -> u32 {
+  header["version"].as_u64().map_or(1, |v| v as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::schema::*;
+
+  #[test]
+  fn test_old_files_load() {
+    let agent = json!({ "production_a": 0.0, "production_b": 0.0, "consumption_a_coeff": 1.0, "consumption_b_coeff": 2.0 });
+    let v1 = json!([{ "id": 0, "agent": agent, "balance": { "a": 1.0, "b": 2.0 } }]);
+    let state = read_state(v1).unwrap();
+    assert_eq!((state.version, state.agents.len()), (VERSION, 1));
+    assert_eq!(read_state(serde_json::to_value(&state).unwrap()).unwrap(), state);
+    assert!(read_state(json!({ "version": VERSION + 1, "agents": [] })).is_err());
+
+    let header = json!({ "type": "run", "seed": 7 });
+    assert_eq!(log_version(&header), 1);
+    assert_eq!(read_event(header, 1).unwrap(), Event::Run { seed: 7, version: VERSION, decay: None, short: None });
+  }
+}
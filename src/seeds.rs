@@ -0,0 +1,118 @@
+// Scanning many seeds for runs whose outcomes match some predicates, to find
+// illustrative or pathological cases without hunting by hand.
+
+use crate::summary::Summary;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Comparison {
+  Lt,
+  Le,
+  Gt,
+  Ge,
+  Eq,
+  Ne,
+}
+
+// `<metric> <op> <value>`, e.g. `final_spread>0.5`, over the metrics in Summary.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Predicate {
+  pub metric: String,
+  pub comparison: Comparison,
+  pub value: f64,
+}
+
+impl Predicate {
+  pub fn parse(s: &str) -> Result<Predicate, String> {
+    // two-character operators first, so `>=` isn't read as `>`
+    let ops = [("<=", Comparison::Le), (">=", Comparison::Ge), ("==", Comparison::Eq), ("!=", Comparison::Ne),
+               ("<", Comparison::Lt), (">", Comparison::Gt), ("=", Comparison::Eq)];
+    for (op, comparison) in ops {
+      if let Some((metric, value)) = s.split_once(op) {
+        let value = value.trim().parse::<f64>().map_err(|e| format!("bad value in {:?}: {}", s, e))?;
+        return Ok(Predicate { metric: metric.trim().to_string(), comparison, value });
+      }
+    }
+    Err(format!("no comparison operator in {:?}", s))
+  }
+
+  pub fn describe(&self) -> String {
+    let op = match self.comparison {
+      Comparison::Lt => "<",
+      Comparison::Le => "<=",
+      Comparison::Gt => ">",
+      Comparison::Ge => ">=",
+      Comparison::Eq => "==",
+      Comparison::Ne => "!=",
+    };
+    format!("{}{}{}", self.metric, op, self.value)
+  }
+
+  // Metrics that are undefined for a run (e.g. final spread with an empty book)
+  // never match.
+  pub fn holds(&self, summary: &Summary) -> bool {
+    summary.metric(&self.metric).is_some_and(|x| self.admits(x))
+  }
+
+  // Whether the metric taking the value `x` would satisfy it.
+  pub fn admits(&self, x: f64) -> bool {
+    match self.comparison {
+      Comparison::Lt => x < self.value,
+      Comparison::Le => x <= self.value,
+      Comparison::Gt => x > self.value,
+      Comparison::Ge => x >= self.value,
+      Comparison::Eq => x == self.value,
+      Comparison::Ne => x != self.value,
+    }
+  }
+}
+
+// Runs `simulate` for each seed and prints the ones satisfying every predicate,
+// stopping after `limit` matches. A predicate on a metric there isn't is an error
+// before anything runs.
+pub fn find_seeds(seeds: &[u64], predicates: &[Predicate], limit: Option<usize>, simulate: impl Fn(u64) -> Summary) -> Result<Vec<u64>, String> {
+  let known = Summary::names();
+  if let Some(p) = predicates.iter().find(|p| !known.contains(&p.metric.as_str())) {
+    return Err(format!("unknown metric {:?}", p.metric));
+  }
+  let mut columns: Vec<&str> = predicates.iter().map(|p| p.metric.as_str()).collect();
+  if columns.is_empty() {
+    columns = vec!["trades", "mean_price", "final_spread", "gini_final"];
+  }
+  let mut found = vec![];
+  for &seed in seeds {
+    let summary = simulate(seed);
+    if predicates.iter().all(|p| p.holds(&summary)) {
+      let values: Vec<String> = columns.iter()
+        .map(|m| format!("{}={}", m, summary.metric(m).map_or("none".to_string(), |v| v.to_string())))
+        .collect();
+      println!("seed {}: {}", seed, values.join(" "));
+      found.push(seed);
+      if Some(found.len()) == limit {
+        break;
+      }
+    }
+  }
+  println!("{} of {} scanned seeds matched", found.len(), seeds.len());
+  Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::seeds::*;
+
+  #[test]
+  fn test_parse_predicate() {
+    assert_eq!(Predicate::parse("final_spread>=0.5"), Ok(Predicate { metric: "final_spread".to_string(), comparison: Comparison::Ge, value: 0.5 }));
+    assert_eq!(Predicate::parse("converged = 0").unwrap().comparison, Comparison::Eq);
+    assert_eq!(Predicate::parse("gini_final<0.3").unwrap().comparison, Comparison::Lt);
+    assert!(Predicate::parse("gini_final").is_err());
+    assert!(Predicate::parse("gini_final>high").is_err());
+  }
+
+  #[test]
+  fn test_unknown_metric() {
+    let nope = Predicate::parse("nope>1").unwrap();
+    let found = find_seeds(&[1, 2], &[nope], None, |_| panic!("ran a seed"));
+    assert_eq!(found, Err("unknown metric \"nope\"".to_string()));
+  }
+}
@@ -0,0 +1,114 @@
+// Trading sessions, as an exchange day runs them: each round opens with a call
+// auction, trades continuously for a set number of ticks, and closes with another
+// call, over and over until the market is exhausted. The calls clear the orders that
+// built up since the last session at one price (see call) and the continuous session
+// matches arrivals as they come (see continuous); a round takes the continuous
+// session's length plus two ticks of the clock.
+//
+// The report breaks a run's trades down by the session they happened in, with the
+// volatility of the log price change into each trade from the one before it, so
+// prices set at the open and the close can be set against the ones in between.
+
+use crate::clock::Tick;
+use crate::stats::mean;
+use crate::Trade;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Session {
+  Open,
+  Continuous,
+  Close,
+}
+
+pub const ALL: [Session; 3] = [Session::Open, Session::Continuous, Session::Close];
+
+impl Session {
+  // Which session `now` falls in, with `continuous` ticks between the calls.
+  pub fn at(now: Tick, continuous: Tick) -> Session {
+    match now % (continuous + 2) {
+      0 => Session::Open,
+      t if t <= continuous => Session::Continuous,
+      _ => Session::Close,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Session::Open => "open",
+      Session::Continuous => "continuous",
+      Session::Close => "close",
+    }
+  }
+}
+
+pub fn round(now: Tick, continuous: Tick) -> Tick {
+  now / (continuous + 2)
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct SessionStats {
+  pub session: Session,
+  pub trades: usize,
+  pub volume_a: f64,
+  pub mean_price: Option<f64>, // volume-weighted
+  // standard deviation of the log price change into each trade from the last one
+  // before it, None with under two such changes
+  pub volatility: Option<f64>,
+}
+
+pub fn stats(trades: &[Trade], continuous: Tick) -> Vec<SessionStats> {
+  ALL.iter().map(|&session| {
+    let in_session = |t: &Trade| Session::at(t.tick, continuous) == session;
+    let ours: Vec<&Trade> = trades.iter().filter(|t| in_session(t)).collect();
+    let volume_a = ours.iter().fold(0.0, |v, t| v + t.amount_a);
+    let changes: Vec<f64> = trades.windows(2).filter(|w| in_session(&w[1]))
+      .map(|w| (w[1].price_per_a_in_b() / w[0].price_per_a_in_b()).ln())
+      .collect();
+    let volatility = (changes.len() >= 2).then(|| {
+      let m = mean(&changes);
+      (changes.iter().map(|c| (c - m).powi(2)).sum::<f64>() / (changes.len() - 1) as f64).sqrt()
+    });
+    SessionStats {
+      session,
+      trades: ours.len(),
+      volume_a,
+      mean_price: (volume_a > 0.0).then(|| ours.iter().map(|t| t.amount_b).sum::<f64>() / volume_a),
+      volatility,
+    }
+  }).collect()
+}
+
+pub fn print_report(trades: &[Trade], continuous: Tick) {
+  let rounds = trades.last().map_or(0, |t| round(t.tick, continuous) + 1);
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  println!("sessions ({} continuous ticks a round, trading over {} rounds):", continuous, rounds);
+  for s in stats(trades, continuous) {
+    println!("  {:<11} {} trades, {} A, mean price {}, volatility {}", s.session.name(), s.trades, s.volume_a, show(s.mean_price), show(s.volatility));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::continuous::Matching;
+  use crate::pricing::PricingRule;
+  use crate::session::*;
+  use crate::simulation::SimulationBuilder;
+
+  #[test]
+  fn test_sessions() {
+    assert_eq!((0..6).map(|t| Session::at(t, 2)).collect::<Vec<_>>(), vec![Session::Open, Session::Continuous, Session::Continuous, Session::Close, Session::Open, Session::Continuous]);
+    assert_eq!(round(5, 2), 1);
+    assert_eq!(Matching::parse("sessions:3"), Ok(Matching::Sessions { continuous: 3 }));
+    assert!(Matching::parse("sessions:0").is_err());
+
+    let log = SimulationBuilder::new().agents(40).seed(2).pricing(PricingRule::default().with_matching(Matching::Sessions { continuous: 3 })).build().unwrap().run();
+    let stats = stats(&log.trades, 3);
+    // the opening call clears the whole book built up before it, and the continuous
+    // session trades what's left
+    assert!(stats[0].trades > 0 && stats[1].trades > 0);
+    assert_eq!(stats.iter().map(|s| s.trades).sum::<usize>(), log.trades.len());
+    // every trade in a call goes at its clearing price
+    let first: Vec<&Trade> = log.trades.iter().filter(|t| t.tick == 0).collect();
+    assert!(first.iter().all(|t| (t.price_per_a_in_b() - first[0].price_per_a_in_b()).abs() < 1e-9));
+  }
+}
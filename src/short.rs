@@ -0,0 +1,223 @@
+// Short selling: agents may sell A they don't hold, borrowing it from a lender outside
+// the economy, until what they owe reaches a margin limit the same for everyone. Their
+// position in A (what they hold less what they owe) goes negative. Whatever A a short
+// seller then buys goes back to the lender first, until it's covered. When the run
+// stops, settlement is enforced: every position still short is bought in at the
+// closing price, paid from the seller's B, and whatever it can't pay is in default,
+// written off, and reported as such.
+//
+// Like credit, the loans and returns ride on the trades (see Trade::shorted), so
+// balances never go negative and the trades are still enough to replay a run. The
+// buy-in needs only the positions and the closing price, which the log gives too, so
+// a log carries just the limit in its header and its final holdings are settled (see
+// runlog::RunLog::final_assets). Only linear agents sell short, by batch matching, as
+// for credit.
+
+use serde::{Deserialize, Serialize};
+
+use crate::dispersion::walrasian_price;
+use crate::runlog::RunLog;
+use crate::{Agent, Balance, Price, Trade};
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Short {
+  // the most A anyone may owe
+  pub limit: f64,
+}
+
+impl Short {
+  pub fn is_valid(&self) -> bool {
+    self.limit >= 0.0 && self.limit.is_finite()
+  }
+
+  // What an agent holding `balance` and owing `owed` A can sell.
+  pub fn spendable(&self, balance: Balance, owed: f64) -> Balance {
+    Balance { a: balance.a + (self.limit - owed).max(0.0), ..balance }
+  }
+
+  // Lends `trade`'s seller the A it's short of delivering and takes what the buyer then
+  // holds toward covering what it owes, recording both on the trade and in `owed`. The
+  // loan is rounded up, if need be, so a seller delivering all it holds ends at 0
+  // rather than a hair below.
+  pub fn finance(&self, trade: &mut Trade, assets: &[(Agent, Balance)], owed: &mut [f64]) {
+    let held = assets[trade.seller].1.a;
+    let mut shorted = (trade.amount_a - held).max(0.0);
+    while held + shorted < trade.amount_a {
+      shorted = shorted.next_up();
+    }
+    trade.shorted = shorted;
+    trade.covered = owed[trade.buyer].min(assets[trade.buyer].1.a + trade.amount_a);
+    owed[trade.seller] += trade.shorted;
+    owed[trade.buyer] -= trade.covered;
+  }
+}
+
+// A lent over `trades`, less what was returned.
+pub fn net_shorted(trades: &[Trade]) -> f64 {
+  trades.iter().fold(0.0, |s, t| s + t.shorted - t.covered)
+}
+
+// What everyone owes in A after the `trades`, as the run kept it.
+pub fn owed(n_agents: usize, trades: &[Trade]) -> Vec<f64> {
+  let mut owed = vec![0.0; n_agents];
+  for trade in trades {
+    owed[trade.seller] += trade.shorted;
+    owed[trade.buyer] -= trade.covered;
+  }
+  owed
+}
+
+// The price positions still short are bought in at: the last trade's, or, if nothing
+// traded, the market-clearing price of the `initial` holdings, so a run always has one
+// (None only if either good is missing altogether).
+pub fn closing_price(initial: &[(Agent, Balance)], trades: &[Trade]) -> Option<Price> {
+  trades.last().map(Trade::price_per_a_in_b).or_else(|| walrasian_price(initial))
+}
+
+// Buys in what everyone still `owed` at `price`, out of their B, as far as it goes.
+pub fn buy_in(assets: &mut [(Agent, Balance)], owed: &[f64], price: Price) {
+  for ((_, balance), &owed) in assets.iter_mut().zip(owed) {
+    balance.b -= (owed * price).min(balance.b);
+  }
+}
+
+pub struct Shorting {
+  pub sellers: usize,
+  pub shorted: f64,
+  pub covered: f64,
+  // what's owed when the run stops, by agent, and what its buy-in cost and went unpaid
+  pub outstanding: Vec<f64>,
+  pub price: Option<Price>,
+  pub paid: f64,
+  pub unpaid: f64,
+}
+
+impl Shorting {
+  pub fn of(log: &RunLog) -> Shorting {
+    let n = log.initial_assets.len();
+    let outstanding = owed(n, &log.trades);
+    let price = closing_price(&log.initial_assets, &log.trades);
+    let held = log.assets_at(&[log.end()]).pop().unwrap();
+    let (paid, unpaid) = held.iter().zip(&outstanding).fold((0.0, 0.0), |(p, u), ((_, balance), &owed)| {
+      let cost = owed * price.unwrap_or(0.0);
+      (p + cost.min(balance.b), u + (cost - balance.b).max(0.0))
+    });
+    Shorting {
+      sellers: (0..n).filter(|&id| log.trades.iter().any(|t| t.seller == id && t.shorted > 0.0)).count(),
+      shorted: log.trades.iter().fold(0.0, |s, t| s + t.shorted),
+      covered: log.trades.iter().fold(0.0, |c, t| c + t.covered),
+      outstanding,
+      price,
+      paid,
+      unpaid,
+    }
+  }
+
+  pub fn bought_in(&self) -> f64 {
+    self.outstanding.iter().fold(0.0, |s, x| s + x)
+  }
+}
+
+pub fn print_report(baseline: &RunLog, log: &RunLog, short: Short) {
+  let change = if baseline.volume_a() > 0.0 { format!(", {:+.4}%", 100.0 * (log.volume_a() / baseline.volume_a() - 1.0)) } else { String::new() };
+  println!("short selling of up to {} A vs none:", short.limit);
+  println!("  trades: {} (without {}), volume {} A (without {}{})", log.trades.len(), baseline.trades.len(), log.volume_a(), baseline.volume_a(), change);
+  let shorting = Shorting::of(log);
+  println!("  {} of {} agents sold {} A short in all and covered {} by buying", shorting.sellers, log.initial_assets.len(), shorting.shorted, shorting.covered);
+  match (shorting.outstanding.iter().filter(|&&x| x > 0.0).count(), shorting.price) {
+    (0, _) | (_, None) => println!("  every short was covered"),
+    (n, Some(price)) => {
+      let unpaid = if shorting.unpaid > 0.0 { format!("; {} B of it unpaid, written off", shorting.unpaid) } else { String::new() };
+      println!("  {} still short when the run stopped, bought in {} A at {} for {} B{}", n, shorting.bought_in(), price, shorting.paid + shorting.unpaid, unpaid);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arrivals::Arrivals;
+  use crate::config::Config;
+  use crate::market::Market;
+  use crate::pricing::PricingRule;
+  use crate::risk::RiskRules;
+  use crate::short::*;
+  use crate::simulate;
+  use crate::simulation::SimulationBuilder;
+  use crate::stopping::StoppingRules;
+  use crate::strategy::Strategies;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_short() {
+    assert!(Short { limit: 5.0 }.is_valid() && !Short { limit: -1.0 }.is_valid());
+    let agent = linear_agent(1.0);
+    let mut assets = vec![(agent, Balance { a: 0.0, b: 4.0 }), (agent, Balance { a: 0.0, b: 1.0 })];
+    buy_in(&mut assets, &[1.0, 2.0], 2.0);
+    assert_eq!((assets[0].1.b, assets[1].1.b), (2.0, 0.0));
+
+    // selling short lets sellers offer more, its positions replay from the log, and
+    // the log's final holdings are bought in as the run's were
+    let config = Config { n_agents: 40, ..Config::default() };
+    let shorted = Config { pricing: config.pricing.with_short(Some(Short { limit: 5.0 })), ..config.clone() };
+    let mut simulation = SimulationBuilder::from_config(shorted).seed(3).build().unwrap();
+    while simulation.advance_round().is_none() {}
+    let (positions, held) = (simulation.shorts().to_vec(), simulation.assets().to_vec());
+    let log = simulation.run();
+    let shorting = Shorting::of(&log);
+    assert_eq!(shorting.outstanding, positions);
+    assert!(shorting.sellers > 0 && shorting.shorted > 0.0 && shorting.shorted >= shorting.covered);
+    let mut settled = held;
+    buy_in(&mut settled, &positions, closing_price(&log.initial_assets, &log.trades).unwrap());
+    assert_eq!(log.final_assets(), settled);
+    assert!(log.final_assets().iter().all(|(_, balance)| balance.a >= 0.0 && balance.b >= 0.0));
+    assert!(log.volume_a() > simulate(&config, 3).volume_a());
+  }
+
+  // Runs a pair of linear agents, valuing A at `valuations` B, to the end with sales of
+  // up to 2 A short.
+  fn run_pair(valuations: (f64, f64), held: [Balance; 2]) -> RunLog {
+    let assets = vec![(linear_agent(valuations.0), held[0]), (linear_agent(valuations.1), held[1])];
+    let pricing = PricingRule::default().with_short(Some(Short { limit: 2.0 }));
+    let mut market = Market::new(assets, Arrivals::every_tick(0), pricing, RiskRules::default(), StoppingRules::default());
+    market.run(&mut Strategies::truthful_for(&market.assets), |_| {});
+    market.into_outcome().into_log(0, vec![])
+  }
+
+  #[test]
+  fn test_short_past_limit() {
+    // the buyer would spend all 10 B at 2, but the seller, holding no A, can sell only
+    // the 2 it may borrow, and then nothing more
+    let log = run_pair((3.0, 1.0), [Balance { a: 0.0, b: 10.0 }, Balance { a: 0.0, b: 1.0 }]);
+    assert_eq!(log.trades.len(), 1);
+    assert_eq!((log.trades[0].amount_a, log.trades[0].amount_b, log.trades[0].shorted), (2.0, 4.0, 2.0));
+    let held: Vec<Balance> = log.assets_at(&[log.end()]).pop().unwrap().into_iter().map(|(_, b)| b).collect();
+    assert_eq!(held, vec![Balance { a: 2.0, b: 6.0 }, Balance { a: 0.0, b: 5.0 }]);
+    assert_eq!(owed(2, &log.trades), vec![0.0, 2.0]);
+  }
+
+  #[test]
+  fn test_short_bought_in() {
+    // the 2 A still short at the end are bought in at the trade's price of 2, out of the
+    // seller's 5 B; a seller with only the 4 B of its sale pays it all
+    let log = run_pair((3.0, 1.0), [Balance { a: 0.0, b: 10.0 }, Balance { a: 0.0, b: 1.0 }]);
+    let settled: Vec<Balance> = log.final_assets().into_iter().map(|(_, b)| b).collect();
+    assert_eq!(settled, vec![Balance { a: 2.0, b: 6.0 }, Balance { a: 0.0, b: 1.0 }]);
+    let shorting = Shorting::of(&log);
+    assert_eq!((shorting.price, shorting.bought_in(), shorting.paid, shorting.unpaid), (Some(2.0), 2.0, 4.0, 0.0));
+    let log = run_pair((3.0, 1.0), [Balance { a: 0.0, b: 10.0 }, Balance { a: 0.0, b: 0.0 }]);
+    assert_eq!(log.final_assets()[1].1, Balance { a: 0.0, b: 0.0 });
+  }
+
+  #[test]
+  fn test_short_without_trades() {
+    // agents valuing A alike never trade, but the run still has a closing price: the
+    // clearing price of what they hold, which is their valuation
+    let held = [Balance { a: 1.0, b: 3.0 }, Balance { a: 4.0, b: 0.0 }];
+    let log = run_pair((2.0, 2.0), held);
+    assert!(log.trades.is_empty());
+    assert_eq!(closing_price(&log.initial_assets, &log.trades), Some(2.0));
+    let shorting = Shorting::of(&log);
+    assert_eq!((shorting.price, shorting.bought_in(), shorting.paid), (Some(2.0), 0.0, 0.0));
+    assert_eq!(log.final_assets().into_iter().map(|(_, b)| b).collect::<Vec<_>>(), held.to_vec());
+  }
+}
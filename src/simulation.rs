@@ -0,0 +1,495 @@
+// Setting up and driving a run without going through the command line. The builder
+// starts from Config's defaults and checks that the options make sense together
+// before anything is generated; Config::from_args goes through it too. A built
+// Simulation can run to completion, or be advanced a tick or a trade at a time.
+
+use std::sync::atomic::Ordering;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::checkpoint::{self, Checkpoint};
+use crate::clock::Tick;
+use crate::config::Config;
+use crate::continuous::Matching;
+use crate::copula::GaussianCopula;
+use crate::dealer::Dealers;
+use crate::entry;
+use crate::fat_finger::FatFinger;
+use crate::forecast::Forecast;
+use crate::influence::{Change, Perturbation};
+use crate::market::Market;
+use crate::numeraire::Numeraire;
+use crate::population::Population;
+use crate::pricing::PricingRule;
+use crate::privilege::Privilege;
+use crate::risk::RiskRules;
+use crate::runlog::{self, RunLog};
+use crate::sampling::Sampling;
+use crate::stopping::{Convergence, GainsTarget, Stop};
+use crate::strategy::{Strategies, Strategy};
+use crate::subsidy::Subsidy;
+use crate::tax::Tax;
+use crate::transparency::Transparency;
+use crate::utility::UtilityFn;
+use crate::welfare::Currency;
+use crate::zero_intelligence::ZeroIntelligence;
+use crate::zip::ZipSpec;
+use crate::{AgentId, Agent, Balance, Price, Trade, QUIET};
+
+// An intervention for SimulationBuilder::policy, each setting its part of the pricing
+// rule; call policy again for more than one.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Policy {
+  Tax(Tax),
+  Subsidy(Subsidy),
+  Limits { floor: Option<Price>, cap: Option<Price> },
+}
+
+#[derive(Default)]
+pub struct SimulationBuilder {
+  config: Config,
+  // None until set, so a loaded state can supply it
+  n_agents: Option<usize>,
+  seed: u64,
+}
+
+pub struct Simulation {
+  pub seed: u64,
+  config: Config,
+  strategies: Strategies,
+  market: Market,
+}
+
+impl SimulationBuilder {
+  pub fn new() -> SimulationBuilder {
+    SimulationBuilder::default()
+  }
+
+  pub fn from_config(config: Config) -> SimulationBuilder {
+    SimulationBuilder { n_agents: Some(config.n_agents), config, seed: 0 }
+  }
+
+  pub fn agents(mut self, n: usize) -> Self { self.n_agents = Some(n); self }
+  pub fn seed(mut self, seed: u64) -> Self { self.seed = seed; self }
+  pub fn population(mut self, population: Population) -> Self { self.config.population = population; self }
+  pub fn preference_correlation(mut self, rho: f64) -> Self { self.config.preference_correlation = rho; self }
+  pub fn inequality(mut self, sigma: f64) -> Self { self.config.inequality = sigma; self }
+  pub fn transfer(mut self, share: f64) -> Self { self.config.transfer = share; self }
+  pub fn perturbation(mut self, perturbation: Perturbation) -> Self { self.config.perturbation = Some(perturbation); self }
+  pub fn copula(mut self, copula: GaussianCopula) -> Self { self.config.copula = Some(copula); self }
+  pub fn sampling(mut self, sampling: Sampling) -> Self { self.config.sampling = sampling; self }
+  pub fn utility(mut self, utility: UtilityFn) -> Self { self.config.utility = utility; self }
+  pub fn initial_state(mut self, path: &str) -> Self { self.config.initial_state = Some(path.to_string()); self }
+  pub fn monopoly(mut self, monopoly: bool) -> Self { self.config.monopoly = monopoly; self }
+  pub fn adaptive(mut self, adaptive: bool) -> Self { self.config.adaptive = adaptive; self }
+  pub fn reporting_delay(mut self, ticks: Tick) -> Self { self.config.reporting_delay = ticks; self }
+  pub fn horizon(mut self, rounds: Tick) -> Self { self.config.horizon = rounds; self }
+  pub fn transparency(mut self, transparency: Transparency) -> Self { self.config.transparency = transparency; self }
+  pub fn zero_intelligence(mut self, zi: ZeroIntelligence) -> Self { self.config.zero_intelligence = Some(zi); self }
+  pub fn zip(mut self, zip: ZipSpec) -> Self { self.config.zip = Some(zip); self }
+  pub fn dealers(mut self, dealers: Dealers) -> Self { self.config.dealers = Some(dealers); self }
+  pub fn cartel(mut self, members: Vec<AgentId>) -> Self { self.config.cartel = members; self }
+  pub fn privilege(mut self, privilege: Privilege) -> Self { self.config.privilege = privilege; self }
+  pub fn defection(mut self, probability: f64) -> Self { self.config.defection = probability; self }
+  pub fn entry_cost(mut self, cost: f64) -> Self { self.config.entry_cost = cost; self }
+  pub fn arrival_rate(mut self, range: (f64, f64)) -> Self { self.config.arrival_rate = Some(range); self }
+  pub fn fat_finger(mut self, fat_finger: FatFinger) -> Self { self.config.fat_finger = Some(fat_finger); self }
+  pub fn pricing(mut self, pricing: PricingRule) -> Self { self.config.pricing = pricing; self }
+  pub fn mechanism(mut self, matching: Matching) -> Self { self.config.pricing = self.config.pricing.with_matching(matching); self }
+  // floor below cap is checked with the rest, not asserted as with_limits does
+  pub fn policy(mut self, policy: Policy) -> Self {
+    self.config.pricing = match policy {
+      Policy::Tax(tax) => self.config.pricing.with_tax(Some(tax)),
+      Policy::Subsidy(subsidy) => self.config.pricing.with_subsidy(Some(subsidy)),
+      Policy::Limits { floor, cap } => PricingRule { floor, cap, ..self.config.pricing },
+    };
+    self
+  }
+  pub fn risk(mut self, risk: RiskRules) -> Self { self.config.risk = risk; self }
+  pub fn max_trades(mut self, n: usize) -> Self { self.config.max_trades = Some(n); self }
+  pub fn max_ticks(mut self, n: Tick) -> Self { self.config.max_ticks = Some(n); self }
+  pub fn convergence(mut self, convergence: Convergence) -> Self { self.config.convergence = Some(convergence); self }
+  pub fn gains_target(mut self, target: GainsTarget) -> Self { self.config.gains_target = Some(target); self }
+  pub fn time_limit(mut self, seconds: f64) -> Self { self.config.time_limit = Some(seconds); self }
+  pub fn memory_limit(mut self, mb: f64) -> Self { self.config.memory_limit = Some(mb); self }
+  pub fn numeraire(mut self, numeraire: Numeraire) -> Self { self.config.numeraire = numeraire; self }
+  pub fn welfare_currency(mut self, currency: Currency) -> Self { self.config.welfare_currency = currency; self }
+
+  pub fn build(self) -> Result<Simulation, String> {
+    let seed = self.seed;
+    Ok(Simulation::new(self.validate()?, seed))
+  }
+
+  // The checked config, without generating anything.
+  pub fn validate(self) -> Result<Config, String> {
+    let mut config = self.config;
+    config.n_agents = match (&config.initial_state, self.n_agents) {
+      (Some(path), n) => {
+        let loaded = runlog::read_state(path).map_err(|e| format!("can't read {}: {}", path, e))?.len();
+        if n.is_some_and(|n| n != loaded) {
+          return Err(format!("{} agents requested but {} has {}", n.unwrap(), path, loaded));
+        }
+        loaded
+      }
+      (None, n) => n.unwrap_or(config.n_agents),
+    };
+    if config.n_agents == 0 {
+      return Err("need at least one agent".to_string());
+    }
+    if !(-1.0..=1.0).contains(&config.preference_correlation) {
+      return Err(format!("preference correlation must be in [-1, 1], got {}", config.preference_correlation));
+    }
+    if !(config.inequality >= 0.0 && config.inequality.is_finite()) {
+      return Err(format!("endowment inequality must be a non-negative sigma, got {}", config.inequality));
+    }
+    if !(0.0..=1.0).contains(&config.transfer) {
+      return Err(format!("transfer must be a share of endowments in [0, 1], got {}", config.transfer));
+    }
+    if let Some(p) = config.perturbation {
+      if p.agent >= config.n_agents {
+        return Err(format!("can't perturb agent {} of {}", p.agent, config.n_agents));
+      }
+      let (Change::Set(x) | Change::Scale(x)) = p.change;
+      if !(x >= 0.0 && x.is_finite()) {
+        return Err(format!("{} would leave it negative or infinite", p.describe()));
+      }
+    }
+    if config.copula.is_some() && config.sampling != Sampling::Independent {
+      return Err("a copula draws the population itself, so it can't be combined with antithetic or stratified sampling".to_string());
+    }
+    if config.utility.needs_both_goods() && matches!(config.population, Population::Monopolist { .. } | Population::Complementary) {
+      return Err(format!("{:?} utility needs every agent to start with some of both goods", config.utility));
+    }
+    if config.monopoly && config.population.sellers().is_none() {
+      return Err("monopoly needs a population with designated sellers (monopolist or oligopoly:<k>)".to_string());
+    }
+    if config.monopoly && !config.utility.is_linear() {
+      return Err("a monopolist prices against linear buyers' demand, so monopoly needs linear utility".to_string());
+    }
+    if let Some(zi) = &config.zero_intelligence {
+      if !(zi.bound > 0.0 && zi.bound.is_finite()) {
+        return Err(format!("zero-intelligence agents need a positive bound to ask up to, got {}", zi.bound));
+      }
+      if zi.ids(config.n_agents).iter().any(|&id| id >= config.n_agents) {
+        return Err("zero-intelligence agent out of range".to_string());
+      }
+      if config.max_ticks.is_none() {
+        return Err("zero-intelligence agents may never find the last gains, so they need a --max-ticks".to_string());
+      }
+    }
+    if let Some(zip) = &config.zip {
+      if !(zip.rate > 0.0 && zip.rate <= 1.0) {
+        return Err(format!("ZIP agents need a learning rate in (0, 1], got {}", zip.rate));
+      }
+      if zip.ids(config.n_agents).iter().any(|&id| id >= config.n_agents) {
+        return Err("ZIP agent out of range".to_string());
+      }
+    }
+    if let Some(dealers) = config.dealers {
+      if !(dealers.n > 0 && dealers.n < config.n_agents) {
+        return Err(format!("need between 1 and {} dealers, leaving someone to deal with, got {}", config.n_agents - 1, dealers.n));
+      }
+      if !(dealers.spread > 0.0 && dealers.spread < 2.0 && dealers.inventory > 0.0) {
+        return Err(format!("dealers need a spread in (0, 2) and a positive inventory, got {}:{}", dealers.spread, dealers.inventory));
+      }
+    }
+    if config.reporting_delay > 0 && !config.adaptive && config.zip.is_none() && config.horizon <= 1 {
+      return Err("only adaptive and ZIP agents and those with a horizon read the tape, so a reporting delay needs --adaptive, --zip or --horizon".to_string());
+    }
+    if config.horizon == 0 {
+      return Err("a horizon takes in at least the current round".to_string());
+    }
+    if config.horizon > 1 && !config.transparency.shows_tape() {
+      return Err("agents with a horizon expect the last price on the tape, which this transparency hides".to_string());
+    }
+    if config.cartel.iter().any(|&id| id >= config.n_agents) {
+      return Err("cartel member out of range".to_string());
+    }
+    if !config.cartel.is_empty() && !config.utility.is_linear() {
+      return Err("a cartel prices against linear buyers' demand, so it needs linear utility".to_string());
+    }
+    if config.privilege.agents.iter().any(|&id| id >= config.n_agents) {
+      return Err("privileged agent out of range".to_string());
+    }
+    if !(0.0..=1.0).contains(&config.defection) {
+      return Err(format!("defection must be a probability, got {}", config.defection));
+    }
+    if config.entry_cost < 0.0 {
+      return Err(format!("entry cost can't be negative, got {}", config.entry_cost));
+    }
+    if let Some((lo, hi)) = config.arrival_rate {
+      if !(lo > 0.0 && lo <= hi) {
+        return Err(format!("arrival rates must be positive, got {}..{}", lo, hi));
+      }
+    }
+    if let Some(f) = config.fat_finger {
+      if !((0.0..=1.0).contains(&f.probability) && f.factor > 1.0) {
+        return Err(format!("fat-finger errors need a probability and a factor above 1, got {}:{}", f.probability, f.factor));
+      }
+    }
+    let pricing = config.pricing;
+    if !(pricing.k > 0.0 && pricing.k < 1.0) {
+      return Err(format!("k must be strictly between 0 and 1, got {}", pricing.k));
+    }
+    if let (Some(floor), Some(cap)) = (pricing.floor, pricing.cap) {
+      if floor >= cap {
+        return Err(format!("price floor {} must be below the cap {}", floor, cap));
+      }
+    }
+    if pricing.matching != Matching::Batch && config.privilege.priority && !config.privilege.agents.is_empty() {
+      return Err(format!("privileged priority has no meaning with {} matching", pricing.matching.name()));
+    }
+    if matches!(pricing.matching, Matching::Call | Matching::Sessions { .. }) && pricing.lots.is_some() {
+      return Err("the call auction's pro-rata fills can't be rounded to lots".to_string());
+    }
+    if let Some(tax) = pricing.tax {
+      if !tax.is_valid() {
+        return Err(format!("a tax must be non-negative, got {}", tax.describe()));
+      }
+      if pricing.lots.is_some() || matches!(pricing.matching, Matching::Call | Matching::Sessions { .. }) {
+        return Err("a tax can't be levied on fills rounded to lots or cleared by a call auction".to_string());
+      }
+    }
+    if let Some(subsidy) = pricing.subsidy {
+      if !(subsidy.b >= 0.0 && subsidy.b.is_finite()) {
+        return Err(format!("a subsidy must be non-negative, got {}", subsidy.describe()));
+      }
+      if pricing.tax.is_some() || pricing.lots.is_some() || matches!(pricing.matching, Matching::Call | Matching::Sessions { .. }) {
+        return Err("a subsidy can't be paid alongside a tax, on fills rounded to lots, or on a call auction's".to_string());
+      }
+      if !config.utility.is_linear() {
+        return Err("a subsidy is paid only to linear agents".to_string());
+      }
+    }
+    if let Some(regions) = pricing.regions {
+      if !(regions.home > 0.0 && regions.home < 1.0) {
+        return Err(format!("the share of agents at home must be strictly between 0 and 1, got {}", regions.home));
+      }
+      if regions.tariff.is_some_and(|t| !t.is_valid()) {
+        return Err(format!("a tariff must be non-negative, got {}", regions.tariff.unwrap().describe()));
+      }
+      if pricing.tax.is_some() || pricing.subsidy.is_some() || pricing.lots.is_some() || pricing.matching != Matching::Batch {
+        return Err("regions trade only by batch matching, without a tax, a subsidy or lots".to_string());
+      }
+      if config.privilege.priority && !config.privilege.agents.is_empty() {
+        return Err("privileged priority has no meaning across regions' separate books".to_string());
+      }
+    }
+    if let Some(pool) = pricing.dark_pool {
+      if !(pool.share > 0.0 && pool.share < 1.0) {
+        return Err(format!("the share of agents in the dark pool must be strictly between 0 and 1, got {}", pool.share));
+      }
+      if pricing.regions.is_some() || pricing.tax.is_some() || pricing.subsidy.is_some() || pricing.lots.is_some() || pricing.matching != Matching::Batch {
+        return Err("a dark pool trades only beside one batch-matched lit book, without a tax, a subsidy or lots".to_string());
+      }
+      if config.privilege.priority && !config.privilege.agents.is_empty() {
+        return Err("privileged priority has no meaning across venues".to_string());
+      }
+    }
+    if let Some(decay) = pricing.decay.filter(|d| !d.is_valid()) {
+      return Err(format!("decay rates must be in [0, 1), got {} for A and {} for B", decay.a, decay.b));
+    }
+    if let Some(credit) = pricing.credit {
+      if !credit.is_valid() {
+        return Err(format!("a line of credit and its interest rate must be non-negative, got {} and {}", credit.limit, credit.rate));
+      }
+      if pricing.matching != Matching::Batch || !config.utility.is_linear() {
+        return Err("credit is extended only to linear agents, by batch matching".to_string());
+      }
+    }
+    if let Some(short) = pricing.short {
+      if !short.is_valid() {
+        return Err(format!("a margin limit must be non-negative, got {}", short.limit));
+      }
+      if pricing.matching != Matching::Batch || !config.utility.is_linear() {
+        return Err("short selling is open only to linear agents, by batch matching".to_string());
+      }
+    }
+    for (limit, name) in [(config.time_limit, "time"), (config.memory_limit, "memory")] {
+      if let Some(limit) = limit.filter(|&x| !(x > 0.0 && x.is_finite())) {
+        return Err(format!("a {} limit must be positive, got {}", name, limit));
+      }
+    }
+    if let Some(target) = config.gains_target {
+      if !(target.share > 0.0 && target.share <= 1.0) {
+        return Err(format!("the share of gains to stop at must be in (0, 1], got {}", target.share));
+      }
+    }
+    if let Some(lots) = pricing.lots {
+      if !(lots.a > 0.0 && lots.b > 0.0) {
+        return Err(format!("lot and tick sizes must be positive, got {}:{}", lots.a, lots.b));
+      }
+    }
+    Ok(config)
+  }
+}
+
+impl Simulation {
+  // Unchecked: from outside, runs are built by SimulationBuilder::build.
+  pub(crate) fn new(config: Config, seed: u64) -> Simulation {
+    let strategies = config.strategies(seed);
+    Simulation::with_strategies(config, seed, strategies)
+  }
+
+  // The config's population and market, quoted with other strategies than its own.
+  pub fn with_strategies(config: Config, seed: u64, mut strategies: Strategies) -> Simulation {
+    let mut assets = config.initial_assets(seed, &mut StdRng::seed_from_u64(seed));
+    // the run starts after entry fees are paid, so surplus is gross of them
+    entry::enter(&mut assets, config.entry_cost, config.privilege.exempt_agents(), &mut strategies);
+    strategies.price(&assets);
+    let market = Market::new(assets, config.arrivals(seed), config.pricing, config.risk, config.stopping());
+    Simulation { seed, config, strategies, market }
+  }
+
+  // A paused run, to go on under `config`: the one it was started with or, say, the
+  // same with a later max_ticks. Abstainers are restored as such, since who paid to
+  // enter was settled before the run.
+  pub fn from_checkpoint(checkpoint: Checkpoint, config: Config) -> Simulation {
+    let seed = checkpoint.seed;
+    let mut strategies = config.strategies(seed);
+    for &id in &checkpoint.abstaining {
+      strategies.set(id, Strategy::Abstain);
+    }
+    strategies.restore_defections(&checkpoint.defections);
+    strategies.price(&checkpoint.market.assets);
+    let market = Market::from_state(checkpoint.market, config.arrivals(seed), config.pricing, config.risk, config.stopping());
+    Simulation { seed, config, strategies, market }
+  }
+
+  pub fn checkpoint(&self) -> Checkpoint {
+    Checkpoint {
+      version: checkpoint::VERSION,
+      seed: self.seed,
+      config: self.config.clone(),
+      abstaining: self.strategies.agents_using(Strategy::Abstain),
+      defections: self.strategies.coalitions().iter().map(|c| c.defections).collect(),
+      market: self.market.state(),
+    }
+  }
+
+  pub fn assets(&self) -> &[(Agent, Balance)] {
+    &self.market.assets
+  }
+
+  // What each agent owes, with credit.
+  pub fn debts(&self) -> &[f64] {
+    self.market.debts()
+  }
+
+  // What each agent owes in A, with short selling; bought in only once the run is over.
+  pub fn shorts(&self) -> &[f64] {
+    self.market.shorts()
+  }
+
+  pub fn trades(&self) -> &[Trade] {
+    self.market.trades()
+  }
+
+  pub fn stop(&self) -> Option<Stop> {
+    self.market.stop()
+  }
+
+  // How much of the attainable surplus the run has realized so far, by `forecast`.
+  pub fn realized_share(&self, forecast: Forecast) -> Option<f64> {
+    self.market.realized_share(forecast)
+  }
+
+  // Runs one tick, returning the stop once the run is over.
+  pub fn advance_round(&mut self) -> Option<Stop> {
+    let quiet = QUIET.swap(true, Ordering::Relaxed);
+    let stop = self.market.step(&mut self.strategies, |_| {});
+    QUIET.store(quiet, Ordering::Relaxed);
+    stop
+  }
+
+  // Runs until the next trade, returning it, or None once the run is over.
+  pub fn advance_trade(&mut self) -> Option<&Trade> {
+    let quiet = QUIET.swap(true, Ordering::Relaxed);
+    let trade = self.market.step_to_trade(&mut self.strategies, |_| {});
+    QUIET.store(quiet, Ordering::Relaxed);
+    trade
+  }
+
+  pub fn run(mut self) -> RunLog {
+    while self.advance_round().is_none() {}
+    self.market.into_outcome().into_log(self.seed, vec![])
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::simulation::*;
+  use crate::stopping::StopReason;
+  use crate::subsidy::Recipient;
+
+  #[test]
+  fn test_build_validates() {
+    let simulation = SimulationBuilder::new().agents(20).seed(7).build().unwrap();
+    assert_eq!((simulation.assets().len(), simulation.seed), (20, 7));
+    assert!(SimulationBuilder::new().agents(3).cartel(vec![1, 5]).build().is_err());
+    // a cartel runs dry against linear buyers, but non-linear ones take dust at its ask for ever
+    let cartel = SimulationBuilder::new().agents(40).seed(1).cartel(vec![0, 1, 2]).build().unwrap().run();
+    assert_eq!(cartel.stop.map(|s| s.reason), Some(StopReason::Exhausted));
+    assert!(SimulationBuilder::new().agents(40).cartel(vec![0, 1, 2]).utility(UtilityFn::CobbDouglas).validate().is_err());
+    assert!(SimulationBuilder::new().monopoly(true).build().is_err());
+    assert!(SimulationBuilder::new().population(Population::Monopolist { sellers: 1 }).monopoly(true).validate().is_ok());
+    // log utility lets a monopolist's buyers start with no A, but they'd take dust at its price for ever
+    assert!(SimulationBuilder::new().population(Population::Monopolist { sellers: 3 }).monopoly(true).utility(UtilityFn::Log).validate().is_err());
+    // non-linear agents would wash-trade a subsidy for ever
+    let subsidized = PricingRule::default().with_subsidy(Some(Subsidy { b: 0.1, to: Recipient::Sellers }));
+    assert!(SimulationBuilder::new().pricing(subsidized).validate().is_ok());
+    assert!(SimulationBuilder::new().pricing(subsidized).utility(UtilityFn::CobbDouglas).validate().is_err());
+    assert!(SimulationBuilder::new().pricing(subsidized).utility(UtilityFn::Log).validate().is_err());
+    // random quotes can miss each other for ever
+    let zi = ZeroIntelligence { bound: 10.0, agents: None };
+    assert!(SimulationBuilder::new().zero_intelligence(zi.clone()).validate().is_err());
+    assert!(SimulationBuilder::new().zero_intelligence(zi).max_ticks(1000).validate().is_ok());
+    assert!(SimulationBuilder::new().horizon(0).validate().is_err());
+    assert!(SimulationBuilder::new().horizon(3).transparency(Transparency::Dark).validate().is_err());
+    assert!(SimulationBuilder::new().horizon(3).reporting_delay(2).validate().is_ok());
+  }
+
+  #[test]
+  fn test_mechanism_and_policy() {
+    let tax = Tax::AdValorem { rate: 0.05 };
+    let config = SimulationBuilder::new().agents(50).mechanism(Matching::Continuous).policy(Policy::Tax(tax)).validate().unwrap();
+    assert_eq!(config.pricing, PricingRule::default().with_matching(Matching::Continuous).with_tax(Some(tax)));
+    // checked together at build time: a call auction's fills can't be taxed
+    assert!(SimulationBuilder::new().agents(50).mechanism(Matching::Call).policy(Policy::Tax(tax)).build().is_err());
+    let limits = Policy::Limits { floor: Some(2.0), cap: Some(1.0) };
+    assert!(SimulationBuilder::new().policy(limits).build().is_err());
+    let log = SimulationBuilder::new().agents(50).seed(7).mechanism(Matching::Call).policy(Policy::Limits { floor: Some(0.5), cap: None }).build().unwrap().run();
+    assert!(!log.trades.is_empty() && log.trades.iter().all(|t| t.price_per_a_in_b() >= 0.5));
+  }
+
+  #[test]
+  fn test_clamped_limits_run() {
+    // a clamped cap leaves gains from trade on the table as much as a rejecting one
+    let cap = Policy::Limits { floor: None, cap: Some(0.9) };
+    let log = SimulationBuilder::new().agents(50).seed(3).policy(cap).privilege(Privilege::parse("1,2").unwrap()).build().unwrap().run();
+    assert_eq!(log.stop.map(|s| s.reason), Some(StopReason::Exhausted));
+  }
+
+  #[test]
+  fn test_continuous_clamped_run() {
+    // arrivals clamped to the limit one by one don't reach the corner a batch would
+    for limits in [Policy::Limits { floor: None, cap: Some(0.9) }, Policy::Limits { floor: Some(1.1), cap: None }] {
+      let log = SimulationBuilder::new().agents(50).seed(1).mechanism(Matching::Continuous).policy(limits).build().unwrap().run();
+      assert_eq!(log.stop.map(|s| s.reason), Some(StopReason::Exhausted));
+    }
+  }
+
+  #[test]
+  fn test_advance_matches_run() {
+    let build = || SimulationBuilder::new().agents(30).seed(2).build().unwrap();
+    let log = build().run();
+    let mut stepped = build();
+    let first = stepped.advance_trade().cloned();
+    assert_eq!(first.as_ref(), log.trades.first());
+    while stepped.advance_round().is_none() {}
+    assert_eq!(stepped.trades(), &log.trades[..]);
+    assert_eq!(stepped.stop(), log.stop);
+    assert_eq!(stepped.advance_trade(), None);
+  }
+}
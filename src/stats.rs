@@ -0,0 +1,51 @@
+pub fn mean(xs: &[f64]) -> f64 {
+  xs.iter().sum::<f64>() / xs.len() as f64
+}
+
+pub fn std_dev(xs: &[f64]) -> f64 {
+  let m = mean(xs);
+  (xs.iter().map(|x| (x - m).powi(2)).sum::<f64>() / xs.len() as f64).sqrt()
+}
+
+// Linearly interpolated quantile, for q in [0, 1].
+pub fn quantile(xs: &[f64], q: f64) -> f64 {
+  let mut sorted = xs.to_vec();
+  sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+  let pos = q * (sorted.len() - 1) as f64;
+  let (i, frac) = (pos.floor() as usize, pos.fract());
+  match sorted.get(i + 1) {
+    Some(next) => sorted[i] + frac * (next - sorted[i]),
+    None => sorted[i],
+  }
+}
+
+// Pearson correlation; None if either side has no variance.
+pub fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+  assert_eq!(xs.len(), ys.len());
+  let (mx, my) = (mean(xs), mean(ys));
+  let mut cov = 0.0;
+  let mut var_x = 0.0;
+  let mut var_y = 0.0;
+  for (x, y) in xs.iter().zip(ys.iter()) {
+    cov += (x - mx) * (y - my);
+    var_x += (x - mx).powi(2);
+    var_y += (y - my).powi(2);
+  }
+  if var_x == 0.0 || var_y == 0.0 {
+    return None;
+  }
+  Some(cov / (var_x * var_y).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::stats::*;
+
+  #[test]
+  fn test_pearson() {
+    assert_eq!(pearson(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]), Some(1.0));
+    assert_eq!(pearson(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]), Some(-1.0));
+    assert_eq!(pearson(&[1.0, 2.0, 3.0], &[5.0, 5.0, 5.0]), None);
+    assert_eq!(quantile(&[3.0, 1.0, 2.0, 4.0], 0.5), 2.5);
+  }
+}
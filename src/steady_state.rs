@@ -0,0 +1,127 @@
+// Burn-in: the trades before the market settles into its long-run behavior, which
+// bias any average taken over the whole run toward wherever it started. The cut is
+// found by MSER-5 (White's marginal standard error rule, on means of batches of five
+// trades): drop the prefix that minimizes the standard error of what's left, looking
+// no further than halfway in. A minimum at that limit means the series never settled
+// within the run, and the moments after it are reported with that warning. It can
+// also be set by hand, as a number of trades.
+//
+// What's left is summarized by its mean, standard deviation and a 95% interval for
+// the mean from batch means, which unlike the naive interval allows for the
+// correlation between successive trades.
+
+use crate::numeraire::Numeraire;
+use crate::sampling::interval;
+use crate::stats::{mean, std_dev};
+use crate::Trade;
+
+pub const BATCH: usize = 5;
+// batches for the interval on the mean
+const INTERVAL_BATCHES: usize = 20;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum BurnIn {
+  Auto,
+  Trades(usize),
+}
+
+impl BurnIn {
+  pub fn parse(s: &str) -> Result<BurnIn, String> {
+    match s {
+      "auto" => Ok(BurnIn::Auto),
+      _ => s.parse().map(BurnIn::Trades).map_err(|_| format!("expected auto or a number of trades, got {:?}", s)),
+    }
+  }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Moments {
+  pub n: usize,
+  pub mean: f64,
+  pub std_dev: f64,
+  // of a 95% interval on the mean, None from too few trades
+  pub half_width: Option<f64>,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct SteadyState {
+  // trades dropped
+  pub burn_in: usize,
+  // false if MSER's cut came at its limit
+  pub settled: bool,
+  pub price: Moments,
+  pub size_a: Moments,
+}
+
+// The MSER-`batch` cut into `series`, in observations, and whether it's short of the
+// halfway limit; None from under two batches.
+pub fn mser(series: &[f64], batch: usize) -> Option<(usize, bool)> {
+  let means: Vec<f64> = series.chunks_exact(batch).map(mean).collect();
+  if means.len() < 2 {
+    return None;
+  }
+  let limit = means.len() / 2;
+  let statistic = |d: usize| {
+    let rest = &means[d..];
+    let m = mean(rest);
+    rest.iter().map(|z| (z - m).powi(2)).sum::<f64>() / (rest.len() as f64).powi(2)
+  };
+  let best = (0..=limit).min_by(|&x, &y| statistic(x).partial_cmp(&statistic(y)).unwrap()).unwrap();
+  Some((best * batch, best < limit || limit == 0))
+}
+
+pub fn moments(series: &[f64]) -> Moments {
+  let size = (series.len() / INTERVAL_BATCHES).max(1);
+  let batch_means: Vec<f64> = series.chunks_exact(size).map(mean).collect();
+  Moments {
+    n: series.len(),
+    mean: mean(series),
+    std_dev: std_dev(series),
+    half_width: interval(&batch_means).map(|(_, half)| half),
+  }
+}
+
+// None if there aren't enough trades after the burn-in to say anything.
+pub fn analyse(trades: &[Trade], burn_in: BurnIn, numeraire: Numeraire) -> Option<SteadyState> {
+  let prices: Vec<f64> = trades.iter().map(|t| numeraire.price(t.price_per_a_in_b())).collect();
+  let (burn_in, settled) = match burn_in {
+    BurnIn::Auto => mser(&prices, BATCH)?,
+    BurnIn::Trades(n) => (n, true),
+  };
+  if prices.len() < burn_in + 2 {
+    return None;
+  }
+  let sizes: Vec<f64> = trades[burn_in..].iter().map(|t| t.amount_a).collect();
+  Some(SteadyState { burn_in, settled, price: moments(&prices[burn_in..]), size_a: moments(&sizes) })
+}
+
+pub fn print_report(steady: Option<&SteadyState>) {
+  let Some(s) = steady else {
+    println!("steady state: too few trades to say");
+    return;
+  };
+  println!("steady state after a burn-in of {} trades{}:", s.burn_in, if s.settled { "" } else { " (warning: the series hadn't settled by halfway; these moments still carry its trend)" });
+  for (name, m) in [("price", s.price), ("trade size (A)", s.size_a)] {
+    let half = m.half_width.map_or("n/a".to_string(), |h| format!("± {}", h));
+    println!("  {}: mean {} {}, sd {} (over {} trades)", name, m.mean, half, m.std_dev, m.n);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::steady_state::*;
+
+  #[test]
+  fn test_mser() {
+    // a transient decaying onto a level with a little alternating noise
+    let series: Vec<f64> = (0..200).map(|i| 1.0 + 5.0 * (-(i as f64) / 8.0).exp() + if i % 2 == 0 { 0.01 } else { -0.01 }).collect();
+    let (cut, settled) = mser(&series, BATCH).unwrap();
+    assert!(settled && (20..=80).contains(&cut), "{}", cut);
+    assert!((moments(&series[cut..]).mean - 1.0).abs() < 0.01);
+    // a trend never settles
+    let trend: Vec<f64> = (0..100).map(|i| i as f64).collect();
+    assert_eq!(mser(&trend, BATCH), Some((50, false)));
+    assert_eq!(mser(&[1.0; 9], BATCH), None);
+    assert_eq!(BurnIn::parse("40"), Ok(BurnIn::Trades(40)));
+  }
+}
@@ -0,0 +1,182 @@
+// When a run ends. Exhaustion (nothing left crosses) always applies; the other rules
+// are optional, and whichever fires first wins.
+//
+// The time and memory limits are budgets for runs that may not end of their own
+// accord, so one pathological run in a sweep can't hold up or exhaust the rest: a run
+// over budget stops at the next tick like any other, and its summary is flagged as
+// truncated. Time is what the run has spent stepping (see timing); memory is an
+// estimate of what its records take, the trades, rejections and quote changes a log
+// keeps, those being what grows without bound.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::forecast::{self, Forecast};
+use crate::{Agent, Balance, Trade};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+  Exhausted,
+  MaxTrades,
+  MaxTicks,
+  Converged,
+  GainsRealized,
+  Signal,
+  // the one-shot call auction has run (see call)
+  Cleared,
+  TimeLimit,
+  MemoryLimit,
+}
+
+impl StopReason {
+  // Whether the run was cut off by a budget or from outside (see interrupt).
+  pub fn is_truncation(&self) -> bool {
+    matches!(self, StopReason::TimeLimit | StopReason::MemoryLimit | StopReason::Signal)
+  }
+}
+
+// The last `window` trade prices all lie within `tolerance` (relative) of their mean.
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Convergence {
+  pub window: usize,
+  pub tolerance: f64,
+}
+
+impl Convergence {
+  // `<window>:<tolerance>`, e.g. `50:0.01`
+  pub fn parse(s: &str) -> Result<Convergence, String> {
+    let parsed = s.split_once(':').and_then(|(w, t)| Some(Convergence { window: w.parse().ok()?, tolerance: t.parse().ok()? }));
+    match parsed {
+      Some(c) if c.window > 0 => Ok(c),
+      _ => Err(format!("bad convergence criterion {:?} (expected <window>:<tolerance>)", s)),
+    }
+  }
+
+  fn holds(&self, trades: &[Trade]) -> bool {
+    if trades.len() < self.window {
+      return false;
+    }
+    let prices: Vec<f64> = trades[trades.len() - self.window..].iter().map(|t| t.price_per_a_in_b()).collect();
+    let mean = prices.iter().sum::<f64>() / prices.len() as f64;
+    prices.iter().all(|p| (p - mean).abs() <= self.tolerance * mean)
+  }
+}
+
+// At least `share` of the gains from trade are realized, by `forecast`'s estimate of
+// what's left.
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct GainsTarget {
+  pub share: f64,
+  pub forecast: Forecast,
+}
+
+impl GainsTarget {
+  // `<share>` or `<share>:<forecast>`, e.g. `0.97:geometric`
+  pub fn parse(s: &str) -> Result<GainsTarget, String> {
+    let (share, forecast) = s.split_once(':').unwrap_or((s, "walrasian"));
+    let share = share.parse().map_err(|_| format!("bad gains target {:?} (expected <share>[:<forecast>])", s))?;
+    Ok(GainsTarget { share, forecast: Forecast::parse(forecast)? })
+  }
+}
+
+#[derive(Default, Clone)]
+pub struct StoppingRules {
+  pub max_trades: Option<usize>,
+  pub max_ticks: Option<Tick>,
+  pub convergence: Option<Convergence>,
+  pub gains: Option<GainsTarget>,
+  // set from outside the run (another thread) to stop it at the next tick
+  pub signal: Option<Arc<AtomicBool>>,
+  pub time_limit: Option<Duration>,
+  // in bytes
+  pub memory_limit: Option<usize>,
+}
+
+impl StoppingRules {
+  // Checked at the start of every tick, with the starting and current holdings and
+  // the trades so far.
+  pub fn check(&self, now: Tick, initial: &[(Agent, Balance)], assets: &[(Agent, Balance)], trades: &[Trade]) -> Option<StopReason> {
+    if self.signal.as_ref().is_some_and(|s| s.load(Ordering::Relaxed)) {
+      Some(StopReason::Signal)
+    } else if self.max_trades.is_some_and(|n| trades.len() >= n) {
+      Some(StopReason::MaxTrades)
+    } else if self.max_ticks.is_some_and(|n| now >= n) {
+      Some(StopReason::MaxTicks)
+    } else if self.convergence.is_some_and(|c| c.holds(trades)) {
+      Some(StopReason::Converged)
+    } else if self.gains.is_some_and(|g| forecast::realized_share(g.forecast, initial, assets, trades).is_some_and(|s| s >= g.share)) {
+      Some(StopReason::GainsRealized)
+    } else {
+      None
+    }
+  }
+
+  // Checked alongside, with the time the run has taken and the bytes its records hold.
+  pub fn over_budget(&self, elapsed: Duration, held: usize) -> Option<StopReason> {
+    if self.time_limit.is_some_and(|limit| elapsed >= limit) {
+      Some(StopReason::TimeLimit)
+    } else if self.memory_limit.is_some_and(|limit| held >= limit) {
+      Some(StopReason::MemoryLimit)
+    } else {
+      None
+    }
+  }
+}
+
+// Raises `signal` once `path` exists, polled from a background thread.
+pub fn watch_stop_file(path: PathBuf, signal: Arc<AtomicBool>) {
+  thread::spawn(move || {
+    while !path.exists() {
+      thread::sleep(Duration::from_millis(100));
+    }
+    signal.store(true, Ordering::Relaxed);
+  });
+}
+
+// How and when a run ended.
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Stop {
+  pub tick: Tick,
+  pub reason: StopReason,
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::stopping::*;
+
+  #[test]
+  fn test_first_rule_to_fire_wins() {
+    let trade = |amount_b| Trade { tick: 0, buyer: 0, seller: 1, amount_a: 1.0, amount_b, bid_price: amount_b, ask_price: amount_b, ..Trade::default() };
+    let trades = vec![trade(3.0), trade(2.0), trade(2.01), trade(1.99)];
+    let convergence = Convergence::parse("3:0.01").unwrap();
+    assert!(convergence.holds(&trades));
+    assert!(!convergence.holds(&trades[..3]));
+
+    let rules = StoppingRules { max_trades: Some(10), max_ticks: Some(5), ..StoppingRules::default() };
+    assert_eq!(rules.check(4, &[], &[], &trades), None);
+    assert_eq!(rules.check(5, &[], &[], &trades), Some(StopReason::MaxTicks));
+    let signal = Arc::new(AtomicBool::new(true));
+    assert_eq!(StoppingRules { signal: Some(signal), ..rules.clone() }.check(0, &[], &[], &[]), Some(StopReason::Signal));
+    // nobody holds anything, so there's nothing to gain and it's all realized
+    let gains = Some(GainsTarget::parse("0.97").unwrap());
+    assert_eq!(StoppingRules { gains, ..rules }.check(0, &[], &[], &[]), Some(StopReason::GainsRealized));
+    assert!(GainsTarget::parse("0.97:psychic").is_err());
+    let budgets = StoppingRules { time_limit: Some(Duration::from_secs(1)), memory_limit: Some(1000), ..StoppingRules::default() };
+    assert_eq!(budgets.over_budget(Duration::from_millis(999), 999), None);
+    assert_eq!(budgets.over_budget(Duration::from_secs(1), 0), Some(StopReason::TimeLimit));
+    assert!(budgets.over_budget(Duration::ZERO, 1000).unwrap().is_truncation());
+    // a run over budget stops, with what it traded so far
+    let config = crate::config::Config { n_agents: 40, memory_limit: Some(0.001), ..Default::default() };
+    let log = crate::simulate(&config, 1);
+    assert_eq!(log.stop.unwrap().reason, StopReason::MemoryLimit);
+    assert!(!log.trades.is_empty() && log.trades.len() < crate::simulate(&crate::config::Config { memory_limit: None, ..config }, 1).trades.len());
+    assert!(Convergence::parse("0:0.1").is_err());
+  }
+}
@@ -0,0 +1,496 @@
+// How agents quote. By default every agent bids and asks at its indifference price
+// (see generate_orders); the other strategies deviate from that. Also carries which
+// agents the matching engine serves first (see privilege::Privilege), the mistakes
+// made entering orders (see fat_finger), the tape of reported trades adaptive
+// agents quote from (see reporting), how dealers quote (see dealer), how much of the
+// market the strategic agents see (see transparency), the bound zero-intelligence
+// agents quote within (see zero_intelligence), and the margins ZIP agents have learned
+// (see zip).
+//
+// Quoting from outside the crate goes through TradingStrategy: an agent given a custom
+// strategy (see Strategies::add_custom) quotes whatever it returns from its own state
+// and what the market shows, and learns from the trades reported each tick. Truthful
+// quoting is one too, and generate_orders quotes through it. A custom strategy's quotes
+// are its intended orders, so it can end the run by quoting nothing that crosses.
+
+use serde::Serialize;
+
+use crate::clock::Tick;
+use crate::crn::{Crn, Stream};
+use crate::dealer::Dealers;
+use crate::fat_finger::FatFinger;
+use crate::horizon::Horizon;
+use crate::reporting::{Report, Tape};
+use crate::transparency::Transparency;
+use crate::zero_intelligence::ZeroIntelligence;
+use crate::zip::{Zip, ZipSpec};
+use crate::{quote_around, Agent, AgentId, Balance, Order, Price, Trade};
+
+// Keeps a strategic ask strictly below the bid it targets, so the trade still goes
+// through and the buyer still gains from it.
+const ASK_MARGIN: f64 = 1e-9;
+// How far an adaptive agent moves its quote from its indifference price toward the
+// last price on the tape.
+const ADAPTATION: f64 = 0.5;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+  Truthful,
+  // Asks the single price that maximizes its own gain against the demand curve
+  // revealed by everyone else's bids, recomputed before every match.
+  Monopolist,
+  // A member of the coalition with this index: quotes the coalition's common ask.
+  Cartel { coalition: usize },
+  // Stays out of the market entirely.
+  Abstain,
+  // Shades its quotes toward the last reported trade price, bidding no more than it
+  // and asking no less when that's the better deal: ADAPTATION of the way there from
+  // its indifference price. Truthful until anything is reported. A shaded bid and ask
+  // cross whenever the truthful ones do, so the market still trades out.
+  Adaptive,
+  // A market maker: bids and asks either side of its indifference price, at the
+  // dealers' spread.
+  Dealer,
+  // Quotes at random inside its truthful quotes, as they're submitted; see
+  // zero_intelligence.
+  ZeroIntelligence,
+  // Quotes its truthful quotes less a learned margin, as they're submitted; see zip.
+  Zip,
+  // Quotes through the custom strategy with this index; see TradingStrategy.
+  Custom { index: usize },
+}
+
+// What an agent's strategy sees when it quotes: its own state, and what the market
+// shows it.
+pub struct Observation<'a> {
+  pub id: AgentId,
+  pub agent: &'a Agent,
+  pub balance: &'a Balance,
+  // its indifference price at its balance
+  pub reservation: Price,
+  pub now: Tick,
+  // the last price on the tape, if it can be seen; see transparency
+  pub last_price: Option<Price>,
+}
+
+pub trait TradingStrategy {
+  // The agent's (bid, ask). Sides its balance can't back are withdrawn, and there's
+  // nothing to stop it quoting at a loss.
+  fn quote(&mut self, seen: &Observation) -> (Option<Order>, Option<Order>);
+
+  // Learns from the trades `reported` at `now`, `id`'s among them or not; they're
+  // only those it can see.
+  fn observe(&mut self, _id: AgentId, _reported: &[Trade], _now: Tick) {}
+}
+
+// Bids and asks at the indifference price.
+pub struct Truthful;
+
+impl TradingStrategy for Truthful {
+  fn quote(&mut self, seen: &Observation) -> (Option<Order>, Option<Order>) {
+    quote_around(seen.id, seen.agent, seen.reservation, seen.balance)
+  }
+}
+
+// Sellers coordinating on one ask: the price maximizing their joint gain against
+// everyone else's bids, given their pooled A. Each pass, each member independently
+// defects (quotes truthfully, undercutting the others) with some probability, drawn
+// as a common random number (see crn).
+pub struct Coalition {
+  pub members: Vec<AgentId>,
+  pub defection_probability: f64,
+  pub defections: usize,
+  crn: Crn,
+}
+
+pub struct Strategies {
+  per_agent: Vec<Strategy>,
+  coalitions: Vec<Coalition>,
+  priority: Vec<AgentId>,
+  fat_finger: Option<(FatFinger, Crn)>,
+  // every agent's indifference price, worked out by `price` once the population is
+  // built, and the balance it was worked out at: preferences don't change during a
+  // run, though the price does with the balance for agents whose utility isn't linear,
+  // so theirs is worked out again once a trade has changed it
+  reservations: Vec<Price>,
+  priced_at: Vec<Balance>,
+  tape: Tape,
+  dealers: Option<Dealers>,
+  horizon: Option<Horizon>,
+  transparency: Transparency,
+  // agents whose orders nobody else sees; see dark_pool
+  hidden: Vec<AgentId>,
+  zero_intelligence: Option<(ZeroIntelligence, Crn)>,
+  zip: Option<Zip>,
+  custom: Vec<Box<dyn TradingStrategy>>,
+}
+
+impl Strategies {
+  pub fn truthful(n_agents: usize) -> Strategies {
+    Strategies { per_agent: vec![Strategy::Truthful; n_agents], coalitions: vec![], priority: vec![], fat_finger: None, reservations: vec![], priced_at: vec![], tape: Tape::new(0, 0), dealers: None, horizon: None, transparency: Transparency::Full, hidden: vec![], zero_intelligence: None, zip: None, custom: vec![] }
+  }
+
+  // Everyone truthful, priced for `assets`.
+  pub fn truthful_for(assets: &[(Agent, Balance)]) -> Strategies {
+    let mut strategies = Strategies::truthful(assets.len());
+    strategies.price(assets);
+    strategies
+  }
+
+  pub fn add_coalition(&mut self, members: &[AgentId], defection_probability: f64, seed: u64) -> usize {
+    let coalition = self.coalitions.len();
+    for &id in members {
+      self.set(id, Strategy::Cartel { coalition });
+    }
+    self.coalitions.push(Coalition {
+      members: members.to_vec(),
+      defection_probability,
+      defections: 0,
+      crn: Crn::new(seed),
+    });
+    coalition
+  }
+
+  pub fn coalitions(&self) -> &[Coalition] {
+    &self.coalitions
+  }
+
+  // Picks up each coalition's defection count where a checkpointed run left it.
+  pub fn restore_defections(&mut self, defections: &[usize]) {
+    for (coalition, &n) in self.coalitions.iter_mut().zip(defections) {
+      coalition.defections = n;
+    }
+  }
+
+  pub fn grant_priority(&mut self, agents: &[AgentId]) {
+    self.priority = agents.to_vec();
+  }
+
+  pub fn priority(&self) -> &[AgentId] {
+    &self.priority
+  }
+
+  pub fn set_fat_finger(&mut self, fat_finger: FatFinger, seed: u64) {
+    self.fat_finger = Some((fat_finger, Crn::new(seed)));
+  }
+
+  pub fn set_dealers(&mut self, dealers: Dealers) {
+    for id in dealers.ids(self.per_agent.len()) {
+      self.set(id, Strategy::Dealer);
+    }
+    self.dealers = Some(dealers);
+  }
+
+  // Has everyone but dealers and custom strategies look `horizon` ahead.
+  pub fn set_horizon(&mut self, horizon: Horizon) {
+    self.horizon = Some(horizon);
+  }
+
+  pub fn set_transparency(&mut self, transparency: Transparency) {
+    self.transparency = transparency;
+  }
+
+  pub fn set_zero_intelligence(&mut self, zi: ZeroIntelligence, seed: u64) {
+    for id in zi.ids(self.per_agent.len()) {
+      self.set(id, Strategy::ZeroIntelligence);
+    }
+    self.zero_intelligence = Some((zi, Crn::new(seed)));
+  }
+
+  // Has `agents` quote through `strategy`, returning its index.
+  pub fn add_custom(&mut self, agents: &[AgentId], strategy: Box<dyn TradingStrategy>) -> usize {
+    let index = self.custom.len();
+    for &id in agents {
+      self.set(id, Strategy::Custom { index });
+    }
+    self.custom.push(strategy);
+    index
+  }
+
+  pub fn set_zip(&mut self, spec: &ZipSpec, seed: u64) {
+    for id in spec.ids(self.per_agent.len()) {
+      self.set(id, Strategy::Zip);
+    }
+    self.zip = Some(Zip::new(spec, self.per_agent.len(), seed));
+  }
+
+  pub fn hide(&mut self, agents: &[AgentId]) {
+    self.hidden = agents.to_vec();
+  }
+
+  pub fn set_reporting_delay(&mut self, max_delay: Tick, seed: u64) {
+    self.tape = Tape::new(max_delay, seed);
+  }
+
+  // Puts the trades executed so far to the tape, returning those reported by `now`.
+  pub fn observe(&mut self, trades: &[Trade], now: Tick) -> Vec<Report> {
+    let reports = self.tape.observe(trades, now);
+    // ZIP agents learn once they've quoted, from the last of what's reported
+    let last = reports.last().filter(|_| self.transparency.shows_tape()).map(|r| trades[r.trade].price_per_a_in_b());
+    let agents: Vec<(AgentId, Price)> = self.agents_using(Strategy::Zip).into_iter().map(|id| (id, self.reservations[id])).collect();
+    if let Some(zip) = self.zip.as_mut().filter(|_| !agents.is_empty()) {
+      zip.learn(last, now, &agents);
+    }
+    if !self.custom.is_empty() && self.transparency.shows_tape() {
+      let reported: Vec<Trade> = reports.iter().map(|r| trades[r.trade].clone()).collect();
+      for (id, strategy) in self.per_agent.iter().enumerate() {
+        if let Strategy::Custom { index } = strategy {
+          self.custom[*index].observe(id, &reported, now);
+        }
+      }
+    }
+    reports
+  }
+
+  // Whether a trade still to be reported could move someone's quotes.
+  pub fn awaiting_reports(&self) -> bool {
+    self.tape.is_pending() && self.transparency.shows_tape() && (self.per_agent.contains(&Strategy::Adaptive) || self.horizon.is_some())
+  }
+
+  pub fn set(&mut self, agent: AgentId, strategy: Strategy) {
+    self.per_agent[agent] = strategy;
+  }
+
+  pub fn get(&self, agent: AgentId) -> Strategy {
+    self.per_agent[agent]
+  }
+
+  pub fn agents_using(&self, strategy: Strategy) -> Vec<AgentId> {
+    (0..self.per_agent.len()).filter(|&i| self.per_agent[i] == strategy).collect()
+  }
+
+  pub fn is_truthful(&self) -> bool {
+    self.fat_finger.is_none() && self.horizon.is_none() && self.per_agent.iter().all(|s| *s == Strategy::Truthful)
+  }
+
+  // `orders` as they reach the book at `now`: ZIP agents' with their margins on,
+  // zero-intelligence agents' drawn at random, then some mistyped if there's a fat finger.
+  pub fn submit(&self, orders: &[(Option<Order>, Option<Order>)], now: Tick) -> Vec<(Option<Order>, Option<Order>)> {
+    let mut submitted = orders.to_vec();
+    if let Some(zip) = &self.zip {
+      for (id, quote) in submitted.iter_mut().enumerate().filter(|(id, _)| self.per_agent[*id] == Strategy::Zip) {
+        zip.shade(quote, id);
+      }
+    }
+    if let Some((zi, crn)) = &self.zero_intelligence {
+      for (id, quote) in submitted.iter_mut().enumerate().filter(|(id, _)| self.per_agent[*id] == Strategy::ZeroIntelligence) {
+        zi.draw(quote, crn, id, now);
+      }
+    }
+    if let Some((fat_finger, crn)) = &self.fat_finger {
+      let enter = |order: &mut Order, side| order.price_per_a_in_b = fat_finger.enter(order.price_per_a_in_b, crn, order.agent_id, now, side);
+      for (bid, ask) in submitted.iter_mut() {
+        bid.iter_mut().for_each(|order| enter(order, 0));
+        ask.iter_mut().for_each(|order| enter(order, 1));
+      }
+    }
+    submitted
+  }
+
+  // Whether each agent's quote depends on nothing but its own balance, so that after
+  // a trade only the two parties need quoting again: nobody quotes against the rest of
+  // the book or the tape, and there are no entry errors to draw afresh each tick.
+  pub fn quotes_independently(&self) -> bool {
+    self.fat_finger.is_none() && self.horizon.is_none() && self.per_agent.iter().all(|s| matches!(s, Strategy::Truthful | Strategy::Abstain | Strategy::Dealer))
+  }
+
+  // Works out everyone's indifference price, once the population they quote for is
+  // built and before they quote.
+  pub fn price(&mut self, assets: &[(Agent, Balance)]) {
+    self.reservations = assets.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).collect();
+    self.priced_at = assets.iter().map(|(_, balance)| *balance).collect();
+  }
+
+  // One agent's (bid, ask) before any strategic quoting, as `orders` starts from.
+  pub fn quote(&mut self, id: AgentId, assets: &[(Agent, Balance)], now: Tick) -> (Option<Order>, Option<Order>) {
+    assert_eq!(self.reservations.len(), assets.len(), "quoting for a population the strategies weren't priced for");
+    let (agent, balance) = &assets[id];
+    if !agent.utility_fn.is_linear() && self.priced_at[id] != *balance {
+      self.reservations[id] = agent.indifference_price_at(balance);
+      self.priced_at[id] = *balance;
+    }
+    let last_price = self.tape.last_price().filter(|_| self.transparency.shows_tape());
+    let seen = Observation { id, agent, balance, reservation: self.reservations[id], now, last_price };
+    let mut quote = match (self.per_agent[id], last_price) {
+      (Strategy::Abstain, _) => (None, None),
+      (Strategy::Dealer, _) => return self.dealers.unwrap().quote(id, self.reservations[id], balance),
+      (Strategy::Adaptive, Some(reported)) => {
+        let (mut bid, mut ask) = quote_around(id, agent, self.reservations[id], balance);
+        let toward = self.reservations[id] + ADAPTATION * (reported - self.reservations[id]);
+        bid.iter_mut().for_each(|o| o.price_per_a_in_b = o.price_per_a_in_b.min(toward));
+        ask.iter_mut().for_each(|o| o.price_per_a_in_b = o.price_per_a_in_b.max(toward));
+        (bid, ask)
+      }
+      (Strategy::Custom { index }, _) => return self.custom[index].quote(&seen),
+      _ => Truthful.quote(&seen),
+    };
+    if let (Some(horizon), Some(expected)) = (self.horizon, last_price) {
+      horizon.hold_back(&mut quote, self.reservations[id], expected);
+    }
+    quote
+  }
+
+  // Everyone's (bid, ask), for the matching pass at `now`.
+  pub fn orders(&mut self, assets: &[(Agent, Balance)], now: Tick) -> Vec<(Option<Order>, Option<Order>)> {
+    let mut orders: Vec<(Option<Order>, Option<Order>)> = (0..assets.len()).map(|id| self.quote(id, assets, now)).collect();
+    for id in 0..assets.len() {
+      if self.per_agent[id] == Strategy::Monopolist && orders[id].1.is_some() {
+        let demand = self.transparency.visible(revealed_demand(&orders, assets, &[&[id], &self.hidden[..]].concat()));
+        if let Some(ask) = orders[id].1.as_mut() {
+          ask.price_per_a_in_b = monopoly_price(&demand, assets[id].1.a, self.reservations[id]);
+        }
+      }
+    }
+    for coalition in self.coalitions.iter_mut() {
+      coalition.quote(&mut orders, assets, &self.reservations, self.transparency, &self.hidden, now);
+    }
+    orders
+  }
+}
+
+impl Coalition {
+  fn quote(&mut self, orders: &mut [(Option<Order>, Option<Order>)], assets: &[(Agent, Balance)], reservations: &[Price], transparency: Transparency, hidden: &[AgentId], now: Tick) {
+    let sellers: Vec<AgentId> = self.members.iter().copied().filter(|&id| orders[id].1.is_some()).collect();
+    let supply: f64 = sellers.iter().map(|&id| assets[id].1.a).sum();
+    if supply == 0.0 {
+      return;
+    }
+    // prices against the pooled A's average reservation price
+    let reservation = sellers.iter()
+      .map(|&id| assets[id].1.a * reservations[id])
+      .sum::<f64>() / supply;
+    let demand = transparency.visible(revealed_demand(orders, assets, &[&self.members[..], hidden].concat()));
+    let common = monopoly_price(&demand, supply, reservation);
+    for id in sellers {
+      if self.crn.chance(self.defection_probability, Stream::Defection, id, now, 0) {
+        self.defections += 1;
+        continue;
+      }
+      if let Some(ask) = orders[id].1.as_mut() {
+        // nobody sells below their own reservation price
+        ask.price_per_a_in_b = common.max(ask.price_per_a_in_b);
+      }
+    }
+  }
+}
+
+// (bid price, B available to spend) for the bids of everyone outside `excluded`.
+pub fn revealed_demand(orders: &[(Option<Order>, Option<Order>)], assets: &[(Agent, Balance)], excluded: &[AgentId]) -> Vec<(Price, f64)> {
+  orders.iter()
+    .filter_map(|(bid, _)| *bid)
+    .filter(|bid| !excluded.contains(&bid.agent_id))
+    .map(|bid| (bid.price_per_a_in_b, assets[bid.agent_id].1.b))
+    .collect()
+}
+
+// The ask maximizing a seller's gain (measured in B) from selling up to `supply`
+// units of A with the given reservation price. A buyer bidding r with b units of B
+// takes b/p units of A at any price p <= r, as a linear buyer does; one whose utility
+// isn't linear would take only dust that close to its bid, so strategic sellers need
+// linear utility. Falls back to the reservation price if nobody bids above it.
+pub fn monopoly_price(demand: &[(Price, f64)], supply: f64, reservation: Price) -> Price {
+  let mut bids: Vec<(Price, f64)> = demand.iter().copied().filter(|(price, _)| *price > reservation).collect();
+  bids.sort_by(|x, y| y.0.partial_cmp(&x.0).unwrap());
+
+  let mut best = (reservation, 0.0);
+  let mut budget_above = 0.0;
+  for (price, budget) in bids {
+    budget_above += budget;
+    let quantity = (budget_above / price).min(supply);
+    let gain = quantity * (price - reservation);
+    if gain > best.1 {
+      best = (price * (1.0 - ASK_MARGIN), gain);
+    }
+  }
+  best.0
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::strategy::*;
+  use crate::utility::UtilityFn;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_monopoly_price() {
+    // demand: 1 unit at 10 (budget 10), then 9 more at 5 (budget 45)
+    let demand = vec![(10.0, 10.0), (5.0, 45.0)];
+    // with plenty of supply: price 10 earns 1*(10-1) = 9; price 5 earns 11*(5-1) = 44
+    assert!((monopoly_price(&demand, 100.0, 1.0) - 5.0).abs() < 1e-6);
+    // with one unit, selling it to the top buyer is best
+    assert!((monopoly_price(&demand, 1.0, 1.0) - 10.0).abs() < 1e-6);
+    // nobody values A above the reservation price
+    assert_eq!(monopoly_price(&demand, 1.0, 20.0), 20.0);
+  }
+
+  #[test]
+  fn test_cartel_quotes_common_ask() {
+    let assets = vec![
+      (linear_agent(1.0), Balance { a: 5.0, b: 0.0 }),
+      (linear_agent(1.5), Balance { a: 5.0, b: 0.0 }),
+      (linear_agent(4.0), Balance { a: 0.0, b: 10.0 }),
+    ];
+    let ask = |orders: &[(Option<Order>, Option<Order>)], id: usize| orders[id].1.unwrap().price_per_a_in_b;
+
+    let mut loyal = Strategies::truthful_for(&assets);
+    loyal.add_coalition(&[0, 1], 0.0, 0);
+    let orders = loyal.orders(&assets, 0);
+    assert_eq!(ask(&orders, 0), ask(&orders, 1));
+    assert!((ask(&orders, 0) - 4.0).abs() < 1e-6);
+
+    let mut defecting = Strategies::truthful_for(&assets);
+    defecting.add_coalition(&[0, 1], 1.0, 0);
+    let orders = defecting.orders(&assets, 0);
+    assert_eq!((ask(&orders, 0), ask(&orders, 1)), (1.0, 1.5));
+    assert_eq!(defecting.coalitions()[0].defections, 2);
+  }
+
+  #[test]
+  fn test_reservations_cached() {
+    use rand::SeedableRng;
+    use crate::{all_orders, book::OrderBook, execute_one_trade, population, pricing::PricingRule, risk::RiskRules};
+
+    // priced once for the population, everyone quotes as they would working their
+    // prices out afresh, as trades move the balances under any utility
+    for utility_fn in [UtilityFn::Linear, UtilityFn::CobbDouglas, UtilityFn::Log] {
+      let mut assets = population::generate(population::Population::Uniform, 30, &mut rand::rngs::StdRng::seed_from_u64(3));
+      assets.iter_mut().for_each(|(agent, _)| agent.utility_fn = utility_fn);
+      let mut strategies = Strategies::truthful_for(&assets);
+      for now in 0..20 {
+        let orders = strategies.orders(&assets, now);
+        assert_eq!(orders, all_orders(&assets), "{:?} at {}", utility_fn, now);
+        execute_one_trade(&mut assets, &OrderBook::from_quotes(&orders), PricingRule::default(), &RiskRules::default(), &[], now, |_| {}).unwrap();
+      }
+    }
+  }
+
+  #[test]
+  fn test_custom_strategy() {
+    use crate::config::Config;
+    use crate::simulation::Simulation;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // bids half its indifference price and never sells, counting what it's told
+    struct Lowball(Rc<Cell<usize>>);
+    impl TradingStrategy for Lowball {
+      fn quote(&mut self, seen: &Observation) -> (Option<Order>, Option<Order>) {
+        (Some(Order { agent_id: seen.id, typ: crate::OrderType::Bid, price_per_a_in_b: seen.reservation / 2.0 }), None)
+      }
+      fn observe(&mut self, _id: AgentId, reported: &[Trade], _now: Tick) {
+        self.0.set(self.0.get() + reported.len());
+      }
+    }
+    let (agent, balance) = (linear_agent(2.0), Balance { a: 1.0, b: 1.0 });
+    assert_eq!(crate::generate_orders(0, &agent, &balance), quote_around(0, &agent, 2.0, &balance));
+
+    let told = Rc::new(Cell::new(0));
+    let mut strategies = Strategies::truthful(60);
+    strategies.add_custom(&[0, 1, 2, 3, 4, 5], Box::new(Lowball(told.clone())));
+    let log = Simulation::with_strategies(Config { n_agents: 60, ..Config::default() }, 1, strategies).run();
+    assert!(log.trades.iter().all(|t| t.seller > 5 && (t.buyer > 5 || t.bid_price < log.initial_assets[t.buyer].0.indifference_price_of_a_in_b())));
+    // each of the six hears of every trade but those of the last tick
+    assert!(told.get() >= 6 * (log.trades.len() - 1) && told.get() > 0);
+  }
+}
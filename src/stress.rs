@@ -0,0 +1,150 @@
+// `simmarket stress`: a library of stress scenarios, each a shock to the economy
+// before it trades, run against the same economy unshocked. The shocks are fixed so
+// runs compare across seeds and saved states (--initial-state): half the agents'
+// preferences reversed, 80% of the economy's A destroyed, or half the agents leaving
+// the market. The half is every other agent, so populations generated in order
+// (sellers first, say) lose some of each. Agents that leave abstain rather than
+// disappear, keeping their ids and holdings out of the trading.
+//
+// Every scenario gets the same impact report: how much traded, at what price, how
+// fast prices settled, and the gains from trade. Reversed agents' gains are by their
+// reversed preferences; they're the ones they trade on.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::config::Config;
+use crate::reporting::convergence;
+use crate::runlog::RunLog;
+use crate::{simulate, Agent, AgentId, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scenario {
+  Reversal,
+  Destruction,
+  Exit,
+}
+
+pub const ALL: [Scenario; 3] = [Scenario::Reversal, Scenario::Destruction, Scenario::Exit];
+
+// the share of A the destruction scenario destroys
+const DESTROYED: f64 = 0.8;
+
+impl Scenario {
+  pub fn parse(s: &str) -> Result<Scenario, String> {
+    match s {
+      "reversal" => Ok(Scenario::Reversal),
+      "destruction" => Ok(Scenario::Destruction),
+      "exit" => Ok(Scenario::Exit),
+      _ => Err(format!("unknown stress scenario {:?} (expected reversal, destruction or exit)", s)),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Scenario::Reversal => "reversal",
+      Scenario::Destruction => "destruction",
+      Scenario::Exit => "exit",
+    }
+  }
+
+  pub fn describe(&self) -> &'static str {
+    match self {
+      Scenario::Reversal => "half the agents' preferences reversed",
+      Scenario::Destruction => "80% of A destroyed",
+      Scenario::Exit => "half the agents leave",
+    }
+  }
+
+  // The agents a scenario singles out, of `n_agents`: every other one.
+  pub fn affected(n_agents: usize) -> Vec<AgentId> {
+    (1..n_agents).step_by(2).collect()
+  }
+
+  // Shocks the economy's starting `assets`; leaving is up to the strategies (see
+  // Config::strategies).
+  pub fn apply(&self, assets: &mut [(Agent, Balance)]) {
+    match self {
+      Scenario::Reversal => {
+        for id in Scenario::affected(assets.len()) {
+          let agent = &mut assets[id].0;
+          std::mem::swap(&mut agent.consumption_a_coeff, &mut agent.consumption_b_coeff);
+        }
+      }
+      Scenario::Destruction => assets.iter_mut().for_each(|(_, balance)| balance.a *= 1.0 - DESTROYED),
+      Scenario::Exit => {}
+    }
+  }
+}
+
+pub struct Impact {
+  pub trades: usize,
+  pub volume_a: f64,
+  pub mean_price: Option<Price>,
+  // the tick of the trade prices settled from
+  pub settled_at: Option<Tick>,
+  pub gains: f64,
+}
+
+pub fn impact(log: &RunLog) -> Impact {
+  Impact {
+    trades: log.trades.len(),
+    volume_a: log.volume_a(),
+    mean_price: log.mean_price(),
+    settled_at: convergence(log).settled_at.map(|(_, tick)| tick),
+    gains: log.total_gains(),
+  }
+}
+
+// The unshocked run's impact, then each of `scenarios`'.
+pub fn run(config: &Config, seed: u64, scenarios: &[Scenario]) -> (Impact, Vec<(Scenario, Impact)>) {
+  let baseline = impact(&simulate(&Config { stress: None, ..config.clone() }, seed));
+  let shocked = scenarios.iter().map(|&s| (s, impact(&simulate(&Config { stress: Some(s), ..config.clone() }, seed)))).collect();
+  (baseline, shocked)
+}
+
+pub fn print_report(baseline: &Impact, shocked: &[(Scenario, Impact)]) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.4}", x));
+  let change = |x: f64, base: f64| show((base != 0.0).then(|| x / base - 1.0));
+  println!("stress scenarios vs the unshocked economy:");
+  println!("  {:<12} {:>7} {:>11} {:>9} {:>9} {:>8} {:>12} {:>9}", "scenario", "trades", "volume A", "price", "change", "settled", "gains", "change");
+  let row = |name: &str, i: &Impact| {
+    let price_change = match (i.mean_price, baseline.mean_price) {
+      (Some(p), Some(base)) => change(p, base),
+      _ => "n/a".to_string(),
+    };
+    let settled = i.settled_at.map_or("never".to_string(), |t| t.to_string());
+    println!("  {:<12} {:>7} {:>11.4} {:>9} {:>9} {:>8} {:>12.4} {:>9}", name, i.trades, i.volume_a, show(i.mean_price), price_change, settled, i.gains, change(i.gains, baseline.gains));
+  };
+  row("none", baseline);
+  for (scenario, i) in shocked {
+    row(scenario.name(), i);
+  }
+  for (scenario, _) in shocked {
+    println!("  ({}: {})", scenario.name(), scenario.describe());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::stress::*;
+
+  #[test]
+  fn test_stress() {
+    assert_eq!(Scenario::parse("exit"), Ok(Scenario::Exit));
+    assert_eq!(Scenario::affected(5), vec![1, 3]);
+
+    let config = Config { n_agents: 60, ..Config::default() };
+    let (baseline, shocked) = run(&config, 1, &ALL);
+    let [reversal, destruction, exit] = [&shocked[0].1, &shocked[1].1, &shocked[2].1];
+    // with A scarce it's dearer; with half the market gone less trades
+    assert!(destruction.mean_price.unwrap() > baseline.mean_price.unwrap());
+    assert!(exit.volume_a < baseline.volume_a && exit.gains < baseline.gains);
+    assert!(reversal.mean_price != baseline.mean_price);
+    // leavers keep what they had
+    let log = simulate(&Config { stress: Some(Scenario::Exit), ..config }, 1);
+    let last = log.final_assets();
+    assert!(Scenario::affected(60).iter().all(|&id| last[id].1 == log.initial_assets[id].1));
+  }
+}
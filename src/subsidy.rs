@@ -0,0 +1,132 @@
+// Subsidies on trade, the mirror image of a tax (see tax): a fixed amount of B on
+// each A traded, paid from a pot outside the economy to the seller or, if chosen, the
+// buyer. Either way the subsidy lets a bid meet an ask up to the subsidy above it:
+// the price is set k of the way from the ask to what the bid leaves the seller with
+// the subsidy, and a subsidy to the seller comes off the price the buyer pays. So
+// trades that gained too little to happen, or lost a little, do, and the ledger check
+// at the end of a run takes the pot's outlay back out. Nothing stops a round trip, so
+// agents trade the same A back and forth to collect it, and volume can run far past
+// the unsubsidized run's. Linear agents empty a side with every trade, so the round
+// trips run out; agents whose utility isn't linear trade only as far as their rate
+// meets the price, and would go on collecting it for ever, so they get no subsidy.
+//
+// The report sets a subsidized run against the run of the same seed with no policy:
+// how much volume and surplus the subsidy adds, and what that costs the pot. Whatever
+// of the outlay the added surplus doesn't cover is the subsidy's deadweight loss.
+
+use serde::{Deserialize, Serialize};
+
+use crate::runlog::RunLog;
+use crate::tax::gains_in_b;
+use crate::Trade;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Recipient {
+  Buyers,
+  Sellers,
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Subsidy {
+  // B per A
+  pub b: f64,
+  pub to: Recipient,
+}
+
+impl Subsidy {
+  // `<B per A>` paid to sellers, or `<B per A>:buyers` or `:sellers`, e.g. `0.1:buyers`
+  pub fn parse(s: &str) -> Result<Subsidy, String> {
+    let (b, to) = s.split_once(':').unwrap_or((s, "sellers"));
+    let to = match to {
+      "buyers" => Recipient::Buyers,
+      "sellers" => Recipient::Sellers,
+      _ => return Err(format!("unknown subsidy recipient {:?} (expected buyers or sellers)", to)),
+    };
+    let b = b.parse().map_err(|_| format!("bad subsidy {:?} (expected <B per A>[:buyers|sellers])", s))?;
+    Ok(Subsidy { b, to })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("{} B per A to {}", self.b, match self.to { Recipient::Buyers => "buyers", Recipient::Sellers => "sellers" })
+  }
+
+  // (to the buyer, to the seller), per A
+  pub fn per_unit(&self) -> (f64, f64) {
+    match self.to {
+      Recipient::Buyers => (self.b, 0.0),
+      Recipient::Sellers => (0.0, self.b),
+    }
+  }
+}
+
+// Everything the pot paid out over `trades`, in B.
+pub fn outlay(trades: &[Trade]) -> f64 {
+  trades.iter().fold(0.0, |o, t| o + t.buyer_subsidy + t.seller_subsidy)
+}
+
+pub struct Comparison {
+  // (without, with the subsidy)
+  pub volume_a: (f64, f64),
+  pub outlay: f64,
+  // in B at the unsubsidized run's mean price
+  pub gains: (f64, f64),
+}
+
+impl Comparison {
+  pub fn surplus_added(&self) -> f64 {
+    self.gains.1 - self.gains.0
+  }
+
+  pub fn deadweight_loss(&self) -> f64 {
+    self.outlay - self.surplus_added()
+  }
+}
+
+pub fn compare(baseline: &RunLog, subsidized: &RunLog) -> Option<Comparison> {
+  let price = baseline.mean_price()?;
+  Some(Comparison {
+    volume_a: (baseline.volume_a(), subsidized.volume_a()),
+    outlay: outlay(&subsidized.trades),
+    gains: (
+      gains_in_b(&baseline.initial_assets, &baseline.final_assets(), price),
+      gains_in_b(&subsidized.initial_assets, &subsidized.final_assets(), price),
+    ),
+  })
+}
+
+pub fn print_report(baseline: &RunLog, subsidized: &RunLog, subsidy: Subsidy) {
+  println!("subsidy of {} vs no policy:", subsidy.describe());
+  let Some(c) = compare(baseline, subsidized) else {
+    println!("  nothing traded without the subsidy to compare against");
+    return;
+  };
+  println!("  trades: {} (without {}), volume {} A (without {}, {:+.4}%)", subsidized.trades.len(), baseline.trades.len(), c.volume_a.1, c.volume_a.0, 100.0 * (c.volume_a.1 / c.volume_a.0 - 1.0));
+  println!("  paid out from the pot: {} B", c.outlay);
+  println!("  gains from trade (B): {} without, {} with; {} added", c.gains.0, c.gains.1, c.surplus_added());
+  println!("  deadweight loss: {} B ({} of the outlay)", c.deadweight_loss(), if c.outlay > 0.0 { format!("{:.6}", c.deadweight_loss() / c.outlay) } else { "n/a".to_string() });
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::pricing::PricingRule;
+  use crate::simulation::SimulationBuilder;
+  use crate::subsidy::*;
+
+  #[test]
+  fn test_subsidy() {
+    assert_eq!(Subsidy::parse("0.5"), Ok(Subsidy { b: 0.5, to: Recipient::Sellers }));
+    assert_eq!(Subsidy::parse("0.5:buyers").unwrap().per_unit(), (0.5, 0.0));
+    assert!(Subsidy::parse("0.5:government").is_err());
+
+    let run = |subsidy| SimulationBuilder::new().agents(100).seed(3).pricing(PricingRule::default().with_subsidy(subsidy)).build().unwrap().run();
+    let baseline = run(None);
+    for to in [Recipient::Buyers, Recipient::Sellers] {
+      let subsidized = run(Some(Subsidy { b: 0.2, to }));
+      // the ledger check at the end of the run found the outlay accounted for
+      let c = compare(&baseline, &subsidized).unwrap();
+      assert!((c.outlay - 0.2 * c.volume_a.1).abs() < 1e-6 * c.outlay);
+      assert!(c.volume_a.1 > c.volume_a.0 && c.surplus_added() > 0.0);
+    }
+  }
+}
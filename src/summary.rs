@@ -0,0 +1,293 @@
+// Headline numbers for a finished run, addressable by name so they can be
+// tabulated or filtered on.
+
+use crate::accounting;
+use crate::config::Config;
+use crate::activity;
+use crate::budget_share::{corner_fraction, preferred_shares};
+use crate::curve_fit::fit_market;
+use crate::dispersion::{price_gaps, relative_gaps};
+use crate::stats::mean;
+use crate::inequality::gini;
+use crate::intersection;
+use crate::pricing::total_improvement;
+use crate::nonconvergence::{self, NonConvergence};
+use crate::numeraire::Numeraire;
+use crate::runlog::RunLog;
+use crate::stopping::{Stop, StopReason};
+use crate::subsidy;
+use crate::tax;
+use crate::welfare::{self, Currency, Normalization, Welfare};
+use crate::{best_quotes, realized_surplus, Agent, Balance, Price};
+
+// Prices are stored as B per A and quoted in `numeraire` by `metrics`.
+#[derive(Default)]
+pub struct Summary {
+  pub seed: u64,
+  pub n_agents: usize,
+  pub trades: usize,
+  pub rejections: usize,
+  pub volume_a: f64,
+  pub volume_b: f64,
+  pub mean_price: Option<Price>, // volume-weighted
+  // price used to value wealth: the mean price, or the final quote midpoint if nothing traded
+  pub valuation_price: Price,
+  pub first_price: Option<Price>,
+  pub last_price: Option<Price>,
+  pub final_bid: Option<Price>,
+  pub final_ask: Option<Price>,
+  pub gini_initial: f64, // of wealth valued at valuation_price
+  pub gini_final: f64,
+  pub gini_utility_final: f64, // of raw utility
+  // in `currency`; see accounting
+  pub total_surplus: f64,
+  pub utility_initial: f64,
+  pub utility_final: f64,
+  pub consumer_surplus: f64,
+  pub producer_surplus: f64,
+  pub tax_revenue: f64, // B; see tax
+  pub subsidy_outlay: f64, // B; see subsidy
+  // utilities normalized at the endowment, or money-metric in B; see welfare
+  pub welfare_utilitarian: Option<f64>,
+  pub welfare_rawlsian: Option<f64>,
+  pub welfare_nash: Option<f64>,
+  pub activity_gini: Option<f64>, // of trades per agent
+  pub never_traded: usize,
+  pub mean_abs_price_gap: Option<f64>, // relative to the contemporaneous Walrasian price
+  // among agents with a preference at valuation_price, at the end of the run
+  pub mean_preferred_share: Option<f64>,
+  pub corner_fraction: Option<f64>,
+  pub buyer_surplus_share: Option<f64>, // of the quoted surplus (bid - ask) * quantity
+  // constant-elasticity fits to the initial curves
+  pub demand_elasticity: Option<f64>,
+  pub supply_elasticity: Option<f64>,
+  // where the initial step supply and demand curves cross
+  pub step_clearing_price: Option<Price>,
+  pub step_clearing_quantity: Option<f64>,
+  pub stop: Option<Stop>,
+  pub non_convergence: Option<NonConvergence>,
+  pub numeraire: Numeraire,
+  pub currency: Currency,
+}
+
+pub fn wealth_in_b(assets: &[(Agent, Balance)], price: Price) -> Vec<f64> {
+  assets.iter().map(|(_, balance)| balance.a * price + balance.b).collect()
+}
+
+pub fn utilities(assets: &[(Agent, Balance)]) -> Vec<f64> {
+  assets.iter().map(|(agent, balance)| agent.utility(balance.a, balance.b)).collect()
+}
+
+impl Summary {
+  pub fn of(log: &RunLog) -> Summary {
+    let final_assets = log.final_assets();
+    let volume_a: f64 = log.trades.iter().map(|t| t.amount_a).sum();
+    let volume_b: f64 = log.trades.iter().map(|t| t.amount_b).sum();
+    let mean_price = if log.trades.is_empty() { None } else { Some(volume_b / volume_a) };
+    let trade_counts = activity::trade_counts(log.initial_assets.len(), &log.trades);
+    let (final_bid, final_ask) = best_quotes(&final_assets);
+    let valuation_price = mean_price.unwrap_or(match (final_bid, final_ask) {
+      (Some(bid), Some(ask)) => (bid + ask) / 2.0,
+      (Some(p), None) | (None, Some(p)) => p,
+      (None, None) => 1.0,
+    });
+    let fits = fit_market(&log.initial_assets);
+    // the step curves are linear agents' orders
+    let linear = log.initial_assets.iter().all(|(agent, _)| agent.utility_fn.is_linear());
+    let (bids, asks) = intersection::steps(&log.initial_assets);
+    let crossing = intersection::solve(&bids, &asks).filter(|_| linear);
+    let shares = preferred_shares(&final_assets, valuation_price);
+    let accounts = accounting::of(&log.initial_assets, &log.trades);
+    Summary {
+      seed: log.seed,
+      n_agents: log.initial_assets.len(),
+      trades: log.trades.len(),
+      rejections: log.rejections.len(),
+      volume_a,
+      volume_b,
+      mean_price,
+      valuation_price,
+      first_price: log.trades.first().map(|t| t.price_per_a_in_b()),
+      last_price: log.trades.last().map(|t| t.price_per_a_in_b()),
+      final_bid,
+      final_ask,
+      gini_initial: gini(&wealth_in_b(&log.initial_assets, valuation_price)),
+      gini_final: gini(&wealth_in_b(&final_assets, valuation_price)),
+      gini_utility_final: gini(&utilities(&final_assets)),
+      total_surplus: realized_surplus(&log.initial_assets, &final_assets).iter().sum(),
+      utility_initial: accounts.utility_before,
+      utility_final: accounts.utility_after,
+      consumer_surplus: accounts.consumer_surplus,
+      producer_surplus: accounts.producer_surplus,
+      tax_revenue: tax::revenue(&log.trades),
+      subsidy_outlay: subsidy::outlay(&log.trades),
+      welfare_utilitarian: welfare::welfare(Welfare::Utilitarian, Normalization::Endowment, &log.initial_assets, &final_assets),
+      welfare_rawlsian: welfare::welfare(Welfare::Rawlsian, Normalization::Endowment, &log.initial_assets, &final_assets),
+      welfare_nash: welfare::welfare(Welfare::Nash, Normalization::Endowment, &log.initial_assets, &final_assets),
+      activity_gini: activity::activity_gini(&trade_counts),
+      never_traded: trade_counts.iter().filter(|&&c| c == 0).count(),
+      mean_abs_price_gap: if log.trades.is_empty() { None } else {
+        Some(mean(&relative_gaps(&price_gaps(log)).iter().map(|g| g.abs()).collect::<Vec<_>>()))
+      },
+      mean_preferred_share: if shares.is_empty() { None } else { Some(mean(&shares)) },
+      corner_fraction: if shares.is_empty() { None } else { Some(corner_fraction(&shares)) },
+      buyer_surplus_share: if log.trades.is_empty() { None } else {
+        let (buyers, sellers) = total_improvement(&log.trades);
+        Some(buyers / (buyers + sellers))
+      },
+      demand_elasticity: fits.demand.map(|f| f.log_log.slope),
+      supply_elasticity: fits.supply.map(|f| f.log_log.slope),
+      step_clearing_price: crossing.map(|x| x.price()),
+      step_clearing_quantity: crossing.map(|x| x.quantity_high),
+      stop: log.stop,
+      non_convergence: nonconvergence::detect(&log.trades),
+      numeraire: Numeraire::B,
+      currency: Currency::Utils,
+    }
+  }
+
+  // `log`'s summary as `config` reports it: prices in its numeraire and welfare in its
+  // currency.
+  pub fn reported(log: &RunLog, config: &Config) -> Summary {
+    Summary::of(log).in_currency(log, config.welfare_currency).in_numeraire(config.numeraire)
+  }
+
+  pub fn in_numeraire(self, numeraire: Numeraire) -> Summary {
+    Summary { numeraire, ..self }
+  }
+
+  // The surplus and welfare figures revalued for `log`, the run summarized, in
+  // `currency`.
+  pub fn in_currency(self, log: &RunLog, currency: Currency) -> Summary {
+    let Some(price) = currency.price(self.valuation_price) else { return Summary { currency, ..self } };
+    let (accounts, final_assets) = (accounting::in_b(&log.initial_assets, &log.trades, price), log.final_assets());
+    let welfare = |f| welfare::welfare(f, Normalization::MoneyMetric { price }, &log.initial_assets, &final_assets);
+    Summary {
+      total_surplus: accounts.gains_from_trade(),
+      utility_initial: accounts.utility_before,
+      utility_final: accounts.utility_after,
+      consumer_surplus: accounts.consumer_surplus,
+      producer_surplus: accounts.producer_surplus,
+      welfare_utilitarian: welfare(Welfare::Utilitarian),
+      welfare_rawlsian: welfare(Welfare::Rawlsian),
+      welfare_nash: welfare(Welfare::Nash),
+      currency,
+      ..self
+    }
+  }
+
+  pub fn final_spread(&self) -> Option<Price> {
+    Some(self.final_ask? - self.final_bid?)
+  }
+
+  // Whether the run ended with no crossing orders left.
+  pub fn converged(&self) -> bool {
+    self.final_spread().is_none_or(|s| s >= 0.0)
+  }
+
+  // (name, description, value) for every metric, in display order, with prices in
+  // the numeraire.
+  pub fn metrics(&self) -> Vec<(&'static str, &'static str, Option<f64>)> {
+    let n = self.numeraire;
+    let price = |p: Option<Price>| p.map(|p| n.price(p));
+    // the mean of B per A over A traded is the reciprocal of the mean of A per B over B traded
+    let (final_bid, final_ask) = n.quotes(self.final_bid, self.final_ask);
+    let unit = |utils, b| if self.currency == Currency::Utils { utils } else { b };
+    vec![
+      ("seed", "seed", Some(self.seed as f64)),
+      ("agents", "agents", Some(self.n_agents as f64)),
+      ("trades", "trades", Some(self.trades as f64)),
+      ("rejections", "matched trades rejected by the risk rules", Some(self.rejections as f64)),
+      ("volume_a", "volume of A traded", Some(self.volume_a)),
+      ("volume_b", "volume of B traded", Some(self.volume_b)),
+      ("mean_price", "mean price (volume-weighted)", price(self.mean_price)),
+      ("valuation_price", "price used to value wealth", price(Some(self.valuation_price))),
+      ("first_price", "first trade price", price(self.first_price)),
+      ("last_price", "last trade price", price(self.last_price)),
+      ("final_bid", "final best bid", final_bid),
+      ("final_ask", "final best ask", final_ask),
+      ("final_spread", "final spread", final_ask.zip(final_bid).map(|(ask, bid)| ask - bid)),
+      ("converged", "converged (no crossing orders left)", Some(if self.converged() { 1.0 } else { 0.0 })),
+      ("gini_initial", "Gini of wealth at valuation price, initial", Some(self.gini_initial)),
+      ("gini_final", "Gini of wealth at valuation price, final", Some(self.gini_final)),
+      ("gini_utility_final", "Gini of utility, final", Some(self.gini_utility_final)),
+      ("total_surplus", unit("total realized surplus (utils)", "total realized surplus (B equivalent)"), Some(self.total_surplus)),
+      ("utility_initial", unit("total utility before trading", "total money-metric utility before trading (B)"), Some(self.utility_initial)),
+      ("utility_final", unit("total utility after trading", "total money-metric utility after trading (B)"), Some(self.utility_final)),
+      ("consumer_surplus", unit("buyers' gains from their purchases (utils)", "buyers' gains from their purchases (B equivalent)"), Some(self.consumer_surplus)),
+      ("producer_surplus", unit("sellers' gains from their sales (utils)", "sellers' gains from their sales (B equivalent)"), Some(self.producer_surplus)),
+      ("tax_revenue", "tax collected from buyers (B)", Some(self.tax_revenue)),
+      ("subsidy_outlay", "subsidies paid to traders (B)", Some(self.subsidy_outlay)),
+      ("welfare_utilitarian", unit("mean utility, 1 at the endowment", "mean money-metric utility (B)"), self.welfare_utilitarian),
+      ("welfare_rawlsian", unit("least utility, 1 at the endowment", "least money-metric utility (B)"), self.welfare_rawlsian),
+      ("welfare_nash", unit("geometric mean utility, 1 at the endowment", "geometric mean money-metric utility (B)"), self.welfare_nash),
+      ("activity_gini", "Gini of trades per agent", self.activity_gini),
+      ("never_traded", "agents that never traded", Some(self.never_traded as f64)),
+      ("mean_abs_price_gap", "mean |price - Walrasian price| / Walrasian price", self.mean_abs_price_gap),
+      ("mean_preferred_share", "mean final share of wealth in the preferred good", self.mean_preferred_share),
+      ("corner_fraction", "fraction of agents ending at their corner", self.corner_fraction),
+      ("buyer_surplus_share", "buyers' share of the quoted surplus", self.buyer_surplus_share),
+      ("demand_elasticity", "elasticity of the initial demand curve (log-log fit)", self.demand_elasticity),
+      ("supply_elasticity", "elasticity of the initial supply curve (log-log fit)", self.supply_elasticity),
+      ("step_clearing_price", "price where the initial step curves cross", price(self.step_clearing_price)),
+      ("step_clearing_quantity", "A cleared where the initial step curves cross", self.step_clearing_quantity),
+      ("ticks", "ticks run", self.stop.map(|s| s.tick as f64)),
+      ("stopped_early", "stopped by a rule before the market was exhausted", self.stop.map(|s| if s.reason == StopReason::Exhausted { 0.0 } else { 1.0 })),
+      ("truncated", "cut off by a time or memory limit or a signal, its results partial", self.stop.map(|s| if s.reason.is_truncation() { 1.0 } else { 0.0 })),
+      ("non_convergent", "price path oscillates without damping or trades stop shrinking", Some(if self.non_convergence.is_some() { 1.0 } else { 0.0 })),
+    ]
+  }
+
+  // The names `metrics` gives, before any run.
+  pub fn names() -> Vec<&'static str> {
+    Summary::default().metrics().into_iter().map(|(name, _, _)| name).collect()
+  }
+
+  pub fn metric(&self, name: &str) -> Option<f64> {
+    self.metrics().into_iter().find(|(n, _, _)| *n == name).and_then(|(_, _, v)| v)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::arrivals::Arrivals;
+  use crate::market::Market;
+  use crate::pricing::PricingRule;
+  use crate::risk::RiskRules;
+  use crate::stopping::StoppingRules;
+  use crate::strategy::Strategies;
+  use crate::summary::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_summary() {
+    // a buyer valuing A at 3 B with 10 B and a seller valuing it at 1 with 4 A: all 4
+    // trade at 2, each gaining 4
+    let assets = vec![(linear_agent(3.0), Balance { a: 0.0, b: 10.0 }), (linear_agent(1.0), Balance { a: 4.0, b: 0.0 })];
+    let mut market = Market::new(assets, Arrivals::every_tick(0), PricingRule::default(), RiskRules::default(), StoppingRules::default());
+    market.run(&mut Strategies::truthful_for(&market.assets), |_| {});
+    let summary = Summary::of(&market.into_outcome().into_log(7, vec![]));
+    let expected = vec![
+      ("seed", Some(7.0)), ("agents", Some(2.0)), ("trades", Some(1.0)), ("rejections", Some(0.0)),
+      ("volume_a", Some(4.0)), ("volume_b", Some(8.0)), ("mean_price", Some(2.0)), ("valuation_price", Some(2.0)),
+      ("first_price", Some(2.0)), ("last_price", Some(2.0)),
+      // the buyer, now holding A, bids and asks at its valuation
+      ("final_bid", Some(3.0)), ("final_ask", Some(3.0)), ("final_spread", Some(0.0)), ("converged", Some(1.0)),
+      ("gini_initial", Some(1.0 / 18.0)), ("gini_final", Some(1.0 / 18.0)), ("gini_utility_final", Some(3.0 / 22.0)),
+      ("total_surplus", Some(8.0)), ("utility_initial", Some(14.0)), ("utility_final", Some(22.0)),
+      ("consumer_surplus", Some(4.0)), ("producer_surplus", Some(4.0)), ("tax_revenue", Some(0.0)), ("subsidy_outlay", Some(0.0)),
+      ("welfare_utilitarian", Some(1.7)), ("welfare_rawlsian", Some(1.4)), ("welfare_nash", Some(1.4f64.sqrt() * 2.0f64.sqrt())),
+      ("activity_gini", Some(0.0)), ("never_traded", Some(0.0)),
+      // the initial Walrasian price is 2.5, where the step curves cross
+      ("mean_abs_price_gap", Some(0.2)), ("mean_preferred_share", Some(0.9)), ("corner_fraction", Some(0.5)), ("buyer_surplus_share", Some(0.5)),
+      ("demand_elasticity", Some(-1.0)), ("supply_elasticity", Some(0.0)),
+      ("step_clearing_price", Some(2.5)), ("step_clearing_quantity", Some(4.0)),
+      ("ticks", Some(1.0)), ("stopped_early", Some(0.0)), ("truncated", Some(0.0)), ("non_convergent", Some(0.0)),
+    ];
+    let metrics = summary.metrics();
+    assert_eq!(Summary::names(), expected.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+    for ((name, _, value), (_, want)) in metrics.into_iter().zip(expected) {
+      assert!(value.zip(want).map_or(value == want, |(x, y)| (x - y).abs() < 1e-12), "{}: {:?}, not {:?}", name, value, want);
+    }
+  }
+}
@@ -0,0 +1,154 @@
+// Minimal line charts rendered straight to SVG, so reports don't need a plotting
+// toolchain.
+
+use std::fmt::Write;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 360.0;
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 30.0;
+const MARGIN_BOTTOM: f64 = 45.0;
+const COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#7f7f7f"];
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Scale {
+  Linear,
+  Log,
+}
+
+impl Scale {
+  fn transform(&self, v: f64) -> f64 {
+    match self {
+      Scale::Linear => v,
+      Scale::Log => v.log10(),
+    }
+  }
+
+  fn ticks(&self, lo: f64, hi: f64) -> Vec<f64> {
+    match self {
+      Scale::Linear => (0..=4).map(|i| lo + (hi - lo) * i as f64 / 4.0).collect(),
+      // lo and hi are already log10'd
+      Scale::Log => (lo.ceil() as i32..=hi.floor() as i32).map(|k| 10_f64.powi(k)).collect(),
+    }
+  }
+}
+
+pub struct Series {
+  pub label: String,
+  pub points: Vec<(f64, f64)>,
+}
+
+pub struct Chart {
+  pub title: String,
+  pub x_label: String,
+  pub y_label: String,
+  pub x_scale: Scale,
+  pub y_scale: Scale,
+  pub series: Vec<Series>,
+}
+
+impl Chart {
+  pub fn new(title: &str, x_label: &str, y_label: &str) -> Chart {
+    Chart {
+      title: title.to_string(),
+      x_label: x_label.to_string(),
+      y_label: y_label.to_string(),
+      x_scale: Scale::Linear,
+      y_scale: Scale::Linear,
+      series: vec![],
+    }
+  }
+
+  pub fn log_x(mut self) -> Chart { self.x_scale = Scale::Log; self }
+  pub fn log_y(mut self) -> Chart { self.y_scale = Scale::Log; self }
+
+  pub fn series(mut self, label: &str, points: Vec<(f64, f64)>) -> Chart {
+    self.series.push(Series { label: label.to_string(), points });
+    self
+  }
+
+  // The points that can be drawn on the chosen scales (finite, and positive on a log
+  // axis), already transformed.
+  fn drawable(&self, points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    points.iter()
+      .filter(|(x, y)| (self.x_scale != Scale::Log || *x > 0.0) && (self.y_scale != Scale::Log || *y > 0.0))
+      .map(|(x, y)| (self.x_scale.transform(*x), self.y_scale.transform(*y)))
+      .filter(|(x, y)| x.is_finite() && y.is_finite())
+      .collect()
+  }
+
+  pub fn to_svg(&self) -> String {
+    let all: Vec<(f64, f64)> = self.series.iter().flat_map(|s| self.drawable(&s.points)).collect();
+    let (mut x_lo, mut x_hi, mut y_lo, mut y_hi) = (f64::INFINITY, f64::NEG_INFINITY, f64::INFINITY, f64::NEG_INFINITY);
+    for (x, y) in &all {
+      x_lo = x_lo.min(*x); x_hi = x_hi.max(*x);
+      y_lo = y_lo.min(*y); y_hi = y_hi.max(*y);
+    }
+    if all.is_empty() {
+      x_lo = 0.0; x_hi = 1.0; y_lo = 0.0; y_hi = 1.0;
+    }
+    if x_hi == x_lo { x_hi += 1.0; x_lo -= 1.0; }
+    if y_hi == y_lo { y_hi += 1.0; y_lo -= 1.0; }
+
+    let plot_w = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_h = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let px = |x: f64| MARGIN_LEFT + (x - x_lo) / (x_hi - x_lo) * plot_w;
+    let py = |y: f64| MARGIN_TOP + plot_h - (y - y_lo) / (y_hi - y_lo) * plot_h;
+
+    let mut out = String::new();
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="11">"#, WIDTH, HEIGHT).unwrap();
+    writeln!(out, r#"<text x="{}" y="18" text-anchor="middle" font-size="14">{}</text>"#, WIDTH / 2.0, escape(&self.title)).unwrap();
+    writeln!(out, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="dimgray"/>"#, MARGIN_LEFT, MARGIN_TOP, plot_w, plot_h).unwrap();
+
+    for tick in self.x_scale.ticks(x_lo, x_hi) {
+      let x = px(self.x_scale.transform(tick));
+      writeln!(out, r#"<line x1="{x}" y1="{}" x2="{x}" y2="{}" stroke="gainsboro"/>"#, MARGIN_TOP, MARGIN_TOP + plot_h, x = x).unwrap();
+      writeln!(out, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, x, MARGIN_TOP + plot_h + 14.0, format_tick(tick)).unwrap();
+    }
+    for tick in self.y_scale.ticks(y_lo, y_hi) {
+      let y = py(self.y_scale.transform(tick));
+      writeln!(out, r#"<line x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="gainsboro"/>"#, MARGIN_LEFT, MARGIN_LEFT + plot_w, y = y).unwrap();
+      writeln!(out, r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, MARGIN_LEFT - 4.0, y + 4.0, format_tick(tick)).unwrap();
+    }
+    writeln!(out, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, MARGIN_LEFT + plot_w / 2.0, HEIGHT - 8.0, escape(&self.x_label)).unwrap();
+    writeln!(out, r#"<text x="14" y="{y}" text-anchor="middle" transform="rotate(-90 14 {y})">{}</text>"#, escape(&self.y_label), y = MARGIN_TOP + plot_h / 2.0).unwrap();
+
+    for (i, series) in self.series.iter().enumerate() {
+      let color = COLORS[i % COLORS.len()];
+      let points: Vec<String> = self.drawable(&series.points).iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", px(*x), py(*y)))
+        .collect();
+      writeln!(out, r#"<polyline fill="none" stroke="{}" stroke-width="1.5" points="{}"/>"#, color, points.join(" ")).unwrap();
+      let legend_y = MARGIN_TOP + 14.0 + 14.0 * i as f64;
+      writeln!(out, r#"<text x="{}" y="{}" text-anchor="end" fill="{}">{}</text>"#, MARGIN_LEFT + plot_w - 6.0, legend_y, color, escape(&series.label)).unwrap();
+    }
+    out.push_str("</svg>\n");
+    out
+  }
+}
+
+fn format_tick(v: f64) -> String {
+  if v != 0.0 && (v.abs() >= 10000.0 || v.abs() < 0.01) {
+    format!("{:.0e}", v)
+  } else {
+    let s = format!("{:.2}", v);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+  }
+}
+
+pub fn escape(s: &str) -> String {
+  s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::svg::*;
+
+  #[test]
+  fn test_log_scale_drops_nonpositive_points() {
+    let chart = Chart::new("t", "x", "y").log_y().series("s", vec![(0.0, 1.0), (1.0, 0.0), (2.0, 100.0)]);
+    assert_eq!(chart.drawable(&chart.series[0].points), vec![(0.0, 0.0), (2.0, 2.0)]);
+    assert_eq!(Scale::Log.ticks(-0.5, 2.0), vec![1.0, 10.0, 100.0]);
+  }
+}
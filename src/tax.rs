@@ -0,0 +1,157 @@
+// Taxes on trade, for studying deadweight loss: ad valorem, a share of what the buyer
+// pays the seller, or per unit, a fixed amount of B on each A bought. The buyer pays
+// it in B on top of the price; who is charged makes no difference to who bears it,
+// which the report shows as the incidence. A bid crosses an ask only once it covers
+// the ask and the tax together, so the price is set k of the way from the ask to the
+// bid net of tax, and the trades that would gain less than the tax don't happen. The
+// revenue goes into a government account outside the economy, so the ledger check at
+// the end of a run counts it back in.
+//
+// The report sets a taxed run against the untaxed run of the same seed: the
+// deadweight loss is whatever of the untaxed gains from trade the taxed run neither
+// realizes nor collects as revenue. Gains are valued in B as money-metric utility at
+// a common price (see welfare), so they can be added to revenue.
+
+use serde::{Deserialize, Serialize};
+
+use crate::runlog::RunLog;
+use crate::welfare::money_metric;
+use crate::{Agent, Balance, Price, Trade};
+
+#[derive(PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tax {
+  // a share of the price
+  AdValorem { rate: f64 },
+  // B per A
+  PerUnit { b: f64 },
+}
+
+impl Tax {
+  // `ad-valorem:<rate>` or `per-unit:<B per A>`, e.g. `ad-valorem:0.1`
+  pub fn parse(s: &str) -> Result<Tax, String> {
+    let tax = match s.split_once(':').map(|(kind, x)| (kind, x.parse::<f64>())) {
+      Some(("ad-valorem", Ok(rate))) => Tax::AdValorem { rate },
+      Some(("per-unit", Ok(b))) => Tax::PerUnit { b },
+      _ => return Err(format!("unknown tax {:?} (expected ad-valorem:<rate> or per-unit:<B per A>)", s)),
+    };
+    Ok(tax)
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      Tax::AdValorem { rate } => format!("{}% ad valorem", 100.0 * rate),
+      Tax::PerUnit { b } => format!("{} B per A", b),
+    }
+  }
+
+  pub fn is_valid(&self) -> bool {
+    let x = match *self { Tax::AdValorem { rate } => rate, Tax::PerUnit { b } => b };
+    x >= 0.0 && x.is_finite()
+  }
+
+  // The B owed on buying `amount_a` at `price`.
+  pub fn on(&self, amount_a: f64, price: Price) -> f64 {
+    match *self {
+      Tax::AdValorem { rate } => rate * price * amount_a,
+      Tax::PerUnit { b } => b * amount_a,
+    }
+  }
+
+  // What the buyer pays for each A at `price`, tax included.
+  pub fn gross(&self, price: Price) -> Price {
+    match *self {
+      Tax::AdValorem { rate } => price * (1.0 + rate),
+      Tax::PerUnit { b } => price + b,
+    }
+  }
+
+  // The most a bid of `bid` leaves the seller once the tax is paid.
+  pub fn net(&self, bid: Price) -> Price {
+    match *self {
+      Tax::AdValorem { rate } => bid / (1.0 + rate),
+      Tax::PerUnit { b } => bid - b,
+    }
+  }
+}
+
+// The government account: everything collected over `trades`, in B.
+pub fn revenue(trades: &[Trade]) -> f64 {
+  trades.iter().fold(0.0, |r, t| r + t.tax)
+}
+
+// The gains from trade between `initial` and `last`, in B at `price`.
+pub fn gains_in_b(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], price: Price) -> f64 {
+  initial.iter().zip(last).fold(0.0, |g, ((agent, before), (_, after))| g + money_metric(agent, after, price) - money_metric(agent, before, price))
+}
+
+pub struct Comparison {
+  pub revenue: f64,
+  // (untaxed, taxed) gains from trade, in B at the untaxed run's mean price
+  pub gains: (f64, f64),
+  pub deadweight_loss: f64,
+  // (untaxed, taxed buyers paid per A, taxed sellers received per A)
+  pub prices: (Option<Price>, Option<Price>, Option<Price>),
+  // the share of the tax borne by buyers, as the rise in what they pay over the wedge
+  pub buyers_incidence: Option<f64>,
+}
+
+pub fn compare(untaxed: &RunLog, taxed: &RunLog) -> Option<Comparison> {
+  let mean = |trades: &[Trade], paid: fn(&Trade) -> f64| {
+    let volume_a = trades.iter().fold(0.0, |v, t| v + t.amount_a);
+    (volume_a > 0.0).then(|| trades.iter().fold(0.0, |b, t| b + paid(t)) / volume_a)
+  };
+  let price = mean(&untaxed.trades, |t| t.amount_b)?;
+  let revenue = revenue(&taxed.trades);
+  let gains = (
+    gains_in_b(&untaxed.initial_assets, &untaxed.final_assets(), price),
+    gains_in_b(&taxed.initial_assets, &taxed.final_assets(), price),
+  );
+  let (paid, received) = (mean(&taxed.trades, |t| t.amount_b + t.tax), mean(&taxed.trades, |t| t.amount_b));
+  let buyers_incidence = match (paid, received) {
+    (Some(paid), Some(received)) if paid > received => Some((paid - price) / (paid - received)),
+    _ => None,
+  };
+  Some(Comparison { revenue, gains, deadweight_loss: gains.0 - gains.1 - revenue, prices: (Some(price), paid, received), buyers_incidence })
+}
+
+pub fn print_report(untaxed: &RunLog, taxed: &RunLog, tax: Tax) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  println!("tax of {} vs no tax:", tax.describe());
+  println!("  trades: {} (untaxed {}), volume {} A (untaxed {})", taxed.trades.len(), untaxed.trades.len(), taxed.volume_a(), untaxed.volume_a());
+  let Some(c) = compare(untaxed, taxed) else {
+    println!("  nothing traded untaxed to compare against");
+    return;
+  };
+  println!("  mean price: buyers pay {}, sellers receive {} (untaxed {})", show(c.prices.1), show(c.prices.2), show(c.prices.0));
+  println!("  buyers' share of the tax burden: {}", show(c.buyers_incidence));
+  println!("  government revenue: {} B", c.revenue);
+  println!("  gains from trade (B at {:.6}): {} untaxed, {} taxed", c.prices.0.unwrap(), c.gains.0, c.gains.1);
+  println!("  deadweight loss: {} B ({} of the untaxed gains)", c.deadweight_loss, show((c.gains.0 > 0.0).then(|| c.deadweight_loss / c.gains.0)));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::pricing::PricingRule;
+  use crate::simulation::SimulationBuilder;
+  use crate::tax::*;
+
+  #[test]
+  fn test_tax() {
+    assert_eq!(Tax::parse("ad-valorem:0.25"), Ok(Tax::AdValorem { rate: 0.25 }));
+    assert!(Tax::parse("per-unit").is_err() && Tax::parse("lump-sum:1").is_err());
+    let tax = Tax::AdValorem { rate: 0.25 };
+    assert_eq!((tax.gross(2.0), tax.net(2.5), tax.on(4.0, 2.0)), (2.5, 2.0, 2.0));
+
+    let run = |tax| SimulationBuilder::new().agents(100).seed(3).pricing(PricingRule::default().with_tax(tax)).build().unwrap().run();
+    let (untaxed, taxed) = (run(None), run(Some(Tax::PerUnit { b: 0.2 })));
+    assert_eq!(revenue(&untaxed.trades), 0.0);
+    // every taxed trade pays 0.2 B a unit, and the ledger check at the end of the run
+    // found the revenue accounted for
+    assert!(taxed.trades.iter().all(|t| (t.tax - 0.2 * t.amount_a).abs() < 1e-12));
+    let c = compare(&untaxed, &taxed).unwrap();
+    assert!(c.revenue > 0.0 && c.gains.1 < c.gains.0);
+    // the wedge between what buyers pay and sellers get is the tax
+    assert!((c.prices.1.unwrap() - c.prices.2.unwrap() - 0.2).abs() < 1e-9);
+  }
+}
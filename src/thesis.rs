@@ -0,0 +1,173 @@
+// `simmarket thesis`: runs every experiment in the header comment of main.rs for one
+// seed and writes the answers to a single HTML report.
+
+use std::fmt::Write;
+
+use crate::config::Config;
+use crate::dispersion::walrasian_price;
+use crate::numeraire::Numeraire;
+use crate::report;
+use crate::runlog::RunLog;
+use crate::simulation::SimulationBuilder;
+use crate::summary::Summary;
+use crate::svg::escape;
+use crate::{simulate, supply_demand_at, Agent, Balance, Price};
+
+// How far from the clearing price the floor and cap variants are set.
+const OFFSET: f64 = 0.25;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Limit {
+  Floor,
+  Cap,
+}
+
+// One price-limit variant of the baseline run.
+pub struct Variant {
+  pub limit: Limit,
+  pub price: Price,
+  // whether the initial curves cross on the far side of the limit
+  pub binding: bool,
+  // A traded if the market cleared once along the initial curves, respecting the limit
+  pub predicted_volume: f64,
+  pub summary: Summary,
+}
+
+// Where a limit leaves the one-shot clearing price, and whether it binds.
+pub fn limited_price(limit: Limit, price: Price, clearing: Price) -> (Price, bool) {
+  match limit {
+    Limit::Floor if price > clearing => (price, true),
+    Limit::Cap if price < clearing => (price, true),
+    _ => (clearing, false),
+  }
+}
+
+fn variant(config: &Config, seed: u64, initial: &[(Agent, Balance)], clearing: Price, limit: Limit, price: Price) -> Variant {
+  let (floor, cap) = match limit {
+    Limit::Floor => (Some(price), config.pricing.cap),
+    Limit::Cap => (config.pricing.floor, Some(price)),
+  };
+  let simulation = SimulationBuilder::from_config(config.clone())
+    .pricing(config.pricing.with_limits(floor, cap))
+    .seed(seed)
+    .build()
+    .unwrap();
+  let (at, binding) = limited_price(limit, price, clearing);
+  let (supply, demand) = supply_demand_at(initial, at);
+  Variant { limit, price, binding, predicted_volume: supply.min(demand), summary: Summary::of(&simulation.run()) }
+}
+
+pub struct Thesis {
+  pub baseline: RunLog,
+  pub summary: Summary,
+  pub start_clearing: Price,
+  pub end_clearing: Option<Price>,
+  pub variants: Vec<Variant>,
+}
+
+impl Thesis {
+  pub fn run(config: &Config, seed: u64) -> Thesis {
+    let baseline = simulate(config, seed);
+    let summary = Summary::of(&baseline);
+    let start_clearing = walrasian_price(&baseline.initial_assets).expect("nobody can trade in the initial state");
+    let end_clearing = walrasian_price(&baseline.final_assets());
+    let variants = [
+      (Limit::Floor, 1.0 + OFFSET),
+      (Limit::Floor, 1.0 - OFFSET),
+      (Limit::Cap, 1.0 - OFFSET),
+      (Limit::Cap, 1.0 + OFFSET),
+    ].iter()
+      .map(|&(limit, factor)| variant(config, seed, &baseline.initial_assets, start_clearing, limit, start_clearing * factor))
+      .collect();
+    Thesis { baseline, summary, start_clearing, end_clearing, variants }
+  }
+
+  // Whether the crossing of the final curves sits inside the final spread, allowing
+  // for the bisection's rounding when the spread has closed to a point.
+  pub fn clearing_in_final_spread(&self) -> Option<bool> {
+    let price = self.end_clearing?;
+    let slack = price * 1e-9;
+    Some(self.summary.final_bid.is_none_or(|bid| bid <= price + slack) && self.summary.final_ask.is_none_or(|ask| price - slack <= ask))
+  }
+
+  pub fn print(&self) {
+    let show = |p: Option<f64>| p.map_or("none".to_string(), |p| p.to_string());
+    println!("thesis for seed {}:", self.baseline.seed);
+    println!("  initial curves cross at {}; baseline mean price {}", self.start_clearing, show(self.summary.mean_price));
+    println!("  final spread [{}, {}]; final curves cross at {} (inside the spread: {})",
+      show(self.summary.final_bid), show(self.summary.final_ask), show(self.end_clearing), self.clearing_in_final_spread().map_or("n/a".to_string(), |b| b.to_string()));
+    for v in &self.variants {
+      println!("  {:?} at {} ({}): traded {} A (curves predict {}), mean price {}",
+        v.limit, v.price, if v.binding { "binding" } else { "non-binding" }, v.summary.volume_a, v.predicted_volume, show(v.summary.mean_price));
+    }
+  }
+
+  pub fn html(&self) -> String {
+    let show = |p: Option<f64>| p.map_or("none".to_string(), |p| p.to_string());
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(out, "<html><head><meta charset=\"utf-8\"><title>simmarket thesis {}</title>", self.baseline.seed).unwrap();
+    writeln!(out, "<style>body {{ font-family: sans-serif; max-width: 700px; margin: 2em auto; }} td {{ padding: 2px 12px 2px 0; }}</style>").unwrap();
+    writeln!(out, "</head><body>").unwrap();
+    writeln!(out, "<h1>simmarket thesis, seed {}</h1>", self.baseline.seed).unwrap();
+
+    writeln!(out, "<h2>Supply and demand at the start and when no trades are left</h2>").unwrap();
+    // floors and caps are on the price of A in B, so everything here is quoted in B
+    for (name, chart) in report::charts(&self.baseline, Numeraire::B) {
+      if name == "curves" {
+        writeln!(out, "<div>{}</div>", chart.to_svg()).unwrap();
+      }
+    }
+    writeln!(out, "<p>The initial curves cross at {}; the baseline traded {} A at a mean price of {}.</p>",
+      self.start_clearing, self.summary.volume_a, escape(&show(self.summary.mean_price))).unwrap();
+
+    writeln!(out, "<h2>Final spread against the curves' intersection</h2>").unwrap();
+    writeln!(out, "<table>").unwrap();
+    for (label, value) in [
+      ("final best bid", self.summary.final_bid),
+      ("final best ask", self.summary.final_ask),
+      ("final curves cross at", self.end_clearing),
+      ("initial curves cross at", Some(self.start_clearing)),
+    ] {
+      writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", label, escape(&show(value))).unwrap();
+    }
+    writeln!(out, "</table>").unwrap();
+    let inside = match self.clearing_in_final_spread() {
+      Some(true) => "lies inside the final spread",
+      Some(false) => "lies outside the final spread",
+      None => "does not exist",
+    };
+    writeln!(out, "<p>The crossing of the final curves {}.</p>", inside).unwrap();
+
+    writeln!(out, "<h2>Price floors and caps</h2>").unwrap();
+    writeln!(out, "<p>Each limit is {}% away from the initial crossing. The prediction is the smaller of supply and demand at the price the limit leaves.</p>", OFFSET * 100.0).unwrap();
+    writeln!(out, "<table>").unwrap();
+    writeln!(out, "<tr><th>limit</th><th>price</th><th>binding</th><th>A traded</th><th>predicted</th><th>mean price</th></tr>").unwrap();
+    for v in &self.variants {
+      writeln!(out, "<tr><td>{:?}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+        v.limit, v.price, v.binding, v.summary.volume_a, v.predicted_volume, escape(&show(v.summary.mean_price))).unwrap();
+    }
+    writeln!(out, "</table>").unwrap();
+    writeln!(out, "</body></html>").unwrap();
+    out
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::thesis::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_limits() {
+    let assets = vec![
+      (linear_agent(1.0), Balance { a: 10.0, b: 0.0 }),
+      (linear_agent(4.0), Balance { a: 0.0, b: 20.0 }),
+    ];
+    assert_eq!(supply_demand_at(&assets, 2.0), (10.0, 10.0));
+    assert_eq!(limited_price(Limit::Floor, 3.0, 2.0), (3.0, true));
+    assert_eq!(limited_price(Limit::Floor, 1.5, 2.0), (2.0, false));
+    assert_eq!(limited_price(Limit::Cap, 1.5, 2.0), (1.5, true));
+    assert_eq!(limited_price(Limit::Cap, 3.0, 2.0), (2.0, false));
+  }
+}
@@ -0,0 +1,137 @@
+// Where the matching loop spends its time, for performance work on the engine. Each
+// phase is timed exclusive of any other phase timed inside it (the fill inside a
+// best-price search counts as clearing, not search), and the time no phase accounts
+// for is reported as other. The timers accumulate per thread and are collected per
+// run by the market (see market::Market::step), so runs on parallel threads, or one
+// run nested inside another's report, don't mix. Not saved with a checkpoint: a
+// resumed run's timings start over.
+//
+// Timings vary from machine to machine and run to run, so they're only printed on
+// request and never exported with the summaries.
+
+use std::cell::{Cell, RefCell};
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Phase {
+  // quoting, withdrawing unbacked orders, and putting them on the book
+  OrderGeneration,
+  // finding the best crossing bid and ask
+  BestPriceSearch,
+  // pricing and sizing a matched trade, and the pre-trade risk checks
+  Clearing,
+  Settlement,
+  // the post-trade and end-of-run assertions
+  InvariantChecks,
+}
+
+impl Phase {
+  pub const ALL: [Phase; 5] = [Phase::OrderGeneration, Phase::BestPriceSearch, Phase::Clearing, Phase::Settlement, Phase::InvariantChecks];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Phase::OrderGeneration => "order generation",
+      Phase::BestPriceSearch => "best-price search",
+      Phase::Clearing => "clearing",
+      Phase::Settlement => "settlement",
+      Phase::InvariantChecks => "invariant checks",
+    }
+  }
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct Timings {
+  // time and number of entries, indexed as Phase::ALL
+  pub phases: [(Duration, u64); 5],
+  pub total: Duration,
+}
+
+impl Timings {
+  pub fn of(&self, phase: Phase) -> (Duration, u64) {
+    self.phases[phase as usize]
+  }
+
+  // The time no phase accounts for.
+  pub fn other(&self) -> Duration {
+    self.total.saturating_sub(self.phases.iter().map(|&(d, _)| d).sum())
+  }
+}
+
+impl AddAssign for Timings {
+  fn add_assign(&mut self, other: Timings) {
+    for (mine, theirs) in self.phases.iter_mut().zip(other.phases) {
+      mine.0 += theirs.0;
+      mine.1 += theirs.1;
+    }
+    self.total += other.total;
+  }
+}
+
+thread_local! {
+  static TIMINGS: RefCell<Timings> = RefCell::new(Timings::default());
+  // time spent in phases nested inside the innermost one running
+  static NESTED: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+// Runs `f`, counting its time toward `phase`.
+pub fn time<R>(phase: Phase, f: impl FnOnce() -> R) -> R {
+  let outer = NESTED.with(|n| n.replace(Duration::ZERO));
+  let start = Instant::now();
+  let result = f();
+  let elapsed = start.elapsed();
+  let inner = NESTED.with(|n| n.replace(outer + elapsed));
+  TIMINGS.with(|t| {
+    let entry = &mut t.borrow_mut().phases[phase as usize];
+    entry.0 += elapsed.saturating_sub(inner);
+    entry.1 += 1;
+  });
+  result
+}
+
+// Runs `f` with timers of its own, returning what they recorded and its total time.
+pub fn scoped<R>(f: impl FnOnce() -> R) -> (R, Timings) {
+  let saved = (TIMINGS.with(|t| t.take()), NESTED.with(|n| n.take()));
+  let start = Instant::now();
+  let result = f();
+  let total = start.elapsed();
+  let mut timings = TIMINGS.with(|t| t.replace(saved.0));
+  NESTED.with(|n| n.set(saved.1));
+  timings.total = total;
+  (result, timings)
+}
+
+pub fn print_report(timings: &Timings, n_agents: usize) {
+  let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+  let share = |d: Duration| if timings.total.is_zero() { 0.0 } else { 100.0 * d.as_secs_f64() / timings.total.as_secs_f64() };
+  println!("matching engine time ({} agents): {:.3} ms", n_agents, ms(timings.total));
+  for phase in Phase::ALL {
+    let (d, calls) = timings.of(phase);
+    println!("  {:<18} {:>10.3} ms {:>5.1}%  ({} calls)", phase.name(), ms(d), share(d), calls);
+  }
+  println!("  {:<18} {:>10.3} ms {:>5.1}%", "other", ms(timings.other()), share(timings.other()));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::timing::*;
+
+  #[test]
+  fn test_nested_phases() {
+    let sleep = |ms| std::thread::sleep(Duration::from_millis(ms));
+    let ((), timings) = scoped(|| {
+      time(Phase::BestPriceSearch, || {
+        sleep(10);
+        time(Phase::Clearing, || sleep(20));
+      });
+      // someone else's run, inside this one, keeps its timings to itself
+      let ((), inner) = scoped(|| time(Phase::Settlement, || sleep(5)));
+      assert_eq!(inner.of(Phase::Settlement).1, 1);
+    });
+    let (search, clearing) = (timings.of(Phase::BestPriceSearch).0, timings.of(Phase::Clearing).0);
+    assert!(search >= Duration::from_millis(10) && search < Duration::from_millis(20), "{:?}", search);
+    assert!(clearing >= Duration::from_millis(20));
+    assert_eq!(timings.of(Phase::Settlement), (Duration::ZERO, 0));
+    assert!(timings.total >= Duration::from_millis(35) && timings.other() >= Duration::from_millis(5));
+  }
+}
@@ -0,0 +1,94 @@
+// Streams executed trades as newline-delimited JSON while the run is still going, to
+// a file or a TCP socket (`tcp://host:port`), so a notebook can read them live, e.g.
+// a line at a time off the socket, or with `pandas.read_json(path, lines=True)` on
+// what's been written so far. Each line is a Trade as the run log has it (see
+// runlog). Trades go out in batches, flushed as each fills, so readers see them a
+// batch behind at most.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::TcpStream;
+
+use crate::Trade;
+
+pub const DEFAULT_BATCH_SIZE: usize = 256;
+
+struct Writer {
+  sink: Box<dyn Write>,
+  pending: usize,
+  batch_size: usize,
+}
+
+impl Writer {
+  fn open(target: &str, batch_size: usize) -> io::Result<Writer> {
+    let sink: Box<dyn Write> = match target.strip_prefix("tcp://") {
+      Some(addr) => Box::new(BufWriter::new(TcpStream::connect(addr)?)),
+      None => Box::new(BufWriter::new(File::create(target)?)),
+    };
+    Ok(Writer { sink, pending: 0, batch_size })
+  }
+
+  fn push(&mut self, trade: &Trade) -> io::Result<()> {
+    serde_json::to_writer(&mut self.sink, trade)?;
+    self.sink.write_all(b"\n")?;
+    self.pending += 1;
+    if self.pending >= self.batch_size {
+      // push the batch out now, so readers see it while the run is still going
+      self.sink.flush()?;
+      self.pending = 0;
+    }
+    Ok(())
+  }
+
+  fn finish(mut self) -> io::Result<()> {
+    self.sink.flush()
+  }
+}
+
+// A trade stream that may not be going anywhere, so callers don't need to care
+// whether streaming was requested.
+pub struct TradeStream(Option<Writer>);
+
+impl TradeStream {
+  pub fn open(target: Option<&str>, batch_size: usize) -> io::Result<TradeStream> {
+    Ok(TradeStream(match target {
+      Some(target) => Some(Writer::open(target, batch_size)?),
+      None => None,
+    }))
+  }
+
+  pub fn push(&mut self, trade: &Trade) -> io::Result<()> {
+    match &mut self.0 {
+      Some(writer) => writer.push(trade),
+      None => Ok(()),
+    }
+  }
+
+  pub fn finish(self) -> io::Result<()> {
+    match self.0 {
+      Some(writer) => writer.finish(),
+      None => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::trade_stream::*;
+
+  #[test]
+  fn test_stream_reads_back_in_batches() {
+    let path = std::env::temp_dir().join("simmarket_trade_stream_test.ndjson");
+    let trade = |i: usize| Trade { tick: i as u64, buyer: i, seller: i + 1, amount_a: 1.0, amount_b: i as f64, bid_price: i as f64, ask_price: i as f64, ..Trade::default() };
+    let mut stream = TradeStream::open(Some(path.to_str().unwrap()), 2).unwrap();
+    for i in 0..3 {
+      stream.push(&trade(i)).unwrap();
+    }
+    // the first batch is out before the stream is finished, the rest of the second isn't
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(written.lines().count(), 2);
+    stream.finish().unwrap();
+    let trades: Vec<Trade> = std::fs::read_to_string(&path).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(trades, (0..3).map(trade).collect::<Vec<_>>());
+  }
+}
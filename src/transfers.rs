@@ -0,0 +1,88 @@
+// `simmarket transfers`: the second welfare theorem as an experiment. Every efficient
+// allocation is the competitive equilibrium from some redistribution of endowments,
+// so each of a range of lump-sum transfers (see population::redistribute) picks out
+// one: the equilibrium from the endowments after it. Running the market from each
+// shows which of those efficient allocations it actually reaches, and how far it
+// falls short of the rest. Welfare is normalized at the endowments before any
+// transfer, so the rows compare.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::config::Config;
+use crate::equilibrium::{self, Comparison};
+use crate::inequality::gini;
+use crate::summary::wealth_in_b;
+use crate::welfare::{self, Normalization, Welfare};
+use crate::{simulate, Agent, Balance, Price};
+
+// One transfer's equilibrium and the market's outcome from it.
+pub struct Point {
+  pub share: f64,
+  pub price: Price,
+  // of the transferred endowments at the equilibrium price, which is the
+  // equilibrium's too: everyone's wealth at its price is what they bring to it
+  pub gini_endowment: f64,
+  // (utilitarian, rawlsian) at the equilibrium, then at the market's outcome
+  pub equilibrium_welfare: (Option<f64>, Option<f64>),
+  pub market_welfare: (Option<f64>, Option<f64>),
+  pub comparison: Comparison,
+}
+
+pub fn run(config: &Config, seed: u64, shares: &[f64]) -> Vec<Point> {
+  let untransferred = Config { transfer: 0.0, ..config.clone() }.initial_assets(seed, &mut StdRng::seed_from_u64(seed));
+  let welfare = |last: &[(Agent, Balance)]| {
+    let of = |f| welfare::welfare(f, Normalization::Endowment, &untransferred, last);
+    (of(Welfare::Utilitarian), of(Welfare::Rawlsian))
+  };
+  shares.iter().filter_map(|&share| {
+    let log = simulate(&Config { transfer: share, ..config.clone() }, seed);
+    let eq = equilibrium::solve(&log.initial_assets)?;
+    let at_equilibrium: Vec<(Agent, Balance)> = log.initial_assets.iter().zip(&eq.allocations).map(|((agent, _), b)| (*agent, *b)).collect();
+    let last = log.final_assets();
+    Some(Point {
+      share,
+      price: eq.price,
+      // rounding can take an even split's gini a hair below 0
+      gini_endowment: gini(&wealth_in_b(&log.initial_assets, eq.price)).max(0.0),
+      equilibrium_welfare: welfare(&at_equilibrium),
+      market_welfare: welfare(&last),
+      comparison: equilibrium::compare(&log.initial_assets, &last, &log.trades, &eq),
+    })
+  }).collect()
+}
+
+pub fn print_report(points: &[Point]) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.4}", x));
+  println!("efficient allocations reachable by transfers then trade (welfare normalized before transfers):");
+  println!("  {:>8} {:>9} | {:>9} {:>9} {:>9} | {:>9} {:>9} {:>9} {:>9}", "transfer", "price", "eq gini", "eq util", "eq rawls", "mkt gini", "mkt util", "mkt rawls", "distance");
+  for p in points {
+    println!(
+      "  {:>8} {:>9.4} | {:>9.4} {:>9} {:>9} | {:>9.4} {:>9} {:>9} {:>9.4}",
+      p.share, p.price, p.gini_endowment, show(p.equilibrium_welfare.0), show(p.equilibrium_welfare.1),
+      p.comparison.gini_final, show(p.market_welfare.0), show(p.market_welfare.1), p.comparison.allocation_distance,
+    );
+  }
+  println!("  (distance: share of wealth the market's outcome would have to move to reach the equilibrium)");
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::transfers::*;
+  use crate::utility::UtilityFn;
+
+  #[test]
+  fn test_transfers() {
+    let config = Config { n_agents: 60, utility: UtilityFn::CobbDouglas, ..Config::default() };
+    let points = run(&config, 1, &[0.0, 1.0]);
+    assert_eq!(points.len(), 2);
+    // equal division leaves the equilibrium no wealth inequality, and redistributing
+    // moves the market's outcome with it, staying close to the equilibrium
+    assert!(points[1].gini_endowment < 1e-9 && points[0].gini_endowment > 0.1);
+    assert!(points[1].comparison.gini_final < points[0].comparison.gini_final);
+    assert!(points.iter().all(|p| p.comparison.allocation_distance < 0.2));
+    // with no transfer nobody ends up below their endowment at the equilibrium, while
+    // equal division takes from somebody
+    assert!(points[0].equilibrium_welfare.1.unwrap() >= 1.0 - 1e-9 && points[1].equilibrium_welfare.1.unwrap() < 1.0);
+  }
+}
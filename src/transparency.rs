@@ -0,0 +1,100 @@
+// Pre-trade transparency: how much of the market strategic agents see before they
+// quote. Each level shows less than the one before: the full book and the tape of
+// reported trades; only the best bid and ask, with the tape; only the last reported
+// price; or nothing at all, a dark market. Monopolists and cartels price against the
+// bids they can see, so with the best bid alone they price against one buyer, and with
+// no bids in sight they quote truthfully. Adaptive agents, and agents with a horizon,
+// need the tape. Truthful agents and dealers quote from their own balance, so
+// transparency doesn't touch them.
+//
+// The report sets a run against the same one with the book lit in full.
+
+use serde::{Deserialize, Serialize};
+
+use crate::runlog::RunLog;
+use crate::{realized_surplus, AgentId, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transparency {
+  #[default]
+  Full,
+  Top,
+  LastTrade,
+  Dark,
+}
+
+impl Transparency {
+  pub fn parse(s: &str) -> Result<Transparency, String> {
+    match s {
+      "full" => Ok(Transparency::Full),
+      "top" => Ok(Transparency::Top),
+      "last-trade" => Ok(Transparency::LastTrade),
+      "dark" => Ok(Transparency::Dark),
+      _ => Err(format!("unknown transparency {:?} (expected full, top, last-trade or dark)", s)),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Transparency::Full => "full",
+      Transparency::Top => "top",
+      Transparency::LastTrade => "last-trade",
+      Transparency::Dark => "dark",
+    }
+  }
+
+  pub fn describe(&self) -> &'static str {
+    match self {
+      Transparency::Full => "the full book and the tape",
+      Transparency::Top => "the best bid and ask and the tape",
+      Transparency::LastTrade => "the last reported price",
+      Transparency::Dark => "nothing",
+    }
+  }
+
+  // What of `demand`, (bid price, B available) for everyone else's bids, can be seen.
+  pub fn visible(&self, demand: Vec<(Price, f64)>) -> Vec<(Price, f64)> {
+    match self {
+      Transparency::Full => demand,
+      Transparency::Top => demand.into_iter().max_by(|x, y| x.0.total_cmp(&y.0)).into_iter().collect(),
+      Transparency::LastTrade | Transparency::Dark => vec![],
+    }
+  }
+
+  pub fn shows_tape(&self) -> bool {
+    *self != Transparency::Dark
+  }
+}
+
+pub fn print_report(lit: &RunLog, run: &RunLog, transparency: Transparency, strategic: &[AgentId]) {
+  println!("strategic agents ({}) seeing {} vs the full book:", strategic.len(), transparency.describe());
+  for (label, log) in [("full", lit), (transparency.name(), run)] {
+    let surplus = realized_surplus(&log.initial_assets, &log.final_assets());
+    let theirs = strategic.iter().fold(0.0, |s, &id| s + surplus[id]);
+    let total = surplus.iter().fold(0.0, |s, x| s + x);
+    let volume = log.trades.iter().fold(0.0, |v, t| v + t.amount_a);
+    let price = log.trades.iter().fold(0.0, |b, t| b + t.amount_b) / volume;
+    println!("  {:<10}  {} trades, {} A at a mean price {:.6}; surplus {} theirs, {} everyone else's", label, log.trades.len(), volume, price, theirs, total - theirs);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::population::Population;
+  use crate::simulation::SimulationBuilder;
+  use crate::transparency::*;
+
+  #[test]
+  fn test_transparency() {
+    assert_eq!(Transparency::parse("last-trade"), Ok(Transparency::LastTrade));
+    let demand = vec![(1.0, 5.0), (3.0, 1.0), (2.0, 2.0)];
+    assert_eq!((Transparency::Top.visible(demand.clone()), Transparency::Dark.visible(demand)), (vec![(3.0, 1.0)], vec![]));
+
+    let run = |transparency| SimulationBuilder::new().agents(50).seed(1).population(Population::Monopolist { sellers: 1 }).monopoly(true).transparency(transparency).build().unwrap().run();
+    let surplus = |log: &RunLog| realized_surplus(&log.initial_assets, &log.final_assets())[0];
+    // a monopolist seeing nothing can't price against anyone, and does worse for it
+    let (lit, dark) = (run(Transparency::Full), run(Transparency::Dark));
+    assert!(surplus(&dark) < surplus(&lit));
+  }
+}
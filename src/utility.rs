@@ -0,0 +1,171 @@
+// The shape of an agent's preferences over its holdings of A and B, weighted by its
+// two consumption coefficients. Linear utility, the original, values every unit the
+// same so an agent's indifference price never moves; under the others it's the
+// marginal rate of substitution at what the agent holds, falling as it holds more A,
+// and a trade is sized to take each side no further than where its rate meets the
+// price. Cobb-Douglas weighs the goods by the coefficients' shares of their sum; Log
+// is shifted by one unit, so an agent with none of a good still has a finite rate.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Balance, Price};
+
+// Non-linear agents bid this fraction below their marginal rate and ask this fraction
+// above it, so two agents whose rates have met stop crossing instead of trading ever
+// smaller amounts.
+pub const SETTLED: f64 = 1e-6;
+
+#[derive(PartialEq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UtilityFn {
+  #[default]
+  Linear,
+  CobbDouglas,
+  // constant elasticity of substitution 1 / (1 - rho), for rho < 1 and not 0
+  Ces { rho: f64 },
+  Log,
+}
+
+impl UtilityFn {
+  // `linear`, `cobb-douglas`, `ces:<rho>` or `log`
+  pub fn parse(s: &str) -> Result<UtilityFn, String> {
+    match s {
+      "linear" => Ok(UtilityFn::Linear),
+      "cobb-douglas" => Ok(UtilityFn::CobbDouglas),
+      "log" => Ok(UtilityFn::Log),
+      _ => match s.strip_prefix("ces:").map(|rho| rho.parse::<f64>()) {
+        Some(Ok(rho)) if rho < 1.0 && rho != 0.0 => Ok(UtilityFn::Ces { rho }),
+        _ => Err(format!("unknown utility {:?} (expected linear, cobb-douglas, ces:<rho> with rho < 1 and not 0, or log)", s)),
+      },
+    }
+  }
+
+  pub fn is_linear(&self) -> bool {
+    *self == UtilityFn::Linear
+  }
+
+  // Whether an agent's rate is unbounded with none of A (and undefined with none of
+  // either), so every agent has to start out holding some of both.
+  pub fn needs_both_goods(&self) -> bool {
+    matches!(self, UtilityFn::CobbDouglas | UtilityFn::Ces { .. })
+  }
+
+  pub fn level(&self, (ca, cb): (f64, f64), a: f64, b: f64) -> f64 {
+    match *self {
+      UtilityFn::Linear => ca * a + cb * b,
+      UtilityFn::CobbDouglas => {
+        let alpha = ca / (ca + cb);
+        a.powf(alpha) * b.powf(1.0 - alpha)
+      }
+      UtilityFn::Ces { rho } => (ca * a.powf(rho) + cb * b.powf(rho)).powf(1.0 / rho),
+      UtilityFn::Log => ca * a.ln_1p() + cb * b.ln_1p(),
+    }
+  }
+
+  // The level over any number of goods, of which `level` is the two-good case.
+  // Between any two goods the rate depends on nothing but their coefficients and
+  // amounts, so marginal_rate and demand serve as they are for each pair.
+  pub fn level_of(&self, coeffs: &[f64], amounts: &[f64]) -> f64 {
+    let terms = coeffs.iter().zip(amounts);
+    match *self {
+      UtilityFn::Linear => terms.fold(0.0, |u, (c, x)| u + c * x),
+      UtilityFn::CobbDouglas => {
+        let total = coeffs.iter().fold(0.0, |s, c| s + c);
+        terms.fold(1.0, |u, (c, x)| u * x.powf(c / total))
+      }
+      UtilityFn::Ces { rho } => terms.fold(0.0, |u, (c, x)| u + c * x.powf(rho)).powf(1.0 / rho),
+      UtilityFn::Log => terms.fold(0.0, |u, (c, x)| u + c * x.ln_1p()),
+    }
+  }
+
+  // B per A at which the agent is indifferent to a marginal trade: the ratio of its
+  // marginal utilities of A and B, in closed form so an empty side gives 0 or infinity
+  // rather than a ratio of infinities.
+  pub fn marginal_rate(&self, (ca, cb): (f64, f64), a: f64, b: f64) -> Price {
+    match *self {
+      UtilityFn::Linear => ca / cb,
+      UtilityFn::CobbDouglas => ca * b / (cb * a),
+      UtilityFn::Ces { rho } => ca / cb * (a / b).powf(rho - 1.0),
+      UtilityFn::Log => ca * (1.0 + b) / (cb * (1.0 + a)),
+    }
+  }
+
+  // The utility of a marginal unit of A and of B.
+  pub fn marginal_utilities(&self, (ca, cb): (f64, f64), a: f64, b: f64) -> (f64, f64) {
+    match *self {
+      UtilityFn::Linear => (ca, cb),
+      UtilityFn::CobbDouglas => {
+        let alpha = ca / (ca + cb);
+        (alpha * (b / a).powf(1.0 - alpha), (1.0 - alpha) * (a / b).powf(alpha))
+      }
+      UtilityFn::Ces { rho } => {
+        let scale = self.level((ca, cb), a, b).powf(1.0 - rho);
+        (ca * a.powf(rho - 1.0) * scale, cb * b.powf(rho - 1.0) * scale)
+      }
+      UtilityFn::Log => (ca / (1.0 + a), cb / (1.0 + b)),
+    }
+  }
+
+  // The A the agent would buy at `price` (negative to sell) to reach its best bundle
+  // on the budget line through `balance`, within what it holds.
+  pub fn demand(&self, coeffs: (f64, f64), balance: &Balance, price: Price) -> f64 {
+    let excess = |q: f64| self.marginal_rate(coeffs, balance.a + q, balance.b - price * q) - price;
+    let (lo, hi) = (-balance.a, balance.b / price);
+    let start = excess(0.0);
+    if start == 0.0 {
+      return 0.0;
+    }
+    // the rate only falls moving along the line towards more A, so either the end
+    // it moves towards is best or it crosses the price once on the way
+    let (mut inside, mut end) = if start > 0.0 { (0.0, hi) } else { (0.0, lo) };
+    if (excess(end) > 0.0) == (start > 0.0) {
+      return end;
+    }
+    for _ in 0..100 {
+      let mid = (inside + end) / 2.0;
+      if (excess(mid) > 0.0) == (start > 0.0) { inside = mid } else { end = mid }
+    }
+    inside
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::realized_surplus;
+  use crate::simulation::SimulationBuilder;
+  use crate::utility::*;
+
+  #[test]
+  fn test_utility_fns() {
+    assert_eq!(UtilityFn::parse("ces:0.5"), Ok(UtilityFn::Ces { rho: 0.5 }));
+    assert!(UtilityFn::parse("ces:1").is_err() && UtilityFn::parse("quadratic").is_err());
+
+    let balance = Balance { a: 10.0, b: 10.0 };
+    // linear: all of the B at any price below the rate, all of the A above
+    assert_eq!(UtilityFn::Linear.demand((2.0, 1.0), &balance, 1.0), 10.0);
+    assert_eq!(UtilityFn::Linear.demand((2.0, 1.0), &balance, 4.0), -10.0);
+    // Cobb-Douglas spends the coefficient's share of wealth on each good: at price 1
+    // that's 15 of 20 on A with weights 3:1
+    assert!((UtilityFn::CobbDouglas.demand((3.0, 1.0), &balance, 1.0) - 5.0).abs() < 1e-9);
+    for f in [UtilityFn::CobbDouglas, UtilityFn::Ces { rho: -1.0 }, UtilityFn::Log] {
+      let q = f.demand((3.0, 1.0), &balance, 2.0);
+      let after = (balance.a + q, balance.b - 2.0 * q);
+      assert!((f.marginal_rate((3.0, 1.0), after.0, after.1) - 2.0).abs() < 1e-9, "{:?}", f);
+      // and any other point on the line is worse
+      let level = |q: f64| f.level((3.0, 1.0), balance.a + q, balance.b - 2.0 * q);
+      assert!(level(q) > level(q - 0.1) && level(q) > level(q + 0.1));
+      assert!((f.level_of(&[3.0, 1.0], &[2.0, 5.0]) - f.level((3.0, 1.0), 2.0, 5.0)).abs() < 1e-12, "{:?}", f);
+    }
+  }
+
+  #[test]
+  fn test_market_settles_at_common_rate() {
+    let log = SimulationBuilder::new().agents(40).utility(UtilityFn::CobbDouglas).build().unwrap().run();
+    let last = log.final_assets();
+    assert!(realized_surplus(&log.initial_assets, &last).iter().all(|&s| s >= 0.0));
+    // everyone ends with some of both goods, at nearly the same rate
+    let rates: Vec<f64> = last.iter().map(|(agent, balance)| agent.indifference_price_at(balance)).collect();
+    let (lo, hi) = (rates.iter().cloned().fold(f64::INFINITY, f64::min), rates.iter().cloned().fold(0.0, f64::max));
+    assert!(hi / lo < 1.0 + 1e-4, "{} .. {}", lo, hi);
+  }
+}
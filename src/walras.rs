@@ -0,0 +1,206 @@
+// General-equilibrium prices for an N-good exchange economy with linear utilities,
+// as a benchmark for what the market should converge to.
+//
+// With linear utilities each agent's demand is a correspondence (it spends everything
+// on whichever goods have the best utility per unit price), so excess demand jumps as
+// prices cross an agent's indifference point and Newton or plain tatonnement on it
+// chatter instead of converging. Instead this iterates on spending: proportional
+// response dynamics, where each agent splits its wealth across goods in proportion to
+// the utility its last bundle got from each, and prices are total spending per unit.
+// Its fixed points are the equilibria; half-stepping towards each new split keeps it
+// from cycling on small, sparse economies. The excess demands at the result are
+// reported so the benchmark's own accuracy is visible.
+//
+// Under any other utility a coefficient isn't a marginal utility, so an economy with
+// agents whose utility isn't linear has no benchmark here, and its reports are left
+// out; equilibrium solves the two-good market's for any utility.
+
+use crate::goods::Goods;
+use crate::{Agent, Balance, Bundle, Preferences};
+
+// How far each iteration moves the spending towards its proportional response.
+const DAMPING: f64 = 0.5;
+
+pub struct Economy {
+  // endowments[i][j]: agent i's holding of good j
+  pub endowments: Vec<Vec<f64>>,
+  // utilities[i][j]: agent i's marginal utility of good j
+  pub utilities: Vec<Vec<f64>>,
+}
+
+pub struct Equilibrium {
+  // scaled so the numeraire's price is 1
+  pub prices: Vec<f64>,
+  pub iterations: usize,
+  // the largest |demand - supply| / supply over goods, at `prices`
+  pub max_excess_demand: f64,
+}
+
+impl Economy {
+  // The two-good economy the market trades, with goods ordered [A, B], if everyone's
+  // utility is linear.
+  pub fn from_assets(assets: &[(Agent, Balance)]) -> Option<Economy> {
+    assets.iter().all(|(agent, _)| agent.utility_fn.is_linear()).then(|| Economy {
+      endowments: assets.iter().map(|(_, balance)| vec![balance.a, balance.b]).collect(),
+      utilities: assets.iter().map(|(agent, _)| vec![agent.consumption_a_coeff, agent.consumption_b_coeff]).collect(),
+    })
+  }
+
+  // An economy of any number of goods, as `simmarket goods` runs it, if everyone's
+  // utility is linear.
+  pub fn from_bundles(assets: &[(Preferences, Bundle)]) -> Option<Economy> {
+    assets.iter().all(|(prefs, _)| prefs.utility_fn.is_linear()).then(|| Economy {
+      endowments: assets.iter().map(|(_, bundle)| bundle.0.clone()).collect(),
+      utilities: assets.iter().map(|(prefs, _)| prefs.coeffs.clone()).collect(),
+    })
+  }
+
+  fn n_goods(&self) -> usize {
+    self.endowments.first().map_or(0, |e| e.len())
+  }
+
+  fn supplies(&self) -> Vec<f64> {
+    (0..self.n_goods()).map(|j| self.endowments.iter().map(|e| e[j]).sum()).collect()
+  }
+
+  // Each agent's utility-maximizing demand at `prices`, splitting evenly among ties.
+  pub fn demands(&self, prices: &[f64]) -> Vec<Vec<f64>> {
+    self.endowments.iter().zip(&self.utilities).map(|(endowment, utility)| {
+      let wealth: f64 = endowment.iter().zip(prices).map(|(e, p)| e * p).sum();
+      let bang: Vec<f64> = utility.iter().zip(prices).map(|(u, p)| u / p).collect();
+      let best = bang.iter().cloned().fold(0.0, f64::max);
+      let ties: Vec<usize> = (0..bang.len()).filter(|&j| bang[j] >= best * (1.0 - 1e-12)).collect();
+      let mut demand = vec![0.0; bang.len()];
+      for &j in &ties {
+        demand[j] = wealth / ties.len() as f64 / prices[j];
+      }
+      demand
+    }).collect()
+  }
+
+  // The largest excess demand relative to supply, over goods. Ties are split evenly,
+  // which needn't clear the market exactly even at an equilibrium.
+  pub fn max_excess_demand(&self, prices: &[f64]) -> f64 {
+    let supplies = self.supplies();
+    let demands = self.demands(prices);
+    (0..supplies.len())
+      .map(|j| (demands.iter().map(|d| d[j]).sum::<f64>() - supplies[j]).abs() / supplies[j])
+      .fold(0.0, f64::max)
+  }
+
+  // Iterates until prices move less than `tolerance` (relative) in a step, or
+  // `max_iterations` run out. Every good must be held by someone.
+  pub fn equilibrium(&self, numeraire: usize, tolerance: f64, max_iterations: usize) -> Equilibrium {
+    let supplies = self.supplies();
+    assert!(supplies.iter().all(|&s| s > 0.0), "every good needs a positive supply");
+    let n = supplies.len();
+    // in units where each good's total supply is 1
+    let shares: Vec<Vec<f64>> = self.endowments.iter().map(|e| (0..n).map(|j| e[j] / supplies[j]).collect()).collect();
+    let utilities: Vec<Vec<f64>> = self.utilities.iter().map(|u| (0..n).map(|j| u[j] * supplies[j]).collect()).collect();
+
+    // everyone starts out spending its wealth evenly, which is consistent with equal prices
+    let mut prices = vec![1.0 / n as f64; n];
+    let mut bids: Vec<Vec<f64>> = shares.iter()
+      .map(|share| {
+        let wealth: f64 = share.iter().zip(&prices).map(|(s, p)| s * p).sum();
+        vec![wealth / n as f64; n]
+      })
+      .collect();
+    let mut iterations = 0;
+    while iterations < max_iterations {
+      iterations += 1;
+      for (i, bid) in bids.iter_mut().enumerate() {
+        let wealth: f64 = shares[i].iter().zip(&prices).map(|(s, p)| s * p).sum();
+        let gains: Vec<f64> = (0..n).map(|j| utilities[i][j] * bid[j] / prices[j]).collect();
+        let total: f64 = gains.iter().sum();
+        if total > 0.0 {
+          for j in 0..n {
+            bid[j] += DAMPING * (wealth * gains[j] / total - bid[j]);
+          }
+        }
+      }
+      let next: Vec<f64> = (0..n).map(|j| bids.iter().map(|b| b[j]).sum()).collect();
+      let moved = next.iter().zip(&prices).map(|(p, q)| (p - q).abs() / q).fold(0.0, f64::max);
+      prices = next;
+      if moved < tolerance {
+        break;
+      }
+    }
+    let prices: Vec<f64> = (0..n).map(|j| prices[j] / supplies[j]).collect();
+    let prices: Vec<f64> = prices.iter().map(|p| p / prices[numeraire]).collect();
+    Equilibrium { max_excess_demand: self.max_excess_demand(&prices), prices, iterations }
+  }
+}
+
+pub fn print_report(assets: &[(Agent, Balance)]) {
+  let Some(economy) = Economy::from_assets(assets).filter(|e| !e.supplies().contains(&0.0)) else {
+    return;
+  };
+  // B is the numeraire, so prices[0] is the price of A in B
+  let eq = economy.equilibrium(1, 1e-10, 100_000);
+  println!("general equilibrium benchmark: {} B per A after {} iterations (max relative excess demand {})",
+    eq.prices[0], eq.iterations, eq.max_excess_demand);
+}
+
+// The benchmark for an economy of `goods`, every price in the last good (money, if
+// there is any).
+pub fn print_goods_report(goods: &Goods, assets: &[(Preferences, Bundle)]) {
+  let Some(economy) = Economy::from_bundles(assets).filter(|e| !e.supplies().contains(&0.0)) else {
+    return;
+  };
+  let numeraire = goods.len() - 1;
+  let eq = economy.equilibrium(numeraire, 1e-10, 100_000);
+  let prices: Vec<String> = goods.names.iter().zip(&eq.prices).take(numeraire).map(|(name, p)| format!("{} {}", name, p)).collect();
+  println!("general equilibrium benchmark, in {}: {} after {} iterations (max relative excess demand {})",
+    goods.names[numeraire], prices.join(", "), eq.iterations, eq.max_excess_demand);
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::utility::UtilityFn;
+  use crate::walras::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_equilibrium() {
+    // three agents, each holding one good and wanting the next one round
+    let economy = Economy {
+      endowments: vec![vec![1.0, 0.0, 0.0], vec![0.0, 2.0, 0.0], vec![0.0, 0.0, 4.0]],
+      utilities: vec![vec![1.0, 2.0, 0.0], vec![0.0, 1.0, 2.0], vec![8.0, 0.0, 1.0]],
+    };
+    let eq = economy.equilibrium(0, 1e-12, 100_000);
+    // each agent sells all it has for the next good, so 1 * p0 = 2 * p1 = 4 * p2
+    for (p, expected) in eq.prices.iter().zip([1.0, 0.5, 0.25]) {
+      assert!((p - expected).abs() < 1e-6, "{:?}", eq.prices);
+    }
+
+    // everyone holding a unit of one good and valuing them alike: nobody can gain, and
+    // only prices in proportion to the utilities clear the market
+    let economy = Economy {
+      endowments: vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]],
+      utilities: vec![vec![1.0, 2.0, 3.0]; 3],
+    };
+    let eq = economy.equilibrium(2, 1e-12, 100_000);
+    for (p, expected) in eq.prices.iter().zip([1.0 / 3.0, 2.0 / 3.0, 1.0]) {
+      assert!((p - expected).abs() < 1e-6, "{:?}", eq.prices);
+    }
+
+    // a goods economy of two goods is the market's own
+    let agent = linear_agent(3.0);
+    let assets = vec![(agent, Balance { a: 2.0, b: 5.0 })];
+    let bundles: Vec<(Preferences, Bundle)> = assets.iter().map(|(agent, balance)| (agent.into(), balance.into())).collect();
+    let (x, y) = (Economy::from_assets(&assets).unwrap(), Economy::from_bundles(&bundles).unwrap());
+    assert_eq!((x.endowments, x.utilities), (y.endowments, y.utilities));
+    // and neither is a benchmark unless everyone's utility is linear
+    let curved = vec![(Agent { utility_fn: UtilityFn::CobbDouglas, ..agent }, Balance { a: 2.0, b: 5.0 })];
+    let bundles: Vec<(Preferences, Bundle)> = curved.iter().map(|(agent, balance)| (agent.into(), balance.into())).collect();
+    assert!(Economy::from_assets(&curved).is_none() && Economy::from_bundles(&bundles).is_none());
+
+    // with two goods it agrees with the bisection on the market's own curves
+    let mut rng = rand::SeedableRng::seed_from_u64(4);
+    let assets = crate::population::generate(crate::population::Population::Uniform, 200, &mut rng);
+    let eq = Economy::from_assets(&assets).unwrap().equilibrium(1, 1e-12, 100_000);
+    let walrasian = crate::dispersion::walrasian_price(&assets).unwrap();
+    assert!((eq.prices[0] - walrasian).abs() / walrasian < 1e-4, "{} vs {}", eq.prices[0], walrasian);
+  }
+}
@@ -0,0 +1,206 @@
+// Social welfare over a run's outcome, under a choice of aggregation: the utilitarian
+// sum, the Rawlsian minimum, or the Nash product, between which policy rankings can
+// flip. Adding or multiplying raw utilities across agents would let whoever has the
+// largest coefficients dominate, so each agent's utility is first normalized. By
+// default that's by its utility at its own endowment: everyone starts at 1, a 10% gain
+// counts the same for every agent, and the aggregates are unchanged by rescaling any
+// one agent's preferences. Money-metric utility instead measures each agent's
+// holdings as the B it would need, at a common reference price, to buy a bundle it
+// likes as well, so utilities are compared in a unit everyone shares; raw utilities
+// are still on offer, to show the bias. The sum is reported as a mean and the product
+// as a geometric mean, so both read on the same scale as the minimum.
+//
+// Welfare and surplus can also be reported in B rather than utils, as equivalent
+// variation: each agent's change in money-metric utility at a reference price, the B
+// it would have taken to leave it as well off without trading. That reads as "agents
+// gained the equivalent of 340 B" and adds up across agents without favouring the
+// keenest.
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::mean;
+use crate::{Agent, Balance, Price};
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Welfare {
+  Utilitarian,
+  Rawlsian,
+  Nash,
+}
+
+pub const ALL: [Welfare; 3] = [Welfare::Utilitarian, Welfare::Rawlsian, Welfare::Nash];
+
+impl Welfare {
+  pub fn parse(s: &str) -> Result<Welfare, String> {
+    match s {
+      "utilitarian" => Ok(Welfare::Utilitarian),
+      "rawlsian" => Ok(Welfare::Rawlsian),
+      "nash" => Ok(Welfare::Nash),
+      _ => Err(format!("unknown welfare function {:?} (expected utilitarian, rawlsian or nash)", s)),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Welfare::Utilitarian => "utilitarian",
+      Welfare::Rawlsian => "rawlsian",
+      Welfare::Nash => "nash",
+    }
+  }
+
+  pub fn aggregate(&self, normalized: &[f64]) -> f64 {
+    match self {
+      Welfare::Utilitarian => mean(normalized),
+      Welfare::Rawlsian => normalized.iter().cloned().fold(f64::INFINITY, f64::min),
+      Welfare::Nash => mean(&normalized.iter().map(|u| u.ln()).collect::<Vec<_>>()).exp(),
+    }
+  }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub enum Normalization {
+  #[default]
+  Endowment,
+  MoneyMetric { price: Price },
+  Raw,
+}
+
+impl Normalization {
+  // `endowment`, `money-metric:<price>` or `raw`
+  pub fn parse(s: &str) -> Result<Normalization, String> {
+    match s {
+      "endowment" => Ok(Normalization::Endowment),
+      "raw" => Ok(Normalization::Raw),
+      _ => match s.strip_prefix("money-metric:").map(|p| p.parse::<f64>()) {
+        Some(Ok(price)) if price > 0.0 => Ok(Normalization::MoneyMetric { price }),
+        _ => Err(format!("unknown normalization {:?} (expected endowment, money-metric:<price> with price > 0, or raw)", s)),
+      },
+    }
+  }
+
+  pub fn describe(&self) -> String {
+    match self {
+      Normalization::Endowment => "utilities normalized to 1 at each agent's endowment".to_string(),
+      Normalization::MoneyMetric { price } => format!("money-metric utilities, in B at {} B per A", price),
+      Normalization::Raw => "raw utilities, not comparable across agents".to_string(),
+    }
+  }
+}
+
+// What welfare and surplus figures are reported in: utils, or B at a reference price,
+// by default the run's valuation price (see summary).
+#[derive(PartialEq, Debug, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Currency {
+  #[default]
+  Utils,
+  B { price: Option<Price> },
+}
+
+impl Currency {
+  // `utils`, `b` at the run's valuation price, or `b:<price>`
+  pub fn parse(s: &str) -> Result<Currency, String> {
+    match s {
+      "utils" => Ok(Currency::Utils),
+      "b" | "B" => Ok(Currency::B { price: None }),
+      _ => match s.strip_prefix("b:").or_else(|| s.strip_prefix("B:")).map(|p| p.parse::<f64>()) {
+        Some(Ok(price)) if price > 0.0 => Ok(Currency::B { price: Some(price) }),
+        _ => Err(format!("unknown welfare currency {:?} (expected utils, b, or b:<price> with price > 0)", s)),
+      },
+    }
+  }
+
+  // The price figures are valued at, for a run valued at `valuation`; None in utils.
+  pub fn price(&self, valuation: Price) -> Option<Price> {
+    match self {
+      Currency::Utils => None,
+      Currency::B { price } => Some(price.unwrap_or(valuation)),
+    }
+  }
+
+  pub fn unit(&self, valuation: Price) -> String {
+    self.price(valuation).map_or("utils".to_string(), |p| format!("B at {} B per A", p))
+  }
+}
+
+// The least B that buys, at `price`, a bundle the agent likes as well as `balance`:
+// found by bisecting on wealth, spending each candidate as the agent would.
+pub fn money_metric(agent: &Agent, balance: &Balance, price: Price) -> f64 {
+  let target = agent.utility(balance.a, balance.b);
+  let best = |wealth: f64| {
+    let cash = Balance { a: 0.0, b: wealth };
+    let q = agent.demand_for_a(&cash, price);
+    agent.utility(q, wealth - price * q)
+  };
+  // the balance itself is affordable with its own value
+  let (mut lo, mut hi) = (0.0, balance.a * price + balance.b);
+  for _ in 0..100 {
+    let mid = (lo + hi) / 2.0;
+    if best(mid) < target { lo = mid } else { hi = mid }
+  }
+  hi
+}
+
+// Each agent's normalized utility at `last`; None if anyone's initial utility isn't
+// positive, which leaves nothing to normalize by at the endowment.
+pub fn normalized(norm: Normalization, initial: &[(Agent, Balance)], last: &[(Agent, Balance)]) -> Option<Vec<f64>> {
+  initial.iter().zip(last).map(|((agent, before), (_, after))| match norm {
+    Normalization::Endowment => {
+      let base = agent.utility(before.a, before.b);
+      if base > 0.0 { Some(agent.utility(after.a, after.b) / base) } else { None }
+    }
+    Normalization::MoneyMetric { price } => Some(money_metric(agent, after, price)),
+    Normalization::Raw => Some(agent.utility(after.a, after.b)),
+  }).collect()
+}
+
+pub fn welfare(welfare: Welfare, norm: Normalization, initial: &[(Agent, Balance)], last: &[(Agent, Balance)]) -> Option<f64> {
+  normalized(norm, initial, last).map(|u| welfare.aggregate(&u))
+}
+
+pub fn print_report(initial: &[(Agent, Balance)], last: &[(Agent, Balance)], fns: &[Welfare], norm: Normalization) {
+  println!("social welfare, {}:", norm.describe());
+  let show = |w: Option<f64>| w.map_or("n/a".to_string(), |w| w.to_string());
+  for f in fns {
+    println!("  {}: {} -> {}", f.name(), show(welfare(*f, norm, initial, initial)), show(welfare(*f, norm, initial, last)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::welfare::*;
+  use crate::linear_agent;
+
+  #[test]
+  fn test_aggregates() {
+    // agent 0 trades 1 A for 2 B and gains half again; agent 1, a hundred times as keen
+    // on A, gains far more utils but only 24%
+    let initial = vec![(linear_agent(1.0), Balance { a: 2.0, b: 0.0 }), (linear_agent(100.0), Balance { a: 4.0, b: 2.0 })];
+    let last = vec![(linear_agent(1.0), Balance { a: 1.0, b: 2.0 }), (linear_agent(100.0), Balance { a: 5.0, b: 0.0 })];
+    let u = normalized(Normalization::Endowment, &initial, &last).unwrap();
+    assert!((u[0] - 1.5).abs() < 1e-12 && (u[1] - 500.0 / 402.0).abs() < 1e-12);
+    assert_eq!(Welfare::Rawlsian.aggregate(&u), u[1]);
+    assert_eq!(Welfare::Utilitarian.aggregate(&[1.0, 4.0]), 2.5);
+    assert!((Welfare::Nash.aggregate(&[1.0, 4.0]) - 2.0).abs() < 1e-12);
+    assert_eq!(normalized(Normalization::Endowment, &[(linear_agent(1.0), Balance { a: 0.0, b: 0.0 })], &initial[..1]), None);
+
+    // at 2 B per A agent 1 would rather spend its B on A, so its 5 A are worth 10 B
+    // however keen it is; agent 0 would rather have B, so its 3 utils are worth 3 B
+    let money = normalized(Normalization::MoneyMetric { price: 2.0 }, &initial, &last).unwrap();
+    assert!((money[0] - 3.0).abs() < 1e-9 && (money[1] - 10.0).abs() < 1e-9, "{:?}", money);
+    assert_eq!(Normalization::parse("money-metric:2"), Ok(Normalization::MoneyMetric { price: 2.0 }));
+    assert!(Normalization::parse("money-metric:0").is_err());
+    assert_eq!(Currency::parse("b:2"), Ok(Currency::B { price: Some(2.0) }));
+    assert_eq!(Currency::parse("b").map(|c| c.price(1.5)), Ok(Some(1.5)));
+    assert!(Currency::parse("b:-1").is_err());
+
+    // in B, a run's gains are everyone's equivalent variation at the valuation price
+    let config = crate::config::Config { n_agents: 30, welfare_currency: Currency::B { price: None }, ..Default::default() };
+    let log = crate::simulate(&config, 1);
+    let summary = crate::summary::Summary::reported(&log, &config);
+    let price = summary.valuation_price;
+    let ev = log.initial_assets.iter().zip(&log.final_assets()).fold(0.0, |s, ((agent, before), (_, after))| s + money_metric(agent, after, price) - money_metric(agent, before, price));
+    assert!(ev > 0.0 && (summary.total_surplus - ev).abs() < 1e-6 * ev, "{} vs {}", summary.total_surplus, ev);
+    assert!((summary.consumer_surplus + summary.producer_surplus - ev).abs() < 1e-6 * ev);
+  }
+}
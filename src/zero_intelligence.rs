@@ -0,0 +1,99 @@
+// Zero-intelligence traders, after Gode and Sunder (1993): agents that know nothing
+// of the market and quote at random, held only to never trading at a loss. Each tick a
+// zero-intelligence agent bids uniformly between 0 and its indifference price and asks
+// uniformly between that price and a common bound (or at its price, if that's above the
+// bound), drawn afresh as common random numbers keyed by the agent and tick (see crn).
+// The draws go on the orders as they're submitted, like entry errors (see fat_finger):
+// the truthful quotes are what the agent would settle for, so the market is exhausted
+// only once those no longer cross. The last gains, between agents whose prices are all
+// but equal, can take random quotes for ever to find, and meanwhile every tick each
+// agent's two fresh draws are quote changes the run records, so a run with any
+// zero-intelligence agents needs a --max-ticks.
+//
+// The report sets a run against the same population all quoting truthfully, to show
+// how much of the convergence and the gains survive the lack of any strategy.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::crn::{Crn, Stream};
+use crate::reporting::convergence;
+use crate::runlog::RunLog;
+use crate::{AgentId, Order, Price};
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ZeroIntelligence {
+  // the highest price asked
+  pub bound: Price,
+  // None for everyone
+  pub agents: Option<Vec<AgentId>>,
+}
+
+impl ZeroIntelligence {
+  // `<bound>` for everyone, or `<bound>:<ids>` for some, e.g. `10:0,3,7`
+  pub fn parse(s: &str) -> Result<ZeroIntelligence, String> {
+    let (bound, ids) = match s.split_once(':') {
+      Some((bound, ids)) => (bound, Some(ids)),
+      None => (s, None),
+    };
+    let bound = bound.parse().map_err(|_| format!("bad zero-intelligence bound {:?} (expected <bound>[:<ids>])", bound))?;
+    let agents = ids.map(|ids| ids.split(',').map(|id| id.parse().map_err(|_| format!("can't read agent id {:?}", id))).collect()).transpose()?;
+    Ok(ZeroIntelligence { bound, agents })
+  }
+
+  pub fn ids(&self, n_agents: usize) -> Vec<AgentId> {
+    self.agents.clone().unwrap_or_else(|| (0..n_agents).collect())
+  }
+
+  // A truthful quote as a zero-intelligence agent submits it at `now`.
+  pub fn draw(&self, (bid, ask): &mut (Option<Order>, Option<Order>), crn: &Crn, agent: AgentId, now: Tick) {
+    let u = |side| crn.uniform(Stream::ZeroIntelligence, &[agent as u64, now, side]);
+    if let Some(bid) = bid.as_mut() {
+      bid.price_per_a_in_b *= u(0);
+    }
+    if let Some(ask) = ask.as_mut() {
+      ask.price_per_a_in_b += u(1) * (self.bound - ask.price_per_a_in_b).max(0.0);
+    }
+  }
+}
+
+pub fn print_report(truthful: &RunLog, run: &RunLog, zi: &ZeroIntelligence) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  let agents = zi.agents.as_ref().map_or("all agents".to_string(), |ids| format!("{} agents", ids.len()));
+  println!("zero intelligence ({}, asking up to {}) vs truthful:", agents, zi.bound);
+  for (label, log) in [("truthful", truthful), ("random", run)] {
+    let c = convergence(log);
+    let settled = c.settled_at.map_or("never".to_string(), |(i, tick)| format!("from trade {} (tick {})", i, tick));
+    println!("  {:<8}  {} trades over {} ticks, settled {}, final price {}, surplus {}", label, c.trades, c.ticks, settled, show(c.final_price), log.total_gains());
+  }
+  println!("  efficiency: {}", show((truthful.total_gains() > 0.0).then(|| run.total_gains() / truthful.total_gains())));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::simulation::SimulationBuilder;
+  use crate::zero_intelligence::*;
+  use crate::OrderType;
+
+  #[test]
+  fn test_zero_intelligence() {
+    assert_eq!(ZeroIntelligence::parse("5:1,2"), Ok(ZeroIntelligence { bound: 5.0, agents: Some(vec![1, 2]) }));
+    assert!(ZeroIntelligence::parse("5:x").is_err());
+    let zi = ZeroIntelligence { bound: 5.0, agents: None };
+    let (crn, order) = (Crn::new(1), |typ| Some(Order { agent_id: 0, typ, price_per_a_in_b: 2.0 }));
+    for now in 0..50 {
+      let mut quote = (order(OrderType::Bid), order(OrderType::Ask));
+      zi.draw(&mut quote, &crn, 0, now);
+      assert!((0.0..=2.0).contains(&quote.0.unwrap().price_per_a_in_b) && (2.0..=5.0).contains(&quote.1.unwrap().price_per_a_in_b));
+    }
+
+    // random quoting still realizes nearly all the gains, if more slowly
+    let run = |zi| {
+      let builder = SimulationBuilder::new().agents(60).seed(2).max_ticks(2000);
+      match zi { Some(zi) => builder.zero_intelligence(zi), None => builder }.build().unwrap().run()
+    };
+    let (truthful, random) = (run(None), run(Some(ZeroIntelligence { bound: 10.0, agents: None })));
+    assert!(random.total_gains() > 0.9 * truthful.total_gains() && random.total_gains() < truthful.total_gains());
+    assert!(random.trades.last().unwrap().tick > truthful.trades.last().unwrap().tick);
+  }
+}
@@ -0,0 +1,141 @@
+// ZIP traders (zero intelligence plus, after Cliff 1997): agents that learn a profit
+// margin from how the market responds to their quotes. Each bids its indifference
+// price less a margin and asks it plus another, starting from margins drawn between
+// 5% and 35% as common random numbers (see crn). After a tick that traded at q, a
+// seller asking no more than q was priced to sell and raises its ask toward a little
+// above q, and one asking more lowers it toward a little below; buyers do the mirror
+// image, and after a tick with nothing reported everyone gives a share of their margins
+// back. They learn from the tape (see reporting), so in the dark they quote truthfully.
+// Each step moves the quote `rate` of the way to its target (the Widrow-Hoff rule), and
+// a margin never goes below 0, so nobody trades at a loss.
+//
+// The margins go on the orders as they're submitted, like entry errors (see
+// fat_finger): the truthful quotes are what the agent would settle for, so the market
+// is exhausted only once those no longer cross, and the margins given back on the
+// ticks without a trade bring the quotes back to them.
+//
+// The report sets a run against the same population all quoting truthfully.
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Tick;
+use crate::crn::{Crn, Stream};
+use crate::reporting::convergence;
+use crate::runlog::RunLog;
+use crate::{AgentId, Order, Price};
+
+// how far past the last price a quote aims, at most, as a share of it
+const OVERSHOOT: f64 = 0.05;
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ZipSpec {
+  // the share of the way to its target a quote moves after each tick
+  pub rate: f64,
+  // None for everyone
+  pub agents: Option<Vec<AgentId>>,
+}
+
+impl ZipSpec {
+  // `<rate>` for everyone, or `<rate>:<ids>` for some, e.g. `0.3:0,3,7`
+  pub fn parse(s: &str) -> Result<ZipSpec, String> {
+    let (rate, ids) = match s.split_once(':') {
+      Some((rate, ids)) => (rate, Some(ids)),
+      None => (s, None),
+    };
+    let rate = rate.parse().map_err(|_| format!("bad ZIP learning rate {:?} (expected <rate>[:<ids>])", rate))?;
+    let agents = ids.map(|ids| ids.split(',').map(|id| id.parse().map_err(|_| format!("can't read agent id {:?}", id))).collect()).transpose()?;
+    Ok(ZipSpec { rate, agents })
+  }
+
+  pub fn ids(&self, n_agents: usize) -> Vec<AgentId> {
+    self.agents.clone().unwrap_or_else(|| (0..n_agents).collect())
+  }
+}
+
+pub struct Zip {
+  rate: f64,
+  crn: Crn,
+  // (bid, ask) margin per agent, as shares of its indifference price
+  margins: Vec<(f64, f64)>,
+}
+
+impl Zip {
+  pub fn new(spec: &ZipSpec, n_agents: usize, seed: u64) -> Zip {
+    let crn = Crn::new(seed);
+    let initial = |id: AgentId, side| 0.05 + 0.3 * crn.uniform(Stream::Zip, &[id as u64, u64::MAX, side]);
+    Zip { rate: spec.rate, margins: (0..n_agents).map(|id| (initial(id, 0), initial(id, 1))).collect(), crn }
+  }
+
+  pub fn margins(&self, id: AgentId) -> (f64, f64) {
+    self.margins[id]
+  }
+
+  // A truthful quote with `id`'s margins on it.
+  pub fn shade(&self, (bid, ask): &mut (Option<Order>, Option<Order>), id: AgentId) {
+    let (bid_margin, ask_margin) = self.margins[id];
+    bid.iter_mut().for_each(|o| o.price_per_a_in_b *= 1.0 - bid_margin);
+    ask.iter_mut().for_each(|o| o.price_per_a_in_b *= 1.0 + ask_margin);
+  }
+
+  // Learns from the `last` price reported since the previous tick, if any, before
+  // quoting at `now`: `agents` with the indifference prices they quote around.
+  pub fn learn(&mut self, last: Option<Price>, now: Tick, agents: &[(AgentId, Price)]) {
+    for &(id, reservation) in agents {
+      let aim = |side, up: bool| {
+        let r = OVERSHOOT * self.crn.uniform(Stream::Zip, &[id as u64, now, side]);
+        if up { 1.0 + r } else { 1.0 - r }
+      };
+      let (bid_margin, ask_margin) = self.margins[id];
+      let (bid, ask) = (reservation * (1.0 - bid_margin), reservation * (1.0 + ask_margin));
+      let (bid_target, ask_target) = match last {
+        Some(q) => (q * aim(0, bid < q), q * aim(1, ask <= q)),
+        None => (reservation, reservation),
+      };
+      let bid = bid + self.rate * (bid_target - bid);
+      let ask = ask + self.rate * (ask_target - ask);
+      self.margins[id] = ((1.0 - bid / reservation).clamp(0.0, 1.0), (ask / reservation - 1.0).max(0.0));
+    }
+  }
+}
+
+pub fn print_report(truthful: &RunLog, run: &RunLog, spec: &ZipSpec) {
+  let show = |x: Option<f64>| x.map_or("n/a".to_string(), |x| format!("{:.6}", x));
+  let agents = spec.agents.as_ref().map_or("all agents".to_string(), |ids| format!("{} agents", ids.len()));
+  println!("ZIP learning ({}, rate {}) vs truthful:", agents, spec.rate);
+  for (label, log) in [("truthful", truthful), ("ZIP", run)] {
+    let c = convergence(log);
+    let settled = c.settled_at.map_or("never".to_string(), |(i, tick)| format!("from trade {} (tick {})", i, tick));
+    println!("  {:<8}  {} trades over {} ticks, settled {}, final price {}, price error {}, surplus {}", label, c.trades, c.ticks, settled, show(c.final_price), show(c.price_error), log.total_gains());
+  }
+  println!("  efficiency: {}", show((truthful.total_gains() > 0.0).then(|| run.total_gains() / truthful.total_gains())));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::simulation::SimulationBuilder;
+  use crate::zip::*;
+
+  #[test]
+  fn test_zip() {
+    assert_eq!(ZipSpec::parse("0.3:4"), Ok(ZipSpec { rate: 0.3, agents: Some(vec![4]) }));
+    let mut zip = Zip::new(&ZipSpec { rate: 0.5, agents: None }, 2, 1);
+    let (bid0, ask0) = zip.margins(0);
+    assert!((0.05..0.35).contains(&bid0) && (0.05..0.35).contains(&ask0));
+    // with no trade both margins shrink; a trade well above a seller's ask raises it
+    zip.learn(None, 0, &[(0, 1.0)]);
+    assert!(zip.margins(0).0 < bid0 && zip.margins(0).1 < ask0);
+    let before = zip.margins(0).1;
+    zip.learn(Some(2.0), 1, &[(0, 1.0)]);
+    assert!(zip.margins(0).1 > before);
+
+    let run = |zip| {
+      let builder = SimulationBuilder::new().agents(60).seed(2);
+      match zip { Some(zip) => builder.zip(zip), None => builder }.build().unwrap().run()
+    };
+    let (truthful, learning) = (run(None), run(Some(ZipSpec { rate: 0.3, agents: None })));
+    // the learners trade the market out to the same gains, if at other prices
+    assert_eq!(learning.stop.unwrap().reason, crate::stopping::StopReason::Exhausted);
+    assert_ne!(learning.trades, truthful.trades);
+    assert!(learning.total_gains() > 0.9 * truthful.total_gains());
+  }
+}